@@ -0,0 +1,28 @@
+//! Скрипт сборки: передаёт в код хеш git-коммита.
+//!
+//! # Для студентов: build.rs
+//!
+//! Cargo компилирует и запускает `build.rs` ПЕРЕД сборкой крейта.
+//! Строки вида `cargo:rustc-env=ИМЯ=значение` становятся переменными
+//! окружения компилятора, их читает макрос `env!("ИМЯ")`.
+
+use std::process::Command;
+
+fn main() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        // Сборка из архива без .git
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=GIT_HASH={}", git_hash);
+
+    // Пересобирать при смене коммита или ветки
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -28,12 +28,30 @@ struct AskResponse {
     source: String,
 }
 
+/// Активный AI бэкенд (часть ответа /health)
+#[derive(Deserialize, Debug)]
+struct BackendInfo {
+    name: String,
+    model: Option<String>,
+    fallback_reason: Option<String>,
+}
+
+/// Сведения о сборке (часть ответа /health)
+#[derive(Deserialize, Debug)]
+struct BuildInfo {
+    git_hash: String,
+    features: Vec<String>,
+}
+
 /// Структура для health check
 #[derive(Deserialize, Debug)]
 struct HealthResponse {
     status: String,
     version: String,
     gigachat_enabled: bool,
+    backend: BackendInfo,
+    uptime_seconds: u64,
+    build: BuildInfo,
 }
 
 #[tokio::main]
@@ -51,10 +69,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("   Статус: {}", health_response.status);
     println!("   Версия: {}", health_response.version);
-    println!("   GigaChat: {}\n", if health_response.gigachat_enabled { "включён" } else { "выключен" });
+    println!("   GigaChat: {}", if health_response.gigachat_enabled { "включён" } else { "выключен" });
+    println!(
+        "   AI бэкенд: {} (модель: {})",
+        health_response.backend.name,
+        health_response.backend.model.as_deref().unwrap_or("-")
+    );
+    if let Some(reason) = &health_response.backend.fallback_reason {
+        println!("   ⚠️  Причина: {}", reason);
+    }
+    println!("   Работает: {} с", health_response.uptime_seconds);
+    println!(
+        "   Сборка: {} (фичи: {:?})\n",
+        health_response.build.git_hash, health_response.build.features
+    );
 
     // 2. Задаём несколько вопросов
-    let questions = vec![
+    let questions = [
         "Что такое Rust?",
         "Что такое Rocket?",
         "Привет!",
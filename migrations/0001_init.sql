@@ -0,0 +1,51 @@
+-- Начальная схема хранилища.
+--
+-- Время хранится в миллисекундах Unix (UTC), идентификаторы - строками UUID.
+-- Номер применённой миграции записывается в PRAGMA user_version.
+
+-- Диалоги: цепочки вопросов одного пользователя
+CREATE TABLE conversations (
+    id          TEXT PRIMARY KEY,
+    subject     TEXT NOT NULL,
+    title       TEXT NOT NULL,
+    created_at  INTEGER NOT NULL,
+    updated_at  INTEGER NOT NULL
+);
+
+CREATE INDEX idx_conversations_subject ON conversations (subject, updated_at);
+
+-- История вопросов и ответов
+CREATE TABLE exchanges (
+    id               TEXT PRIMARY KEY,
+    conversation_id  TEXT REFERENCES conversations (id) ON DELETE CASCADE,
+    subject          TEXT NOT NULL,
+    question         TEXT NOT NULL,
+    answer           TEXT NOT NULL,
+    source           TEXT NOT NULL,
+    created_at       INTEGER NOT NULL
+);
+
+CREATE INDEX idx_exchanges_subject ON exchanges (subject, created_at);
+CREATE INDEX idx_exchanges_conversation ON exchanges (conversation_id, created_at);
+
+-- Учёт использования: каждый вызов AI, включая неудачные
+CREATE TABLE usage_records (
+    id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+    subject            TEXT NOT NULL,
+    backend            TEXT NOT NULL,
+    outcome            TEXT NOT NULL,
+    prompt_tokens      INTEGER NOT NULL,
+    completion_tokens  INTEGER NOT NULL,
+    latency_ms         INTEGER NOT NULL,
+    created_at         INTEGER NOT NULL
+);
+
+CREATE INDEX idx_usage_subject ON usage_records (subject, created_at);
+
+-- API-ключи, выпущенные командой --add-api-key (хранится только SHA-256)
+CREATE TABLE api_keys (
+    name        TEXT PRIMARY KEY,
+    key_hash    TEXT NOT NULL UNIQUE,
+    scopes      TEXT NOT NULL,
+    created_at  INTEGER NOT NULL
+);
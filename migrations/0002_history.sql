@@ -0,0 +1,42 @@
+-- История вопросов: параметры генерации и полнотекстовый поиск.
+
+-- Параметры, с которыми AI отвечал (NULL у mock и у записей до миграции)
+ALTER TABLE exchanges ADD COLUMN model TEXT;
+ALTER TABLE exchanges ADD COLUMN temperature REAL;
+ALTER TABLE exchanges ADD COLUMN max_tokens INTEGER;
+ALTER TABLE exchanges ADD COLUMN system_prompt_applied INTEGER NOT NULL DEFAULT 0;
+
+-- Фильтр по источнику в GET /history
+CREATE INDEX idx_exchanges_source ON exchanges (subject, source, created_at);
+
+-- Полнотекстовый индекс по вопросам и ответам. content='exchanges':
+-- сам текст не дублируется, индекс ссылается на строки exchanges по rowid.
+-- remove_diacritics 0: буквы с диакритикой не приравниваются к базовым,
+-- как и в MemoryStorage
+CREATE VIRTUAL TABLE exchanges_fts USING fts5 (
+    question,
+    answer,
+    content = 'exchanges',
+    tokenize = 'unicode61 remove_diacritics 0'
+);
+
+-- Индекс обновляется триггерами (в том числе при каскадном удалении диалога)
+CREATE TRIGGER exchanges_fts_insert AFTER INSERT ON exchanges BEGIN
+    INSERT INTO exchanges_fts (rowid, question, answer)
+    VALUES (new.rowid, new.question, new.answer);
+END;
+
+CREATE TRIGGER exchanges_fts_delete AFTER DELETE ON exchanges BEGIN
+    INSERT INTO exchanges_fts (exchanges_fts, rowid, question, answer)
+    VALUES ('delete', old.rowid, old.question, old.answer);
+END;
+
+CREATE TRIGGER exchanges_fts_update AFTER UPDATE OF question, answer ON exchanges BEGIN
+    INSERT INTO exchanges_fts (exchanges_fts, rowid, question, answer)
+    VALUES ('delete', old.rowid, old.question, old.answer);
+    INSERT INTO exchanges_fts (rowid, question, answer)
+    VALUES (new.rowid, new.question, new.answer);
+END;
+
+-- Записи, сохранённые до этой миграции
+INSERT INTO exchanges_fts (exchanges_fts) VALUES ('rebuild');
@@ -0,0 +1,19 @@
+-- Оценки ответов (POST /feedback) и версия системного промпта в истории.
+
+-- Версия системного промпта, с которым дан ответ (NULL - промпт не применялся)
+ALTER TABLE exchanges ADD COLUMN system_prompt_version TEXT;
+
+CREATE INDEX idx_exchanges_prompt ON exchanges (model, system_prompt_version);
+
+-- Одна оценка на ответ: повторная заменяет предыдущую.
+-- Удаление записи истории удаляет и её оценку
+CREATE TABLE feedback (
+    answer_id   TEXT PRIMARY KEY REFERENCES exchanges (id) ON DELETE CASCADE,
+    subject     TEXT NOT NULL,
+    rating      TEXT NOT NULL CHECK (rating IN ('up', 'down')),
+    comment     TEXT,
+    categories  TEXT NOT NULL,
+    created_at  INTEGER NOT NULL
+);
+
+CREATE INDEX idx_feedback_created ON feedback (created_at);
@@ -0,0 +1,2 @@
+-- Шаблон промпта, по которому задан вопрос (NULL - вопрос без шаблона).
+ALTER TABLE exchanges ADD COLUMN template TEXT;
@@ -0,0 +1,5 @@
+-- Вариант системного промпта в A/B эксперименте (NULL - ответ вне эксперимента).
+ALTER TABLE exchanges ADD COLUMN prompt_variant TEXT;
+
+DROP INDEX idx_exchanges_prompt;
+CREATE INDEX idx_exchanges_prompt ON exchanges (model, prompt_variant, system_prompt_version);
@@ -0,0 +1,7 @@
+-- Имя A/B эксперимента рядом с вариантом: одинаковые id вариантов в разных
+-- экспериментах - разные группы (NULL - ответ вне эксперимента или записан
+-- до этой миграции).
+ALTER TABLE exchanges ADD COLUMN experiment TEXT;
+
+DROP INDEX idx_exchanges_prompt;
+CREATE INDEX idx_exchanges_prompt ON exchanges (model, experiment, prompt_variant, system_prompt_version);
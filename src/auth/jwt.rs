@@ -0,0 +1,356 @@
+//! Проверка JWT-токенов от SSO/OIDC-провайдера.
+//!
+//! `JwtVerifier` хранит набор открытых ключей (JWKS) и проверяет:
+//! подпись (RS256/ES256), издателя (`iss`), аудиторию (`aud`), срок
+//! действия (`exp`/`nbf` с допуском на расхождение часов). Из проверенного
+//! токена извлекаются субъект (`sub`) и роли.
+//!
+//! # Для студентов: Почему ключи ищутся по `kid`?
+//!
+//! Провайдер периодически меняет ключи подписи (ротация). В JWKS может
+//! лежать несколько ключей сразу, а в заголовке токена указан `kid` -
+//! идентификатор нужного. Если `kid` нам незнаком, JWKS, загруженный по URL,
+//! перечитывается (не чаще раза в минуту) - так новые ключи подхватываются
+//! без перезапуска сервера.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use super::AuthError;
+use crate::config::JwtConfig;
+
+/// Минимальный интервал между повторными загрузками JWKS по URL.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Личность, извлечённая из проверенного токена.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtIdentity {
+    /// Claim `sub` - идентификатор пользователя в SSO
+    pub subject: String,
+
+    /// Роли из claim, указанного в `auth.jwt.roles_claim`
+    pub roles: Vec<String>,
+}
+
+/// Проверяющий JWT: конфигурация + текущий набор ключей.
+pub struct JwtVerifier {
+    config: JwtConfig,
+    algorithms: Vec<Algorithm>,
+    keys: RwLock<JwkSet>,
+    last_refresh: RwLock<Instant>,
+}
+
+impl JwtVerifier {
+    /// Создаёт проверяющего с уже загруженным набором ключей.
+    ///
+    /// # Ошибки
+    ///
+    /// `AuthError::KeyStore`, если в `algorithms` указан неизвестный алгоритм.
+    pub fn new(config: JwtConfig, keys: JwkSet) -> Result<Self, AuthError> {
+        let algorithms = config
+            .algorithms
+            .iter()
+            .map(|name| {
+                Algorithm::from_str(name)
+                    .map_err(|_| AuthError::KeyStore(format!("неизвестный алгоритм JWT '{name}'")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            config,
+            algorithms,
+            keys: RwLock::new(keys),
+            last_refresh: RwLock::new(Instant::now()),
+        })
+    }
+
+    /// Загружает JWKS из `jwks_file` или `jwks_url` и создаёт проверяющего.
+    pub async fn load(config: &JwtConfig) -> Result<Self, AuthError> {
+        let keys = fetch_jwks(config).await?;
+        info!("🔐 JWKS загружен, ключей: {}", keys.keys.len());
+        Self::new(config.clone(), keys)
+    }
+
+    /// Проверяет токен и возвращает субъект и роли.
+    pub async fn verify(&self, token: &str) -> Result<JwtIdentity, AuthError> {
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+
+        if !self.algorithms.contains(&header.alg) {
+            return Err(AuthError::InvalidToken(format!(
+                "algorithm {:?} is not allowed",
+                header.alg
+            )));
+        }
+
+        let key = match self.decoding_key(header.kid.as_deref())? {
+            Some(key) => key,
+            None => {
+                // Незнакомый kid: возможно, провайдер сменил ключи
+                self.refresh().await;
+                self.decoding_key(header.kid.as_deref())?
+                    .ok_or_else(|| AuthError::InvalidToken("unknown signing key".to_string()))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.config.leeway_seconds;
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        let data = decode::<Value>(token, &key, &validation).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AuthError::TokenExpired,
+            _ => AuthError::InvalidToken(e.to_string()),
+        })?;
+
+        let subject = data
+            .claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(JwtIdentity {
+            subject,
+            roles: extract_roles(&data.claims, &self.config.roles_claim),
+        })
+    }
+
+    /// Сколько ключей в текущем JWKS (для `/health/ready`).
+    pub fn key_count(&self) -> usize {
+        self.keys.read().expect("JWKS lock poisoned").keys.len()
+    }
+
+    /// Ищет ключ для проверки подписи.
+    ///
+    /// Без `kid` подходит только JWKS из единственного ключа.
+    /// Симметричные ключи (`kty: oct`) игнорируются: мы проверяем
+    /// только асимметричные подписи.
+    fn decoding_key(&self, kid: Option<&str>) -> Result<Option<DecodingKey>, AuthError> {
+        let keys = self.keys.read().expect("JWKS lock poisoned");
+
+        let jwk = match kid {
+            Some(kid) => keys.find(kid),
+            None if keys.keys.len() == 1 => keys.keys.first(),
+            None => return Err(AuthError::InvalidToken("token has no 'kid'".to_string())),
+        };
+
+        match jwk {
+            Some(jwk) if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) => Ok(None),
+            Some(jwk) => DecodingKey::from_jwk(jwk)
+                .map(Some)
+                .map_err(|e| AuthError::InvalidToken(e.to_string())),
+            None => Ok(None),
+        }
+    }
+
+    /// Перечитывает JWKS по URL, но не чаще `JWKS_REFRESH_INTERVAL`.
+    async fn refresh(&self) {
+        if self.config.jwks_url.is_none() || self.config.jwks_file.is_some() {
+            return;
+        }
+        {
+            let mut last = self.last_refresh.write().expect("JWKS lock poisoned");
+            if last.elapsed() < JWKS_REFRESH_INTERVAL {
+                return;
+            }
+            *last = Instant::now();
+        }
+
+        match fetch_jwks(&self.config).await {
+            Ok(keys) => {
+                info!("🔐 JWKS обновлён, ключей: {}", keys.keys.len());
+                *self.keys.write().expect("JWKS lock poisoned") = keys;
+            }
+            Err(e) => warn!("Не удалось обновить JWKS: {}", e),
+        }
+    }
+}
+
+/// Загружает JWKS из файла или по URL (файл имеет приоритет).
+async fn fetch_jwks(config: &JwtConfig) -> Result<JwkSet, AuthError> {
+    if let Some(path) = &config.jwks_file {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| AuthError::KeyStore(format!("{path}: {e}")))?;
+        return serde_json::from_str(&text).map_err(|e| AuthError::KeyStore(format!("{path}: {e}")));
+    }
+
+    if let Some(url) = &config.jwks_url {
+        return reqwest::get(url)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| AuthError::KeyStore(format!("{url}: {e}")))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| AuthError::KeyStore(format!("{url}: {e}")));
+    }
+
+    Err(AuthError::KeyStore(
+        "для auth.jwt нужно указать jwks_file или jwks_url".to_string(),
+    ))
+}
+
+/// Достаёт роли из claim по пути через точку ("realm_access.roles").
+///
+/// Поддерживается как массив строк, так и одна строка через пробел
+/// (так роли/scopes кладут некоторые провайдеры).
+fn extract_roles(claims: &Value, path: &str) -> Vec<String> {
+    let value = path
+        .split('.')
+        .try_fold(claims, |node, part| node.get(part));
+
+    let roles: HashSet<String> = match value {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect(),
+        Some(Value::String(text)) => text.split_whitespace().map(str::to_string).collect(),
+        _ => HashSet::new(),
+    };
+
+    let mut roles: Vec<String> = roles.into_iter().collect();
+    roles.sort();
+    roles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+    use serde_json::json;
+
+    /// Пара ключей ES256, сгенерированная прямо в тесте, и JWKS с открытым ключом.
+    fn generate_keys(kid: &str) -> (EncodingKey, JwkSet) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+
+        // Открытый ключ: 0x04 || X (32 байта) || Y (32 байта)
+        let point = pair.public_key().as_ref();
+        let jwks = json!({
+            "keys": [{
+                "kty": "EC",
+                "crv": "P-256",
+                "alg": "ES256",
+                "kid": kid,
+                "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+                "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+            }]
+        });
+
+        (
+            EncodingKey::from_ec_der(pkcs8.as_ref()),
+            serde_json::from_value(jwks).unwrap(),
+        )
+    }
+
+    fn config() -> JwtConfig {
+        JwtConfig {
+            enabled: true,
+            issuer: Some("https://sso.example.edu".to_string()),
+            audience: Some("gigachat-demo".to_string()),
+            roles_claim: "realm_access.roles".to_string(),
+            ..JwtConfig::default()
+        }
+    }
+
+    fn token(key: &EncodingKey, kid: &str, claims: Value) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(kid.to_string());
+        encode(&header, &claims, key).unwrap()
+    }
+
+    fn claims(exp_offset: i64) -> Value {
+        let now = jsonwebtoken::get_current_timestamp() as i64;
+        json!({
+            "sub": "student-42",
+            "iss": "https://sso.example.edu",
+            "aud": "gigachat-demo",
+            "exp": now + exp_offset,
+            "realm_access": { "roles": ["student", "lab-group-1"] },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_valid_token() {
+        let (key, jwks) = generate_keys("k1");
+        let verifier = JwtVerifier::new(config(), jwks).unwrap();
+
+        let identity = verifier.verify(&token(&key, "k1", claims(300))).await.unwrap();
+        assert_eq!(identity.subject, "student-42");
+        assert_eq!(identity.roles, vec!["lab-group-1", "student"]);
+    }
+
+    #[tokio::test]
+    async fn test_expired_token_and_leeway() {
+        let (key, jwks) = generate_keys("k1");
+        let verifier = JwtVerifier::new(config(), jwks).unwrap();
+
+        // Истёк 10 минут назад - отклоняем
+        let result = verifier.verify(&token(&key, "k1", claims(-600))).await;
+        assert!(matches!(result, Err(AuthError::TokenExpired)));
+
+        // Истёк 30 секунд назад - в пределах допуска leeway (60 с)
+        assert!(verifier.verify(&token(&key, "k1", claims(-30))).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wrong_issuer_audience_and_key() {
+        let (key, jwks) = generate_keys("k1");
+        let verifier = JwtVerifier::new(config(), jwks).unwrap();
+
+        let mut wrong_iss = claims(300);
+        wrong_iss["iss"] = json!("https://evil.example.com");
+        assert!(verifier.verify(&token(&key, "k1", wrong_iss)).await.is_err());
+
+        let mut wrong_aud = claims(300);
+        wrong_aud["aud"] = json!("another-app");
+        assert!(verifier.verify(&token(&key, "k1", wrong_aud)).await.is_err());
+
+        // Подписано другим ключом с тем же kid
+        let (other_key, _) = generate_keys("k1");
+        assert!(verifier.verify(&token(&other_key, "k1", claims(300))).await.is_err());
+
+        // Неизвестный kid
+        assert!(verifier.verify(&token(&key, "k2", claims(300))).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_algorithm() {
+        let (key, jwks) = generate_keys("k1");
+        let config = JwtConfig {
+            algorithms: vec!["RS256".to_string()],
+            ..config()
+        };
+        let verifier = JwtVerifier::new(config, jwks).unwrap();
+        assert!(verifier.verify(&token(&key, "k1", claims(300))).await.is_err());
+    }
+
+    #[test]
+    fn test_extract_roles() {
+        let claims = json!({ "roles": ["b", "a"], "scope": "ask admin" });
+        assert_eq!(extract_roles(&claims, "roles"), vec!["a", "b"]);
+        assert_eq!(extract_roles(&claims, "scope"), vec!["admin", "ask"]);
+        assert!(extract_roles(&claims, "missing.path").is_empty());
+    }
+}
@@ -0,0 +1,721 @@
+//! Модуль аутентификации по API-ключам и JWT.
+//!
+//! Этот модуль содержит request guard `Authenticated<S>`, который проверяет
+//! заголовок `Authorization: Bearer <ключ или JWT>` или `X-API-Key: <ключ>` и
+//! пропускает запрос к обработчику только при наличии нужной области доступа (scope).
+//! Проверка JWT вынесена в подмодуль [`jwt`].
+//!
+//! # Для студентов: Request Guards в Rocket
+//!
+//! Request guard - это тип, который реализует трейт `FromRequest`.
+//! Если guard указан в параметрах обработчика, Rocket СНАЧАЛА вызывает
+//! `FromRequest::from_request`, и только при успехе - сам обработчик:
+//!
+//! ```text
+//! HTTP-запрос
+//!      ↓
+//! ┌──────────────────────────┐
+//! │ Authenticated<Ask>       │  ← проверка ключа и scope
+//! └──────────────────────────┘
+//!      ↓ Success                ↓ Error(401/403)
+//! ┌──────────────┐        ┌──────────────────────┐
+//! │ handler ask  │        │ catcher 401 / 403    │  ← JSON ErrorResponse
+//! └──────────────┘        └──────────────────────┘
+//! ```
+//!
+//! Так проверка доступа пишется ОДИН раз, а обработчики просто объявляют,
+//! что им нужно: `_auth: Authenticated<scopes::Ask>`.
+//!
+//! # Области доступа (scopes)
+//!
+//! У каждого ключа есть список scopes (`["ask"]`, `["ask", "admin"]`, `["*"]`).
+//! Обработчик требует конкретный scope через тип-маркер из модуля [`scopes`].
+//! Для JWT scope сопоставляется с ролями через `auth.jwt.required_roles`.
+
+// ============================================================================
+// ИМПОРТЫ
+// ============================================================================
+
+pub mod jwt;
+
+use std::fmt::Write as _;
+use std::marker::PhantomData;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::config::{validation, ApiKeyConfig, AppConfig, AuthConfig};
+use crate::storage::StoredApiKey;
+
+pub use jwt::JwtVerifier;
+
+// ============================================================================
+// ТИПЫ ОШИБОК
+// ============================================================================
+
+/// Ошибки аутентификации и загрузки хранилища ключей.
+///
+/// Ошибка запроса сохраняется в `request.local_cache`, откуда её забирают
+/// catchers 401/403, чтобы вернуть клиенту точный код ошибки.
+#[derive(Error, Debug, Clone)]
+pub enum AuthError {
+    /// Клиент не передал ни `Authorization: Bearer`, ни `X-API-Key`
+    #[error("API key is missing")]
+    MissingKey,
+
+    /// Переданный ключ не найден в хранилище
+    #[error("API key is invalid")]
+    InvalidKey,
+
+    /// Ключ (или токен) действителен, но нет нужной области доступа / роли
+    #[error("'{name}' is not allowed to access '{scope}'")]
+    InsufficientScope {
+        /// Имя ключа
+        name: String,
+        /// Требуемый scope
+        scope: String,
+    },
+
+    /// JWT не прошёл проверку (подпись, издатель, аудитория, формат)
+    #[error("Bearer token is invalid: {0}")]
+    InvalidToken(String),
+
+    /// Срок действия JWT истёк
+    #[error("Bearer token has expired")]
+    TokenExpired,
+
+    /// Конфигурация (`AppConfig`) не передана в Rocket или аутентификация
+    /// включена, а хранилище ключей (или JWKS) не передано
+    #[error("Authentication backend is not configured")]
+    StoreUnavailable,
+
+    /// Не удалось прочитать файл с ключами
+    #[error("Не удалось загрузить хранилище ключей: {0}")]
+    KeyStore(String),
+}
+
+impl AuthError {
+    /// Машиночитаемый код ошибки для `ErrorResponse`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthError::MissingKey => "MISSING_API_KEY",
+            AuthError::InvalidKey => "INVALID_API_KEY",
+            AuthError::InvalidToken(_) => "INVALID_TOKEN",
+            AuthError::TokenExpired => "TOKEN_EXPIRED",
+            AuthError::InsufficientScope { .. } => "INSUFFICIENT_SCOPE",
+            AuthError::StoreUnavailable | AuthError::KeyStore(_) => "AUTH_NOT_CONFIGURED",
+        }
+    }
+
+    /// HTTP-статус, соответствующий ошибке.
+    pub fn status(&self) -> Status {
+        match self {
+            AuthError::MissingKey
+            | AuthError::InvalidKey
+            | AuthError::InvalidToken(_)
+            | AuthError::TokenExpired => Status::Unauthorized,
+            AuthError::InsufficientScope { .. } => Status::Forbidden,
+            AuthError::StoreUnavailable | AuthError::KeyStore(_) => Status::InternalServerError,
+        }
+    }
+}
+
+// ============================================================================
+// ОБЛАСТИ ДОСТУПА
+// ============================================================================
+
+/// Область доступа, которую требует обработчик.
+///
+/// # Для студентов: Типы-маркеры
+///
+/// Структуры из модуля [`scopes`] не содержат данных - они нужны только
+/// компилятору, чтобы различать `Authenticated<Ask>` и `Authenticated<Health>`.
+/// Константа `NAME` связывает тип со строкой scope в конфигурации.
+pub trait Scope: Send + Sync + 'static {
+    /// Имя scope в конфигурации ключей
+    const NAME: &'static str;
+
+    /// Доступен ли эндпоинт без ключа при данной конфигурации.
+    fn is_public(_config: &AuthConfig) -> bool {
+        false
+    }
+}
+
+/// Типы-маркеры областей доступа.
+pub mod scopes {
+    use super::Scope;
+    use crate::config::AuthConfig;
+
+    /// Доступ к `POST /ask`
+    pub struct Ask;
+
+    impl Scope for Ask {
+        const NAME: &'static str = "ask";
+    }
+
+    /// Доступ к `GET /health` (может быть публичным, см. `auth.public_health`)
+    pub struct Health;
+
+    impl Scope for Health {
+        const NAME: &'static str = "health";
+
+        fn is_public(config: &AuthConfig) -> bool {
+            config.public_health
+        }
+    }
+
+    /// Доступ к администрированию: `POST /admin/reload`
+    pub struct Admin;
+
+    impl Scope for Admin {
+        const NAME: &'static str = "admin";
+    }
+}
+
+// ============================================================================
+// ХРАНИЛИЩЕ КЛЮЧЕЙ
+// ============================================================================
+
+/// Формат отдельного файла с ключами (`auth.keys_file`).
+///
+/// ```toml
+/// [[keys]]
+/// name = "lab-group-1"
+/// key = "..."
+/// scopes = ["ask"]
+/// ```
+#[derive(Debug, Deserialize)]
+struct KeysFile {
+    #[serde(default)]
+    keys: Vec<ApiKeyConfig>,
+}
+
+/// Префикс ключей, выпущенных командой `--add-api-key`.
+const GENERATED_KEY_PREFIX: &str = "gk_";
+
+/// Хранилище API-ключей: ключи из config.toml, из `keys_file`
+/// и выпущенные командой `--add-api-key` (модуль `storage`).
+///
+/// Создаётся один раз при запуске и передаётся в Rocket через `.manage()`.
+#[derive(Debug, Default)]
+pub struct ApiKeyStore {
+    keys: Vec<ApiKeyConfig>,
+    stored: Vec<StoredApiKey>,
+}
+
+impl ApiKeyStore {
+    /// Создаёт хранилище из готового списка ключей.
+    pub fn new(keys: Vec<ApiKeyConfig>) -> Self {
+        Self {
+            keys,
+            stored: Vec::new(),
+        }
+    }
+
+    /// Собирает хранилище из секции `[auth]`: ключи из конфигурации
+    /// дополняются ключами из файла `keys_file` (если он указан).
+    ///
+    /// # Ошибки
+    ///
+    /// Возвращает `AuthError::KeyStore`, если файл не читается или
+    /// два ключа имеют одинаковое имя.
+    pub fn from_config(config: &AuthConfig) -> Result<Self, AuthError> {
+        let mut keys = config.keys.clone();
+
+        if let Some(path) = &config.keys_file {
+            let mut unknown = Vec::new();
+            let file: KeysFile = config::Config::builder()
+                .add_source(config::File::with_name(path))
+                .build()
+                .and_then(|settings| {
+                    serde_ignored::deserialize(settings, |key| unknown.push(validation::key_path(&key)))
+                })
+                .map_err(|e| AuthError::KeyStore(format!("{path}: {e}")))?;
+            if let Some(key) = unknown.first() {
+                return Err(AuthError::KeyStore(format!("{path}: неизвестный ключ '{key}'")));
+            }
+            keys.extend(file.keys);
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            if key.key.expose().trim().is_empty() {
+                return Err(AuthError::KeyStore(format!("ключ '{}' пустой", key.name)));
+            }
+            if keys[..i].iter().any(|other| other.name == key.name) {
+                return Err(AuthError::KeyStore(format!(
+                    "имя ключа '{}' встречается дважды",
+                    key.name
+                )));
+            }
+        }
+
+        Ok(Self::new(keys))
+    }
+
+    /// Добавляет ключи из постоянного хранилища.
+    ///
+    /// # Ошибки
+    ///
+    /// `AuthError::KeyStore`, если имя уже занято ключом из конфигурации.
+    pub fn with_stored(mut self, stored: Vec<StoredApiKey>) -> Result<Self, AuthError> {
+        if let Some(key) = stored
+            .iter()
+            .find(|key| self.keys.iter().any(|other| other.name == key.name))
+        {
+            return Err(AuthError::KeyStore(format!(
+                "имя ключа '{}' есть и в конфигурации, и в хранилище",
+                key.name
+            )));
+        }
+        self.stored = stored;
+        Ok(self)
+    }
+
+    /// Ищет ключ по значению, переданному клиентом, и возвращает его владельца.
+    ///
+    /// Сравнение выполняется за постоянное время (см. `constant_time_eq`),
+    /// чтобы по времени ответа нельзя было подобрать ключ посимвольно.
+    /// Ключи из хранилища сравниваются по SHA-256 (см. [`hash_key`]).
+    pub fn find(&self, presented: &str) -> Option<Principal> {
+        if let Some(key) = self
+            .keys
+            .iter()
+            .find(|key| constant_time_eq(key.key.expose().as_bytes(), presented.as_bytes()))
+        {
+            return Some(Principal::api_key(&key.name, &key.scopes));
+        }
+
+        let hash = hash_key(presented);
+        self.stored
+            .iter()
+            .find(|key| constant_time_eq(key.key_hash.as_bytes(), hash.as_bytes()))
+            .map(|key| Principal::api_key(&key.name, &key.scopes))
+    }
+
+    /// Количество ключей в хранилище.
+    pub fn len(&self) -> usize {
+        self.keys.len() + self.stored.len()
+    }
+
+    /// Пустое ли хранилище.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Выпускает новый случайный API-ключ: `gk_` + 32 случайных байта в base64url.
+pub fn generate_key() -> String {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("системный генератор случайных чисел недоступен");
+    format!("{GENERATED_KEY_PREFIX}{}", URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// SHA-256 ключа в hex - в таком виде ключ лежит в хранилище.
+///
+/// # Для студентов: Почему хеш?
+///
+/// Если файл базы утечёт, по хешу нельзя восстановить ключ и обратиться
+/// к API. Ключ случайный и длинный, поэтому соль и медленный хеш
+/// (как для паролей) не нужны.
+pub fn hash_key(key: &str) -> String {
+    digest(&SHA256, key.as_bytes())
+        .as_ref()
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Сравнивает две последовательности байт за время, не зависящее от
+/// позиции первого несовпадения.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Каким способом клиент подтвердил личность.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    /// Аутентификация не требовалась (выключена или публичный эндпоинт)
+    Anonymous,
+    /// API-ключ из `[auth]`
+    ApiKey,
+    /// JWT от SSO-провайдера из `[auth.jwt]`
+    Jwt,
+}
+
+/// Аутентифицированный клиент.
+#[derive(Debug, Clone)]
+pub struct Principal {
+    /// Кто это: имя API-ключа, claim `sub` из JWT или "anonymous"
+    pub subject: String,
+
+    /// Области доступа API-ключа (для JWT пусто - используются роли)
+    pub scopes: Vec<String>,
+
+    /// Роли из JWT (для API-ключей пусто)
+    pub roles: Vec<String>,
+
+    /// Способ аутентификации
+    pub method: AuthMethod,
+}
+
+impl Principal {
+    /// Клиент с API-ключом.
+    fn api_key(name: &str, scopes: &[String]) -> Self {
+        Self {
+            subject: name.to_string(),
+            scopes: scopes.to_vec(),
+            roles: Vec::new(),
+            method: AuthMethod::ApiKey,
+        }
+    }
+
+    /// Клиент без ключа - используется, когда аутентификация выключена
+    /// или эндпоинт публичный.
+    pub fn anonymous() -> Self {
+        Self {
+            subject: "anonymous".to_string(),
+            scopes: vec!["*".to_string()],
+            roles: Vec::new(),
+            method: AuthMethod::Anonymous,
+        }
+    }
+
+    /// Есть ли у клиента указанная область доступа.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == "*" || s == scope)
+    }
+
+    /// Есть ли у клиента хотя бы одна из указанных ролей.
+    pub fn has_any_role(&self, roles: &[String]) -> bool {
+        self.roles.iter().any(|role| roles.contains(role))
+    }
+}
+
+/// Request guard: пропускает запрос, только если клиент предъявил
+/// действующий API-ключ со scope `S` или JWT с подходящей ролью.
+///
+/// # Пример
+///
+/// ```rust,ignore
+/// #[post("/ask", data = "<request>")]
+/// pub async fn ask(auth: Authenticated<scopes::Ask>, request: Json<AskRequest>) {
+///     info!("Вопрос от {} с ролями {:?}", auth.principal.subject, auth.principal.roles);
+/// }
+/// ```
+pub struct Authenticated<S: Scope> {
+    /// Кто выполнил запрос
+    pub principal: Principal,
+    _scope: PhantomData<S>,
+}
+
+/// Учётные данные, найденные в заголовках запроса.
+enum Credentials<'r> {
+    /// `Authorization: Bearer <jwt>` - три части, разделённые точками
+    Jwt(&'r str),
+    /// `Authorization: Bearer <ключ>` или `X-API-Key: <ключ>`
+    ApiKey(&'r str),
+}
+
+/// Извлекает учётные данные из `Authorization: Bearer ...` или `X-API-Key`.
+///
+/// Bearer-значение считается JWT, только если проверка JWT включена
+/// и значение похоже на токен (`header.payload.signature`).
+fn credentials<'r>(req: &'r Request<'_>, jwt_enabled: bool) -> Option<Credentials<'r>> {
+    let bearer = req
+        .headers()
+        .get_one("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    if let Some(token) = bearer {
+        if jwt_enabled && token.split('.').count() == 3 {
+            return Some(Credentials::Jwt(token));
+        }
+        return Some(Credentials::ApiKey(token));
+    }
+
+    req.headers()
+        .get_one("X-API-Key")
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(Credentials::ApiKey)
+}
+
+/// Проверяет API-ключ по хранилищу.
+fn authenticate_key(store: &ApiKeyStore, presented: Option<&str>) -> Result<Principal, AuthError> {
+    let presented = presented.ok_or(AuthError::MissingKey)?;
+    store.find(presented).ok_or(AuthError::InvalidKey)
+}
+
+/// Проверяет, разрешён ли клиенту scope.
+///
+/// - API-ключ: scope должен быть в списке `scopes` ключа (или `"*"`);
+/// - JWT: если для scope заданы `auth.jwt.required_roles`, нужна хотя бы
+///   одна из этих ролей, иначе достаточно валидного токена. Исключение -
+///   `admin`: без явно заданных ролей он закрыт для всех токенов, иначе
+///   любой студент из SSO мог бы перезагружать конфигурацию.
+fn authorize(principal: &Principal, scope: &str, config: &AuthConfig) -> Result<(), AuthError> {
+    let allowed = match principal.method {
+        AuthMethod::Anonymous | AuthMethod::ApiKey => principal.has_scope(scope),
+        AuthMethod::Jwt => config
+            .jwt
+            .required_roles
+            .get(scope)
+            .map_or(scope != scopes::Admin::NAME, |roles| principal.has_any_role(roles)),
+    };
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(AuthError::InsufficientScope {
+            name: principal.subject.clone(),
+            scope: scope.to_string(),
+        })
+    }
+}
+
+/// Полная проверка запроса: аутентификация + авторизация для scope.
+async fn authenticate(
+    req: &Request<'_>,
+    config: &AuthConfig,
+    scope: &str,
+) -> Result<Principal, AuthError> {
+    let principal = match credentials(req, config.jwt.enabled) {
+        Some(Credentials::Jwt(token)) => {
+            let verifier = req
+                .rocket()
+                .state::<JwtVerifier>()
+                .ok_or(AuthError::StoreUnavailable)?;
+            let identity = verifier.verify(token).await?;
+            Principal {
+                subject: identity.subject,
+                scopes: Vec::new(),
+                roles: identity.roles,
+                method: AuthMethod::Jwt,
+            }
+        }
+        other => {
+            let store = req
+                .rocket()
+                .state::<ApiKeyStore>()
+                .ok_or(AuthError::StoreUnavailable)?;
+            let presented = match other {
+                Some(Credentials::ApiKey(key)) => Some(key),
+                _ => None,
+            };
+            authenticate_key(store, presented)?
+        }
+    };
+
+    authorize(&principal, scope, config)?;
+    Ok(principal)
+}
+
+#[rocket::async_trait]
+impl<'r, S: Scope> FromRequest<'r> for Authenticated<S> {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        // Без AppConfig неизвестно, включена ли аутентификация: отказываем
+        // (500), а не пропускаем всех - ошибка сборки Rocket не должна
+        // открывать защищённые эндпоинты.
+        let auth = req.rocket().state::<AppConfig>().map(|config| &config.auth);
+
+        let result = match auth {
+            None => Err(AuthError::StoreUnavailable),
+            Some(auth) if !auth.enabled || S::is_public(auth) => Ok(Principal::anonymous()),
+            Some(auth) => authenticate(req, auth, S::NAME).await,
+        };
+
+        match result {
+            Ok(principal) => {
+                // Сохраняем клиента в кеше запроса: его смогут прочитать
+                // другие guards и fairings (например, для логирования).
+                req.local_cache(|| Some(principal.clone()));
+                Outcome::Success(Authenticated {
+                    principal,
+                    _scope: PhantomData,
+                })
+            }
+            Err(e) => {
+                warn!("Запрос {} {} отклонён: {}", req.method(), req.uri(), e);
+                let status = e.status();
+                req.local_cache(|| Some(e.clone()));
+                Outcome::Error((status, e))
+            }
+        }
+    }
+}
+
+/// Возвращает ошибку аутентификации, сохранённую guard'ом в кеше запроса.
+///
+/// Используется catchers 401/403 для формирования `ErrorResponse`.
+pub fn cached_error(req: &Request<'_>) -> Option<AuthError> {
+    req.local_cache(|| None::<AuthError>).clone()
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, value: &str, scopes: &[&str]) -> ApiKeyConfig {
+        ApiKeyConfig {
+            name: name.to_string(),
+            key: value.into(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn store() -> ApiKeyStore {
+        ApiKeyStore::new(vec![
+            key("frontend", "front-secret", &["ask"]),
+            key("teacher", "teacher-secret", &["*"]),
+        ])
+    }
+
+    #[test]
+    fn test_valid_key_with_scope() {
+        let principal = authenticate_key(&store(), Some("front-secret")).unwrap();
+        assert_eq!(principal.subject, "frontend");
+        assert!(authorize(&principal, "ask", &AuthConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_missing_and_invalid_key() {
+        assert!(matches!(
+            authenticate_key(&store(), None),
+            Err(AuthError::MissingKey)
+        ));
+        assert!(matches!(
+            authenticate_key(&store(), Some("front-secreT")),
+            Err(AuthError::InvalidKey)
+        ));
+    }
+
+    #[test]
+    fn test_insufficient_scope_and_wildcard() {
+        let config = AuthConfig::default();
+        let frontend = authenticate_key(&store(), Some("front-secret")).unwrap();
+        let err = authorize(&frontend, "admin", &config).unwrap_err();
+        assert_eq!(err.status(), Status::Forbidden);
+        assert_eq!(err.code(), "INSUFFICIENT_SCOPE");
+
+        let teacher = authenticate_key(&store(), Some("teacher-secret")).unwrap();
+        assert!(authorize(&teacher, "admin", &config).is_ok());
+    }
+
+    #[test]
+    fn test_jwt_roles_restrict_scopes() {
+        let mut config = AuthConfig::default();
+        config
+            .jwt
+            .required_roles
+            .insert("admin".to_string(), vec!["teacher".to_string()]);
+
+        let student = Principal {
+            subject: "student-42".to_string(),
+            scopes: Vec::new(),
+            roles: vec!["student".to_string()],
+            method: AuthMethod::Jwt,
+        };
+
+        // Для "ask" ограничений нет - хватает валидного токена
+        assert!(authorize(&student, "ask", &config).is_ok());
+        assert!(authorize(&student, "admin", &config).is_err());
+
+        let teacher = Principal {
+            roles: vec!["teacher".to_string()],
+            ..student
+        };
+        assert!(authorize(&teacher, "admin", &config).is_ok());
+
+        // Без required_roles.admin админка закрыта для любого токена
+        config.jwt.required_roles.clear();
+        assert!(authorize(&teacher, "ask", &config).is_ok());
+        assert!(authorize(&teacher, "admin", &config).is_err());
+    }
+
+    #[test]
+    fn test_store_rejects_duplicate_names() {
+        let config = AuthConfig {
+            enabled: true,
+            keys: vec![key("a", "1", &[]), key("a", "2", &[])],
+            ..AuthConfig::default()
+        };
+        assert!(ApiKeyStore::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_keys_file_rejects_unknown_key() {
+        let path = std::env::temp_dir().join(format!("keys-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "[[keys]]\nname = \"a\"\nkey = \"1\"\nscope = [\"ask\"]\n").unwrap();
+        let config = AuthConfig {
+            enabled: true,
+            keys_file: Some(path.to_string_lossy().into_owned()),
+            ..AuthConfig::default()
+        };
+
+        let error = ApiKeyStore::from_config(&config).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(error.to_string().contains("keys[0].scope"), "{error}");
+    }
+
+    #[test]
+    fn test_stored_keys_match_by_hash() {
+        let key = generate_key();
+        assert!(key.starts_with(GENERATED_KEY_PREFIX));
+        assert_ne!(key, generate_key());
+
+        let stored = StoredApiKey {
+            name: "lab-group-1".to_string(),
+            key_hash: hash_key(&key),
+            scopes: vec!["ask".to_string()],
+            created_at: 0,
+        };
+        assert_eq!(stored.key_hash.len(), 64);
+
+        let keys = store().with_stored(vec![stored.clone()]).unwrap();
+        assert_eq!(keys.len(), 3);
+        let principal = authenticate_key(&keys, Some(&key)).unwrap();
+        assert_eq!(principal.subject, "lab-group-1");
+        assert_eq!(principal.method, AuthMethod::ApiKey);
+        // Хеш сам по себе ключом не является
+        assert!(authenticate_key(&keys, Some(&stored.key_hash)).is_err());
+
+        let clash = StoredApiKey {
+            name: "frontend".to_string(),
+            ..stored
+        };
+        assert!(store().with_stored(vec![clash]).is_err());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+}
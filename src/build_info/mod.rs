@@ -0,0 +1,92 @@
+//! Модуль сведений о сборке и времени работы сервера.
+//!
+//! Нужен, чтобы по ответу `/health` было понятно, КАКАЯ версия кода
+//! запущена: хеш git-коммита (передаётся из `build.rs`) и включённые
+//! cargo-фичи.
+//!
+//! # Для студентов: `cfg!` против `#[cfg]`
+//!
+//! `#[cfg(feature = "x")]` убирает код из сборки целиком, а макрос
+//! `cfg!(feature = "x")` просто превращается в `true` или `false` -
+//! им удобно проверять фичи в обычных выражениях.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::models::BuildInfo;
+
+/// Короткий хеш git-коммита сборки ("unknown" при сборке без `.git`).
+pub const GIT_HASH: &str = env!("GIT_HASH");
+
+/// Момент запуска сервера.
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Запоминает момент запуска - вызывается в начале `main`.
+///
+/// Если не вызвать, отсчёт начнётся с первого обращения к [`uptime`].
+pub fn mark_started() {
+    STARTED.get_or_init(Instant::now);
+}
+
+/// Сколько времени работает сервер.
+pub fn uptime() -> Duration {
+    STARTED.get_or_init(Instant::now).elapsed()
+}
+
+/// Cargo-фичи, с которыми собрано приложение.
+pub fn enabled_features() -> Vec<&'static str> {
+    [
+        ("gigachat", cfg!(feature = "gigachat")),
+        ("mock", cfg!(feature = "mock")),
+        ("otel", cfg!(feature = "otel")),
+    ]
+    .into_iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Сведения о сборке для ответа `/health`.
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        git_hash: GIT_HASH.to_string(),
+        features: enabled_features().into_iter().map(str::to_string).collect(),
+    }
+}
+
+/// Время в виде "1d 2h 3m 4s" (нулевые старшие части опускаются).
+pub fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, hours, minutes, seconds) =
+        (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60, secs % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{seconds}s"),
+        (0, 0, _) => format!("{minutes}m {seconds}s"),
+        (0, _, _) => format!("{hours}h {minutes}m {seconds}s"),
+        _ => format!("{days}d {hours}h {minutes}m {seconds}s"),
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(Duration::from_secs(5)), "5s");
+        assert_eq!(format_uptime(Duration::from_secs(125)), "2m 5s");
+        assert_eq!(format_uptime(Duration::from_secs(3_600)), "1h 0m 0s");
+        assert_eq!(format_uptime(Duration::from_secs(90_061)), "1d 1h 1m 1s");
+    }
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert!(!info.git_hash.is_empty());
+        assert_eq!(info.features.contains(&"otel".to_string()), cfg!(feature = "otel"));
+    }
+}
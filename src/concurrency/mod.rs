@@ -0,0 +1,438 @@
+//! Модуль ограничения параллельных запросов к AI (concurrency limiting).
+//!
+//! У аккаунта GigaChat есть лимит ОДНОВРЕМЕННЫХ запросов: если превысить
+//! его, API начинает отвечать ошибками. Rate limit (модуль `rate_limit`)
+//! здесь не помогает - он считает запросы за минуту, а не параллельные.
+//!
+//! [`ConcurrencyLimitedService`] - декоратор над любым [`AiService`]:
+//!
+//! ```text
+//!                    ┌──────────── max_concurrent ────────────┐
+//! запрос ──► очередь ──► [ слот ] [ слот ] [ слот ] [ слот ] ──► GigaChat
+//!              │ (FIFO, не длиннее max_queue)
+//!              ├─► очередь полна              → 503 SERVER_BUSY
+//!              └─► ждали дольше queue_timeout → 503 SERVER_BUSY
+//! ```
+//!
+//! # Для студентов: Паттерн "Декоратор"
+//!
+//! Декоратор реализует тот же трейт, что и оборачиваемый объект, и добавляет
+//! к нему поведение. Обработчик `/ask` по-прежнему видит `Box<dyn AiService>`
+//! и не знает, что запрос сначала постоял в очереди.
+//!
+//! # Для студентов: Семафор
+//!
+//! `tokio::sync::Semaphore` - счётчик свободных "слотов". `acquire()` ждёт,
+//! пока слот освободится, а возвращённый permit отдаёт слот при `drop`.
+//! Семафор tokio честный (fair): ожидающие получают слоты в порядке очереди.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use rocket::request::{FromRequest, Outcome, Request};
+use tokio::sync::Semaphore;
+use tracing::warn;
+
+use crate::config::ConcurrencyConfig;
+use crate::models::ConcurrencyStatus;
+use crate::services::{AiService, AiServiceError, AskContext};
+
+// ============================================================================
+// СТАТИСТИКА
+// ============================================================================
+
+/// Счётчики очереди, общие для сервиса и `/health`.
+///
+/// Атомарные типы позволяют обновлять счётчики из разных потоков без `Mutex`.
+#[derive(Debug, Default)]
+pub struct ConcurrencyStats {
+    max_concurrent: usize,
+    max_queue: usize,
+    in_flight: AtomicUsize,
+    queued: AtomicUsize,
+    waited_total: AtomicU64,
+    wait_ms_total: AtomicU64,
+    wait_ms_max: AtomicU64,
+    rejected_total: AtomicU64,
+    timed_out_total: AtomicU64,
+}
+
+impl ConcurrencyStats {
+    /// Снимок текущего состояния для `/health`.
+    pub fn snapshot(&self) -> ConcurrencyStatus {
+        let waited = self.waited_total.load(Ordering::Relaxed);
+        let wait_ms_total = self.wait_ms_total.load(Ordering::Relaxed);
+        ConcurrencyStatus {
+            max_concurrent: self.max_concurrent,
+            max_queue: self.max_queue,
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queue_depth: self.queued.load(Ordering::Relaxed),
+            avg_wait_ms: wait_ms_total.checked_div(waited).unwrap_or(0),
+            max_wait_ms: self.wait_ms_max.load(Ordering::Relaxed),
+            rejected_total: self.rejected_total.load(Ordering::Relaxed),
+            timed_out_total: self.timed_out_total.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_wait(&self, waited: Duration) {
+        let ms = u64::try_from(waited.as_millis()).unwrap_or(u64::MAX);
+        self.waited_total.fetch_add(1, Ordering::Relaxed);
+        self.wait_ms_total.fetch_add(ms, Ordering::Relaxed);
+        self.wait_ms_max.fetch_max(ms, Ordering::Relaxed);
+    }
+}
+
+/// Уменьшает счётчик при выходе из области видимости.
+///
+/// Если клиент отключился, Rocket отменяет future обработчика, и код после
+/// `.await` не выполнится. `Drop` вызывается в любом случае, поэтому
+/// счётчики не "застрянут".
+struct CounterGuard<'a>(&'a AtomicUsize);
+
+impl<'a> CounterGuard<'a> {
+    fn enter(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+
+    /// Как [`CounterGuard::enter`], но только если счётчик меньше `limit`.
+    ///
+    /// Проверка и увеличение - одна атомарная операция (`fetch_update`):
+    /// при отдельных `load` и `fetch_add` два запроса могли бы увидеть
+    /// последнее свободное место одновременно и оба встать в очередь.
+    fn try_enter(counter: &'a AtomicUsize, limit: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| (n < limit).then_some(n + 1))
+            .ok()
+            .map(|_| Self(counter))
+    }
+}
+
+impl Drop for CounterGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// ============================================================================
+// ДЕКОРАТОР AI СЕРВИСА
+// ============================================================================
+
+/// Семафор и счётчики очереди, общие для всех обёрнутых сервисов.
+///
+/// При перезагрузке конфигурации (модуль `reload`) AI сервис создаётся
+/// заново, а лимитер остаётся прежним: запросы к старому и новому сервису
+/// делят одни и те же слоты.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimiter {
+    semaphore: Arc<Semaphore>,
+    queue_timeout: Duration,
+    stats: Arc<ConcurrencyStats>,
+}
+
+impl ConcurrencyLimiter {
+    /// Создаёт лимитер по секции `[concurrency]`.
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        // Семафор на 0 слотов никогда не пропустит запрос
+        let max_concurrent = config.max_concurrent.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(max_concurrent)),
+            queue_timeout: Duration::from_secs(config.queue_timeout_seconds),
+            stats: Arc::new(ConcurrencyStats {
+                max_concurrent,
+                max_queue: config.max_queue,
+                ..ConcurrencyStats::default()
+            }),
+        }
+    }
+
+    /// Оборачивает сервис `inner` этим лимитером.
+    pub fn wrap(&self, inner: Box<dyn AiService>) -> ConcurrencyLimitedService {
+        ConcurrencyLimitedService {
+            inner,
+            limiter: self.clone(),
+        }
+    }
+
+    /// Общие счётчики; передаются в Rocket через `.manage()` для `/health`.
+    pub fn stats(&self) -> Arc<ConcurrencyStats> {
+        Arc::clone(&self.stats)
+    }
+}
+
+/// AI сервис с ограничением числа одновременных запросов и очередью.
+pub struct ConcurrencyLimitedService {
+    inner: Box<dyn AiService>,
+    limiter: ConcurrencyLimiter,
+}
+
+#[async_trait]
+impl AiService for ConcurrencyLimitedService {
+    async fn ask(&self, question: &str, ctx: &AskContext) -> Result<String, AiServiceError> {
+        let ConcurrencyLimiter {
+            semaphore,
+            queue_timeout,
+            stats,
+        } = &self.limiter;
+
+        // Быстрый путь: свободный слот есть и очереди нет.
+        // (try_acquire не обгоняет очередь: пока есть ожидающие,
+        // освободившиеся слоты достаются им)
+        let _permit = match semaphore.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                // Сначала занимаем место в очереди, потом ждём слот
+                let Some(_queued) = CounterGuard::try_enter(&stats.queued, stats.max_queue) else {
+                    stats.rejected_total.fetch_add(1, Ordering::Relaxed);
+                    warn!("AI queue is full ({} waiting), rejecting request", stats.max_queue);
+                    return Err(AiServiceError::Busy(
+                        "очередь запросов заполнена".to_string(),
+                    ));
+                };
+                let started = Instant::now();
+                let acquired =
+                    tokio::time::timeout(*queue_timeout, semaphore.acquire()).await;
+                stats.record_wait(started.elapsed());
+
+                match acquired {
+                    Ok(Ok(permit)) => permit,
+                    Ok(Err(_)) => {
+                        return Err(AiServiceError::InternalError(
+                            "семафор очереди закрыт".to_string(),
+                        ))
+                    }
+                    Err(_) => {
+                        stats.timed_out_total.fetch_add(1, Ordering::Relaxed);
+                        warn!("AI queue wait exceeded {:?}", queue_timeout);
+                        return Err(AiServiceError::Busy(format!(
+                            "ожидание в очереди превысило {} с",
+                            queue_timeout.as_secs()
+                        )));
+                    }
+                }
+            }
+        };
+
+        let _in_flight = CounterGuard::enter(&stats.in_flight);
+        self.inner.ask(question, ctx).await
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn system_prompt_applied(&self) -> bool {
+        self.inner.system_prompt_applied()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+
+    /// Проверка не занимает слот: готовность не зависит от загрузки очереди.
+    async fn probe(&self) -> Result<(), AiServiceError> {
+        self.inner.probe().await
+    }
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Снимок очереди для обработчика `/health`.
+///
+/// `None`, если ограничение выключено и счётчики не переданы в `.manage()`.
+/// Guard никогда не отклоняет запрос.
+pub struct QueueSnapshot(pub Option<ConcurrencyStatus>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for QueueSnapshot {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let stats = req.rocket().state::<Arc<ConcurrencyStats>>();
+        Outcome::Success(QueueSnapshot(stats.map(|stats| stats.snapshot())))
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::Notify;
+
+    /// Сервис, который "висит", пока тест не разрешит ему ответить.
+    struct BlockingService {
+        release: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl AiService for BlockingService {
+        async fn ask(&self, question: &str, _ctx: &AskContext) -> Result<String, AiServiceError> {
+            self.release.notified().await;
+            Ok(question.to_string())
+        }
+
+        fn name(&self) -> &str {
+            "Blocking"
+        }
+
+        fn system_prompt_applied(&self) -> bool {
+            false
+        }
+    }
+
+    fn limited(
+        max_queue: usize,
+        timeout: u64,
+    ) -> (Arc<ConcurrencyLimitedService>, Arc<ConcurrencyStats>, Arc<Notify>) {
+        let release = Arc::new(Notify::new());
+        let config = ConcurrencyConfig {
+            enabled: true,
+            max_concurrent: 1,
+            max_queue,
+            queue_timeout_seconds: timeout,
+        };
+        let limiter = ConcurrencyLimiter::new(&config);
+        let service = limiter.wrap(Box::new(BlockingService { release: Arc::clone(&release) }));
+        (Arc::new(service), limiter.stats(), release)
+    }
+
+    /// Ждёт, пока в очереди окажется `depth` запросов.
+    async fn wait_for_queue(stats: &ConcurrencyStats, depth: usize) {
+        while stats.snapshot().queue_depth < depth {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_queue_full() {
+        let (service, stats, release) = limited(1, 30);
+
+        let first = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { service.ask("first", &AskContext::default()).await }
+        });
+        let second = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { service.ask("second", &AskContext::default()).await }
+        });
+        wait_for_queue(&stats, 1).await;
+
+        // Один выполняется, один ждёт - третьему места нет
+        let third = service.ask("third", &AskContext::default()).await;
+        assert!(matches!(third, Err(AiServiceError::Busy(_))));
+        assert_eq!(stats.snapshot().rejected_total, 1);
+        assert_eq!(stats.snapshot().in_flight, 1);
+
+        release.notify_one();
+        assert_eq!(first.await.unwrap().unwrap(), "first");
+        release.notify_one();
+        assert_eq!(second.await.unwrap().unwrap(), "second");
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.in_flight, 0);
+        assert_eq!(snapshot.queue_depth, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_queue_limit_under_concurrent_callers() {
+        const CALLERS: usize = 64;
+        let (service, stats, release) = limited(2, 30);
+
+        let first = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { service.ask("first", &AskContext::default()).await }
+        });
+        while stats.snapshot().in_flight == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Все вызывающие стартуют одновременно и борются за два места
+        let barrier = Arc::new(tokio::sync::Barrier::new(CALLERS));
+        let callers: Vec<_> = (0..CALLERS)
+            .map(|i| {
+                let service = Arc::clone(&service);
+                let barrier = Arc::clone(&barrier);
+                tokio::spawn(async move {
+                    barrier.wait().await;
+                    service.ask(&i.to_string(), &AskContext::default()).await
+                })
+            })
+            .collect();
+        let settled = || {
+            let snapshot = stats.snapshot();
+            snapshot.rejected_total as usize + snapshot.queue_depth == CALLERS
+        };
+        while !settled() {
+            tokio::task::yield_now().await;
+        }
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.queue_depth, 2);
+        assert_eq!(snapshot.rejected_total, CALLERS as u64 - 2);
+
+        release.notify_one();
+        assert!(first.await.unwrap().is_ok());
+        while !callers.iter().all(|caller| caller.is_finished()) {
+            release.notify_one();
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let mut answered = 0;
+        for caller in callers {
+            answered += usize::from(caller.await.unwrap().is_ok());
+        }
+        assert_eq!(answered, 2);
+        assert_eq!(stats.snapshot().queue_depth, 0);
+    }
+
+    #[tokio::test]
+    async fn test_queue_timeout() {
+        let (service, stats, release) = limited(4, 1);
+
+        let first = tokio::spawn({
+            let service = Arc::clone(&service);
+            async move { service.ask("first", &AskContext::default()).await }
+        });
+        tokio::task::yield_now().await;
+
+        let second = service.ask("second", &AskContext::default()).await;
+        assert!(matches!(second, Err(AiServiceError::Busy(_))));
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.timed_out_total, 1);
+        assert_eq!(snapshot.queue_depth, 0);
+        assert!(snapshot.max_wait_ms >= 1000);
+
+        release.notify_one();
+        assert!(first.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_wrapped_services_share_slots() {
+        // Так работает перезагрузка: новый сервис, тот же лимитер
+        let (old, stats, release) = limited(0, 30);
+        let limiter = old.limiter.clone();
+        let new = limiter.wrap(Box::new(BlockingService { release: Arc::clone(&release) }));
+
+        let first = tokio::spawn({
+            let old = Arc::clone(&old);
+            async move { old.ask("first", &AskContext::default()).await }
+        });
+        while stats.snapshot().in_flight == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Единственный слот занят старым сервисом, очереди нет
+        let second = new.ask("second", &AskContext::default()).await;
+        assert!(matches!(second, Err(AiServiceError::Busy(_))));
+
+        release.notify_one();
+        assert!(first.await.unwrap().is_ok());
+    }
+}
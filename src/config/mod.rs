@@ -17,8 +17,10 @@
 //! Приоритет (от низкого к высокому):
 //!
 //! 1. config.toml         - базовые настройки (в репозитории)
-//! 2. .env файл           - локальные переопределения (НЕ в репозитории)
-//! 3. Переменные окружения - настройки на сервере/в Docker
+//! 2. Профиль (APP_PROFILE) - встроенные настройки профиля и config.<профиль>.toml
+//! 3. .env файл           - локальные переопределения (НЕ в репозитории)
+//! 4. Переменные окружения - PORT и APP_<СЕКЦИЯ>__<КЛЮЧ>, например
+//!    APP_GIGACHAT__MAX_TOKENS=1024 (см. модуль overrides)
 //!
 //! Более высокий приоритет ПЕРЕОПРЕДЕЛЯЕТ более низкий.
 //! ```
@@ -42,14 +44,32 @@
 // ============================================================================
 
 // Deserialize - трейт для создания структуры из внешних данных (TOML, JSON и т.д.)
-use serde::Deserialize;
+// Serialize - обратное преобразование, нужно для показа в GET /admin/config
+use serde::{Deserialize, Serialize};
 
 // std::env - работа с переменными окружения операционной системы
 use std::env;
 
+// HashMap - словарь "scope → допустимые роли" для JWT
+use std::collections::{BTreeMap, HashMap};
+
 // thiserror - макрос для создания типов ошибок
 use thiserror::Error;
 
+// Secret - обёртка для токенов и ключей: в Debug и JSON выводится как ***
+use crate::secrets::{Secret, SecretError, Secrets};
+
+// Проверка диапазонов и связей между полями (src/config/validation.rs)
+pub mod validation;
+pub use validation::ValidationReport;
+
+// Профили и слои: config.toml → профиль → config.<профиль>.toml → APP_*
+pub mod profile;
+pub use profile::ConfigOrigin;
+
+// Переменные окружения APP_<СЕКЦИЯ>__<КЛЮЧ> и PORT
+pub mod overrides;
+
 // ============================================================================
 // ТИПЫ ОШИБОК
 // ============================================================================
@@ -75,9 +95,13 @@ pub enum ConfigError {
     #[error("Не удалось загрузить конфигурацию: {0}")]
     LoadError(#[from] config::ConfigError),  // #[from] - автоматическое преобразование
 
-    /// Ошибка при чтении переменной окружения (отсутствует или невалидна)
-    #[error("Не удалось прочитать переменную окружения: {0}")]
-    EnvError(#[from] env::VarError),
+    /// Секрет (например, токен GigaChat) не найден или не читается
+    #[error("Не удалось получить секрет: {0}")]
+    SecretError(#[from] SecretError),
+
+    /// Файл разобран, но значения некорректны (все проблемы сразу)
+    #[error("{0}")]
+    Invalid(#[from] ValidationReport),
 }
 
 // ============================================================================
@@ -92,7 +116,7 @@ pub enum ConfigError {
 ///
 /// ```text
 /// // config/mod.rs (этот файл):
-/// #[derive(Debug, Deserialize, Clone)]    // ← Clone нужен!
+/// #[derive(Debug, Deserialize, Clone, PartialEq)] // ← Clone нужен!
 /// // НЕТ #[serde(crate = "rocket::serde")] // ← НЕ нужен!
 ///
 /// // models/mod.rs:
@@ -109,6 +133,9 @@ pub enum ConfigError {
 ///
 /// Без Clone пришлось бы использовать `Arc<AppConfig>` или передавать по ссылке.
 ///
+/// `PartialEq` нужен модулю `reload`: при перезагрузке он сравнивает секции
+/// старой и новой конфигурации, чтобы сообщить, что изменилось.
+///
 /// ## Почему НЕТ `#[serde(crate = "rocket::serde")]`?
 ///
 /// Эти структуры используются с библиотекой `config`, а не с Rocket.
@@ -120,9 +147,13 @@ pub enum ConfigError {
 /// config/mod.rs  →  config  →  serde (стандартный)
 /// ```
 ///
-/// ## Почему только Deserialize?
+/// ## Зачем Serialize?
 ///
-/// Конфигурацию мы только ЧИТАЕМ из файла, никогда не записываем обратно.
+/// Из файла конфигурация только ЧИТАЕТСЯ, обратно не записывается.
+/// `Serialize` нужен `GET /admin/config`: работающая конфигурация
+/// отдаётся как JSON (см. `profile::describe`). Секреты хранятся в типе
+/// [`Secret`], который сериализуется как `"***"`, поэтому ключи API и
+/// токены в ответ не попадают, даже если поле забыли скрыть вручную.
 ///
 /// # Структура TOML-файла
 ///
@@ -136,7 +167,7 @@ pub enum ConfigError {
 /// ```
 ///
 /// Serde автоматически сопоставляет секции TOML с полями структуры!
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct AppConfig {
     /// Настройки HTTP-сервера
     pub server: ServerConfig,
@@ -149,12 +180,100 @@ pub struct AppConfig {
     
     /// Мета-информация о приложении
     pub application: ApplicationConfig,
+
+    /// Настройки аутентификации по API-ключам.
+    ///
+    /// `#[serde(default)]` - секция необязательна: если её нет в config.toml,
+    /// используется `AuthConfig::default()` (аутентификация выключена).
+    #[serde(default)]
+    pub auth: AuthConfig,
+
+    /// Ограничение частоты запросов (секция `[rate_limit]`, необязательна)
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+
+    /// Ограничение параллельных запросов к AI (секция `[concurrency]`, необязательна)
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+
+    /// Политика CORS для web-интерфейса (секция `[cors]`, необязательна)
+    #[serde(default)]
+    pub cors: CorsConfig,
+
+    /// Метрики Prometheus (секция `[metrics]`, необязательна)
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    /// Экспорт трассировок OpenTelemetry (секция `[telemetry]`, необязательна)
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Плавная остановка сервера (секция `[shutdown]`, необязательна)
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+
+    /// Перезагрузка конфигурации без перезапуска (секция `[reload]`, необязательна)
+    #[serde(default)]
+    pub reload: ReloadConfig,
+
+    /// Источники секретов (секция `[secrets]`, необязательна)
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+
+    /// Постоянное хранилище (секция `[storage]`, необязательна)
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    /// Библиотека шаблонов промптов (секция `[templates]`, необязательна)
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+
+    /// A/B эксперимент с системными промптами (секция `[experiment]`, необязательна)
+    #[serde(default)]
+    pub experiment: ExperimentConfig,
+
+    /// Защита системного промпта (секция `[guardrails]`, необязательна)
+    #[serde(default)]
+    pub guardrails: GuardrailsConfig,
+
+    /// Модерация вопросов и ответов (секция `[moderation]`, необязательна)
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+
+    /// Профиль и источник каждого значения; заполняется при загрузке,
+    /// в файле не задаётся.
+    #[serde(skip)]
+    pub origin: ConfigOrigin,
 }
 
+/// Секции [`AppConfig`] - первые части ключей (`server.port`, `gigachat.model`).
+///
+/// По ним модуль `overrides` узнаёт переменные старого формата `APP_SERVER_PORT`.
+pub const SECTIONS: &[&str] = &[
+    "server",
+    "gigachat",
+    "logging",
+    "application",
+    "auth",
+    "rate_limit",
+    "concurrency",
+    "cors",
+    "metrics",
+    "telemetry",
+    "shutdown",
+    "reload",
+    "secrets",
+    "storage",
+    "templates",
+    "experiment",
+    "guardrails",
+    "moderation",
+];
+
 /// Конфигурация HTTP-сервера.
 ///
 /// Соответствует секции `[server]` в config.toml
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ServerConfig {
     /// IP-адрес для прослушивания.
     /// "127.0.0.1" - только локальные подключения
@@ -165,8 +284,9 @@ pub struct ServerConfig {
     /// Стандартные: 80 (HTTP), 443 (HTTPS), 8000/8080 (разработка)
     pub port: u16,
     
-    /// Окружение: "development" или "production"
-    /// Влияет на уровень логирования и отображение ошибок
+    /// Окружение: "development", "production" или "test".
+    /// Всегда совпадает с профилем (`APP_PROFILE`, см. модуль `profile`);
+    /// без `APP_PROFILE` значение из config.toml само выбирает профиль.
     pub environment: String,
 }
 
@@ -180,7 +300,7 @@ pub struct ServerConfig {
 /// - **temperature** - "креативность" (0.0 = детерминированно, 1.0 = случайно)
 /// - **max_tokens** - максимальная длина ответа
 /// - **model** - версия модели (разные по скорости/качеству)
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct GigaChatConfig {
     /// Использовать ли реальный API (true) или mock (false)
     pub enabled: bool,
@@ -196,24 +316,72 @@ pub struct GigaChatConfig {
     
     /// Таймаут HTTP-запроса в секундах
     pub timeout_seconds: u64,
+
+    /// Переходить ли на mock, если токен не найден (`enabled = true`).
+    /// `false` - сервер с недоступным токеном не запускается
+    /// (так по умолчанию в профиле `production`, см. модуль `profile`)
+    #[serde(default = "default_true")]
+    pub mock_fallback: bool,
 }
 
 /// Конфигурация системы логирования.
 ///
 /// Соответствует секции `[logging]` в config.toml
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct LoggingConfig {
     /// Минимальный уровень логов: "trace", "debug", "info", "warn", "error"
     pub level: String,
     
-    /// Формат вывода: "json" или "pretty"
+    /// Формат вывода: "compact", "pretty" или "json"
     pub format: String,
+
+    /// Уровни для отдельных модулей (target), например `rocket = "warn"`.
+    /// Переопределяют `level` для указанных модулей.
+    #[serde(default)]
+    pub targets: HashMap<String, String>,
+
+    /// Запись логов в файл (секция `[logging.file]`, необязательна)
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
+}
+
+/// Настройки записи логов в файл с ротацией.
+///
+/// Соответствует секции `[logging.file]` в config.toml
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct LogFileConfig {
+    /// Каталог для файлов логов
+    pub directory: String,
+
+    /// Префикс имени файла: `app.log` → `app.log.2024-05-01`
+    #[serde(default = "default_log_file_prefix")]
+    pub prefix: String,
+
+    /// Ротация: "minutely", "hourly", "daily" или "never"
+    #[serde(default = "default_log_rotation")]
+    pub rotation: String,
+
+    /// Сколько файлов хранить (старые удаляются). Не задано - хранить все.
+    #[serde(default)]
+    pub max_files: Option<usize>,
+
+    /// Формат логов в файле; не задан - такой же, как `format`
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+fn default_log_file_prefix() -> String {
+    "app.log".to_string()
+}
+
+fn default_log_rotation() -> String {
+    "daily".to_string()
 }
 
 /// Мета-информация о приложении.
 ///
 /// Соответствует секции `[application]` в config.toml
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ApplicationConfig {
     /// Название приложения (отображается в логах и API)
     pub name: String,
@@ -228,6 +396,861 @@ pub struct ApplicationConfig {
     pub system_prompt: String,
 }
 
+/// Сколько шестнадцатеричных символов SHA-256 идёт в версию промпта.
+const PROMPT_VERSION_LEN: usize = 12;
+
+impl ApplicationConfig {
+    /// Версия системного промпта - начало SHA-256 его текста
+    /// (`None`, если промпт пустой).
+    ///
+    /// Сохраняется с каждым ответом: по ней отзывы на ответы группируются
+    /// в `GET /admin/feedback`. Любая правка текста даёт новую версию,
+    /// а пробелы по краям не считаются (они и не отправляются в GigaChat).
+    pub fn system_prompt_version(&self) -> Option<String> {
+        prompt_version(&self.system_prompt)
+    }
+}
+
+/// Версия произвольного системного промпта (см. [`ApplicationConfig::system_prompt_version`]).
+///
+/// Ею же помечаются промпты вариантов `[[experiment.variants]]`.
+pub fn prompt_version(prompt: &str) -> Option<String> {
+    let prompt = prompt.trim();
+    if prompt.is_empty() {
+        return None;
+    }
+    let digest = ring::digest::digest(&ring::digest::SHA256, prompt.as_bytes());
+    let hex: String = digest.as_ref().iter().map(|byte| format!("{byte:02x}")).collect();
+    Some(hex[..PROMPT_VERSION_LEN].to_string())
+}
+
+/// Конфигурация аутентификации по API-ключам.
+///
+/// Соответствует секции `[auth]` в config.toml
+///
+/// # Для студентов: Зачем аутентификация?
+///
+/// Каждый вызов `/ask` тратит токены GigaChat. Если сервер доступен из интернета
+/// (например, в Serverless Container), любой, кто знает URL, может расходовать
+/// наш баланс. API-ключ - простейший способ ограничить доступ "своими".
+///
+/// ```toml
+/// [auth]
+/// enabled = true
+/// public_health = true
+/// keys_file = "api_keys.toml"
+///
+/// [[auth.keys]]
+/// name = "frontend"
+/// key = "change-me"
+/// scopes = ["ask"]
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct AuthConfig {
+    /// Требовать ли API-ключ для защищённых эндпоинтов
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Оставить `/health` доступным без ключа (для балансировщиков и мониторинга)
+    #[serde(default = "default_true")]
+    pub public_health: bool,
+
+    /// Ключи, заданные прямо в config.toml
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+
+    /// Путь к отдельному файлу-хранилищу ключей (TOML со списком `[[keys]]`).
+    /// Файл удобно не коммитить в репозиторий, в отличие от config.toml.
+    #[serde(default)]
+    pub keys_file: Option<String>,
+
+    /// Проверка JWT от университетского SSO (подсекция `[auth.jwt]`)
+    #[serde(default)]
+    pub jwt: JwtConfig,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            public_health: true,
+            keys: Vec::new(),
+            keys_file: None,
+            jwt: JwtConfig::default(),
+        }
+    }
+}
+
+/// Конфигурация проверки JWT (bearer-токенов от SSO/OIDC-провайдера).
+///
+/// Соответствует подсекции `[auth.jwt]` в config.toml
+///
+/// # Для студентов: Что проверяется в JWT?
+///
+/// ```text
+/// header.payload.signature
+///   │       │        └─ подпись ключом провайдера (проверяем по JWKS)
+///   │       └────────── claims: sub, iss, aud, exp, roles...
+///   └────────────────── алгоритм (RS256/ES256) и kid - id ключа в JWKS
+/// ```
+///
+/// Открытые ключи провайдер публикует в формате JWKS (JSON Web Key Set):
+/// либо файлом, либо по URL вида `https://sso/.well-known/jwks.json`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct JwtConfig {
+    /// Принимать ли JWT в заголовке `Authorization: Bearer`
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Путь к локальному файлу JWKS
+    #[serde(default)]
+    pub jwks_file: Option<String>,
+
+    /// URL JWKS провайдера (используется, если `jwks_file` не задан)
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+
+    /// Ожидаемый издатель токена (claim `iss`)
+    #[serde(default)]
+    pub issuer: Option<String>,
+
+    /// Ожидаемая аудитория токена (claim `aud`)
+    #[serde(default)]
+    pub audience: Option<String>,
+
+    /// Разрешённые алгоритмы подписи
+    #[serde(default = "default_jwt_algorithms")]
+    pub algorithms: Vec<String>,
+
+    /// Допустимое расхождение часов при проверке `exp`/`nbf`, в секундах
+    #[serde(default = "default_leeway_seconds")]
+    pub leeway_seconds: u64,
+
+    /// Claim со списком ролей; вложенные claims через точку: "realm_access.roles"
+    #[serde(default = "default_roles_claim")]
+    pub roles_claim: String,
+
+    /// Какие роли допускаются к scope: `ask = ["student", "teacher"]`.
+    /// Если scope здесь не указан - достаточно любого валидного токена;
+    /// `admin` без ролей закрыт для всех токенов.
+    #[serde(default)]
+    pub required_roles: HashMap<String, Vec<String>>,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            jwks_file: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+            algorithms: default_jwt_algorithms(),
+            leeway_seconds: default_leeway_seconds(),
+            roles_claim: default_roles_claim(),
+            required_roles: HashMap::new(),
+        }
+    }
+}
+
+fn default_jwt_algorithms() -> Vec<String> {
+    vec!["RS256".to_string(), "ES256".to_string()]
+}
+
+fn default_leeway_seconds() -> u64 {
+    60
+}
+
+fn default_roles_claim() -> String {
+    "roles".to_string()
+}
+
+/// Описание одного API-ключа.
+///
+/// Соответствует элементу массива `[[auth.keys]]` в config.toml
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ApiKeyConfig {
+    /// Имя владельца ключа (для логов и лимитов), например "frontend"
+    pub name: String,
+
+    /// Значение ключа, которое клиент передаёт в заголовке
+    /// (в логах и `/admin/config` выводится как `***`)
+    pub key: Secret,
+
+    /// Разрешённые области доступа: "ask", "health", "admin" или "*" (всё)
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// Значение по умолчанию `true` для `#[serde(default = "...")]`.
+fn default_true() -> bool {
+    true
+}
+
+/// Конфигурация ограничения частоты запросов (rate limiting).
+///
+/// Соответствует секции `[rate_limit]` в config.toml
+///
+/// # Для студентов: Алгоритм "ведро с токенами" (token bucket)
+///
+/// ```text
+/// ┌───────────┐  +refill_per_minute токенов в минуту (равномерно)
+/// │ ● ● ● ○ ○ │  capacity - размер ведра (допустимый "всплеск")
+/// └───────────┘  каждый запрос забирает 1 токен; ведро пустое → 429
+/// ```
+///
+/// Лимиты действуют на трёх уровнях одновременно: на IP клиента,
+/// на API-ключ/пользователя и на весь сервер. Уровень без настроек не ограничен.
+///
+/// IP клиента - адрес TCP-соединения. Заголовок `ip_header` клиент может
+/// подставить сам, поэтому он учитывается, только если соединение пришло
+/// от адреса из `trusted_proxies`:
+///
+/// ```toml
+/// [rate_limit]
+/// trusted_proxies = ["10.0.0.5"]   # nginx перед сервером
+/// ip_header = "X-Forwarded-For"
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct RateLimitConfig {
+    /// Включено ли ограничение
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Лимит на один IP-адрес
+    #[serde(default)]
+    pub per_ip: Option<BucketConfig>,
+
+    /// Лимит на один API-ключ или пользователя JWT
+    #[serde(default)]
+    pub per_key: Option<BucketConfig>,
+
+    /// Общий лимит на весь сервер (защищает квоту GigaChat)
+    #[serde(default)]
+    pub global: Option<BucketConfig>,
+
+    /// IP-адреса обратных прокси, которым доверяем `ip_header`;
+    /// пусто - заголовок игнорируется
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+
+    /// Заголовок с IP клиента, который выставляет прокси
+    /// (`X-Real-IP` или `X-Forwarded-For`)
+    #[serde(default = "default_ip_header")]
+    pub ip_header: String,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            per_ip: None,
+            per_key: None,
+            global: None,
+            trusted_proxies: Vec::new(),
+            ip_header: default_ip_header(),
+        }
+    }
+}
+
+fn default_ip_header() -> String {
+    "X-Real-IP".to_string()
+}
+
+/// Параметры одного "ведра с токенами".
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+pub struct BucketConfig {
+    /// Максимум запросов подряд (размер ведра)
+    pub capacity: u32,
+
+    /// Сколько запросов в минуту восполняется
+    pub refill_per_minute: u32,
+}
+
+/// Конфигурация ограничения параллельных обращений к AI-сервису.
+///
+/// Соответствует секции `[concurrency]` в config.toml
+///
+/// # Для студентов: Rate limit vs concurrency limit
+///
+/// Rate limit ограничивает, СКОЛЬКО запросов приходит за минуту.
+/// Concurrency limit ограничивает, сколько запросов выполняется
+/// ОДНОВРЕМЕННО: у аккаунта GigaChat есть лимит параллельных запросов.
+/// Лишние запросы ждут в очереди, а если очередь полна - получают 503.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ConcurrencyConfig {
+    /// Включено ли ограничение
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Максимум одновременных запросов к AI
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+
+    /// Максимум запросов, ожидающих в очереди
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+
+    /// Сколько секунд запрос может ждать в очереди
+    #[serde(default = "default_queue_timeout_seconds")]
+    pub queue_timeout_seconds: u64,
+}
+
+fn default_max_concurrent() -> usize {
+    4
+}
+
+fn default_max_queue() -> usize {
+    16
+}
+
+fn default_queue_timeout_seconds() -> u64 {
+    10
+}
+
+/// Конфигурация CORS (Cross-Origin Resource Sharing).
+///
+/// Соответствует секции `[cors]` в config.toml
+///
+/// # Для студентов: Шаблоны origin
+///
+/// Origin - это схема, хост и порт страницы, с которой браузер шлёт запрос.
+/// В `allowed_origins` можно указать:
+///
+/// ```text
+/// "http://127.0.0.1:8080"      - точное совпадение
+/// "https://*.example.com"      - любой поддомен example.com
+/// "http://localhost:*"         - localhost на любом порту
+/// "*"                          - любой origin (только для разработки!)
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct CorsConfig {
+    /// Разрешённые origin (точные или с `*`)
+    #[serde(default = "default_cors_origins")]
+    pub allowed_origins: Vec<String>,
+
+    /// Разрешённые HTTP-методы
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+
+    /// Разрешённые заголовки запроса
+    #[serde(default = "default_cors_headers")]
+    pub allowed_headers: Vec<String>,
+
+    /// Заголовки ответа, доступные JavaScript на странице
+    #[serde(default = "default_cors_expose_headers")]
+    pub expose_headers: Vec<String>,
+
+    /// Разрешить cookies и заголовок Authorization в кросс-доменных запросах
+    #[serde(default)]
+    pub allow_credentials: bool,
+
+    /// Сколько секунд браузер может кешировать ответ на preflight
+    #[serde(default = "default_cors_max_age")]
+    pub max_age_seconds: u64,
+}
+
+fn default_cors_origins() -> Vec<String> {
+    vec!["http://127.0.0.1:8080".to_string()]
+}
+
+fn default_cors_methods() -> Vec<String> {
+    ["GET", "POST", "DELETE", "OPTIONS"].map(String::from).to_vec()
+}
+
+fn default_cors_headers() -> Vec<String> {
+    ["Content-Type", "Authorization", "X-API-Key", "X-Request-Id"].map(String::from).to_vec()
+}
+
+fn default_cors_expose_headers() -> Vec<String> {
+    [
+        "Retry-After",
+        "RateLimit-Limit",
+        "RateLimit-Remaining",
+        "RateLimit-Reset",
+        "X-Request-Id",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_cors_max_age() -> u64 {
+    3600
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: default_cors_origins(),
+            allowed_methods: default_cors_methods(),
+            allowed_headers: default_cors_headers(),
+            expose_headers: default_cors_expose_headers(),
+            allow_credentials: false,
+            max_age_seconds: default_cors_max_age(),
+        }
+    }
+}
+
+/// Конфигурация эндпоинта метрик Prometheus.
+///
+/// Соответствует секции `[metrics]` в config.toml
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct MetricsConfig {
+    /// Включён ли сбор метрик и эндпоинт
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Путь эндпоинта (должен начинаться с `/`)
+    #[serde(default = "default_metrics_path")]
+    pub path: String,
+}
+
+fn default_metrics_path() -> String {
+    "/metrics".to_string()
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_metrics_path(),
+        }
+    }
+}
+
+/// Конфигурация экспорта трассировок OpenTelemetry.
+///
+/// Соответствует секции `[telemetry]` в config.toml. Работает только в сборке
+/// с фичей `otel` (`cargo run --features otel`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct TelemetryConfig {
+    /// Включён ли экспорт span'ов
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Имя сервиса (`service.name`) в системе трассировки
+    #[serde(default = "default_telemetry_service_name")]
+    pub service_name: String,
+
+    /// Куда отправлять span'ы: `otlp`, `stdout` или `file`
+    #[serde(default = "default_telemetry_exporter")]
+    pub exporter: String,
+
+    /// Адрес OTLP/HTTP коллектора (для `exporter = "otlp"`)
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    #[serde(default = "default_telemetry_endpoint")]
+    pub endpoint: String,
+
+    /// Файл для span'ов в формате JSON Lines (для `exporter = "file"`)
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    #[serde(default = "default_telemetry_file")]
+    pub file: String,
+
+    /// Доля записываемых трасс: от 0.0 (ничего) до 1.0 (все)
+    #[serde(default = "default_telemetry_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_telemetry_service_name() -> String {
+    "rust-gigachat-demo".to_string()
+}
+
+fn default_telemetry_exporter() -> String {
+    "otlp".to_string()
+}
+
+fn default_telemetry_endpoint() -> String {
+    "http://localhost:4318/v1/traces".to_string()
+}
+
+fn default_telemetry_file() -> String {
+    "logs/traces.jsonl".to_string()
+}
+
+fn default_telemetry_sample_ratio() -> f64 {
+    1.0
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            service_name: default_telemetry_service_name(),
+            exporter: default_telemetry_exporter(),
+            endpoint: default_telemetry_endpoint(),
+            file: default_telemetry_file(),
+            sample_ratio: default_telemetry_sample_ratio(),
+        }
+    }
+}
+
+/// Конфигурация плавной остановки (graceful shutdown).
+///
+/// Соответствует секции `[shutdown]` в config.toml. Получив SIGTERM или
+/// Ctrl+C, сервер перестаёт принимать соединения и ждёт завершения
+/// начатых запросов не дольше `grace_seconds`, затем ещё `mercy_seconds`
+/// закрывает соединения.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ShutdownConfig {
+    /// Сколько секунд ждать завершения начатых запросов (в т.ч. вызовов AI)
+    #[serde(default = "default_grace_seconds")]
+    pub grace_seconds: u32,
+
+    /// Сколько секунд после grace даётся на закрытие соединений
+    #[serde(default = "default_mercy_seconds")]
+    pub mercy_seconds: u32,
+}
+
+fn default_grace_seconds() -> u32 {
+    25
+}
+
+fn default_mercy_seconds() -> u32 {
+    5
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            grace_seconds: default_grace_seconds(),
+            mercy_seconds: default_mercy_seconds(),
+        }
+    }
+}
+
+/// Конфигурация перезагрузки config.toml без перезапуска.
+///
+/// Соответствует секции `[reload]` в config.toml. Перезагрузку также можно
+/// запустить запросом `POST /admin/reload` (см. модуль `reload`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ReloadConfig {
+    /// Следить ли за изменением файла конфигурации
+    #[serde(default)]
+    pub watch: bool,
+
+    /// Как часто проверять время изменения файла, в секундах
+    #[serde(default = "default_poll_interval_seconds")]
+    pub poll_interval_seconds: u64,
+
+    /// Перезагружать ли конфигурацию по сигналу SIGHUP (только Unix)
+    #[serde(default = "default_true")]
+    pub sighup: bool,
+}
+
+fn default_poll_interval_seconds() -> u64 {
+    2
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            watch: false,
+            poll_interval_seconds: default_poll_interval_seconds(),
+            sighup: true,
+        }
+    }
+}
+
+/// Конфигурация источников секретов.
+///
+/// Соответствует секции `[secrets]` в config.toml. Сами секреты здесь
+/// не хранятся - только путь к зашифрованному файлу (см. модуль `secrets`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct SecretsConfig {
+    /// Зашифрованный файл секретов (создаётся командой `--seal-secrets`);
+    /// пароль - в переменной `SECRETS_PASSPHRASE` или `SECRETS_PASSPHRASE_FILE`
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// Конфигурация постоянного хранилища.
+///
+/// Соответствует секции `[storage]` в config.toml. Хранятся диалоги,
+/// история вопросов и ответов, учёт использования и API-ключи
+/// (см. модуль `storage`).
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct StorageConfig {
+    /// Движок: "sqlite" (файл на диске) или "memory" (данные теряются
+    /// при перезапуске - для тестов и экспериментов)
+    #[serde(default = "default_storage_backend")]
+    pub backend: String,
+
+    /// Путь к файлу базы SQLite; каталог создаётся при запуске
+    #[serde(default = "default_storage_path")]
+    pub path: String,
+
+    /// Сколько миллисекунд ждать, если база занята другим соединением
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+}
+
+fn default_storage_backend() -> String {
+    "sqlite".to_string()
+}
+
+fn default_storage_path() -> String {
+    "data/gigachat.db".to_string()
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: default_storage_backend(),
+            path: default_storage_path(),
+            busy_timeout_ms: default_busy_timeout_ms(),
+        }
+    }
+}
+
+/// Конфигурация библиотеки шаблонов промптов.
+///
+/// Соответствует секции `[templates]` в config.toml (см. модуль `templates`).
+///
+/// ```toml
+/// [templates]
+/// directory = "prompts"
+///
+/// [templates.defaults]
+/// language = "Rust"
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct TemplatesConfig {
+    /// Каталог с файлами `<имя>.prompt`; без него шаблонов нет
+    #[serde(default)]
+    pub directory: Option<String>,
+
+    /// Значения переменных по умолчанию для всех шаблонов
+    #[serde(default)]
+    pub defaults: BTreeMap<String, String>,
+}
+
+/// A/B эксперимент: несколько вариантов системного промпта одновременно.
+///
+/// Соответствует секции `[experiment]` в config.toml (см. модуль `experiment`).
+/// Каждый клиент (имя ключа или `sub` из JWT) всегда получает один и тот
+/// же вариант; доли вариантов пропорциональны `weight`.
+///
+/// ```toml
+/// [experiment]
+/// enabled = true
+/// name = "socratic-2026-10"
+///
+/// [[experiment.variants]]
+/// id = "control"        # без system_prompt - application.system_prompt
+/// weight = 50
+///
+/// [[experiment.variants]]
+/// id = "socratic"
+/// weight = 50
+/// system_prompt = "Не давай готовый ответ, задавай наводящие вопросы"
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ExperimentConfig {
+    /// Распределять ли клиентов по вариантам
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Имя эксперимента; другое имя - другое распределение клиентов
+    #[serde(default = "default_experiment_name")]
+    pub name: String,
+
+    /// Варианты системного промпта
+    #[serde(default)]
+    pub variants: Vec<PromptVariantConfig>,
+}
+
+/// Вариант системного промпта в `[[experiment.variants]]`.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PromptVariantConfig {
+    /// Идентификатор варианта: в ответах `/ask`, истории и `GET /admin/feedback`
+    pub id: String,
+
+    /// Доля клиентов относительно суммы весов (0 - вариант выключен)
+    #[serde(default = "default_variant_weight")]
+    pub weight: u32,
+
+    /// Текст промпта; без него - `application.system_prompt`
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+fn default_experiment_name() -> String {
+    "default".to_string()
+}
+
+fn default_variant_weight() -> u32 {
+    1
+}
+
+impl Default for ExperimentConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: default_experiment_name(),
+            variants: Vec::new(),
+        }
+    }
+}
+
+/// Защита системного промпта от prompt injection.
+///
+/// Соответствует секции `[guardrails]` в config.toml (см. модуль `guardrails`).
+/// Вопрос проверяется на попытки отменить инструкции ("забудь все
+/// инструкции", "ignore previous instructions"), ответ - на пересказ
+/// системного промпта. Действие для каждой проверки:
+///
+/// - `block` - отказать (вопрос: 400, ответ: 502);
+/// - `sanitize` - вырезать подозрительный фрагмент и продолжить;
+/// - `flag` - пропустить как есть, но отметить в ответе и логе.
+///
+/// ```toml
+/// [guardrails]
+/// enabled = true
+/// input_action = "flag"
+/// output_action = "block"
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GuardrailsConfig {
+    /// Проверять ли вопросы и ответы
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Действие при попытке prompt injection в вопросе
+    #[serde(default = "default_guardrails_input_action")]
+    pub input_action: String,
+
+    /// Действие при пересказе системного промпта в ответе
+    #[serde(default = "default_guardrails_output_action")]
+    pub output_action: String,
+
+    /// Дополнительные шаблоны: начала слов через пробел, например "слей промпт"
+    #[serde(default)]
+    pub extra_patterns: Vec<String>,
+
+    /// Длина фрагмента промпта в словах, по которой ищется пересказ
+    #[serde(default = "default_guardrails_ngram_size")]
+    pub ngram_size: usize,
+
+    /// Доля фрагментов промпта в ответе, начиная с которой это утечка
+    #[serde(default = "default_guardrails_leak_threshold")]
+    pub leak_threshold: f64,
+}
+
+fn default_guardrails_input_action() -> String {
+    "flag".to_string()
+}
+
+fn default_guardrails_output_action() -> String {
+    "block".to_string()
+}
+
+fn default_guardrails_ngram_size() -> usize {
+    4
+}
+
+fn default_guardrails_leak_threshold() -> f64 {
+    0.3
+}
+
+impl Default for GuardrailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            input_action: default_guardrails_input_action(),
+            output_action: default_guardrails_output_action(),
+            extra_patterns: Vec::new(),
+            ngram_size: default_guardrails_ngram_size(),
+            leak_threshold: default_guardrails_leak_threshold(),
+        }
+    }
+}
+
+/// Модерация вопросов и ответов.
+///
+/// Соответствует секции `[moderation]` в config.toml (см. модуль `moderation`).
+/// Стоп-лист (слова и регулярные выражения) и поиск персональных данных
+/// работают до отправки вопроса в GigaChat и после получения ответа.
+/// Действие для каждой проверки:
+///
+/// - `block` - отказать (вопрос - 400 `CONTENT_BLOCKED`, ответ - 502 `ANSWER_BLOCKED`);
+/// - `redact` - заменить найденное маской вида `[email]` и продолжить;
+/// - `warn` - пропустить как есть, но записать в лог.
+///
+/// ```toml
+/// [moderation]
+/// enabled = true
+/// keywords = ["казино"]
+/// patterns = ['(?i)скача\w* .*ответ\w* (к|на) (лаб|экзамен)']
+/// pii = ["email", "phone", "passport"]
+/// pii_action = "redact"
+/// ```
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ModerationConfig {
+    /// Проверять ли вопросы и ответы
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Стоп-слова: целые слова без учёта регистра
+    #[serde(default)]
+    pub keywords: Vec<String>,
+
+    /// Стоп-выражения: регулярные выражения (синтаксис крейта `regex`)
+    #[serde(default)]
+    pub patterns: Vec<String>,
+
+    /// Действие, если найдено стоп-слово или стоп-выражение
+    #[serde(default = "default_blocklist_action")]
+    pub blocklist_action: String,
+
+    /// Какие персональные данные искать: `email`, `phone`, `passport`
+    #[serde(default = "default_pii_kinds")]
+    pub pii: Vec<String>,
+
+    /// Действие, если найдены персональные данные
+    #[serde(default = "default_pii_action")]
+    pub pii_action: String,
+}
+
+fn default_blocklist_action() -> String {
+    "block".to_string()
+}
+
+fn default_pii_kinds() -> Vec<String> {
+    vec!["email".to_string(), "phone".to_string(), "passport".to_string()]
+}
+
+fn default_pii_action() -> String {
+    "redact".to_string()
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keywords: Vec::new(),
+            patterns: Vec::new(),
+            blocklist_action: default_blocklist_action(),
+            pii: default_pii_kinds(),
+            pii_action: default_pii_action(),
+        }
+    }
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_concurrent: default_max_concurrent(),
+            max_queue: default_max_queue(),
+            queue_timeout_seconds: default_queue_timeout_seconds(),
+        }
+    }
+}
+
 // ============================================================================
 // РЕАЛИЗАЦИЯ AppConfig
 // ============================================================================
@@ -258,7 +1281,12 @@ impl AppConfig {
     /// └────────┬────────┘
     ///          ↓
     /// ┌─────────────────┐
-    /// │ 3. APP_* env    │  ← Переопределяют config.toml
+    /// │ 3. Профиль      │  ← APP_PROFILE: встроенные настройки профиля
+    /// │                 │    и config.<профиль>.toml (если есть)
+    /// └────────┬────────┘
+    ///          ↓
+    /// ┌─────────────────┐
+    /// │ 4. APP_* env    │  ← Переопределяют всё остальное
     /// └────────┬────────┘
     ///          ↓
     /// ┌─────────────────┐
@@ -272,38 +1300,67 @@ impl AppConfig {
     /// - Файл config.toml не найден
     /// - Файл содержит синтаксические ошибки
     /// - Типы полей не совпадают (например, строка вместо числа)
+    /// - В файле есть неизвестный ключ (например, опечатка `tempreature`)
+    /// - Значения не прошли [`AppConfig::validate`]
     pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(&Self::path())
+    }
+
+    /// Как [`AppConfig::load`], но из указанного файла (`--check-config <путь>`).
+    pub fn load_from(config_path: &str) -> Result<Self, ConfigError> {
         // Загружаем переменные окружения из файла .env (если он существует).
         // .ok() превращает Result в Option, игнорируя ошибки.
         // Это нормально - файл .env опционален.
         dotenv::dotenv().ok();
 
-        // Позволяем переопределить путь к конфигу через переменную окружения.
-        // unwrap_or_else - если переменная не найдена, используем значение по умолчанию.
-        let config_path = env::var("CONFIG_PATH")
-            .unwrap_or_else(|_| "config.toml".to_string());
-
-        // Создаём конфигурацию с помощью Builder Pattern
-        let settings = config::Config::builder()
-            // Источник 1: TOML-файл
-            .add_source(config::File::with_name(&config_path))
-            // Источник 2: Переменные окружения с префиксом APP_
-            // Например: APP_SERVER_PORT=9000 → server.port = 9000
-            .add_source(
-                config::Environment::with_prefix("APP")
-                    .separator("_")       // APP_SERVER_PORT → server.port
-                    .try_parsing(true),   // Пытаться парсить "9000" как число
-            )
-            .build()?;  // ? - пробрасываем ошибку, если build() вернул Err
+        // Собираем слои: config.toml → профиль → config.<профиль>.toml
+        // → PORT → APP_<СЕКЦИЯ>__<КЛЮЧ> (подробнее - в модулях profile и overrides)
+        let (settings, origin) = profile::layered(config_path, env::vars().collect())?;
 
+        Self::from_settings(settings, origin)
+    }
+
+    /// Разбирает собранные слои и проверяет результат.
+    ///
+    /// Неизвестные ключи не обрывают разбор: `serde_ignored` собирает их
+    /// пути, и они попадают в [`ValidationReport`] вместе с ошибками
+    /// диапазонов - `--check-config` показывает всё сразу.
+    pub fn from_settings(settings: config::Config, origin: ConfigOrigin) -> Result<Self, ConfigError> {
         // Десериализуем в нашу структуру.
         // Serde проверит, что все обязательные поля есть и типы совпадают.
-        let config: AppConfig = settings.try_deserialize()?;
+        let mut unknown = Vec::new();
+        let mut config: AppConfig =
+            serde_ignored::deserialize(settings, |path| unknown.push(validation::key_path(&path)))?;
+        config.origin = origin;
+
+        // Serde проверил типы; диапазоны и связи полей проверяем отдельно
+        validation::validate_with_unknown(&config, &unknown)?;
 
         Ok(config)
     }
 
-    /// Возвращает токен GigaChat из переменной окружения.
+    /// Проверяет диапазоны, допустимые значения и связи между полями.
+    ///
+    /// Возвращает ВСЕ найденные проблемы сразу, с путями ключей
+    /// (`gigachat.temperature`, `auth.keys[1].scopes`, ...).
+    pub fn validate(&self) -> Result<(), ValidationReport> {
+        validation::validate(self)
+    }
+
+    /// Путь к файлу конфигурации: `CONFIG_PATH` или `config.toml`.
+    ///
+    /// За этим файлом следит перезагрузка (модуль `reload`).
+    pub fn path() -> String {
+        // Позволяем переопределить путь к конфигу через переменную окружения.
+        // unwrap_or_else - если переменная не найдена, используем значение по умолчанию.
+        env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".to_string())
+    }
+
+    /// Возвращает токен GigaChat.
+    ///
+    /// Токен ищется в `GIGACHAT_TOKEN`, затем в файле из `GIGACHAT_TOKEN_FILE`
+    /// (Docker/Kubernetes secrets), затем в зашифрованном файле `secrets.file`
+    /// (см. модуль `secrets`).
     ///
     /// # Для студентов: Почему токен в переменной окружения?
     ///
@@ -331,10 +1388,12 @@ impl AppConfig {
     /// # .env (добавьте в .gitignore!)
     /// GIGACHAT_TOKEN=your_secret_token
     /// ```
-    pub fn get_gigachat_token(&self) -> Result<String, ConfigError> {
-        // env::var возвращает Result<String, VarError>
-        // map_err преобразует VarError → ConfigError
-        env::var("GIGACHAT_TOKEN").map_err(ConfigError::from)
+    pub fn get_gigachat_token(&self) -> Result<Secret, ConfigError> {
+        // Secrets::get возвращает Result<Secret, SecretError>,
+        // map_err преобразует SecretError → ConfigError
+        Secrets::from_config(&self.secrets)
+            .get("GIGACHAT_TOKEN")
+            .map_err(ConfigError::from)
     }
 
     /// Проверяет, включён ли режим разработки.
@@ -349,6 +1408,11 @@ impl AppConfig {
     pub fn is_gigachat_enabled(&self) -> bool {
         self.gigachat.enabled
     }
+
+    /// Проверяет, включена ли аутентификация по API-ключам.
+    pub fn is_auth_enabled(&self) -> bool {
+        self.auth.enabled
+    }
 }
 
 #[cfg(test)]
@@ -362,4 +1426,35 @@ mod tests {
         let result = AppConfig::load();
         assert!(result.is_ok() || result.is_err()); // Просто проверяем, что функция работает
     }
+
+    #[test]
+    fn test_sections_match_config() {
+        let (settings, _) = profile::layered("config.toml", HashMap::new()).unwrap();
+        let config: AppConfig = settings.try_deserialize().unwrap();
+        let value = serde_json::to_value(config).unwrap();
+        let mut fields: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+        let mut sections: Vec<_> = SECTIONS.iter().map(|s| s.to_string()).collect();
+        fields.sort();
+        sections.sort();
+        assert_eq!(fields, sections);
+    }
+
+    #[test]
+    fn test_system_prompt_version() {
+        let mut application = ApplicationConfig {
+            name: "demo".to_string(),
+            version: "0.1.0".to_string(),
+            description: String::new(),
+            system_prompt: "Отвечай кратко".to_string(),
+        };
+        let version = application.system_prompt_version().unwrap();
+        assert_eq!(version.len(), PROMPT_VERSION_LEN);
+
+        application.system_prompt = "  Отвечай кратко\n".to_string();
+        assert_eq!(application.system_prompt_version(), Some(version.clone()));
+        application.system_prompt = "Отвечай подробно".to_string();
+        assert_ne!(application.system_prompt_version(), Some(version));
+        application.system_prompt = "   ".to_string();
+        assert_eq!(application.system_prompt_version(), None);
+    }
 }
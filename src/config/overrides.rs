@@ -0,0 +1,277 @@
+//! Переопределение конфигурации переменными окружения.
+//!
+//! Уровни вложенности разделяются ДВОЙНЫМ подчёркиванием, а одиночное
+//! остаётся частью имени ключа:
+//!
+//! ```text
+//! APP_SERVER__PORT=9000                 → server.port
+//! APP_GIGACHAT__MAX_TOKENS=1024         → gigachat.max_tokens
+//! APP_APPLICATION__SYSTEM_PROMPT="..."  → application.system_prompt
+//! APP_AUTH__JWT__ENABLED=true           → auth.jwt.enabled
+//! APP_CORS__ALLOWED_ORIGINS=https://a.ru,https://b.ru  → список через запятую
+//! PORT=8080                             → server.port (serverless-платформы)
+//! ```
+//!
+//! `APP_SERVER__PORT` важнее `PORT`: платформа задаёт `PORT` сама, а явная
+//! настройка приложения должна побеждать. Список ключей API (`auth.keys`)
+//! переменными не задаётся - для него есть `auth.keys_file`; варианты
+//! эксперимента (`experiment.variants`) и стоп-выражения модерации
+//! (`moderation.patterns`, в них бывают запятые) задаются только в файле.
+//!
+//! # Для студентов: Почему не одно подчёркивание?
+//!
+//! В схеме `APP_GIGACHAT_MAX_TOKENS` непонятно, где граница: это
+//! `gigachat.max_tokens` или `gigachat.max.tokens`? Двойное подчёркивание
+//! снимает неоднозначность. Переменные старого вида (`APP_SERVER_PORT`)
+//! не игнорируются молча, а дают ошибку с подсказкой.
+
+use std::collections::HashMap;
+
+use config::{Config, ConfigError, Environment, File, FileFormat};
+
+use super::validation::KNOWN_SCOPES;
+use super::SECTIONS;
+
+/// Префикс переменных, переопределяющих конфигурацию.
+pub const ENV_PREFIX: &str = "APP_";
+
+/// Разделитель уровней вложенности.
+pub const ENV_SEPARATOR: &str = "__";
+
+/// Порт, который задают serverless-платформы (Cloud Run, Serverless Containers).
+pub const PORT_ENV: &str = "PORT";
+
+/// Разделитель элементов списка в значении переменной.
+pub const LIST_SEPARATOR: &str = ",";
+
+/// Ключи-списки: их значения разбиваются по запятой.
+const LIST_KEYS: &[&str] = &[
+    "auth.jwt.algorithms",
+    "cors.allowed_origins",
+    "cors.allowed_methods",
+    "cors.allowed_headers",
+    "cors.expose_headers",
+    "guardrails.extra_patterns",
+    "moderation.keywords",
+    "moderation.pii",
+    "rate_limit.trusted_proxies",
+];
+
+/// Имя переменной для ключа: `gigachat.max_tokens` → `APP_GIGACHAT__MAX_TOKENS`.
+pub fn env_var(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.to_uppercase().replace('.', ENV_SEPARATOR))
+}
+
+/// Слой `PORT`, если переменная задана.
+pub fn port_layer(env: &HashMap<String, String>) -> Result<Option<Config>, ConfigError> {
+    let Some(port) = env.get(PORT_ENV).filter(|port| !port.is_empty()) else {
+        return Ok(None);
+    };
+    let port: u16 = port
+        .trim()
+        .parse()
+        .map_err(|_| ConfigError::Message(format!("{PORT_ENV}: '{port}' не является номером порта")))?;
+    Config::builder()
+        .add_source(File::from_str(&format!("[server]\nport = {port}\n"), FileFormat::Toml))
+        .build()
+        .map(Some)
+}
+
+/// Слой переменных `APP_<СЕКЦИЯ>__<КЛЮЧ>`.
+///
+/// Переменные без `__` (например, `APP_PROFILE`) ключами не считаются,
+/// но старый формат `APP_<СЕКЦИЯ>_<КЛЮЧ>` отклоняется с подсказкой.
+pub fn env_layer(env: &HashMap<String, String>) -> Result<Config, ConfigError> {
+    let mut overrides = HashMap::new();
+    let mut legacy: Vec<&str> = Vec::new();
+    for (name, value) in env {
+        let Some(rest) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if rest.contains(ENV_SEPARATOR) {
+            overrides.insert(name.clone(), value.clone());
+        } else if SECTIONS
+            .iter()
+            .any(|section| rest.to_lowercase().starts_with(&format!("{section}_")))
+        {
+            legacy.push(name);
+        }
+    }
+    if !legacy.is_empty() {
+        legacy.sort();
+        return Err(ConfigError::Message(format!(
+            "переменные {} в старом формате: уровни разделяются двойным подчёркиванием, \
+             например APP_SERVER__PORT или APP_GIGACHAT__MAX_TOKENS",
+            legacy.join(", ")
+        )));
+    }
+
+    let list_keys = LIST_KEYS
+        .iter()
+        .map(|key| key.to_string())
+        .chain(KNOWN_SCOPES.iter().map(|scope| format!("auth.jwt.required_roles.{scope}")));
+    let mut environment = Environment::with_prefix(ENV_PREFIX.trim_end_matches('_'))
+        .prefix_separator("_")
+        .separator(ENV_SEPARATOR)
+        .list_separator(LIST_SEPARATOR)
+        .try_parsing(true)
+        .source(Some(overrides));
+    for key in list_keys {
+        environment = environment.with_list_parse_key(&key);
+    }
+
+    Config::builder().add_source(environment).build()
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::profile::{self, layered};
+    use crate::config::AppConfig;
+    use serde_json::{json, Value};
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    /// Загружает config.toml с переменными и возвращает итог в JSON.
+    fn load(vars: &HashMap<String, String>) -> (Value, profile::ConfigOrigin) {
+        let (settings, origin) = layered("config.toml", vars.clone())
+            .unwrap_or_else(|e| panic!("{vars:?}: {e}"));
+        let config: AppConfig = settings
+            .try_deserialize()
+            .unwrap_or_else(|e| panic!("{vars:?}: {e}"));
+        (serde_json::to_value(&config).unwrap(), origin)
+    }
+
+    fn lookup<'a>(value: &'a Value, key: &str) -> &'a Value {
+        key.split('.').fold(value, |value, part| &value[part])
+    }
+
+    /// Значение переменной, отличное от текущего, и ожидаемый результат.
+    fn replacement(key: &str, current: &Value) -> Option<(String, Value)> {
+        match current {
+            Value::Bool(b) => Some(((!b).to_string(), json!(!b))),
+            Value::Number(n) if n.is_u64() => {
+                let next = n.as_u64().unwrap() + 1;
+                Some((next.to_string(), json!(next)))
+            }
+            Value::Number(n) => {
+                let half = n.as_f64().unwrap() / 2.0;
+                Some((half.to_string(), json!(half)))
+            }
+            Value::String(_) => Some(("override".to_string(), json!("override"))),
+            Value::Array(_) if LIST_KEYS.contains(&key) => {
+                Some(("a,b".to_string(), json!(["a", "b"])))
+            }
+            _ => None,
+        }
+    }
+
+    /// Каждое значение из config.toml и значений по умолчанию
+    /// переопределяется своей переменной APP_*.
+    #[test]
+    fn test_every_field_is_overridable() {
+        let (base, _) = load(&HashMap::new());
+        let mut keys = Vec::new();
+        collect(&base, String::new(), &mut keys);
+        assert!(keys.len() > 50, "слишком мало ключей: {keys:?}");
+
+        for (key, current) in keys {
+            let Some((raw, expected)) = replacement(&key, &current) else {
+                // Значения-таблицы без полей и необязательные секции - ниже;
+                // массивы таблиц ([[auth.keys]]) одной переменной не задать,
+                // а регулярные выражения нельзя делить по запятой
+                assert!(
+                    NOT_IN_BASE.iter().any(|(k, _, _)| k.starts_with(&key))
+                        || ["auth.keys", "experiment.variants", "moderation.patterns"]
+                            .contains(&key.as_str()),
+                    "{key}: нет теста переопределения"
+                );
+                continue;
+            };
+            let var = env_var(&key);
+            let (config, origin) = load(&env(&[(&var, &raw)]));
+            assert_eq!(lookup(&config, &key), &expected, "{var}={raw}");
+            let leaf = if expected.is_array() { format!("{key}[0]") } else { key.clone() };
+            assert_eq!(origin.source_of(&leaf), format!("env:{var}"));
+        }
+    }
+
+    /// Ключи, которых нет в config.toml (необязательные поля и секции).
+    const NOT_IN_BASE: &[(&str, &str, &str)] = &[
+        ("logging.file.directory", "logs", r#""logs""#),
+        ("logging.file.prefix", "app.log", r#""app.log""#),
+        ("logging.file.rotation", "hourly", r#""hourly""#),
+        ("logging.file.max_files", "3", "3"),
+        ("logging.file.format", "json", r#""json""#),
+        ("auth.keys_file", "keys.toml", r#""keys.toml""#),
+        ("auth.jwt.jwks_file", "jwks.json", r#""jwks.json""#),
+        ("auth.jwt.jwks_url", "https://sso/jwks", r#""https://sso/jwks""#),
+        ("auth.jwt.issuer", "https://sso", r#""https://sso""#),
+        ("auth.jwt.audience", "demo", r#""demo""#),
+        ("auth.jwt.required_roles.ask", "student,teacher", r#"["student","teacher"]"#),
+        ("auth.jwt.required_roles.admin", "teacher", r#"["teacher"]"#),
+        ("secrets.file", "secrets.enc", r#""secrets.enc""#),
+    ];
+
+    #[test]
+    fn test_optional_fields_are_overridable() {
+        for (key, raw, expected) in NOT_IN_BASE {
+            let mut vars = env(&[(&env_var(key), raw)]);
+            // logging.file - необязательная секция с обязательным directory
+            vars.insert(env_var("logging.file.directory"), "logs".to_string());
+            let (config, _) = load(&vars);
+            let expected: Value = serde_json::from_str(expected).unwrap();
+            assert_eq!(lookup(&config, key), &expected, "{}", env_var(key));
+        }
+    }
+
+    #[test]
+    fn test_port_and_precedence() {
+        let (config, origin) = load(&env(&[(PORT_ENV, "8080")]));
+        assert_eq!(config["server"]["port"], 8080);
+        assert_eq!(origin.source_of("server.port"), "env:PORT");
+
+        let (config, _) = load(&env(&[(PORT_ENV, "8080"), ("APP_SERVER__PORT", "9000")]));
+        assert_eq!(config["server"]["port"], 9000);
+
+        assert!(layered("config.toml", env(&[(PORT_ENV, "http")])).is_err());
+    }
+
+    #[test]
+    fn test_legacy_and_unrelated_variables() {
+        let error = layered("config.toml", env(&[("APP_SERVER_PORT", "9000")])).unwrap_err();
+        assert!(error.to_string().contains("APP_SERVER_PORT"), "{error}");
+
+        // Переменные других приложений с префиксом APP_ не мешают
+        let (config, _) = load(&env(&[("APP_NAME", "x"), ("APP_PROFILE", "test")]));
+        assert_eq!(config["server"]["environment"], "test");
+
+        // Опечатка в ключе - ошибка, а не молчаливое игнорирование
+        let vars = env(&[("APP_GIGACHAT__MAX_TOKEN", "1")]);
+        let (settings, origin) = layered("config.toml", vars).unwrap();
+        let error = AppConfig::from_settings(settings, origin).unwrap_err();
+        assert!(error.to_string().contains("gigachat.max_token"), "{error}");
+    }
+
+    fn collect(value: &Value, path: String, keys: &mut Vec<(String, Value)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (name, child) in map {
+                    let child_path = if path.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{path}.{name}")
+                    };
+                    collect(child, child_path, keys);
+                }
+            }
+            _ => keys.push((path, value.clone())),
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Профили окружений и слои конфигурации.
+//!
+//! Итоговая конфигурация собирается из слоёв; каждый следующий
+//! переопределяет предыдущие:
+//!
+//! ```text
+//! 1. default                  - значения по умолчанию в коде (#[serde(default)])
+//! 2. config.toml              - базовые настройки
+//! 3. profile:<профиль>        - встроенные настройки профиля (см. preset)
+//! 4. config.<профиль>.toml    - файл профиля, необязателен
+//! 5. env:PORT                 - порт от serverless-платформы
+//! 6. env:APP_<СЕКЦИЯ>__<КЛЮЧ> - переменные окружения (см. модуль overrides)
+//! ```
+//!
+//! Профиль выбирается переменной `APP_PROFILE` (или `APP_SERVER__ENVIRONMENT`);
+//! если она не задана - берётся `server.environment` из config.toml
+//! (по умолчанию `development`).
+//! Слой профиля записывает имя профиля в `server.environment`, поэтому
+//! `AppConfig::is_development()` всегда согласован с профилем.
+//!
+//! Для каждого ключа запоминается слой, из которого взято значение, -
+//! это показывает `GET /admin/config`.
+//!
+//! # Для студентов: Зачем профили?
+//!
+//! Разработка, тесты и прод отличаются немногим: форматом логов, адресом,
+//! ключами. Вместо трёх почти одинаковых файлов общая часть лежит в
+//! config.toml, а в `config.production.toml` - только отличия.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use config::{Config, ConfigError, File, FileFormat};
+use serde_json::Value;
+
+use super::{overrides, AppConfig};
+
+/// Переменная окружения, выбирающая профиль.
+pub const PROFILE_ENV: &str = "APP_PROFILE";
+
+/// Профиль, если не задан ни `APP_PROFILE`, ни `server.environment`.
+pub const DEFAULT_PROFILE: &str = "development";
+
+/// Источник значений, не заданных ни в одном слое.
+pub const DEFAULT_SOURCE: &str = "default";
+
+/// Имя слоя переменных `APP_*`.
+const ENV_SOURCE: &str = "env";
+
+/// Чем заменяются секреты в `GET /admin/config`.
+pub const REDACTED: &str = "***";
+
+/// Имена полей, значения которых никогда не показываются.
+const SECRET_FIELDS: &[&str] = &["key", "token", "secret", "password"];
+
+/// Встроенные настройки профиля (слой `profile:<имя>`).
+///
+/// Их можно переопределить в `config.<профиль>.toml` или через `APP_*`.
+fn preset(profile: &str) -> &'static str {
+    match profile {
+        // В проде логи собирает агрегатор - ему нужен JSON, а ответы -
+        // только настоящие: без токена сервер не запускается, а не
+        // молча отвечает заглушкой (см. validation::mock_fallback)
+        "production" => {
+            "[logging]\nformat = \"json\"\n\n[gigachat]\nenabled = true\nmock_fallback = false\n"
+        }
+        _ => "",
+    }
+}
+
+/// Откуда взялась конфигурация: профиль, файлы и источник каждого значения.
+///
+/// Заполняется при загрузке и хранится в `AppConfig::origin`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConfigOrigin {
+    /// Выбранный профиль
+    pub profile: String,
+
+    /// Прочитанные файлы (за ними следит перезагрузка)
+    pub files: Vec<String>,
+
+    /// Ключ (`gigachat.temperature`, `auth.keys[0].name`) → слой
+    /// (`config.toml`, `profile:production`, `env:APP_SERVER__PORT`, ...)
+    pub sources: BTreeMap<String, String>,
+}
+
+impl ConfigOrigin {
+    /// Слой, из которого взято значение ключа.
+    pub fn source_of(&self, key: &str) -> &str {
+        self.sources.get(key).map_or(DEFAULT_SOURCE, String::as_str)
+    }
+
+    /// Берёт источники указанных секций из `other` (при перезагрузке).
+    pub fn adopt(&mut self, other: &ConfigOrigin, sections: &[&str]) {
+        let in_sections = |key: &String| sections.iter().any(|s| in_section(key, s));
+        self.sources.retain(|key, _| !in_sections(key));
+        self.sources.extend(
+            other
+                .sources
+                .iter()
+                .filter(|(key, _)| in_sections(key))
+                .map(|(key, layer)| (key.clone(), layer.clone())),
+        );
+    }
+}
+
+fn in_section(key: &str, section: &str) -> bool {
+    key.strip_prefix(section)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+}
+
+/// Путь к файлу профиля: `config.toml` → `config.production.toml`.
+pub fn profile_path(base: &str, profile: &str) -> String {
+    match base.strip_suffix(".toml") {
+        Some(stem) => format!("{stem}.{profile}.toml"),
+        None => format!("{base}.{profile}"),
+    }
+}
+
+/// Собирает слои конфигурации.
+///
+/// `env` - переменные окружения (в тестах передаются явно).
+pub fn layered(
+    base_path: &str,
+    env: HashMap<String, String>,
+) -> Result<(Config, ConfigOrigin), ConfigError> {
+    let base = Config::builder().add_source(File::with_name(base_path)).build()?;
+
+    let profile = match env
+        .get(PROFILE_ENV)
+        .or_else(|| env.get(&overrides::env_var("server.environment")))
+    {
+        Some(profile) => profile.clone(),
+        None => base
+            .get_string("server.environment")
+            .unwrap_or_else(|_| DEFAULT_PROFILE.to_string()),
+    };
+    if profile.is_empty()
+        || !profile.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ConfigError::Message(format!(
+            "{PROFILE_ENV}: недопустимое имя профиля '{profile}' (латиница, цифры, '-', '_')"
+        )));
+    }
+
+    let preset = format!("[server]\nenvironment = \"{profile}\"\n{}", preset(&profile));
+    let preset = Config::builder()
+        .add_source(File::from_str(&preset, FileFormat::Toml))
+        .build()?;
+
+    let overlay_path = profile_path(base_path, &profile);
+    let overlay = Config::builder()
+        .add_source(File::with_name(&overlay_path).required(false))
+        .build()?;
+
+    let port = overrides::port_layer(&env)?;
+    let environment = overrides::env_layer(&env)?;
+
+    let mut files = vec![base_path.to_string()];
+    if Path::new(&overlay_path).exists() {
+        files.push(overlay_path.clone());
+    }
+
+    let layers = [
+        (base_path.to_string(), Some(base)),
+        (format!("profile:{profile}"), Some(preset)),
+        (overlay_path, Some(overlay)),
+        (format!("env:{}", overrides::PORT_ENV), port),
+        (ENV_SOURCE.to_string(), Some(environment)),
+    ];
+
+    let mut sources = BTreeMap::new();
+    let mut merged = Config::builder();
+    for (name, layer) in layers {
+        let Some(layer) = layer else {
+            continue;
+        };
+        for key in leaf_keys(&layer.clone().try_deserialize::<Value>()?) {
+            // Для переменных окружения источник - имя переменной
+            let source = if name == ENV_SOURCE {
+                let field = key.split('[').next().unwrap_or(&key);
+                format!("env:{}", overrides::env_var(field))
+            } else {
+                name.clone()
+            };
+            sources.insert(key, source);
+        }
+        merged = merged.add_source(layer);
+    }
+
+    Ok((merged.build()?, ConfigOrigin { profile, files, sources }))
+}
+
+// ============================================================================
+// ПРЕДСТАВЛЕНИЕ ДЛЯ /admin/config
+// ============================================================================
+
+/// Пути всех листовых значений: `server.port`, `auth.keys[0].scopes[1]`.
+pub fn leaf_keys(value: &Value) -> Vec<String> {
+    let mut keys = Vec::new();
+    collect_keys(value, String::new(), &mut keys);
+    keys
+}
+
+fn collect_keys(value: &Value, path: String, keys: &mut Vec<String>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (name, child) in map {
+                let child_path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                collect_keys(child, child_path, keys);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, child) in items.iter().enumerate() {
+                collect_keys(child, format!("{path}[{i}]"), keys);
+            }
+        }
+        _ if !path.is_empty() => keys.push(path),
+        _ => {}
+    }
+}
+
+/// Итоговая конфигурация без секретов и источник каждого её значения.
+pub fn describe(config: &AppConfig) -> (Value, BTreeMap<String, String>) {
+    let mut value = serde_json::to_value(config).expect("AppConfig сериализуется в JSON");
+    redact(&mut value);
+    let sources = leaf_keys(&value)
+        .into_iter()
+        .map(|key| {
+            let layer = config.origin.source_of(&key).to_string();
+            (key, layer)
+        })
+        .collect();
+    (value, sources)
+}
+
+/// Заменяет значения секретных полей на [`REDACTED`].
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (name, child) in map.iter_mut() {
+                if is_secret(name) && !child.is_null() {
+                    *child = Value::String(REDACTED.to_string());
+                } else {
+                    redact(child);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+fn is_secret(field: &str) -> bool {
+    SECRET_FIELDS
+        .iter()
+        .any(|secret| field == *secret || field.ends_with(&format!("_{secret}")))
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{validation, ValidationReport};
+    use crate::secrets::Secrets;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn load(vars: &[(&str, &str)]) -> (AppConfig, ConfigOrigin) {
+        let (settings, origin) = layered("config.toml", env(vars)).unwrap();
+        (settings.try_deserialize().unwrap(), origin)
+    }
+
+    #[test]
+    fn test_profile_path() {
+        assert_eq!(profile_path("config.toml", "production"), "config.production.toml");
+        assert_eq!(profile_path("conf/app", "test"), "conf/app.test");
+    }
+
+    #[test]
+    fn test_profile_from_base_file() {
+        let (config, origin) = load(&[]);
+        assert_eq!(origin.profile, "development");
+        assert_eq!(config.server.environment, "development");
+        assert_eq!(origin.source_of("server.port"), "config.toml");
+        assert_eq!(origin.source_of("server.environment"), "profile:development");
+        assert_eq!(origin.source_of("auth.jwt.jwks_file"), DEFAULT_SOURCE);
+    }
+
+    #[test]
+    fn test_production_preset_and_env_override() {
+        let (config, origin) = load(&[(PROFILE_ENV, "production"), ("APP_SERVER__PORT", "9000")]);
+        assert_eq!(config.server.environment, "production");
+        assert_eq!(config.logging.format, "json");
+        assert_eq!(origin.source_of("logging.format"), "profile:production");
+        assert_eq!(config.server.port, 9000);
+        assert_eq!(origin.source_of("server.port"), "env:APP_SERVER__PORT");
+    }
+
+    #[test]
+    fn test_production_refuses_mock_fallback() {
+        let (config, origin) = load(&[(PROFILE_ENV, "production")]);
+        assert!(config.gigachat.enabled);
+        assert!(!config.gigachat.mock_fallback);
+        assert_eq!(origin.source_of("gigachat.mock_fallback"), "profile:production");
+
+        // Без токена - ошибка проверки, а не mock
+        let mut report = ValidationReport::default();
+        validation::mock_fallback(&config, &Secrets::new(None, HashMap::new()), &mut report);
+        assert_eq!(report.issues[0].path, "gigachat.mock_fallback");
+
+        // С токеном - порядок (если сборка умеет ходить в GigaChat)
+        let mut report = ValidationReport::default();
+        let secrets = Secrets::new(None, env(&[("GIGACHAT_TOKEN", "token")]));
+        validation::mock_fallback(&config, &secrets, &mut report);
+        assert_eq!(report.issues.is_empty(), cfg!(feature = "gigachat"));
+
+        // В разработке заглушка разрешена
+        let (config, _) = load(&[]);
+        let mut report = ValidationReport::default();
+        validation::mock_fallback(&config, &Secrets::new(None, HashMap::new()), &mut report);
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn test_describe_redacts_keys() {
+        let (mut config, origin) = load(&[]);
+        config.auth.keys = vec![crate::config::ApiKeyConfig {
+            name: "frontend".to_string(),
+            key: "sk-123".into(),
+            scopes: vec!["ask".to_string()],
+        }];
+        config.origin = origin;
+
+        let (value, sources) = describe(&config);
+        assert_eq!(value["auth"]["keys"][0]["key"], REDACTED);
+        assert_eq!(sources["auth.keys[0].key"], DEFAULT_SOURCE);
+        assert_eq!(sources["gigachat.model"], "config.toml");
+        assert!(!value.to_string().contains("sk-123"));
+        assert!(!format!("{config:?}").contains("sk-123"));
+    }
+
+    #[test]
+    fn test_invalid_profile_name() {
+        assert!(layered("config.toml", env(&[(PROFILE_ENV, "../etc")])).is_err());
+    }
+
+    #[test]
+    fn test_redact_secret_fields() {
+        let mut value = serde_json::json!({
+            "auth": { "keys": [{ "name": "frontend", "key": "sk-123" }] },
+            "gigachat": { "access_token": "t", "model": "GigaChat" },
+            "telemetry": { "token": null }
+        });
+        redact(&mut value);
+        assert_eq!(value["auth"]["keys"][0]["key"], REDACTED);
+        assert_eq!(value["auth"]["keys"][0]["name"], "frontend");
+        assert_eq!(value["gigachat"]["access_token"], REDACTED);
+        assert_eq!(value["gigachat"]["model"], "GigaChat");
+        assert!(value["telemetry"]["token"].is_null());
+        assert_eq!(leaf_keys(&value)[0], "auth.keys[0].key");
+    }
+}
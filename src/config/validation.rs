@@ -0,0 +1,678 @@
+//! Проверка значений конфигурации.
+//!
+//! Serde проверяет только ТИПЫ: `temperature = 5.0` - корректное число,
+//! `level = "verbose"` - корректная строка. Здесь проверяются диапазоны,
+//! допустимые значения и связи между полями. Все найденные проблемы
+//! собираются в один [`ValidationReport`] с путями ключей:
+//!
+//! ```text
+//! Конфигурация содержит ошибки (2):
+//!   - gigachat.temperature: должно быть от 0.0 до 2.0, указано 5
+//!   - logging.level: Неизвестный уровень логов 'verbose' (допустимо: trace, debug, info, warn, error, off)
+//! ```
+//!
+//! Неизвестные ключи (опечатки вроде `tempreature`) serde пропускает, а
+//! [`AppConfig::load_from`] собирает их через `serde_ignored` и передаёт
+//! в [`validate_with_unknown`] - они попадают в тот же отчёт:
+//!
+//! ```text
+//! Конфигурация содержит ошибки (2):
+//!   - gigachat.temprature: неизвестный ключ (опечатка?)
+//!   - gigachat.temperature: должно быть от 0.0 до 2.0, указано 5
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use jsonwebtoken::Algorithm;
+use thiserror::Error;
+
+use super::{AppConfig, BucketConfig};
+use crate::experiment;
+use crate::logging::{self, LogFormat};
+use crate::models::GuardAction;
+use crate::moderation::{Blocklist, ModerationAction, PiiDetector};
+use crate::secrets::Secrets;
+use crate::storage;
+use crate::telemetry::ExporterKind;
+use crate::templates;
+
+/// Допустимые значения `server.environment`.
+pub const ENVIRONMENTS: &[&str] = &["development", "production", "test"];
+
+/// Scopes, которые понимают обработчики (см. `auth::scopes`).
+pub const KNOWN_SCOPES: &[&str] = &["ask", "health", "admin", "*"];
+
+/// Одна проблема: путь ключа и что с ним не так.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationIssue {
+    /// Путь ключа в config.toml, например `gigachat.temperature`
+    pub path: String,
+
+    /// Описание проблемы
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// Все проблемы конфигурации сразу - чтобы не исправлять их по одной.
+#[derive(Error, Debug, Clone, PartialEq, Default)]
+#[error("{}", render(.issues))]
+pub struct ValidationReport {
+    /// Найденные проблемы в порядке секций config.toml
+    pub issues: Vec<ValidationIssue>,
+}
+
+fn render(issues: &[ValidationIssue]) -> String {
+    let mut text = format!("Конфигурация содержит ошибки ({}):", issues.len());
+    for issue in issues {
+        text.push_str(&format!("\n  - {}", issue));
+    }
+    text
+}
+
+impl ValidationReport {
+    fn push(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check(&mut self, ok: bool, path: &str, message: impl FnOnce() -> String) {
+        if !ok {
+            self.push(path, message());
+        }
+    }
+}
+
+/// Путь пропущенного serde ключа в записи отчёта: `auth.keys[1].scope`.
+///
+/// ## Для студентов
+///
+/// `serde_ignored` вызывает колбэк для каждого ключа, которого нет в
+/// структуре, и передаёт путь от корня. Обёртки `Option` и newtype-структур
+/// в пути не нужны - в файле их не видно.
+pub fn key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path;
+
+    match path {
+        Path::Root => String::new(),
+        Path::Seq { parent, index } => format!("{}[{index}]", key_path(parent)),
+        Path::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{parent}.{key}"),
+        },
+        Path::Some { parent } | Path::NewtypeStruct { parent } | Path::NewtypeVariant { parent } => {
+            key_path(parent)
+        }
+    }
+}
+
+// ============================================================================
+// ПРОВЕРКИ ПО СЕКЦИЯМ
+// ============================================================================
+
+/// Проверяет конфигурацию целиком.
+pub fn validate(config: &AppConfig) -> Result<(), ValidationReport> {
+    validate_with_unknown(config, &[])
+}
+
+/// Как [`validate`], но отчёт начинается с неизвестных ключей `unknown`
+/// (пути вида `gigachat.temprature`, см. [`key_path`]).
+pub fn validate_with_unknown(config: &AppConfig, unknown: &[String]) -> Result<(), ValidationReport> {
+    let mut report = ValidationReport::default();
+    for path in unknown {
+        report.push(path.as_str(), "неизвестный ключ (опечатка?)");
+    }
+    server(config, &mut report);
+    gigachat(config, &mut report);
+    logging(config, &mut report);
+    application(config, &mut report);
+    auth(config, &mut report);
+    rate_limit(config, &mut report);
+    concurrency(config, &mut report);
+    cors(config, &mut report);
+    observability(config, &mut report);
+    storage(config, &mut report);
+    templates(config, &mut report);
+    experiment(config, &mut report);
+    guardrails(config, &mut report);
+    moderation(config, &mut report);
+
+    if report.issues.is_empty() {
+        Ok(())
+    } else {
+        Err(report)
+    }
+}
+
+fn server(config: &AppConfig, report: &mut ValidationReport) {
+    let server = &config.server;
+    report.check(IpAddr::from_str(&server.address).is_ok(), "server.address", || {
+        format!("'{}' не является IP-адресом (например, 127.0.0.1 или 0.0.0.0)", server.address)
+    });
+    report.check(server.port != 0, "server.port", || "должен быть от 1 до 65535".to_string());
+    report.check(
+        ENVIRONMENTS.contains(&server.environment.as_str()),
+        "server.environment",
+        || format!("неизвестное окружение '{}' ({})", server.environment, ENVIRONMENTS.join(", ")),
+    );
+}
+
+fn gigachat(config: &AppConfig, report: &mut ValidationReport) {
+    let gigachat = &config.gigachat;
+    report.check(!gigachat.model.trim().is_empty(), "gigachat.model", || {
+        "не может быть пустым".to_string()
+    });
+    report.check(
+        (1..=32_768).contains(&gigachat.max_tokens),
+        "gigachat.max_tokens",
+        || format!("должно быть от 1 до 32768, указано {}", gigachat.max_tokens),
+    );
+    report.check(
+        (0.0..=2.0).contains(&gigachat.temperature),
+        "gigachat.temperature",
+        || format!("должно быть от 0.0 до 2.0, указано {}", gigachat.temperature),
+    );
+    report.check(
+        (1..=600).contains(&gigachat.timeout_seconds),
+        "gigachat.timeout_seconds",
+        || format!("должно быть от 1 до 600, указано {}", gigachat.timeout_seconds),
+    );
+    mock_fallback(config, &Secrets::from_config(&config.secrets), report);
+}
+
+/// С `gigachat.mock_fallback = false` (профиль `production`) включённый
+/// GigaChat обязан быть доступен: иначе `reload::select_backend` молча
+/// перешёл бы на заглушку.
+pub(super) fn mock_fallback(config: &AppConfig, secrets: &Secrets, report: &mut ValidationReport) {
+    let gigachat = &config.gigachat;
+    if !gigachat.enabled || gigachat.mock_fallback {
+        return;
+    }
+    if !cfg!(feature = "gigachat") {
+        report.push("gigachat.mock_fallback", "сборка без фичи gigachat, а переход на mock запрещён");
+    } else if let Err(e) = secrets.get("GIGACHAT_TOKEN") {
+        report.push(
+            "gigachat.mock_fallback",
+            format!("токен GigaChat недоступен ({e}), а переход на mock запрещён"),
+        );
+    }
+}
+
+fn logging(config: &AppConfig, report: &mut ValidationReport) {
+    let logging = &config.logging;
+    if let Err(e) = logging::parse_level(&logging.level) {
+        report.push("logging.level", e.to_string());
+    }
+    if let Err(e) = logging.format.parse::<LogFormat>() {
+        report.push("logging.format", e.to_string());
+    }
+
+    let mut targets: Vec<_> = logging.targets.iter().collect();
+    targets.sort();
+    for (target, level) in targets {
+        if let Err(e) = logging::parse_level(level) {
+            report.push(format!("logging.targets.{}", target), e.to_string());
+        }
+    }
+
+    if let Some(file) = &logging.file {
+        report.check(!file.directory.trim().is_empty(), "logging.file.directory", || {
+            "не может быть пустым".to_string()
+        });
+        if let Err(e) = logging::parse_rotation(&file.rotation) {
+            report.push("logging.file.rotation", e.to_string());
+        }
+        report.check(file.max_files != Some(0), "logging.file.max_files", || {
+            "должно быть не меньше 1 (или не задано - хранить все)".to_string()
+        });
+        if let Some(Err(e)) = file.format.as_deref().map(str::parse::<LogFormat>) {
+            report.push("logging.file.format", e.to_string());
+        }
+    }
+}
+
+fn application(config: &AppConfig, report: &mut ValidationReport) {
+    let application = &config.application;
+    report.check(!application.name.trim().is_empty(), "application.name", || {
+        "не может быть пустым".to_string()
+    });
+    let is_semver = application.version.split('.').count() == 3
+        && application
+            .version
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+    report.check(is_semver, "application.version", || {
+        format!("'{}' - ожидается версия вида MAJOR.MINOR.PATCH", application.version)
+    });
+}
+
+fn auth(config: &AppConfig, report: &mut ValidationReport) {
+    let auth = &config.auth;
+
+    let mut names = HashSet::new();
+    for (i, key) in auth.keys.iter().enumerate() {
+        let path = format!("auth.keys[{}]", i);
+        report.check(!key.name.trim().is_empty(), &format!("{path}.name"), || {
+            "не может быть пустым".to_string()
+        });
+        report.check(names.insert(key.name.as_str()), &format!("{path}.name"), || {
+            format!("ключ с именем '{}' уже есть", key.name)
+        });
+        report.check(!key.key.expose().is_empty(), &format!("{path}.key"), || {
+            "не может быть пустым".to_string()
+        });
+        for scope in &key.scopes {
+            report.check(KNOWN_SCOPES.contains(&scope.as_str()), &format!("{path}.scopes"), || {
+                format!("неизвестный scope '{}' ({})", scope, KNOWN_SCOPES.join(", "))
+            });
+        }
+    }
+
+    let jwt = &auth.jwt;
+    if jwt.enabled {
+        report.check(auth.enabled, "auth.jwt.enabled", || {
+            "не действует без auth.enabled = true".to_string()
+        });
+        report.check(
+            jwt.jwks_file.is_some() || jwt.jwks_url.is_some(),
+            "auth.jwt",
+            || "нужен jwks_file или jwks_url".to_string(),
+        );
+    }
+    report.check(!jwt.algorithms.is_empty(), "auth.jwt.algorithms", || {
+        "нужен хотя бы один алгоритм".to_string()
+    });
+    for name in &jwt.algorithms {
+        report.check(Algorithm::from_str(name).is_ok(), "auth.jwt.algorithms", || {
+            format!("неизвестный алгоритм '{}'", name)
+        });
+    }
+    let mut scopes: Vec<_> = jwt.required_roles.keys().collect();
+    scopes.sort();
+    for scope in scopes {
+        report.check(
+            KNOWN_SCOPES.contains(&scope.as_str()),
+            &format!("auth.jwt.required_roles.{}", scope),
+            || format!("неизвестный scope ({})", KNOWN_SCOPES.join(", ")),
+        );
+    }
+}
+
+fn rate_limit(config: &AppConfig, report: &mut ValidationReport) {
+    let limits = &config.rate_limit;
+    for (name, bucket) in [
+        ("per_ip", &limits.per_ip),
+        ("per_key", &limits.per_key),
+        ("global", &limits.global),
+    ] {
+        let Some(BucketConfig { capacity, refill_per_minute }) = bucket else {
+            continue;
+        };
+        report.check(*capacity > 0, &format!("rate_limit.{name}.capacity"), || {
+            "должно быть больше 0, иначе все запросы получат 429".to_string()
+        });
+        report.check(*refill_per_minute > 0, &format!("rate_limit.{name}.refill_per_minute"), || {
+            "должно быть больше 0, иначе ведро никогда не наполнится".to_string()
+        });
+    }
+    for (i, proxy) in limits.trusted_proxies.iter().enumerate() {
+        report.check(proxy.parse::<IpAddr>().is_ok(), &format!("rate_limit.trusted_proxies[{}]", i), || {
+            format!("'{}' - не IP-адрес", proxy)
+        });
+    }
+    report.check(
+        limits.trusted_proxies.is_empty() || !limits.ip_header.trim().is_empty(),
+        "rate_limit.ip_header",
+        || "не может быть пустым, если заданы trusted_proxies".to_string(),
+    );
+}
+
+fn concurrency(config: &AppConfig, report: &mut ValidationReport) {
+    let concurrency = &config.concurrency;
+    if !concurrency.enabled {
+        return;
+    }
+    report.check(concurrency.max_concurrent > 0, "concurrency.max_concurrent", || {
+        "должно быть больше 0".to_string()
+    });
+    report.check(
+        concurrency.queue_timeout_seconds > 0,
+        "concurrency.queue_timeout_seconds",
+        || "должно быть больше 0".to_string(),
+    );
+}
+
+fn cors(config: &AppConfig, report: &mut ValidationReport) {
+    let cors = &config.cors;
+    for origin in &cors.allowed_origins {
+        report.check(
+            origin == "*" || origin.starts_with("http://") || origin.starts_with("https://"),
+            "cors.allowed_origins",
+            || format!("'{}' - ожидается \"*\" или origin вида https://host[:port]", origin),
+        );
+    }
+    // Браузеры отклоняют `Access-Control-Allow-Origin: *` вместе с credentials
+    report.check(
+        !(cors.allow_credentials && cors.allowed_origins.iter().any(|o| o == "*")),
+        "cors.allow_credentials",
+        || "нельзя сочетать с allowed_origins = [\"*\"]".to_string(),
+    );
+}
+
+fn observability(config: &AppConfig, report: &mut ValidationReport) {
+    let metrics = &config.metrics;
+    report.check(metrics.path.starts_with('/'), "metrics.path", || {
+        format!("'{}' должен начинаться с '/'", metrics.path)
+    });
+
+    let telemetry = &config.telemetry;
+    if let Err(e) = telemetry.exporter.parse::<ExporterKind>() {
+        report.push("telemetry.exporter", e.to_string());
+    }
+    report.check(
+        (0.0..=1.0).contains(&telemetry.sample_ratio),
+        "telemetry.sample_ratio",
+        || format!("должно быть от 0.0 до 1.0, указано {}", telemetry.sample_ratio),
+    );
+
+    let reload = &config.reload;
+    report.check(
+        !reload.watch || reload.poll_interval_seconds > 0,
+        "reload.poll_interval_seconds",
+        || "должно быть больше 0 при watch = true".to_string(),
+    );
+}
+
+fn storage(config: &AppConfig, report: &mut ValidationReport) {
+    let storage = &config.storage;
+    report.check(
+        storage::BACKENDS.contains(&storage.backend.as_str()),
+        "storage.backend",
+        || format!("неизвестный движок '{}' ({})", storage.backend, storage::BACKENDS.join(", ")),
+    );
+    report.check(
+        storage.backend != "sqlite" || !storage.path.trim().is_empty(),
+        "storage.path",
+        || "не может быть пустым для backend = \"sqlite\"".to_string(),
+    );
+}
+
+fn templates(config: &AppConfig, report: &mut ValidationReport) {
+    let templates = &config.templates;
+    report.check(
+        templates.directory.as_deref().is_none_or(|dir| !dir.trim().is_empty()),
+        "templates.directory",
+        || "не может быть пустым (уберите ключ, если шаблоны не нужны)".to_string(),
+    );
+    for (name, value) in &templates.defaults {
+        let path = format!("templates.defaults.{}", name);
+        report.check(name != templates::QUESTION, &path, || {
+            "вопрос подставляется из запроса, значения по умолчанию у него нет".to_string()
+        });
+        report.check(value.chars().count() <= templates::MAX_VALUE_CHARS, &path, || {
+            format!("значение длиннее {} символов", templates::MAX_VALUE_CHARS)
+        });
+    }
+}
+
+fn experiment(config: &AppConfig, report: &mut ValidationReport) {
+    let experiment = &config.experiment;
+    report.check(!experiment.name.trim().is_empty(), "experiment.name", || {
+        "не может быть пустым: от имени зависит распределение клиентов".to_string()
+    });
+
+    let mut ids = HashSet::new();
+    for (i, variant) in experiment.variants.iter().enumerate() {
+        let path = format!("experiment.variants[{}]", i);
+        report.check(experiment::is_valid_id(&variant.id), &format!("{path}.id"), || {
+            format!(
+                "'{}' - строчные латинские буквы, цифры, '-' и '_', до {} символов",
+                variant.id,
+                experiment::MAX_VARIANT_ID_LEN
+            )
+        });
+        report.check(ids.insert(variant.id.as_str()), &format!("{path}.id"), || {
+            format!("вариант '{}' уже есть", variant.id)
+        });
+        report.check(
+            variant.system_prompt.as_deref().is_none_or(|prompt| !prompt.trim().is_empty()),
+            &format!("{path}.system_prompt"),
+            || "не может быть пустым (уберите ключ, чтобы взять application.system_prompt)".to_string(),
+        );
+    }
+    if experiment.enabled {
+        // Без аутентификации все клиенты - "anonymous" и попадают в один вариант
+        report.check(config.auth.enabled, "experiment.enabled", || {
+            "не действует без auth.enabled = true: все клиенты анонимны и получают один вариант"
+                .to_string()
+        });
+        report.check(
+            experiment.variants.iter().any(|variant| variant.weight > 0),
+            "experiment.variants",
+            || "нужен хотя бы один вариант с weight > 0".to_string(),
+        );
+    }
+}
+
+fn guardrails(config: &AppConfig, report: &mut ValidationReport) {
+    let guardrails = &config.guardrails;
+    for (path, action) in [
+        ("guardrails.input_action", &guardrails.input_action),
+        ("guardrails.output_action", &guardrails.output_action),
+    ] {
+        report.check(GuardAction::parse(action).is_some(), path, || {
+            format!("'{}' - допустимо block, sanitize или flag", action)
+        });
+    }
+    for (i, pattern) in guardrails.extra_patterns.iter().enumerate() {
+        report.check(!pattern.trim().is_empty(), &format!("guardrails.extra_patterns[{}]", i), || {
+            "не может быть пустым".to_string()
+        });
+    }
+    report.check(guardrails.ngram_size >= 2, "guardrails.ngram_size", || {
+        format!("{} - нужно хотя бы 2 слова, иначе утечкой сочтётся любое общее слово", guardrails.ngram_size)
+    });
+    report.check(
+        guardrails.leak_threshold > 0.0 && guardrails.leak_threshold <= 1.0,
+        "guardrails.leak_threshold",
+        || format!("{} - должен быть больше 0 и не больше 1", guardrails.leak_threshold),
+    );
+}
+
+fn moderation(config: &AppConfig, report: &mut ValidationReport) {
+    let moderation = &config.moderation;
+    for (path, action) in [
+        ("moderation.blocklist_action", &moderation.blocklist_action),
+        ("moderation.pii_action", &moderation.pii_action),
+    ] {
+        report.check(ModerationAction::parse(action).is_some(), path, || {
+            format!("'{}' - допустимо block, redact или warn", action)
+        });
+    }
+    for (i, keyword) in moderation.keywords.iter().enumerate() {
+        report.check(!keyword.trim().is_empty(), &format!("moderation.keywords[{}]", i), || {
+            "не может быть пустым".to_string()
+        });
+    }
+    for (i, pattern) in moderation.patterns.iter().enumerate() {
+        if let Err(e) = Blocklist::new(&[], std::slice::from_ref(pattern)) {
+            report.push(format!("moderation.patterns[{}]", i), e.to_string());
+        }
+    }
+    for (i, kind) in moderation.pii.iter().enumerate() {
+        report.check(PiiDetector::is_known(kind), &format!("moderation.pii[{}]", i), || {
+            format!("'{}' - допустимо email, phone или passport", kind)
+        });
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigError;
+
+    fn has(report: &ValidationReport, path: &str) -> bool {
+        report.issues.iter().any(|issue| issue.path == path)
+    }
+
+    fn config() -> AppConfig {
+        AppConfig::load().expect("config.toml должен быть корректным")
+    }
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert_eq!(validate(&config()), Ok(()));
+    }
+
+    #[test]
+    fn test_collects_all_issues() {
+        let mut config = config();
+        config.gigachat.temperature = 5.0;
+        config.gigachat.max_tokens = 0;
+        config.logging.level = "verbose".to_string();
+        config.server.address = "localhost:8000".to_string();
+
+        let report = validate(&config).unwrap_err();
+        assert_eq!(report.issues.len(), 4);
+        for path in ["gigachat.temperature", "gigachat.max_tokens", "logging.level", "server.address"] {
+            assert!(has(&report, path), "нет проблемы {path}: {report}");
+        }
+        assert!(report.to_string().starts_with("Конфигурация содержит ошибки (4):"));
+    }
+
+    #[test]
+    fn test_cross_field_rules() {
+        let mut config = config();
+        config.cors.allowed_origins = vec!["*".to_string()];
+        config.cors.allow_credentials = true;
+        config.auth.enabled = false;
+        config.auth.jwt.enabled = true;
+        config.auth.jwt.jwks_file = None;
+        config.auth.jwt.jwks_url = None;
+
+        config.rate_limit.trusted_proxies = vec!["10.0.0.5".to_string(), "nginx".to_string()];
+        config.rate_limit.ip_header = String::new();
+
+        let report = validate(&config).unwrap_err();
+        assert!(has(&report, "cors.allow_credentials"));
+        assert!(has(&report, "auth.jwt.enabled"));
+        assert!(has(&report, "auth.jwt"));
+        assert!(has(&report, "rate_limit.trusted_proxies[1]"));
+        assert!(!has(&report, "rate_limit.trusted_proxies[0]"));
+        assert!(has(&report, "rate_limit.ip_header"));
+    }
+
+    #[test]
+    fn test_template_defaults() {
+        let mut config = config();
+        config.templates.directory = Some(" ".to_string());
+        config.templates.defaults.insert("question".to_string(), "?".to_string());
+        config.templates.defaults.insert("level".to_string(), "x".repeat(templates::MAX_VALUE_CHARS + 1));
+
+        let report = validate(&config).unwrap_err();
+        assert_eq!(report.issues.len(), 3, "{report}");
+        assert!(has(&report, "templates.directory"));
+        assert!(has(&report, "templates.defaults.question"));
+        assert!(has(&report, "templates.defaults.level"));
+    }
+
+    #[test]
+    fn test_experiment_variants() {
+        let mut config = config();
+        config.experiment.enabled = true;
+        assert!(has(&validate(&config).unwrap_err(), "experiment.enabled"));
+        config.auth.enabled = true;
+        assert!(validate(&config).is_ok());
+        config.experiment.variants.iter_mut().for_each(|variant| variant.weight = 0);
+        assert!(has(&validate(&config).unwrap_err(), "experiment.variants"));
+
+        let variant = |id: &str, prompt: Option<&str>| crate::config::PromptVariantConfig {
+            id: id.to_string(),
+            weight: 1,
+            system_prompt: prompt.map(str::to_string),
+        };
+        config.experiment.variants = vec![
+            variant("control", None),
+            variant("control", Some("Отвечай вопросом")),
+            variant("Socratic", Some(" ")),
+        ];
+        let report = validate(&config).unwrap_err();
+        assert_eq!(report.issues.len(), 3, "{report}");
+        assert!(has(&report, "experiment.variants[1].id"));
+        assert!(has(&report, "experiment.variants[2].id"));
+        assert!(has(&report, "experiment.variants[2].system_prompt"));
+    }
+
+    #[test]
+    fn test_guardrails() {
+        let mut config = config();
+        config.guardrails.input_action = "warn".to_string();
+        config.guardrails.extra_patterns = vec!["  ".to_string()];
+        config.guardrails.ngram_size = 1;
+        config.guardrails.leak_threshold = 0.0;
+
+        let report = validate(&config).unwrap_err();
+        assert_eq!(report.issues.len(), 4, "{report}");
+        for path in [
+            "guardrails.input_action",
+            "guardrails.extra_patterns[0]",
+            "guardrails.ngram_size",
+            "guardrails.leak_threshold",
+        ] {
+            assert!(has(&report, path), "нет проблемы {path}: {report}");
+        }
+    }
+
+    #[test]
+    fn test_moderation() {
+        let mut config = config();
+        config.moderation.pii_action = "mask".to_string();
+        config.moderation.keywords = vec!["казино".to_string(), " ".to_string()];
+        config.moderation.patterns = vec![r"\d{3}".to_string(), "(".to_string()];
+        config.moderation.pii = vec!["email".to_string(), "snils".to_string()];
+
+        let report = validate(&config).unwrap_err();
+        assert_eq!(report.issues.len(), 4, "{report}");
+        for path in [
+            "moderation.pii_action",
+            "moderation.keywords[1]",
+            "moderation.patterns[1]",
+            "moderation.pii[1]",
+        ] {
+            assert!(has(&report, path), "нет проблемы {path}: {report}");
+        }
+    }
+
+    #[test]
+    fn test_unknown_key_is_rejected() {
+        let text = std::fs::read_to_string("config.toml").unwrap();
+        let text = text.replacen("temperature = 0.7", "temperature = 5.0\ntempreature = 0.7", 1);
+        let text = text.replacen("[auth]", "[auth]\nenabeld = true", 1);
+        let settings = config::Config::builder()
+            .add_source(config::File::from_str(&text, config::FileFormat::Toml))
+            .build()
+            .unwrap();
+
+        // Опечатки и ошибки диапазонов - в одном отчёте, с путями
+        let result = AppConfig::from_settings(settings, Default::default());
+        let Err(ConfigError::Invalid(report)) = result else {
+            panic!("ожидался отчёт проверки: {result:?}");
+        };
+        assert!(has(&report, "gigachat.tempreature"), "{report}");
+        assert!(has(&report, "auth.enabeld"), "{report}");
+        assert!(has(&report, "gigachat.temperature"), "{report}");
+    }
+}
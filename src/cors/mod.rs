@@ -0,0 +1,291 @@
+//! Модуль CORS (Cross-Origin Resource Sharing).
+//!
+//! Web-интерфейс и API работают на разных origin (другой порт или домен),
+//! поэтому браузер пропускает ответы API к странице, только если сервер
+//! явно это разрешил заголовками `Access-Control-*`.
+//!
+//! ```text
+//! браузер                                     сервер
+//!   │ OPTIONS /ask  (preflight)                  │
+//!   │ Origin: https://ui.example.com ──────────► │ origin разрешён? метод? заголовки?
+//!   │ ◄──── 204 + Access-Control-Allow-*  ─────  │   нет → 403 Forbidden
+//!   │ POST /ask                                  │
+//!   │ ◄──── JSON + Access-Control-Allow-Origin   │
+//! ```
+//!
+//! # Компоненты
+//!
+//! - [`CorsPolicy`] - правила из секции `[cors]` (передаются через `.manage()`);
+//! - [`Cors`] - fairing, добавляющий заголовки к каждому ответу;
+//! - [`Preflight`] - request guard с заголовками preflight-запроса
+//!   (используется обработчиком `cors_preflight`).
+//!
+//! # Для студентов: Почему origin "эхом", а не `*`?
+//!
+//! `Access-Control-Allow-Origin` допускает только ОДИН origin или `*`.
+//! Чтобы поддержать список и шаблоны, сервер возвращает тот origin, с
+//! которого пришёл запрос, если он разрешён. Раз ответ зависит от заголовка
+//! `Origin`, добавляем `Vary: Origin` - иначе прокси или кеш браузера могут
+//! отдать ответ для одного сайта другому.
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Method};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Response;
+
+use crate::config::CorsConfig;
+
+// ============================================================================
+// ПОЛИТИКА
+// ============================================================================
+
+/// Правила CORS, подготовленные из конфигурации.
+#[derive(Debug, Clone)]
+pub struct CorsPolicy {
+    config: CorsConfig,
+}
+
+/// Почему preflight-запрос отклонён.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreflightRejection {
+    /// Origin не входит в `allowed_origins`
+    Origin(String),
+    /// Метод не входит в `allowed_methods`
+    Method(String),
+    /// Заголовок не входит в `allowed_headers`
+    Header(String),
+}
+
+impl CorsPolicy {
+    /// Создаёт политику из секции `[cors]`.
+    pub fn new(config: CorsConfig) -> Self {
+        Self { config }
+    }
+
+    /// Разрешён ли origin (точное совпадение или шаблон со `*`).
+    pub fn is_origin_allowed(&self, origin: &str) -> bool {
+        self.config
+            .allowed_origins
+            .iter()
+            .any(|pattern| origin_matches(pattern, origin))
+    }
+
+    /// Проверяет preflight-запрос: origin, метод и запрошенные заголовки.
+    pub fn check_preflight(&self, preflight: &Preflight) -> Result<(), PreflightRejection> {
+        // Без Origin это не CORS-запрос (например, curl) - проверять нечего
+        let Some(origin) = &preflight.origin else {
+            return Ok(());
+        };
+        if !self.is_origin_allowed(origin) {
+            return Err(PreflightRejection::Origin(origin.clone()));
+        }
+
+        if let Some(method) = &preflight.method {
+            let allowed = self
+                .config
+                .allowed_methods
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(method));
+            if !allowed {
+                return Err(PreflightRejection::Method(method.clone()));
+            }
+        }
+
+        // Имена заголовков регистронезависимы
+        for header in &preflight.headers {
+            let allowed = self
+                .config
+                .allowed_headers
+                .iter()
+                .any(|h| h == "*" || h.eq_ignore_ascii_case(header));
+            if !allowed {
+                return Err(PreflightRejection::Header(header.clone()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Сравнивает origin с шаблоном.
+///
+/// `*` заменяет один непустой фрагмент без `/`: поддомен или порт.
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.split_once('*') {
+        None => pattern.eq_ignore_ascii_case(origin),
+        Some((prefix, suffix)) => {
+            let origin = origin.to_ascii_lowercase();
+            let (prefix, suffix) = (prefix.to_ascii_lowercase(), suffix.to_ascii_lowercase());
+            origin.len() > prefix.len() + suffix.len()
+                && origin.starts_with(&prefix)
+                && origin.ends_with(&suffix)
+                && !origin[prefix.len()..origin.len() - suffix.len()].contains('/')
+        }
+    }
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Заголовки preflight-запроса (`OPTIONS`).
+#[derive(Debug, Clone, Default)]
+pub struct Preflight {
+    /// `Origin` - страница, с которой идёт запрос
+    pub origin: Option<String>,
+    /// `Access-Control-Request-Method` - метод будущего запроса
+    pub method: Option<String>,
+    /// `Access-Control-Request-Headers` - заголовки будущего запроса
+    pub headers: Vec<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preflight {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        Outcome::Success(Preflight {
+            origin: headers.get_one("Origin").map(str::to_string),
+            method: headers
+                .get_one("Access-Control-Request-Method")
+                .map(str::to_string),
+            headers: headers
+                .get("Access-Control-Request-Headers")
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+        })
+    }
+}
+
+// ============================================================================
+// FAIRING
+// ============================================================================
+
+/// Fairing, добавляющий CORS-заголовки к ответам.
+///
+/// Политику берёт из `State<CorsPolicy>`; если её нет - ничего не делает.
+pub struct Cors;
+
+#[rocket::async_trait]
+impl Fairing for Cors {
+    fn info(&self) -> Info {
+        Info {
+            name: "Add CORS headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(policy) = req.rocket().state::<CorsPolicy>() else {
+            return;
+        };
+
+        res.adjoin_header(Header::new("Vary", "Origin"));
+
+        let Some(origin) = req.headers().get_one("Origin") else {
+            return;
+        };
+        if !policy.is_origin_allowed(origin) {
+            return;
+        }
+
+        let config = &policy.config;
+        res.set_header(Header::new("Access-Control-Allow-Origin", origin.to_string()));
+        if config.allow_credentials {
+            res.set_header(Header::new("Access-Control-Allow-Credentials", "true"));
+        }
+        if !config.expose_headers.is_empty() {
+            res.set_header(Header::new(
+                "Access-Control-Expose-Headers",
+                config.expose_headers.join(", "),
+            ));
+        }
+
+        if req.method() == Method::Options {
+            res.set_header(Header::new(
+                "Access-Control-Allow-Methods",
+                config.allowed_methods.join(", "),
+            ));
+            res.set_header(Header::new(
+                "Access-Control-Allow-Headers",
+                config.allowed_headers.join(", "),
+            ));
+            res.set_header(Header::new(
+                "Access-Control-Max-Age",
+                config.max_age_seconds.to_string(),
+            ));
+        }
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(origins: &[&str]) -> CorsPolicy {
+        CorsPolicy::new(CorsConfig {
+            allowed_origins: origins.iter().map(|o| o.to_string()).collect(),
+            ..CorsConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_origin_patterns() {
+        let policy = policy(&["http://127.0.0.1:8080", "https://*.example.com", "http://localhost:*"]);
+
+        assert!(policy.is_origin_allowed("http://127.0.0.1:8080"));
+        assert!(policy.is_origin_allowed("https://ui.example.com"));
+        assert!(policy.is_origin_allowed("HTTPS://UI.Example.com"));
+        assert!(policy.is_origin_allowed("http://localhost:3000"));
+
+        assert!(!policy.is_origin_allowed("http://127.0.0.1:8081"));
+        assert!(!policy.is_origin_allowed("https://example.com"));
+        assert!(!policy.is_origin_allowed("http://ui.example.com"));
+        assert!(!policy.is_origin_allowed("https://evil.com/.example.com"));
+        assert!(!policy.is_origin_allowed("https://example.com.evil.org"));
+    }
+
+    #[test]
+    fn test_wildcard_allows_any_origin() {
+        assert!(policy(&["*"]).is_origin_allowed("https://anything.org"));
+    }
+
+    #[test]
+    fn test_check_preflight() {
+        let policy = policy(&["http://127.0.0.1:8080"]);
+        let preflight = |origin: &str, method: &str, headers: &[&str]| Preflight {
+            origin: Some(origin.to_string()),
+            method: Some(method.to_string()),
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+        };
+
+        assert_eq!(
+            policy.check_preflight(&preflight("http://127.0.0.1:8080", "POST", &["content-type"])),
+            Ok(())
+        );
+        assert_eq!(
+            policy.check_preflight(&preflight("http://evil.com", "POST", &[])),
+            Err(PreflightRejection::Origin("http://evil.com".to_string()))
+        );
+        assert_eq!(
+            policy.check_preflight(&preflight("http://127.0.0.1:8080", "PUT", &[])),
+            Err(PreflightRejection::Method("PUT".to_string()))
+        );
+        assert_eq!(
+            policy.check_preflight(&preflight("http://127.0.0.1:8080", "POST", &["X-Custom"])),
+            Err(PreflightRejection::Header("X-Custom".to_string()))
+        );
+        assert_eq!(policy.check_preflight(&Preflight::default()), Ok(()));
+    }
+}
@@ -0,0 +1,225 @@
+//! Модуль A/B экспериментов с системными промптами.
+//!
+//! Преподаватель правит `application.system_prompt` и не знает, стало ли
+//! лучше: оценки до и после правки собраны в разные дни и на разных
+//! вопросах. Эксперимент держит несколько вариантов промпта одновременно
+//! и делит между ними клиентов:
+//!
+//! ```text
+//! [experiment] name = "socratic-2026-10"
+//!
+//!  клиент "lab-group-1" ─► SHA-256("socratic-2026-10" + клиент) ─► 0..100
+//!                                                                   │
+//!        ┌──────── control (weight 50) ────────┬──── socratic (50) ─┴──┐
+//!        0                                     50                     100
+//! ```
+//!
+//! Вариант записывается в ответ `/ask` (`prompt_variant`) и в историю -
+//! вместе с именем эксперимента, а `GET /admin/feedback` показывает долю 👍
+//! по каждой паре (эксперимент, вариант): `control` нового эксперимента не
+//! смешивается с `control` прошлого.
+//!
+//! Клиента различает аутентификация (имя API-ключа или `sub` из JWT),
+//! поэтому эксперимент требует `auth.enabled = true`: без неё все
+//! запросы приходят от "anonymous" и попадают в один вариант - проверка
+//! конфигурации такое сочетание не пропускает.
+//!
+//! Секция `[experiment]` применяется через `POST /admin/reload`. Пока
+//! имя и веса не меняются, клиент остаётся в своём варианте; новое имя
+//! эксперимента перемешивает клиентов заново.
+//!
+//! # Для студентов: Почему хеш, а не случайное число?
+//!
+//! Случайный выбор на каждый вопрос смешал бы варианты в одном диалоге, и
+//! студент видел бы то сократовский стиль, то обычный. Хеш от имени
+//! клиента детерминирован: тот же клиент - тот же вариант, после
+//! перезапуска и на любом экземпляре сервера, без хранения назначений.
+
+use std::collections::BTreeMap;
+
+use crate::config::{ExperimentConfig, PromptVariantConfig};
+use crate::models::{FeedbackSummary, VariantSummary};
+use crate::storage;
+
+/// Наибольшая длина идентификатора варианта.
+pub const MAX_VARIANT_ID_LEN: usize = 32;
+
+/// Вариант промпта для клиента `subject`; `None`, если эксперимент выключен.
+///
+/// Клиент попадает в вариант с вероятностью `weight / сумма весов`.
+/// Варианты с `weight = 0` не выбираются.
+pub fn assign<'a>(config: &'a ExperimentConfig, subject: &str) -> Option<&'a PromptVariantConfig> {
+    if !config.enabled {
+        return None;
+    }
+    let total: u64 = config.variants.iter().map(|variant| u64::from(variant.weight)).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut point = bucket(&config.name, subject) % total;
+    config.variants.iter().find(|variant| {
+        let weight = u64::from(variant.weight);
+        if point < weight {
+            return true;
+        }
+        point -= weight;
+        false
+    })
+}
+
+/// Число, которое определяет вариант клиента: первые 8 байт SHA-256.
+fn bucket(experiment: &str, subject: &str) -> u64 {
+    let input = format!("{}\n{}", experiment, subject);
+    let digest = ring::digest::digest(&ring::digest::SHA256, input.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest.as_ref()[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Идентификатор варианта: строчная латиница, цифры, `-` и `_`.
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_VARIANT_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// Сводка по вариантам из групп `GET /admin/feedback`, по эксперименту
+/// и варианту в алфавитном порядке.
+///
+/// Группы одного варианта одного эксперимента с разными моделями и
+/// версиями промпта складываются; ответы вне эксперимента не учитываются.
+pub fn variant_report(groups: &[FeedbackSummary]) -> Vec<VariantSummary> {
+    let mut variants: BTreeMap<(Option<&str>, &str), VariantSummary> = BTreeMap::new();
+    for group in groups {
+        let Some(id) = group.prompt_variant.as_deref() else {
+            continue;
+        };
+        let experiment = group.experiment.as_deref();
+        let variant = variants.entry((experiment, id)).or_insert_with(|| VariantSummary {
+            experiment: experiment.map(str::to_string),
+            prompt_variant: id.to_string(),
+            answers: 0,
+            up: 0,
+            down: 0,
+            satisfaction: None,
+        });
+        variant.answers += group.answers;
+        variant.up += group.up;
+        variant.down += group.down;
+    }
+    variants
+        .into_values()
+        .map(|variant| VariantSummary {
+            satisfaction: storage::satisfaction(variant.up, variant.down),
+            ..variant
+        })
+        .collect()
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn experiment(weights: &[(&str, u32)]) -> ExperimentConfig {
+        ExperimentConfig {
+            enabled: true,
+            name: "socratic-2026-10".to_string(),
+            variants: weights
+                .iter()
+                .map(|(id, weight)| PromptVariantConfig {
+                    id: id.to_string(),
+                    weight: *weight,
+                    system_prompt: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn subjects() -> impl Iterator<Item = String> {
+        (0..2000).map(|i| format!("lab-group-{i}"))
+    }
+
+    #[test]
+    fn test_assignment_is_deterministic() {
+        let config = experiment(&[("control", 50), ("socratic", 50)]);
+        for subject in subjects().take(50) {
+            let first = assign(&config, &subject).unwrap();
+            assert_eq!(assign(&config, &subject).unwrap(), first);
+        }
+
+        // Другое имя эксперимента - другое распределение
+        let renamed = ExperimentConfig {
+            name: "socratic-2026-11".to_string(),
+            ..config.clone()
+        };
+        assert!(subjects()
+            .take(50)
+            .any(|s| assign(&config, &s).unwrap().id != assign(&renamed, &s).unwrap().id));
+    }
+
+    #[test]
+    fn test_split_follows_weights() {
+        let config = experiment(&[("control", 70), ("socratic", 30), ("off", 0)]);
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for subject in subjects() {
+            *counts.entry(assign(&config, &subject).unwrap().id.clone()).or_default() += 1;
+        }
+        let control = counts["control"] as f64 / 2000.0;
+        assert!((0.65..0.75).contains(&control), "{counts:?}");
+        assert!(!counts.contains_key("off"));
+    }
+
+    #[test]
+    fn test_disabled_experiment() {
+        let mut config = experiment(&[("control", 1)]);
+        config.enabled = false;
+        assert_eq!(assign(&config, "alice"), None);
+        assert_eq!(assign(&experiment(&[("control", 0)]), "alice"), None);
+        assert_eq!(assign(&experiment(&[]), "alice"), None);
+    }
+
+    #[test]
+    fn test_variant_report() {
+        let group = |model: &str, experiment: &str, variant: Option<&str>, up, down| FeedbackSummary {
+            model: Some(model.to_string()),
+            experiment: variant.map(|_| experiment.to_string()),
+            prompt_variant: variant.map(str::to_string),
+            system_prompt_version: None,
+            answers: up + down + 1,
+            up,
+            down,
+            satisfaction: None,
+            categories: BTreeMap::new(),
+        };
+        let report = variant_report(&[
+            group("GigaChat", "socratic-2026-10", Some("socratic"), 3, 1),
+            group("GigaChat-Pro", "socratic-2026-10", Some("socratic"), 1, 3),
+            group("GigaChat", "socratic-2026-10", Some("control"), 0, 0),
+            group("GigaChat", "socratic-2026-11", Some("control"), 2, 0),
+            group("GigaChat", "", None, 9, 0),
+        ]);
+        assert_eq!(report.len(), 3);
+        assert_eq!(report[0].prompt_variant, "control");
+        assert_eq!(report[0].satisfaction, None);
+        assert_eq!((report[1].answers, report[1].up, report[1].down), (10, 4, 4));
+        assert_eq!(report[1].satisfaction, Some(0.5));
+        // control другого эксперимента - отдельная строка
+        assert_eq!(report[2].experiment.as_deref(), Some("socratic-2026-11"));
+        assert_eq!(report[2].prompt_variant, "control");
+        assert_eq!(report[2].satisfaction, Some(1.0));
+    }
+
+    #[test]
+    fn test_variant_ids() {
+        assert!(is_valid_id("socratic-v2"));
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("Socratic"));
+        assert!(!is_valid_id(&"x".repeat(MAX_VARIANT_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,614 @@
+//! Модуль экспорта истории и диалогов.
+//!
+//! Студенты прикладывают переписку с AI к отчёту по лабораторной работе
+//! (`lab/lab_work.md`, этап 5). Экспорт превращает записи истории в
+//! документ одного из форматов ([`ExportFormat`]):
+//!
+//! - **Markdown** - для отчёта в `.md`; блоки кода из ответов сохраняются;
+//! - **JSON Lines** - одна запись на строку, как элемент `GET /history`;
+//! - **HTML** - самодостаточная страница (стили внутри, без внешних
+//!   файлов), которую можно открыть в браузере или распечатать в PDF.
+//!
+//! Экспорт доступен через `GET /export` и команду `--export`.
+//!
+//! # Для студентов: Детерминированный вывод
+//!
+//! Одни и те же записи всегда дают один и тот же документ, байт в байт:
+//! в нём нет текущего времени, случайных идентификаторов и порядка
+//! `HashMap`. Поэтому рендеринг проверяется "золотыми" тестами -
+//! сравнением с заранее записанным эталоном.
+
+use thiserror::Error;
+
+use crate::models::HistoryEntry;
+use crate::storage::{Conversation, Exchange, HistoryCursor, HistoryFilter, Storage, StorageError};
+
+/// Сколько записей можно выгрузить за раз.
+pub const MAX_EXPORT_ENTRIES: usize = 1000;
+
+/// Записей, читаемых из хранилища за один запрос.
+const PAGE_SIZE: usize = 100;
+
+/// Миллисекунд в сутках.
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Ошибки экспорта.
+#[derive(Error, Debug)]
+pub enum ExportError {
+    /// Диалога нет или он принадлежит другому клиенту
+    #[error("Диалог '{0}' не найден")]
+    ConversationNotFound(String),
+
+    /// Записей больше [`MAX_EXPORT_ENTRIES`]
+    #[error("Слишком много записей для экспорта (больше {0}): сузьте период from/to")]
+    TooLarge(usize),
+
+    /// Хранилище недоступно
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// Формат документа.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Markdown (`.md`)
+    Markdown,
+    /// JSON Lines (`.jsonl`)
+    JsonLines,
+    /// HTML-страница (`.html`)
+    Html,
+}
+
+impl ExportFormat {
+    /// Допустимые значения `format` (для сообщений об ошибках).
+    pub const NAMES: &'static [&'static str] = &["markdown", "jsonl", "html"];
+
+    /// Формат по имени: `markdown`/`md`, `jsonl`/`json`, `html`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "markdown" | "md" => Some(ExportFormat::Markdown),
+            "jsonl" | "json" => Some(ExportFormat::JsonLines),
+            "html" => Some(ExportFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// Расширение файла.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::JsonLines => "jsonl",
+            ExportFormat::Html => "html",
+        }
+    }
+
+    /// Значение заголовка `Content-Type`.
+    pub fn media_type(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::JsonLines => "application/jsonl; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+/// Что экспортируется: записи одного клиента, возможно - одного диалога.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportDocument {
+    /// Чья история
+    pub subject: String,
+
+    /// Диалог, если экспортируется он
+    pub conversation: Option<Conversation>,
+
+    /// Записи, от старых к новым
+    pub exchanges: Vec<Exchange>,
+}
+
+impl ExportDocument {
+    /// Читает из хранилища записи по фильтру, а если в фильтре задан
+    /// диалог - и сам диалог (только свой: чужой выглядит как несуществующий).
+    pub fn load(storage: &dyn Storage, filter: &HistoryFilter) -> Result<Self, ExportError> {
+        let conversation = match &filter.conversation_id {
+            Some(id) => match storage.conversation(id)? {
+                Some(conversation) if conversation.subject == filter.subject => Some(conversation),
+                _ => return Err(ExportError::ConversationNotFound(id.clone())),
+            },
+            None => None,
+        };
+        Ok(Self {
+            subject: filter.subject.clone(),
+            conversation,
+            exchanges: collect(storage, filter)?,
+        })
+    }
+
+    /// Заголовок документа.
+    pub fn title(&self) -> String {
+        match &self.conversation {
+            Some(conversation) => format!("Диалог {}: {}", conversation.id, conversation.title),
+            None => "История вопросов".to_string(),
+        }
+    }
+
+    /// Имя файла для скачивания: `conversation-lab-3.md`, `history.html`.
+    pub fn file_name(&self, format: ExportFormat) -> String {
+        match &self.conversation {
+            Some(conversation) => format!("conversation-{}.{}", conversation.id, format.extension()),
+            None => format!("history.{}", format.extension()),
+        }
+    }
+
+    /// Документ в нужном формате.
+    pub fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Markdown => self.to_markdown(),
+            ExportFormat::JsonLines => self.to_json_lines(),
+            ExportFormat::Html => self.to_html(),
+        }
+    }
+
+    /// Период "первая запись - последняя запись".
+    fn period(&self) -> Option<String> {
+        let first = self.exchanges.first()?;
+        let last = self.exchanges.last()?;
+        Some(format!("{} - {}", format_time(first.created_at), format_time(last.created_at)))
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.title());
+        out.push_str(&format!("- Пользователь: {}\n", self.subject));
+        out.push_str(&format!("- Записей: {}\n", self.exchanges.len()));
+        if let Some(period) = self.period() {
+            out.push_str(&format!("- Период: {}\n", period));
+        }
+
+        for (number, exchange) in self.exchanges.iter().enumerate() {
+            out.push_str(&format!("\n## {}. {}\n\n", number + 1, format_time(exchange.created_at)));
+            for (name, value) in metadata(exchange) {
+                out.push_str(&format!("- {}: {}\n", name, value));
+            }
+            out.push_str("\n### Вопрос\n\n");
+            out.push_str(&markdown_block(&exchange.question));
+            out.push_str("\n### Ответ\n\n");
+            out.push_str(&markdown_block(&exchange.answer));
+        }
+        out
+    }
+
+    fn to_json_lines(&self) -> String {
+        self.exchanges
+            .iter()
+            .map(|exchange| {
+                let entry = HistoryEntry::from(exchange.clone());
+                // Сериализация структуры без карт не может завершиться ошибкой
+                serde_json::to_string(&entry).expect("history entry is serializable") + "\n"
+            })
+            .collect()
+    }
+
+    fn to_html(&self) -> String {
+        let title = escape_html(&self.title());
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html lang=\"ru\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<ul class=\"meta\">\n"
+        );
+        out.push_str(&format!("<li>Пользователь: {}</li>\n", escape_html(&self.subject)));
+        out.push_str(&format!("<li>Записей: {}</li>\n", self.exchanges.len()));
+        if let Some(period) = self.period() {
+            out.push_str(&format!("<li>Период: {}</li>\n", period));
+        }
+        out.push_str("</ul>\n");
+
+        for (number, exchange) in self.exchanges.iter().enumerate() {
+            out.push_str(&format!(
+                "<section>\n<h2>{}. {}</h2>\n<ul class=\"meta\">\n",
+                number + 1,
+                format_time(exchange.created_at)
+            ));
+            for (name, value) in metadata(exchange) {
+                out.push_str(&format!("<li>{}: {}</li>\n", name, escape_html(&value)));
+            }
+            out.push_str("</ul>\n<h3>Вопрос</h3>\n<div class=\"question\">\n");
+            out.push_str(&text_to_html(&exchange.question));
+            out.push_str("</div>\n<h3>Ответ</h3>\n<div class=\"answer\">\n");
+            out.push_str(&text_to_html(&exchange.answer));
+            out.push_str("</div>\n</section>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+/// Стили HTML-страницы: всё внутри документа, чтобы он открывался без сети.
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; line-height: 1.5; }
+.meta { color: #555; font-size: 0.9em; }
+section { border-top: 1px solid #ddd; margin-top: 2em; }
+.question { background: #f3f6fb; padding: 0.5em 1em; }
+pre { background: #f6f8fa; padding: 0.75em; overflow-x: auto; }
+code { font-family: monospace; }
+";
+
+/// Источник, модель и системный промпт записи - в порядке вывода.
+fn metadata(exchange: &Exchange) -> Vec<(&'static str, String)> {
+    let parameters = &exchange.parameters;
+    let mut model = parameters.model.clone().unwrap_or_else(|| "-".to_string());
+    if let (Some(temperature), Some(max_tokens)) = (parameters.temperature, parameters.max_tokens) {
+        model.push_str(&format!(" (temperature {}, max_tokens {})", temperature, max_tokens));
+    }
+    let mut details = Vec::new();
+    if let Some(version) = parameters.system_prompt_version.as_ref().filter(|_| parameters.system_prompt_applied) {
+        details.push(format!("версия {}", version));
+    }
+    match (&parameters.experiment, &parameters.prompt_variant) {
+        (Some(experiment), Some(variant)) => {
+            details.push(format!("вариант {} эксперимента {}", variant, experiment))
+        }
+        (None, Some(variant)) => details.push(format!("вариант {}", variant)),
+        _ => {}
+    }
+    let mut prompt = if parameters.system_prompt_applied { "применён" } else { "не применён" }.to_string();
+    if !details.is_empty() {
+        prompt.push_str(&format!(" ({})", details.join(", ")));
+    }
+
+    let mut fields = vec![("Источник", exchange.source.clone()), ("Модель", model)];
+    if let Some(conversation_id) = &exchange.conversation_id {
+        fields.push(("Диалог", conversation_id.clone()));
+    }
+    fields.push(("Системный промпт", prompt));
+    fields.push(("ID", exchange.id.clone()));
+    fields
+}
+
+/// Строка-ограда блока кода (```` ``` ```` или `~~~`).
+fn is_fence(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Текст как есть, но с закрытым блоком кода: ответ, оборванный на
+/// середине кода (например, по `max_tokens`), не должен "съесть"
+/// остаток Markdown-документа.
+fn markdown_block(text: &str) -> String {
+    let text = text.trim_end();
+    let mut out = format!("{}\n", text);
+    if !text.lines().filter(|line| is_fence(line)).count().is_multiple_of(2) {
+        out.push_str("```\n");
+    }
+    out
+}
+
+/// Текст ответа → HTML: блоки кода - `<pre><code>`, остальное - абзацы,
+/// `` `код` `` внутри строки - `<code>`.
+fn text_to_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut paragraph: Vec<String> = Vec::new();
+    // Язык открытого блока кода и его строки
+    let mut code: Option<(String, Vec<&str>)> = None;
+
+    let flush = |paragraph: &mut Vec<String>, out: &mut String| {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", paragraph.join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+
+    for line in text.lines() {
+        if is_fence(line) {
+            match code.take() {
+                Some((language, lines)) => out.push_str(&code_block(&language, &lines)),
+                None => {
+                    flush(&mut paragraph, &mut out);
+                    let language = line.trim_start()[3..].trim().to_string();
+                    code = Some((language, Vec::new()));
+                }
+            }
+        } else if let Some((_, lines)) = &mut code {
+            lines.push(line);
+        } else if line.trim().is_empty() {
+            flush(&mut paragraph, &mut out);
+        } else {
+            paragraph.push(inline_code(line));
+        }
+    }
+    if let Some((language, lines)) = code {
+        out.push_str(&code_block(&language, &lines));
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+fn code_block(language: &str, lines: &[&str]) -> String {
+    let class = if language.is_empty() {
+        String::new()
+    } else {
+        format!(" class=\"language-{}\"", escape_html(language))
+    };
+    format!("<pre><code{}>{}</code></pre>\n", class, escape_html(&lines.join("\n")))
+}
+
+/// Экранирует строку и превращает `` `x` `` в `<code>x</code>`.
+fn inline_code(line: &str) -> String {
+    let parts: Vec<&str> = line.split('`').collect();
+    // Непарный обратный апостроф - не разметка, а просто символ
+    if parts.len().is_multiple_of(2) {
+        return escape_html(line);
+    }
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if i % 2 == 1 {
+                format!("<code>{}</code>", escape_html(part))
+            } else {
+                escape_html(part)
+            }
+        })
+        .collect()
+}
+
+/// Экранирует символы, значимые для HTML.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Время из миллисекунд Unix: `2026-10-18 09:20:00 UTC`.
+///
+/// Всегда UTC: часовой пояс сервера не должен менять документ.
+pub fn format_time(millis: i64) -> String {
+    let days = millis.div_euclid(DAY_MILLIS);
+    let secs = millis.rem_euclid(DAY_MILLIS) / 1000;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Дата григорианского календаря по номеру дня от 1970-01-01.
+///
+/// Обратный к `days_from_civil` из `handlers` алгоритм Говарда Хиннанта.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Все записи по фильтру, от старых к новым (не больше [`MAX_EXPORT_ENTRIES`]).
+///
+/// Хранилище отдаёт историю страницами от новых к старым; документ
+/// читается в хронологическом порядке, поэтому страницы разворачиваются.
+fn collect(storage: &dyn Storage, filter: &HistoryFilter) -> Result<Vec<Exchange>, ExportError> {
+    let mut exchanges = Vec::new();
+    let mut cursor: Option<HistoryCursor> = None;
+    loop {
+        let page = storage.history(filter, cursor.as_ref(), PAGE_SIZE)?;
+        exchanges.extend(page.items);
+        if exchanges.len() > MAX_EXPORT_ENTRIES {
+            return Err(ExportError::TooLarge(MAX_EXPORT_ENTRIES));
+        }
+        match page.next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    exchanges.reverse();
+    Ok(exchanges)
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AskParameters;
+    use crate::storage::MemoryStorage;
+
+    fn exchange(id: &str, question: &str, answer: &str, created_at: i64) -> Exchange {
+        Exchange {
+            id: id.to_string(),
+            conversation_id: Some("lab-3".to_string()),
+            subject: "alice".to_string(),
+            question: question.to_string(),
+            answer: answer.to_string(),
+            source: "gigachat".to_string(),
+            parameters: AskParameters {
+                model: Some("GigaChat".to_string()),
+                temperature: Some(0.7),
+                max_tokens: Some(1024),
+                system_prompt_applied: true,
+                system_prompt_version: Some("3f2a9c01b7de".to_string()),
+                experiment: Some("socratic-2026-10".to_string()),
+                prompt_variant: Some("socratic".to_string()),
+                template: None,
+            },
+            created_at,
+        }
+    }
+
+    fn document_conversation(subject: &str) -> Conversation {
+        Conversation {
+            id: "lab-3".to_string(),
+            subject: subject.to_string(),
+            title: "Что такое трейт?".to_string(),
+            created_at: 1_760_779_200_000,
+            updated_at: 1_760_779_500_000,
+        }
+    }
+
+    fn document() -> ExportDocument {
+        let mut mock = exchange("e-2", "А <T>?", "Обобщённый тип `T`.", 1_760_779_500_000);
+        mock.source = "mock ai service".to_string();
+        mock.parameters = AskParameters::default();
+        ExportDocument {
+            subject: "alice".to_string(),
+            conversation: Some(document_conversation("alice")),
+            exchanges: vec![
+                exchange(
+                    "e-1",
+                    "Что такое трейт?",
+                    "Набор методов:\n\n```rust\ntrait Greet {\n    fn hi(&self) -> &str;\n}\n```",
+                    1_760_779_200_000,
+                ),
+                mock,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_time(1_760_779_200_000), "2025-10-18 09:20:00 UTC");
+        assert_eq!(format_time(951_782_400_000), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_time(-1000), "1969-12-31 23:59:59 UTC");
+    }
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(ExportFormat::parse("MD"), Some(ExportFormat::Markdown));
+        assert_eq!(ExportFormat::parse("json"), Some(ExportFormat::JsonLines));
+        assert_eq!(ExportFormat::parse("pdf"), None);
+        for name in ExportFormat::NAMES {
+            let format = ExportFormat::parse(name).unwrap();
+            assert!(document().file_name(format).ends_with(format.extension()));
+        }
+    }
+
+    /// Золотой тест: документ Markdown совпадает с эталоном байт в байт.
+    #[test]
+    fn test_markdown_golden() {
+        let expected = "# Диалог lab-3: Что такое трейт?
+
+- Пользователь: alice
+- Записей: 2
+- Период: 2025-10-18 09:20:00 UTC - 2025-10-18 09:25:00 UTC
+
+## 1. 2025-10-18 09:20:00 UTC
+
+- Источник: gigachat
+- Модель: GigaChat (temperature 0.7, max_tokens 1024)
+- Диалог: lab-3
+- Системный промпт: применён (версия 3f2a9c01b7de, вариант socratic эксперимента socratic-2026-10)
+- ID: e-1
+
+### Вопрос
+
+Что такое трейт?
+
+### Ответ
+
+Набор методов:
+
+```rust
+trait Greet {
+    fn hi(&self) -> &str;
+}
+```
+
+## 2. 2025-10-18 09:25:00 UTC
+
+- Источник: mock ai service
+- Модель: -
+- Диалог: lab-3
+- Системный промпт: не применён
+- ID: e-2
+
+### Вопрос
+
+А <T>?
+
+### Ответ
+
+Обобщённый тип `T`.
+";
+        assert_eq!(document().render(ExportFormat::Markdown), expected);
+    }
+
+    #[test]
+    fn test_json_lines_golden() {
+        let expected = concat!(
+            r#"{"id":"e-1","question":"Что такое трейт?","answer":"Набор методов:\n\n```rust\ntrait Greet {\n    fn hi(&self) -> &str;\n}\n```","source":"gigachat","conversation_id":"lab-3","parameters":{"model":"GigaChat","temperature":0.7,"max_tokens":1024,"system_prompt_applied":true,"system_prompt_version":"3f2a9c01b7de","experiment":"socratic-2026-10","prompt_variant":"socratic"},"created_at":1760779200000}"#,
+            "\n",
+            r#"{"id":"e-2","question":"А <T>?","answer":"Обобщённый тип `T`.","source":"mock ai service","conversation_id":"lab-3","parameters":{"system_prompt_applied":false},"created_at":1760779500000}"#,
+            "\n",
+        );
+        assert_eq!(document().render(ExportFormat::JsonLines), expected);
+    }
+
+    #[test]
+    fn test_html_escapes_and_keeps_code() {
+        let html = document().render(ExportFormat::Html);
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<title>Диалог lab-3: Что такое трейт?</title>"));
+        assert!(!html.contains("<link") && !html.contains("<script"), "страница самодостаточна");
+        assert!(html.contains(
+            "<pre><code class=\"language-rust\">trait Greet {\n    fn hi(&amp;self) -&gt; &amp;str;\n}</code></pre>"
+        ));
+        assert!(html.contains("<p>А &lt;T&gt;?</p>"));
+        assert!(html.contains("<p>Обобщённый тип <code>T</code>.</p>"));
+        assert_eq!(html, document().render(ExportFormat::Html));
+    }
+
+    #[test]
+    fn test_unterminated_code_block_is_closed() {
+        assert_eq!(markdown_block("Код:\n```rust\nfn main() {"), "Код:\n```rust\nfn main() {\n```\n");
+        assert_eq!(
+            text_to_html("```\n<b>\n\nx"),
+            "<pre><code>&lt;b&gt;\n\nx</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_load_oldest_first() {
+        let storage = MemoryStorage::new();
+        for (i, created_at) in [300, 100, 200].into_iter().enumerate() {
+            let mut exchange = exchange(&format!("e-{i}"), "?", "!", created_at);
+            exchange.conversation_id = None;
+            storage.save_exchange(&exchange).unwrap();
+        }
+        let filter = HistoryFilter {
+            subject: "alice".to_string(),
+            ..HistoryFilter::default()
+        };
+        let document = ExportDocument::load(&storage, &filter).unwrap();
+        let times: Vec<i64> = document.exchanges.iter().map(|e| e.created_at).collect();
+        assert_eq!(times, vec![100, 200, 300]);
+        assert_eq!(document.title(), "История вопросов");
+
+        // Чужой диалог не экспортируется
+        storage.save_conversation(&document_conversation("bob")).unwrap();
+        let filter = HistoryFilter {
+            conversation_id: Some("lab-3".to_string()),
+            ..filter
+        };
+        assert!(matches!(
+            ExportDocument::load(&storage, &filter),
+            Err(ExportError::ConversationNotFound(id)) if id == "lab-3"
+        ));
+    }
+}
@@ -34,6 +34,7 @@
 //!
 //! - `#[get("/path")]` - обработчик GET-запросов
 //! - `#[post("/path")]` - обработчик POST-запросов
+//! - `#[delete("/path")]` - обработчик DELETE-запросов
 //! - `#[catch(код)]` - обработчик ошибок (404, 500 и т.д.)
 
 // ============================================================================
@@ -50,19 +51,76 @@ use rocket::serde::json::Json;
 // Позволяет получить доступ к данным, переданным через .manage()
 use rocket::State;
 
+// Status - HTTP-статус ответа (200, 400, 503...)
+use rocket::http::{ContentType, Header, Status};
+
+// Request - полный HTTP-запрос; нужен catchers, чтобы прочитать кеш запроса
+use rocket::Request;
+
 // Макросы маршрутизации - ОБЯЗАТЕЛЬНО импортировать явно!
 // Rocket 0.5 требует явного импорта, в отличие от старых версий.
-use rocket::{catch, get, options, post};
+use rocket::{catch, delete, get, options, post, FromForm, Responder};
 
 // tracing - библиотека структурированного логирования.
 // info! - информационные сообщения
 // error! - сообщения об ошибках
-use tracing::{error, info};
+use tracing::{error, field, info, info_span, warn, Instrument};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::auth::{cached_error, scopes, AuthError, Authenticated};
+use crate::build_info;
+use crate::concurrency::QueueSnapshot;
+use crate::config::{self, profile, GuardrailsConfig, PromptVariantConfig};
+use crate::cors::{CorsPolicy, Preflight};
+use crate::experiment;
+use crate::export::{ExportDocument, ExportError, ExportFormat};
+use crate::guardrails;
+use crate::readiness::Dependencies;
+use crate::reload::{LiveRuntime, Reloader, Runtime};
+use crate::rate_limit::{cached_decision, RateLimited};
+use crate::metrics::{self, Metrics};
+use crate::models::{
+    AskParameters, AskRequest, AskResponse, ConfigResponse, DeleteHistoryResponse, ErrorResponse,
+    FeedbackReport, FeedbackRequest, FeedbackResponse, GuardrailDecision, HealthResponse,
+    HistoryEntry, HistoryResponse, LivenessResponse, ReadinessResponse, ReloadResponse,
+    TemplatesResponse, UsageResponse,
+};
+use crate::request_id::RequestId;
+use crate::services::{AiService, AiServiceError, AskContext};
+use crate::shutdown::Drain;
+use crate::storage::{
+    self, Conversation, Exchange, Feedback, HistoryCursor, HistoryFilter, SharedStorage, Store,
+    UsageRecord,
+};
+use crate::templates::{TemplateError, TemplateLibrary, Templates, MAX_VALUE_CHARS};
+
+/// Максимальная длина `conversation_id`.
+const MAX_CONVERSATION_ID_LEN: usize = 64;
+
+/// Сколько символов первого вопроса идёт в заголовок диалога.
+const CONVERSATION_TITLE_CHARS: usize = 80;
+
+/// Записей на странице `GET /history`, если `limit` не задан.
+const DEFAULT_HISTORY_LIMIT: usize = 20;
+
+/// Наибольший `limit` для `GET /history`.
+const MAX_HISTORY_LIMIT: usize = 100;
+
+/// Миллисекунд в сутках (для фильтра по дате).
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// Категории, которые можно отметить в `POST /feedback`.
+const FEEDBACK_CATEGORIES: &[&str] = &["wrong", "off_topic", "too_long", "other"];
+
+/// Наибольшая длина комментария к оценке, символов.
+const MAX_FEEDBACK_COMMENT_CHARS: usize = 1000;
 
-use crate::config::AppConfig;
-use crate::models::{AskRequest, AskResponse, ErrorResponse, HealthResponse};
-use crate::services::AiService;
+/// Сколько последних оценок показывает `GET /admin/feedback` по умолчанию.
+const DEFAULT_RECENT_FEEDBACK: usize = 20;
 
 // ============================================================================
 // ОБРАБОТЧИКИ ЭНДПОИНТОВ
@@ -72,11 +130,12 @@ use crate::services::AiService;
 ///
 /// # Для студентов: Dependency Injection через State
 ///
-/// Обратите внимание на параметр `config: &State<AppConfig>`.
+/// Обратите внимание на параметр `runtime: &State<LiveRuntime>`.
 ///
 /// ## Как это работает?
 ///
-/// 1. В `main.rs` мы вызываем `.manage(config)` - передаём конфигурацию в Rocket
+/// 1. В `main.rs` мы вызываем `.manage(live_runtime)` - передаём конфигурацию
+///    и AI сервис в Rocket
 /// 2. Rocket сохраняет её во внутреннем хранилище
 /// 3. В любом обработчике мы можем "запросить" эти данные через `State<T>`
 /// 4. Rocket автоматически передаст нужный объект
@@ -90,6 +149,9 @@ use crate::services::AiService;
 /// Это паттерн "Dependency Injection" - зависимости "внедряются" извне,
 /// а не создаются внутри функции.
 ///
+/// `LiveRuntime` - не сама конфигурация, а "ячейка" с её текущей версией:
+/// `runtime.current()` возвращает актуальный снимок (см. модуль `reload`).
+///
 /// # Эндпоинт
 ///
 /// `GET /`
@@ -100,21 +162,52 @@ use crate::services::AiService;
 /// curl http://localhost:8000/
 /// ```
 #[get("/")]
-pub fn index(config: &State<AppConfig>) -> String {
+pub fn index(runtime: &State<LiveRuntime>) -> String {
+    let runtime = runtime.current();
+    let (config, backend) = (&runtime.config, &runtime.backend);
+    let build = build_info::build_info();
     format!(
         "🚀 {} v{}\n\n\
         {}\n\n\
+        Состояние:\n\
+        - AI бэкенд: {}\n\
+        - Модель: {}\n\
+        - Системный промпт: {}\n\
+        - Время работы: {}\n\
+        - Сборка: {} (фичи: {})\n\n\
         Доступные эндпоинты:\n\
-        - GET  /           - Это сообщение\n\
-        - GET  /health     - Проверка состояния сервера\n\
-        - POST /ask        - Задать вопрос AI помощнику\n\n\
+        - GET  /             - Это сообщение\n\
+        - GET  /health       - Проверка состояния сервера\n\
+        - GET  /health/live  - Liveness: процесс жив\n\
+        - GET  /health/ready - Readiness: AI бэкенд и зависимости\n\
+        - GET  /metrics      - Метрики Prometheus (если metrics.enabled, путь - metrics.path)\n\
+        - POST /ask          - Задать вопрос AI помощнику\n\
+        - GET  /templates    - Шаблоны промптов для поля template в /ask (scope ask)\n\
+        - POST /admin/reload - Перечитать config.toml (scope admin)\n\
+        - GET  /admin/config - Итоговая конфигурация и источники значений (scope admin)\n\
+        - GET  /history      - История своих вопросов: поиск, фильтры, страницы (scope ask)\n\
+        - DELETE /history    - Удалить историю по фильтру или всю (?all=true)\n\
+        - DELETE /history/<id> - Удалить одну запись истории\n\
+        - GET  /export       - Выгрузка истории: markdown, jsonl или html (scope ask)\n\
+        - POST /feedback     - Оценить ответ: up/down, комментарий, категории (scope ask)\n\
+        - GET  /admin/feedback - Оценки по моделям и версиям промпта (scope admin)\n\
+        - GET  /admin/usage  - Использование AI по клиентам (scope admin)\n\n\
         Пример запроса:\n\
         curl -X POST http://localhost:8000/ask \\\n\
           -H \"Content-Type: application/json\" \\\n\
           -d '{{\"question\": \"Что такое Rust?\"}}'",
         config.application.name,
         config.application.version,
-        config.application.description
+        config.application.description,
+        match &backend.fallback_reason {
+            Some(reason) => format!("{} ({})", backend.name, reason),
+            None => backend.name.clone(),
+        },
+        backend.model.as_deref().unwrap_or("-"),
+        if backend.system_prompt_applied { "применяется" } else { "не применяется" },
+        build_info::format_uptime(build_info::uptime()),
+        build.git_hash,
+        if build.features.is_empty() { "нет".to_string() } else { build.features.join(", ") },
     )
 }
 
@@ -133,26 +226,104 @@ pub fn index(config: &State<AppConfig>) -> String {
 /// {
 ///   "status": "ok",
 ///   "version": "0.1.0",
-///   "gigachat_enabled": true
+///   "gigachat_enabled": true,
+///   "backend": {"name": "GigaChat", "model": "GigaChat", "system_prompt_applied": true},
+///   "uptime_seconds": 3600,
+///   "build": {"git_hash": "1b3b10a", "features": ["gigachat"]}
 /// }
 /// ```
 ///
+/// Поля описывают ФАКТИЧЕСКИЙ бэкенд: если GigaChat включён в конфиге, но
+/// токена нет и отвечает mock, `gigachat_enabled` будет `false`, в
+/// `backend.fallback_reason` - причина, а `status` - `"degraded"`.
+///
+/// # Аутентификация
+///
+/// При `auth.enabled = true` эндпоинт требует scope `health`,
+/// если только не включён `auth.public_health` (по умолчанию - включён).
+///
+/// # Очередь к AI
+///
+/// При `concurrency.enabled = true` в ответ добавляется поле `concurrency`
+/// с глубиной очереди и временем ожидания (см. модуль `concurrency`).
+/// Состояние очереди необязательно, поэтому его достаёт guard [`QueueSnapshot`]:
+/// с `&State<T>` Rocket отказался бы стартовать без `.manage()`.
+///
 /// # Примеры
 ///
 /// ```bash
 /// curl http://localhost:8000/health
 /// ```
 #[get("/health")]
-pub fn health(config: &State<AppConfig>) -> Json<HealthResponse> {
+pub fn health(
+    _auth: Authenticated<scopes::Health>,
+    runtime: &State<LiveRuntime>,
+    queue: QueueSnapshot,
+) -> Json<HealthResponse> {
     info!("Health check requested");
 
+    let runtime = runtime.current();
+    let (config, backend) = (&runtime.config, &runtime.backend);
+    let fallback = backend.fallback_reason.is_some();
     Json(HealthResponse {
-        status: "ok".to_string(),
+        status: if fallback { "degraded" } else { "ok" }.to_string(),
         version: config.application.version.clone(),
-        gigachat_enabled: config.is_gigachat_enabled(),
+        gigachat_enabled: config.is_gigachat_enabled() && !fallback,
+        backend: backend.clone(),
+        uptime_seconds: build_info::uptime().as_secs(),
+        build: build_info::build_info(),
+        concurrency: queue.0,
+    })
+}
+
+/// Liveness probe: отвечает 200, пока процесс способен обрабатывать запросы.
+///
+/// Зависимости не проверяются - см. модуль `readiness`.
+///
+/// # Эндпоинт
+///
+/// `GET /health/live` (доступ - как у `/health`)
+#[get("/health/live")]
+pub fn live(_auth: Authenticated<scopes::Health>) -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "ok".to_string(),
     })
 }
 
+/// Readiness probe: проверяет активный AI бэкенд и другие зависимости.
+///
+/// Возвращает 200 при статусе `ok` или `degraded` и 503 при `unavailable`
+/// (см. модуль `readiness`). В ответе видно, какой бэкенд реально отвечает
+/// и почему, например: `"fallback_reason": "GIGACHAT_TOKEN is not set, using mock"`.
+///
+/// # Эндпоинт
+///
+/// `GET /health/ready` (доступ - как у `/health`)
+///
+/// # Примеры
+///
+/// ```bash
+/// curl -i http://localhost:8000/health/ready
+/// ```
+#[get("/health/ready")]
+pub async fn ready(
+    _auth: Authenticated<scopes::Health>,
+    dependencies: Dependencies<'_>,
+    runtime: &State<LiveRuntime>,
+) -> (Status, Json<ReadinessResponse>) {
+    let runtime = runtime.current();
+    let response = dependencies
+        .check(runtime.ai_service.as_ref(), &runtime.backend)
+        .await;
+    let status = if response.status == "unavailable" {
+        warn!("Readiness check failed: {:?}", response.checks);
+        Status::ServiceUnavailable
+    } else {
+        Status::Ok
+    };
+    (status, Json(response))
+}
+
 /// Обработчик эндпоинта для вопросов к AI - главная функциональность API.
 ///
 /// # Для студентов: Разбор сложной сигнатуры
@@ -168,20 +339,26 @@ pub fn health(config: &State<AppConfig>) -> Json<HealthResponse> {
 ///
 /// pub async fn ask(
 ///     request: Json<AskRequest>,            // Тело запроса (автоматически парсится из JSON)
-///     ai_service: &State<Box<dyn AiService>>, // AI-сервис из State (DI)
-/// ) -> Result<Json<AskResponse>, Json<ErrorResponse>>
-///      ^^^^^^ ^^^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^^^
+///     runtime: &State<LiveRuntime>,           // AI-сервис из State (DI)
+/// ) -> Result<Json<AskResponse>, (Status, Json<ErrorResponse>)>
+///      ^^^^^^ ^^^^^^^^^^^^^^^^^  ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
 ///        |          |                    |
-///        |          |                    +-- Ошибка (если Result::Err)
+///        |          |                    +-- Ошибка с HTTP-статусом (если Result::Err)
 ///        |          +------------------------ Успех (если Result::Ok)
 ///        +----------------------------------- Тип Result позволяет вернуть или Ok, или Err
 /// ```
 ///
 /// ## Зачем `Box<dyn AiService>`?
 ///
-/// В `State<>` хранится trait object - конкретный тип (GigaChat или Mock)
+/// В `Runtime` хранится trait object - конкретный тип (GigaChat или Mock)
 /// определяется во время выполнения программы. См. модуль `services`.
 ///
+/// ## Перезагрузка конфигурации
+///
+/// Обработчик берёт снимок `runtime.current()` один раз и держит его до
+/// конца запроса: если во время ответа AI конфигурацию перезагрузят
+/// (модуль `reload`), этот запрос доработает со старым сервисом.
+///
 /// ## Автоматическая обработка JSON
 ///
 /// Rocket + Serde делают магию:
@@ -194,36 +371,232 @@ pub fn health(config: &State<AppConfig>) -> Json<HealthResponse> {
 ///
 /// `POST /ask`
 ///
+/// При `auth.enabled = true` требуется ключ со scope `ask`
+/// (см. модуль `auth`). При `rate_limit.enabled = true` частота запросов
+/// ограничена (см. модуль `rate_limit`), превышение - 429 Too Many Requests.
+///
+/// Каждый вызов AI записывается в учёт использования, а ответ - в историю
+/// (модуль `storage`). Ошибка записи не мешает вернуть ответ клиенту.
+///
+/// # Коды ошибок
+///
+/// - `400 EMPTY_QUESTION` - пустой вопрос;
+/// - `400 INVALID_CONVERSATION_ID` - `conversation_id` длиннее 64 символов
+///   или содержит что-то кроме латиницы, цифр, `-` и `_`;
+/// - `404 CONVERSATION_NOT_FOUND` - диалог принадлежит другому клиенту;
+/// - `400 UNKNOWN_TEMPLATE` - шаблона с именем `template` нет;
+/// - `400 MISSING_TEMPLATE_VARIABLE` - у переменной шаблона нет значения;
+/// - `400 INVALID_TEMPLATE_VARIABLE` - шаблон не использует переменную,
+///   значение длиннее 200 символов или `variables` переданы без `template`;
+/// - `400 PROMPT_INJECTION` - вопрос похож на попытку подменить инструкции
+///   (при `guardrails.input_action = "block"`, см. модуль `guardrails`);
+/// - `400 CONTENT_BLOCKED` - вопрос не прошёл модерацию: стоп-лист
+///   или персональные данные при действии `block` (см. модуль `moderation`);
+/// - `500 STORAGE_ERROR` - хранилище не смогло прочитать диалог;
+/// - `503 SERVER_BUSY` - очередь к AI переполнена (см. модуль `concurrency`);
+/// - `503 SHUTTING_DOWN` - сервер останавливается (см. модуль `shutdown`);
+/// - `502 AI_SERVICE_ERROR` - AI сервис вернул ошибку;
+/// - `502 SYSTEM_PROMPT_LEAK` - ответ пересказывает системный промпт
+///   (при `guardrails.output_action = "block"`);
+/// - `502 ANSWER_BLOCKED` - ответ модели не прошёл модерацию.
+///
 /// # Примеры
 ///
 /// ```bash
 /// curl -X POST http://localhost:8000/ask \
 ///   -H "Content-Type: application/json" \
+///   -H "X-API-Key: your_key" \
 ///   -d '{"question": "Что такое Rust?"}'
+///
+/// # Вопрос по шаблону (см. GET /templates)
+/// curl -X POST http://localhost:8000/ask \
+///   -H "Content-Type: application/json" \
+///   -H "X-API-Key: your_key" \
+///   -d '{"question": "Что такое трейт?", "template": "explain", "variables": {"level": "опытный"}}'
 /// ```
 #[post("/ask", format = "json", data = "<request>")]
+#[allow(clippy::too_many_arguments)] // аргументы - guard'ы Rocket, а не параметры вызова
 pub async fn ask(
+    auth: Authenticated<scopes::Ask>,
+    _limit: RateLimited,
+    request_id: RequestId,
     request: Json<AskRequest>,
-    ai_service: &State<Box<dyn AiService>>,
-) -> Result<Json<AskResponse>, Json<ErrorResponse>> {
+    runtime: &State<LiveRuntime>,
+    drain: Drain<'_>,
+    store: Store<'_>,
+    templates: Templates<'_>,
+) -> Result<Json<AskResponse>, (Status, Json<ErrorResponse>)> {
+    // Пока guard жив, плавная остановка ждёт этот запрос
+    let _in_flight = drain.enter().map_err(|e| {
+        warn!("Rejecting question: {}", e);
+        (
+            Status::ServiceUnavailable,
+            Json(
+                ErrorResponse::with_code("Server is shutting down, please retry", "SHUTTING_DOWN")
+                    .with_request_id(request_id.as_str()),
+            ),
+        )
+    })?;
+
+    // Все логи обработки (и вызова AI) попадут в span запроса
+    // и получат поле request_id
+    let span = request_id.span().clone();
+    let runtime = runtime.current();
+    // Вариант промпта зависит только от клиента: его вопросы не смешивают варианты
+    let variant = experiment::assign(&runtime.config.experiment, &auth.principal.subject);
+    let parameters = ask_parameters(&runtime, variant);
+    let ctx = AskContext::with_request_id(request_id.as_str())
+        .with_system_prompt(variant.and_then(|variant| variant.system_prompt.clone()));
+    answer_question(
+        &auth.principal.subject,
+        &request,
+        &runtime,
+        parameters,
+        ctx,
+        store.0,
+        templates.0,
+    )
+    .instrument(span)
+    .await
+    .map_err(|(status, error)| (status, Json(error.with_request_id(request_id.as_str()))))
+}
+
+/// Параметры генерации, с которыми ответит текущий AI сервис.
+///
+/// Температура и `max_tokens` есть только у модели GigaChat: mock их
+/// не использует, и в историю они не пишутся. Собственный промпт варианта
+/// эксперимента тоже применяет только сервис с моделью.
+fn ask_parameters(runtime: &Runtime, variant: Option<&PromptVariantConfig>) -> AskParameters {
+    let model = runtime.backend.model.clone();
+    let gigachat = &runtime.config.gigachat;
+    let (system_prompt_applied, version) = match variant.and_then(|v| v.system_prompt.as_deref()) {
+        Some(prompt) => (model.is_some(), config::prompt_version(prompt)),
+        None => (
+            runtime.backend.system_prompt_applied,
+            runtime.config.application.system_prompt_version(),
+        ),
+    };
+    AskParameters {
+        temperature: model.as_ref().map(|_| gigachat.temperature),
+        max_tokens: model.as_ref().map(|_| gigachat.max_tokens),
+        model,
+        system_prompt_applied,
+        system_prompt_version: version.filter(|_| system_prompt_applied),
+        experiment: variant.map(|_| runtime.config.experiment.name.clone()),
+        prompt_variant: variant.map(|variant| variant.id.clone()),
+        template: None,
+    }
+}
+
+/// Тело обработчика `/ask`, выполняемое внутри span'а запроса.
+async fn answer_question(
+    subject: &str,
+    request: &AskRequest,
+    runtime: &Runtime,
+    parameters: AskParameters,
+    ctx: AskContext,
+    storage: Option<&SharedStorage>,
+    templates: Option<&TemplateLibrary>,
+) -> Result<Json<AskResponse>, (Status, ErrorResponse)> {
     let question = &request.question;
+    let ai_service = runtime.ai_service.as_ref();
+    let guardrails = &runtime.config.guardrails;
 
-    // Логируем входящий запрос
-    info!("Received question: {}", question);
+    // Логируем входящий запрос - без текста: до модерации в нём могут
+    // быть персональные данные
+    info!("Received question from {} ({} chars)", subject, question.chars().count());
 
     // Check that question is not empty
     if question.trim().is_empty() {
         error!("Empty question received");
-        return Err(Json(ErrorResponse::with_code(
-            "Question cannot be empty",
-            "EMPTY_QUESTION",
-        )));
+        return Err((
+            Status::BadRequest,
+            ErrorResponse::with_code("Question cannot be empty", "EMPTY_QUESTION"),
+        ));
+    }
+
+    // Попытку подменить инструкции проверяем до шаблона: текст шаблона
+    // наш, а вопрос и значения переменных присылает клиент
+    let mut decisions = Vec::new();
+    let checked_question = guard_input(guardrails, subject, None, question, &mut decisions)?;
+    let mut variables = BTreeMap::new();
+    for (name, value) in &request.variables {
+        let value = guard_input(guardrails, subject, Some(name), value, &mut decisions)?;
+        variables.insert(name.clone(), value.into_owned());
     }
 
+    // Модель получает текст шаблона, а в историю попадает сам вопрос
+    let prompt = render_prompt(templates, request, &checked_question, &variables)?;
+    let mut parameters = parameters;
+    parameters.template = request.template.clone();
+
+    // Диалог проверяем ДО вызова AI: с чужим id незачем тратить токены
+    let conversation = open_conversation(storage, subject, request).await?;
+
     // Отправляем вопрос в AI сервис и ждём ответ
-    match ai_service.ask(question).await {
-        Ok(answer) => {
+    // Отдельный span для вызова AI: в трассировке (модуль telemetry)
+    // видно, сколько времени запроса ушло на ответ модели
+    let ai_span = info_span!(
+        "ai_call",
+        otel.kind = "client",
+        otel.status_code = field::Empty,
+        backend = %ai_service.name(),
+    );
+    let started = Instant::now();
+    let result = ai_service.ask(&prompt, &ctx).instrument(ai_span.clone()).await;
+    if result.is_err() {
+        ai_span.record("otel.status_code", "ERROR");
+    }
+    drop(ai_span); // закрываем span сразу после вызова
+
+    if let Some(storage) = storage {
+        record_usage(storage, subject, ai_service, &prompt, &result, started.elapsed()).await;
+    }
+
+    // Промпт, который модель действительно получила: его и ищем в ответе
+    let system_prompt = ctx
+        .system_prompt
+        .as_deref()
+        .unwrap_or(&runtime.config.application.system_prompt);
+    let result = result.map(|answer| {
+        guardrails::check_answer(
+            guardrails,
+            answer,
+            Some(system_prompt).filter(|_| parameters.system_prompt_applied),
+        )
+    });
+
+    match result {
+        Ok(Err(decision)) => {
+            log_decision(subject, &decision);
+            Err((
+                Status::BadGateway,
+                ErrorResponse::with_code(
+                    format!("Answer withheld by guardrails: {}", decision.detail),
+                    "SYSTEM_PROMPT_LEAK",
+                ),
+            ))
+        }
+        Ok(Ok((answer, decision))) => {
+            if let Some(decision) = decision {
+                log_decision(subject, &decision);
+                decisions.push(decision);
+            }
             info!("Successfully got answer from {}", ai_service.name());
+            let answer_id = uuid::Uuid::new_v4().to_string();
+            if let Some(storage) = storage {
+                let exchange = Exchange {
+                    id: answer_id.clone(),
+                    conversation_id: None,
+                    subject: subject.to_string(),
+                    question: question.clone(),
+                    answer: answer.clone(),
+                    source: ai_service.name().to_lowercase(),
+                    parameters: parameters.clone(),
+                    created_at: storage::now_millis(),
+                };
+                save_answer(storage, conversation, exchange).await;
+            }
             
             // ═══════════════════════════════════════════════════════════════
             // Для студентов: ЗДЕСЬ создаётся AskResponse!
@@ -244,19 +617,949 @@ pub async fn ask(
             // ═══════════════════════════════════════════════════════════════
             
             Ok(Json(AskResponse {
+                answer_id,                               // ← ключ записи в истории
                 answer,                                  // ← из AI сервиса
                 source: ai_service.name().to_lowercase(), // ← наше поле
-                system_prompt_applied: ai_service.system_prompt_applied(),
+                system_prompt_applied: parameters.system_prompt_applied,
+                conversation_id: request.conversation_id.clone(),
+                template: request.template.clone(),
+                prompt_variant: parameters.prompt_variant,
+                guardrails: decisions,
             }))
         }
+        Err(AiServiceError::ContentBlocked(reason)) => {
+            warn!("Content blocked: {}", reason);
+            Err((
+                Status::BadRequest,
+                ErrorResponse::with_code(
+                    format!("Content blocked by moderation: {}", reason),
+                    "CONTENT_BLOCKED",
+                ),
+            ))
+        }
+        Err(AiServiceError::AnswerBlocked(reason)) => {
+            warn!("Answer blocked: {}", reason);
+            Err((
+                Status::BadGateway,
+                ErrorResponse::with_code(
+                    format!("Answer blocked by moderation: {}", reason),
+                    "ANSWER_BLOCKED",
+                ),
+            ))
+        }
+        Err(AiServiceError::Busy(reason)) => {
+            error!("AI service is busy: {}", reason);
+            Err((
+                Status::ServiceUnavailable,
+                ErrorResponse::with_code(
+                    format!("Server is busy, please retry later: {}", reason),
+                    "SERVER_BUSY",
+                ),
+            ))
+        }
         Err(e) => {
             error!("Error getting answer: {}", e);
-            Err(Json(ErrorResponse::with_code(
-                format!("Failed to get answer: {}", e),
-                "AI_SERVICE_ERROR",
-            )))
+            Err((
+                Status::BadGateway,
+                ErrorResponse::with_code(
+                    format!("Failed to get answer: {}", e),
+                    "AI_SERVICE_ERROR",
+                ),
+            ))
+        }
+    }
+}
+
+/// Проверяет на prompt injection вопрос (`variable = None`) или значение
+/// переменной шаблона; сработавшая проверка добавляется в `decisions`.
+fn guard_input<'a>(
+    guardrails: &GuardrailsConfig,
+    subject: &str,
+    variable: Option<&str>,
+    text: &'a str,
+    decisions: &mut Vec<GuardrailDecision>,
+) -> Result<Cow<'a, str>, (Status, ErrorResponse)> {
+    let in_variable = |mut decision: GuardrailDecision| {
+        if let Some(name) = variable {
+            decision.detail = format!("variable '{}': {}", name, decision.detail);
+        }
+        log_decision(subject, &decision);
+        decision
+    };
+    let (text, decision) = guardrails::check_question(guardrails, text).map_err(|decision| {
+        let decision = in_variable(decision);
+        (
+            Status::BadRequest,
+            ErrorResponse::with_code(
+                format!("Question rejected by guardrails: {}", decision.detail),
+                "PROMPT_INJECTION",
+            ),
+        )
+    })?;
+    decisions.extend(decision.map(in_variable));
+    Ok(text)
+}
+
+/// Пишет в лог сработавшую проверку `guardrails`.
+fn log_decision(subject: &str, decision: &GuardrailDecision) {
+    warn!(
+        "Guardrail {} for {}: {} ({})",
+        decision.check,
+        subject,
+        decision.action.as_str(),
+        decision.detail
+    );
+}
+
+/// Текст запроса к модели: вопрос как есть или шаблон с подставленным вопросом.
+///
+/// `question` и `variables` - после проверки `guardrails` (могут быть очищены).
+fn render_prompt<'a>(
+    templates: Option<&TemplateLibrary>,
+    request: &AskRequest,
+    question: &'a str,
+    variables: &BTreeMap<String, String>,
+) -> Result<Cow<'a, str>, (Status, ErrorResponse)> {
+    let Some(name) = &request.template else {
+        if variables.is_empty() {
+            return Ok(Cow::Borrowed(question));
+        }
+        return Err((
+            Status::BadRequest,
+            ErrorResponse::with_code("'variables' require a 'template'", "INVALID_TEMPLATE_VARIABLE"),
+        ));
+    };
+    let result = match templates {
+        Some(library) => library.render(name, question, variables),
+        None => Err(TemplateError::Unknown(name.clone())),
+    };
+    result.map(Cow::Owned).map_err(|e| {
+        warn!("Rejecting question: {}", e);
+        let (message, code) = match e {
+            TemplateError::Unknown(name) => (
+                format!("Template '{}' not found, see GET /templates", name),
+                "UNKNOWN_TEMPLATE",
+            ),
+            TemplateError::MissingVariables { template, names } => (
+                format!("Template '{}' needs values for: {}", template, names.join(", ")),
+                "MISSING_TEMPLATE_VARIABLE",
+            ),
+            TemplateError::UnknownVariables { template, names } => (
+                format!("Template '{}' does not use: {}", template, names.join(", ")),
+                "INVALID_TEMPLATE_VARIABLE",
+            ),
+            TemplateError::ValueTooLong(name) => (
+                format!("Variable '{}' is longer than {} characters", name, MAX_VALUE_CHARS),
+                "INVALID_TEMPLATE_VARIABLE",
+            ),
+            other => (other.to_string(), "INVALID_TEMPLATE_VARIABLE"),
+        };
+        (Status::BadRequest, ErrorResponse::with_code(message, code))
+    })
+}
+
+/// Диалог для `conversation_id` из запроса: найденный в хранилище или новый.
+///
+/// Новый диалог сохраняется только вместе с первым ответом (см. [`remember`]).
+/// Чужой диалог выглядит как несуществующий: по ответу нельзя узнать,
+/// что такой id кем-то занят.
+async fn open_conversation(
+    storage: Option<&SharedStorage>,
+    subject: &str,
+    request: &AskRequest,
+) -> Result<Option<Conversation>, (Status, ErrorResponse)> {
+    let Some(id) = &request.conversation_id else {
+        return Ok(None);
+    };
+    let valid = !id.is_empty()
+        && id.len() <= MAX_CONVERSATION_ID_LEN
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err((
+            Status::BadRequest,
+            ErrorResponse::with_code(
+                format!(
+                    "conversation_id must be 1-{} characters: latin letters, digits, '-' or '_'",
+                    MAX_CONVERSATION_ID_LEN
+                ),
+                "INVALID_CONVERSATION_ID",
+            ),
+        ));
+    }
+    let Some(storage) = storage else {
+        return Ok(None);
+    };
+
+    let lookup = id.clone();
+    match storage::blocking(storage, move |storage| storage.conversation(&lookup)).await {
+        Ok(Some(conversation)) if conversation.subject == subject => Ok(Some(conversation)),
+        Ok(Some(_)) => Err((
+            Status::NotFound,
+            ErrorResponse::with_code(format!("Conversation '{}' not found", id), "CONVERSATION_NOT_FOUND"),
+        )),
+        Ok(None) => {
+            let now = storage::now_millis();
+            Ok(Some(Conversation {
+                id: id.clone(),
+                subject: subject.to_string(),
+                title: request.question.trim().chars().take(CONVERSATION_TITLE_CHARS).collect(),
+                created_at: now,
+                updated_at: now,
+            }))
+        }
+        Err(e) => {
+            error!("Failed to load conversation {}: {}", id, e);
+            Err((
+                Status::InternalServerError,
+                ErrorResponse::with_code("Failed to load conversation", "STORAGE_ERROR"),
+            ))
+        }
+    }
+}
+
+/// Записывает вызов AI (в том числе неудачный) в учёт использования.
+///
+/// Ошибки хранилища здесь и в [`save_answer`] только логируются: ответ AI
+/// уже получен (и оплачен), и клиент должен его увидеть.
+async fn record_usage(
+    storage: &SharedStorage,
+    subject: &str,
+    ai_service: &dyn AiService,
+    question: &str,
+    result: &Result<String, AiServiceError>,
+    latency: Duration,
+) {
+    let usage = UsageRecord {
+        subject: subject.to_string(),
+        backend: ai_service.name().to_string(),
+        outcome: match result {
+            Ok(_) => "ok",
+            Err(AiServiceError::Busy(_)) => "busy",
+            Err(AiServiceError::ContentBlocked(_) | AiServiceError::AnswerBlocked(_)) => "blocked",
+            Err(_) => "error",
+        }
+        .to_string(),
+        prompt_tokens: metrics::estimate_tokens(question),
+        completion_tokens: result.as_deref().map_or(0, metrics::estimate_tokens),
+        latency_ms: u64::try_from(latency.as_millis()).unwrap_or(u64::MAX),
+        created_at: storage::now_millis(),
+    };
+    if let Err(e) = storage::blocking(storage, move |storage| storage.record_usage(&usage)).await {
+        warn!("Failed to record usage: {}", e);
+    }
+}
+
+/// Сохраняет ответ в историю, а диалог (если он есть) - с новым `updated_at`.
+async fn save_answer(
+    storage: &SharedStorage,
+    conversation: Option<Conversation>,
+    mut exchange: Exchange,
+) {
+    let saved = storage::blocking(storage, move |storage| {
+        if let Some(conversation) = conversation {
+            let conversation = Conversation {
+                updated_at: exchange.created_at,
+                ..conversation
+            };
+            if let Err(e) = storage.save_conversation(&conversation) {
+                warn!("Failed to save conversation {}: {}", conversation.id, e);
+            }
+            exchange.conversation_id = Some(conversation.id);
         }
+        storage.save_exchange(&exchange)
+    });
+    if let Err(e) = saved.await {
+        warn!("Failed to save answer to history: {}", e);
+    }
+}
+
+// ============================================================================
+// ИСТОРИЯ ВОПРОСОВ
+// ============================================================================
+
+/// Фильтры истории из строки запроса (общие для `GET` и `DELETE /history`).
+///
+/// # Для студентов: `FromForm` и `<filter..>`
+///
+/// Маршрут `/history?<cursor>&<limit>&<filter..>` забирает `cursor` и
+/// `limit` в отдельные параметры, а все остальные поля строки запроса
+/// Rocket собирает в эту структуру. Необязательные поля - `Option`:
+/// без них запрос тоже подходит к маршруту.
+#[derive(Debug, FromForm)]
+pub struct HistoryParams {
+    /// Начало периода: дата `YYYY-MM-DD` (UTC) или миллисекунды Unix
+    from: Option<String>,
+
+    /// Конец периода включительно: дата (весь день) или миллисекунды Unix
+    to: Option<String>,
+
+    /// Источник ответа (`gigachat`, `mock ai service`)
+    source: Option<String>,
+
+    /// Слова для поиска в вопросах и ответах
+    q: Option<String>,
+
+    /// Только записи одного диалога
+    conversation_id: Option<String>,
+}
+
+impl HistoryParams {
+    /// Фильтр хранилища для истории клиента `subject`.
+    fn into_filter(self, subject: &str) -> Result<HistoryFilter, (Status, ErrorResponse)> {
+        let invalid_date = |value: &str| {
+            (
+                Status::BadRequest,
+                ErrorResponse::with_code(
+                    format!("Invalid date '{}': expected YYYY-MM-DD or unix milliseconds", value),
+                    "INVALID_DATE",
+                ),
+            )
+        };
+        let from = match &self.from {
+            Some(from) => Some(parse_time(from, false).ok_or_else(|| invalid_date(from))?),
+            None => None,
+        };
+        let until = match &self.to {
+            Some(to) => Some(parse_time(to, true).ok_or_else(|| invalid_date(to))?),
+            None => None,
+        };
+        // Запрос из одних знаков препинания ничего не ищет - как будто его нет
+        let search = self.q.filter(|q| !storage::words(q).is_empty());
+
+        Ok(HistoryFilter {
+            subject: subject.to_string(),
+            conversation_id: self.conversation_id,
+            source: self.source.map(|source| source.to_lowercase()),
+            from,
+            until,
+            search,
+        })
+    }
+}
+
+/// Время из `from`/`to`: миллисекунды Unix или дата `YYYY-MM-DD` (UTC).
+///
+/// Для конца периода (`end = true`) возвращается первая миллисекунда
+/// ПОСЛЕ него: `to=2026-10-18` включает весь день 18 октября.
+pub fn parse_time(value: &str, end: bool) -> Option<i64> {
+    let value = value.trim();
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+        let millis: i64 = value.parse().ok()?;
+        return Some(if end { millis.saturating_add(1) } else { millis });
+    }
+
+    let mut parts = value.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (i64, u32, u32) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if day == 0 || day > days_in_month {
+        return None;
+    }
+    let days = days_from_civil(year, month, day) + i64::from(end);
+    Some(days * DAY_MILLIS)
+}
+
+/// Номер дня от 1970-01-01 по дате григорианского календаря.
+///
+/// Алгоритм Говарда Хиннанта (`days_from_civil`): год сдвигается так,
+/// чтобы он начинался с марта, - тогда февраль с его 28/29 днями
+/// оказывается в конце года и не мешает считать дни до месяца.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = i64::from((month + 9) % 12);
+    let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Ответ `500 STORAGE_ERROR`; подробности ошибки остаются в логе.
+fn storage_error(
+    request_id: &RequestId,
+    action: &str,
+    error: storage::StorageError,
+) -> (Status, Json<ErrorResponse>) {
+    error!("Failed to {}: {}", action, error);
+    (
+        Status::InternalServerError,
+        Json(
+            ErrorResponse::with_code(format!("Failed to {}", action), "STORAGE_ERROR")
+                .with_request_id(request_id.as_str()),
+        ),
+    )
+}
+
+/// История своих вопросов и ответов, от новых к старым.
+///
+/// Каждый клиент видит только свои записи (по имени API-ключа или `sub`
+/// из JWT). Без аутентификации все запросы идут от `anonymous` и видят
+/// общую историю.
+///
+/// # Эндпоинт
+///
+/// `GET /history` (scope `ask`)
+///
+/// # Параметры строки запроса
+///
+/// - `limit` - записей на странице (по умолчанию 20, не больше 100);
+/// - `cursor` - `next_cursor` из предыдущей страницы;
+/// - `from`, `to` - период: `YYYY-MM-DD` (UTC) или миллисекунды Unix;
+/// - `source` - источник ответа (`gigachat`, `mock ai service`);
+/// - `q` - слова для поиска в вопросах и ответах (все должны встретиться,
+///   каждое - как начало слова, без учёта регистра);
+/// - `conversation_id` - только записи одного диалога.
+///
+/// # Коды ошибок
+///
+/// - `400 INVALID_CURSOR` - курсор испорчен;
+/// - `400 INVALID_DATE` - `from`/`to` не дата и не число;
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl "http://localhost:8000/history?q=трейт&from=2026-10-01&limit=10" -H "X-API-Key: your_key"
+/// ```
+#[get("/history?<cursor>&<limit>&<filter..>")]
+pub async fn history(
+    auth: Authenticated<scopes::Ask>,
+    request_id: RequestId,
+    cursor: Option<&str>,
+    limit: Option<usize>,
+    filter: HistoryParams,
+    storage: &State<SharedStorage>,
+) -> Result<Json<HistoryResponse>, (Status, Json<ErrorResponse>)> {
+    let bad_request = |(status, error): (Status, ErrorResponse)| {
+        (status, Json(error.with_request_id(request_id.as_str())))
+    };
+    let filter = filter.into_filter(&auth.principal.subject).map_err(bad_request)?;
+    let cursor = match cursor {
+        Some(cursor) => Some(HistoryCursor::decode(cursor).ok_or_else(|| {
+            bad_request((
+                Status::BadRequest,
+                ErrorResponse::with_code("Invalid history cursor", "INVALID_CURSOR"),
+            ))
+        })?),
+        None => None,
+    };
+    let limit = limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT);
+
+    let page = storage::blocking(storage, move |storage| {
+        storage.history(&filter, cursor.as_ref(), limit)
+    })
+    .await
+    .map_err(|e| storage_error(&request_id, "read history", e))?;
+    Ok(Json(HistoryResponse {
+        items: page.items.into_iter().map(HistoryEntry::from).collect(),
+        next_cursor: page.next.map(|cursor| cursor.encode()),
+    }))
+}
+
+/// Удаляет записи своей истории по фильтру.
+///
+/// Фильтры - те же, что у `GET /history`. Чтобы случайный запрос без
+/// параметров не стёр всё, удаление всей истории требует `?all=true`.
+/// Вместе с записями удаляются опустевшие диалоги.
+///
+/// # Эндпоинт
+///
+/// `DELETE /history` (scope `ask`)
+///
+/// # Коды ошибок
+///
+/// - `400 FILTER_REQUIRED` - не задан ни один фильтр и нет `all=true`;
+/// - `400 INVALID_DATE` - `from`/`to` не дата и не число;
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl -X DELETE "http://localhost:8000/history?to=2026-09-30" -H "X-API-Key: your_key"
+/// curl -X DELETE "http://localhost:8000/history?all=true" -H "X-API-Key: your_key"
+/// ```
+#[delete("/history?<all>&<filter..>")]
+pub async fn delete_history(
+    auth: Authenticated<scopes::Ask>,
+    request_id: RequestId,
+    all: Option<bool>,
+    filter: HistoryParams,
+    storage: &State<SharedStorage>,
+) -> Result<Json<DeleteHistoryResponse>, (Status, Json<ErrorResponse>)> {
+    let subject = &auth.principal.subject;
+    let filter = filter
+        .into_filter(subject)
+        .map_err(|(status, error)| (status, Json(error.with_request_id(request_id.as_str()))))?;
+    let everything = HistoryFilter {
+        subject: subject.clone(),
+        ..HistoryFilter::default()
+    };
+    if filter == everything && all != Some(true) {
+        return Err((
+            Status::BadRequest,
+            Json(
+                ErrorResponse::with_code(
+                    "Specify from, to, source, q or conversation_id, or all=true to delete the whole history",
+                    "FILTER_REQUIRED",
+                )
+                .with_request_id(request_id.as_str()),
+            ),
+        ));
+    }
+
+    let deleted = storage::blocking(storage, move |storage| storage.delete_history(&filter))
+        .await
+        .map_err(|e| storage_error(&request_id, "delete history", e))?;
+    info!("{} deleted {} history entries", subject, deleted);
+    Ok(Json(DeleteHistoryResponse { deleted }))
+}
+
+/// Удаляет одну запись своей истории.
+///
+/// # Эндпоинт
+///
+/// `DELETE /history/<id>` (scope `ask`)
+///
+/// # Коды ошибок
+///
+/// - `404 HISTORY_NOT_FOUND` - записи нет (или она чужая);
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl -X DELETE http://localhost:8000/history/9b1d... -H "X-API-Key: your_key"
+/// ```
+#[delete("/history/<id>")]
+pub async fn delete_history_entry(
+    auth: Authenticated<scopes::Ask>,
+    request_id: RequestId,
+    id: &str,
+    storage: &State<SharedStorage>,
+) -> Result<Json<DeleteHistoryResponse>, (Status, Json<ErrorResponse>)> {
+    let (subject, entry) = (auth.principal.subject.clone(), id.to_string());
+    let deleted = storage::blocking(storage, move |storage| {
+        storage.delete_exchange(&subject, &entry)
+    })
+    .await
+    .map_err(|e| storage_error(&request_id, "delete history entry", e))?;
+    if !deleted {
+        return Err((
+            Status::NotFound,
+            Json(
+                ErrorResponse::with_code(format!("History entry '{}' not found", id), "HISTORY_NOT_FOUND")
+                    .with_request_id(request_id.as_str()),
+            ),
+        ));
     }
+    Ok(Json(DeleteHistoryResponse { deleted: 1 }))
+}
+
+// ============================================================================
+// ЭКСПОРТ
+// ============================================================================
+
+/// Документ экспорта: тело с типом формата и имя файла для сохранения.
+///
+/// # Для студентов: `#[derive(Responder)]`
+///
+/// Первое поле становится телом ответа (кортеж `(ContentType, String)`
+/// задаёт и тип), остальные поля добавляются заголовками.
+#[derive(Responder)]
+pub struct ExportResponse {
+    body: (ContentType, String),
+    disposition: Header<'static>,
+}
+
+/// Выгрузка своей истории или диалога в Markdown, JSON Lines или HTML.
+///
+/// Записи идут от старых к новым, у каждой - время, модель, источник и
+/// признак системного промпта. Одни и те же записи всегда дают один и
+/// тот же документ. Заголовок `Content-Disposition` подсказывает
+/// браузеру имя файла: `conversation-lab-3.md`, `history.html`.
+///
+/// # Эндпоинт
+///
+/// `GET /export` (scope `ask`)
+///
+/// # Параметры строки запроса
+///
+/// - `format` - `markdown` (по умолчанию), `jsonl` или `html`;
+/// - `conversation_id`, `from`, `to`, `source`, `q` - как у `GET /history`.
+///
+/// # Коды ошибок
+///
+/// - `400 INVALID_FORMAT` - неизвестный формат;
+/// - `400 INVALID_DATE` - `from`/`to` не дата и не число;
+/// - `400 EXPORT_TOO_LARGE` - больше 1000 записей, нужно сузить период;
+/// - `404 CONVERSATION_NOT_FOUND` - диалога нет (или он чужой);
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl "http://localhost:8000/export?format=markdown&conversation_id=lab-3" \
+///   -H "X-API-Key: your_key" -o lab-3.md
+/// curl "http://localhost:8000/export?format=html&from=2026-10-01" -H "X-API-Key: your_key" -o history.html
+/// ```
+#[get("/export?<format>&<filter..>")]
+pub async fn export_history(
+    auth: Authenticated<scopes::Ask>,
+    request_id: RequestId,
+    format: Option<&str>,
+    filter: HistoryParams,
+    storage: &State<SharedStorage>,
+) -> Result<ExportResponse, (Status, Json<ErrorResponse>)> {
+    let reject = |status: Status, error: ErrorResponse| {
+        (status, Json(error.with_request_id(request_id.as_str())))
+    };
+    let format = match format {
+        Some(name) => ExportFormat::parse(name).ok_or_else(|| {
+            reject(
+                Status::BadRequest,
+                ErrorResponse::with_code(
+                    format!("Unknown export format '{}': expected {}", name, ExportFormat::NAMES.join(", ")),
+                    "INVALID_FORMAT",
+                ),
+            )
+        })?,
+        None => ExportFormat::Markdown,
+    };
+    let filter = filter
+        .into_filter(&auth.principal.subject)
+        .map_err(|(status, error)| reject(status, error))?;
+
+    let document = storage::blocking(storage, move |storage| {
+        ExportDocument::load(storage, &filter)
+    });
+    let document = document.await.map_err(|e| match e {
+        ExportError::ConversationNotFound(id) => reject(
+            Status::NotFound,
+            ErrorResponse::with_code(format!("Conversation '{}' not found", id), "CONVERSATION_NOT_FOUND"),
+        ),
+        ExportError::TooLarge(max) => reject(
+            Status::BadRequest,
+            ErrorResponse::with_code(
+                format!("More than {} entries to export, narrow the period with from/to", max),
+                "EXPORT_TOO_LARGE",
+            ),
+        ),
+        ExportError::Storage(e) => storage_error(&request_id, "export history", e),
+    })?;
+    info!(
+        "{} exported {} history entries as {}",
+        document.subject,
+        document.exchanges.len(),
+        format.extension()
+    );
+
+    let content_type = ContentType::parse_flexible(format.media_type()).unwrap_or(ContentType::Plain);
+    let disposition = format!("attachment; filename=\"{}\"", document.file_name(format));
+    Ok(ExportResponse {
+        body: (content_type, document.render(format)),
+        disposition: Header::new("Content-Disposition", disposition),
+    })
+}
+
+// ============================================================================
+// ОЦЕНКИ ОТВЕТОВ
+// ============================================================================
+
+/// Оценка ответа: помог (`up`) или нет (`down`).
+///
+/// Оценить можно только свой ответ - по `answer_id` из ответа `/ask`.
+/// Повторная оценка заменяет предыдущую. Оценки вместе с моделью и версией
+/// системного промпта видит преподаватель в `GET /admin/feedback`.
+///
+/// # Эндпоинт
+///
+/// `POST /feedback` (scope `ask`)
+///
+/// # Коды ошибок
+///
+/// - `400 INVALID_CATEGORY` - категория не из списка
+///   `wrong`, `off_topic`, `too_long`, `other`;
+/// - `400 COMMENT_TOO_LONG` - комментарий длиннее 1000 символов;
+/// - `404 ANSWER_NOT_FOUND` - ответа нет (удалён из истории или чужой);
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl -X POST http://localhost:8000/feedback \
+///   -H "Content-Type: application/json" -H "X-API-Key: your_key" \
+///   -d '{"answer_id": "9b1d...", "rating": "down", "comment": "Rocket принят за ракету", "categories": ["off_topic"]}'
+/// ```
+#[post("/feedback", format = "json", data = "<request>")]
+pub async fn feedback(
+    auth: Authenticated<scopes::Ask>,
+    request_id: RequestId,
+    request: Json<FeedbackRequest>,
+    storage: &State<SharedStorage>,
+) -> Result<Json<FeedbackResponse>, (Status, Json<ErrorResponse>)> {
+    let reject = |status: Status, error: ErrorResponse| {
+        (status, Json(error.with_request_id(request_id.as_str())))
+    };
+    let request = request.into_inner();
+    let subject = &auth.principal.subject;
+
+    let mut categories = request.categories;
+    if let Some(unknown) = categories.iter().find(|c| !FEEDBACK_CATEGORIES.contains(&c.as_str())) {
+        return Err(reject(
+            Status::BadRequest,
+            ErrorResponse::with_code(
+                format!("Unknown category '{}', expected one of: {}", unknown, FEEDBACK_CATEGORIES.join(", ")),
+                "INVALID_CATEGORY",
+            ),
+        ));
+    }
+    categories.sort();
+    categories.dedup();
+
+    let comment = request
+        .comment
+        .map(|comment| comment.trim().to_string())
+        .filter(|comment| !comment.is_empty());
+    if comment.as_ref().is_some_and(|c| c.chars().count() > MAX_FEEDBACK_COMMENT_CHARS) {
+        return Err(reject(
+            Status::BadRequest,
+            ErrorResponse::with_code(
+                format!("Comment must be at most {} characters", MAX_FEEDBACK_COMMENT_CHARS),
+                "COMMENT_TOO_LONG",
+            ),
+        ));
+    }
+
+    let answer_id = request.answer_id.clone();
+    let answer = storage::blocking(storage, move |storage| storage.exchange(&answer_id))
+        .await
+        .map_err(|e| storage_error(&request_id, "load answer", e))?;
+    if answer.is_none_or(|answer| &answer.subject != subject) {
+        return Err(reject(
+            Status::NotFound,
+            ErrorResponse::with_code(
+                format!("Answer '{}' not found", request.answer_id),
+                "ANSWER_NOT_FOUND",
+            ),
+        ));
+    }
+
+    let feedback = Feedback {
+        answer_id: request.answer_id.clone(),
+        subject: subject.clone(),
+        rating: request.rating,
+        comment,
+        categories,
+        created_at: storage::now_millis(),
+    };
+    let updated = storage::blocking(storage, move |storage| storage.save_feedback(&feedback))
+        .await
+        .map_err(|e| storage_error(&request_id, "save feedback", e))?;
+    info!("{} rated answer {}: {}", subject, request.answer_id, request.rating.as_str());
+    Ok(Json(FeedbackResponse {
+        answer_id: request.answer_id,
+        rating: request.rating,
+        updated,
+    }))
+}
+
+/// Список шаблонов промптов с переменными и значениями по умолчанию.
+///
+/// Имя шаблона передаётся в `POST /ask` полем `template`, значения
+/// переменных - полем `variables`. Переменная с `"required": true` не
+/// имеет значения по умолчанию: без неё вопрос получит
+/// `400 MISSING_TEMPLATE_VARIABLE`. Если каталог шаблонов не настроен,
+/// список пустой.
+///
+/// # Эндпоинт
+///
+/// `GET /templates` (scope `ask`)
+///
+/// # Пример
+///
+/// ```bash
+/// curl http://localhost:8000/templates -H "X-API-Key: your_key"
+/// ```
+#[get("/templates")]
+pub fn list_templates(_auth: Authenticated<scopes::Ask>, templates: Templates<'_>) -> Json<TemplatesResponse> {
+    Json(TemplatesResponse {
+        templates: templates.0.map(TemplateLibrary::list).unwrap_or_default(),
+    })
+}
+
+/// Перечитывает config.toml и применяет новые настройки без перезапуска.
+///
+/// Новые запросы сразу идут с новым системным промптом, моделью и
+/// температурой; начатые дорабатывают со старыми (см. модуль `reload`).
+/// При ошибке в файле работающая конфигурация не меняется.
+///
+/// # Эндпоинт
+///
+/// `POST /admin/reload` (scope `admin`)
+///
+/// # Коды ошибок
+///
+/// - `422 INVALID_CONFIG` - файл не читается или значения недопустимы.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl -X POST http://localhost:8000/admin/reload -H "X-API-Key: admin_key"
+/// ```
+#[post("/admin/reload")]
+pub async fn admin_reload(
+    auth: Authenticated<scopes::Admin>,
+    request_id: RequestId,
+    reloader: &State<Reloader>,
+) -> Result<Json<ReloadResponse>, (Status, Json<ErrorResponse>)> {
+    info!("Config reload requested by {}", auth.principal.subject);
+    reloader.reload_async().await.map(Json).map_err(|e| {
+        error!("Config reload failed: {}", e);
+        (
+            Status::UnprocessableEntity,
+            Json(
+                ErrorResponse::with_code(e.to_string(), "INVALID_CONFIG")
+                    .with_request_id(request_id.as_str()),
+            ),
+        )
+    })
+}
+
+/// Показывает итоговую конфигурацию и источник каждого значения.
+///
+/// Конфигурация - та, что реально работает: с учётом профиля, переменных
+/// окружения и перезагрузок. Секреты заменены на `"***"`.
+///
+/// # Эндпоинт
+///
+/// `GET /admin/config` (scope `admin`)
+///
+/// # Примеры
+///
+/// ```bash
+/// curl http://localhost:8000/admin/config -H "X-API-Key: admin_key"
+/// ```
+#[get("/admin/config")]
+pub fn admin_config(
+    auth: Authenticated<scopes::Admin>,
+    runtime: &State<LiveRuntime>,
+) -> Json<ConfigResponse> {
+    info!("Config requested by {}", auth.principal.subject);
+    let runtime = runtime.current();
+    let (config, sources) = profile::describe(&runtime.config);
+
+    Json(ConfigResponse {
+        profile: runtime.config.origin.profile.clone(),
+        files: runtime.config.origin.files.clone(),
+        generation: runtime.generation,
+        config,
+        sources,
+    })
+}
+
+/// Использование AI по клиентам: вызовы, ошибки и оценка токенов.
+///
+/// Данные берутся из постоянного хранилища (модуль `storage`) и
+/// переживают перезапуск, в отличие от метрик Prometheus.
+///
+/// # Эндпоинт
+///
+/// `GET /admin/usage` (scope `admin`)
+///
+/// # Коды ошибок
+///
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl http://localhost:8000/admin/usage -H "X-API-Key: admin_key"
+/// ```
+#[get("/admin/usage")]
+pub async fn admin_usage(
+    auth: Authenticated<scopes::Admin>,
+    request_id: RequestId,
+    storage: &State<SharedStorage>,
+) -> Result<Json<UsageResponse>, (Status, Json<ErrorResponse>)> {
+    info!("Usage requested by {}", auth.principal.subject);
+    let subjects = storage::blocking(storage, |storage| storage.usage_summary())
+        .await
+        .map_err(|e| storage_error(&request_id, "read usage", e))?;
+    Ok(Json(UsageResponse {
+        storage: storage.describe(),
+        subjects,
+    }))
+}
+
+/// Оценки ответов по моделям и версиям системного промпта.
+///
+/// Для каждой группы "модель + вариант эксперимента + версия промпта" -
+/// сколько ответов дано, сколько оценок 👍/👎, доля 👍 и отмеченные
+/// категории; ниже - последние оценки вместе с вопросами и ответами для
+/// разбора. Версия промпта - начало SHA-256 его текста: после правки
+/// `application.system_prompt` (и `POST /admin/reload`) ответы попадают в
+/// новую группу. Если шёл A/B эксперимент (секция `[experiment]`), поле
+/// `variants` сравнивает его варианты по всем моделям сразу.
+///
+/// # Эндпоинт
+///
+/// `GET /admin/feedback?limit=<число последних оценок>` (scope `admin`)
+///
+/// # Коды ошибок
+///
+/// - `500 STORAGE_ERROR` - хранилище недоступно.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl http://localhost:8000/admin/feedback -H "X-API-Key: admin_key"
+/// ```
+#[get("/admin/feedback?<limit>")]
+pub async fn admin_feedback(
+    auth: Authenticated<scopes::Admin>,
+    request_id: RequestId,
+    limit: Option<usize>,
+    storage: &State<SharedStorage>,
+) -> Result<Json<FeedbackReport>, (Status, Json<ErrorResponse>)> {
+    info!("Feedback report requested by {}", auth.principal.subject);
+    let limit = limit.unwrap_or(DEFAULT_RECENT_FEEDBACK).min(MAX_HISTORY_LIMIT);
+    let (groups, recent) = storage::blocking(storage, move |storage| {
+        Ok::<_, storage::StorageError>((storage.feedback_summary()?, storage.recent_feedback(limit)?))
+    })
+    .await
+    .map_err(|e| storage_error(&request_id, "read feedback", e))?;
+    Ok(Json(FeedbackReport {
+        storage: storage.describe(),
+        variants: experiment::variant_report(&groups),
+        groups,
+        recent,
+    }))
+}
+
+/// Обработчик эндпоинта метрик Prometheus.
+///
+/// Маршрут объявлен как `/`, а в `main.rs` монтируется по пути из
+/// `metrics.path` (по умолчанию `/metrics`) - так путь настраивается
+/// без перекомпиляции. Эндпоинт подключается только при `metrics.enabled = true`.
+///
+/// # Примеры
+///
+/// ```bash
+/// curl http://localhost:8000/metrics
+/// ```
+#[get("/")]
+pub fn prometheus_metrics(metrics: &State<Arc<Metrics>>, queue: QueueSnapshot) -> (ContentType, String) {
+    (ContentType::Plain, metrics.render(queue.0.as_ref()))
 }
 
 /// Обработчик preflight-запросов для CORS (OPTIONS).
@@ -264,9 +1567,19 @@ pub async fn ask(
 /// Rocket не создаёт OPTIONS‑маршруты автоматически, поэтому браузерный
 /// preflight завершался 404. Этот handler возвращает 204 No Content
 /// для любых путей API, позволяя браузеру продолжить POST/GET запрос.
+///
+/// Preflight с origin, методом или заголовком, не разрешёнными в `[cors]`,
+/// получает 403 Forbidden. Сами заголовки `Access-Control-*` добавляет
+/// fairing `cors::Cors`.
 #[options("/<_path..>")]
-pub fn cors_preflight(_path: PathBuf) -> rocket::http::Status {
-    rocket::http::Status::NoContent
+pub fn cors_preflight(_path: PathBuf, preflight: Preflight, cors: &State<CorsPolicy>) -> Status {
+    match cors.check_preflight(&preflight) {
+        Ok(()) => Status::NoContent,
+        Err(rejection) => {
+            warn!("CORS preflight rejected: {:?}", rejection);
+            Status::Forbidden
+        }
+    }
 }
 
 // ============================================================================
@@ -283,6 +1596,9 @@ pub fn cors_preflight(_path: PathBuf) -> rocket::http::Status {
 //
 // Без catchers Rocket вернёт HTML-страницу с ошибкой.
 // С catchers мы возвращаем JSON - это важно для API!
+//
+// В каждую ошибку добавляется `request_id` (см. модуль `request_id`):
+// по нему клиент и разработчик найдут этот запрос в логах.
 
 /// Обработчик для несуществующих эндпоинтов (404 Not Found).
 ///
@@ -295,11 +1611,56 @@ pub fn cors_preflight(_path: PathBuf) -> rocket::http::Status {
 /// # Вернёт: {"error": "Endpoint not found...", "code": "NOT_FOUND"}
 /// ```
 #[catch(404)]
-pub fn not_found() -> Json<ErrorResponse> {
-    Json(ErrorResponse::with_code(
-        "Endpoint not found. Use GET / to see available endpoints.",
-        "NOT_FOUND",
-    ))
+pub fn not_found(req: &Request) -> Json<ErrorResponse> {
+    error_json(
+        req,
+        ErrorResponse::with_code(
+            "Endpoint not found. Use GET / to see available endpoints.",
+            "NOT_FOUND",
+        ),
+    )
+}
+
+/// Обработчик для запросов без действующего API-ключа (401 Unauthorized).
+///
+/// # Для студентов: Параметр `req: &Request`
+///
+/// Catcher может принимать сам запрос. Guard `Authenticated` кладёт
+/// причину отказа в `req.local_cache`, а здесь мы её достаём, чтобы вернуть
+/// точный код: `MISSING_API_KEY`, `INVALID_API_KEY`, `INVALID_TOKEN`
+/// или `TOKEN_EXPIRED`.
+#[catch(401)]
+pub fn unauthorized(req: &Request) -> Json<ErrorResponse> {
+    let code = cached_error(req).map(|e| e.code()).unwrap_or("UNAUTHORIZED");
+    let message = match cached_error(req) {
+        Some(e @ (AuthError::InvalidToken(_) | AuthError::TokenExpired)) => e.to_string(),
+        _ => "Authentication required. Pass 'Authorization: Bearer <key>' or 'X-API-Key: <key>'."
+            .to_string(),
+    };
+    error_json(req, ErrorResponse::with_code(message, code))
+}
+
+/// Обработчик для запросов с ключом без нужных прав (403 Forbidden).
+#[catch(403)]
+pub fn forbidden(req: &Request) -> Json<ErrorResponse> {
+    let error = match cached_error(req) {
+        Some(e) => ErrorResponse::with_code(e.to_string(), e.code()),
+        None => ErrorResponse::with_code("Access denied", "FORBIDDEN"),
+    };
+    error_json(req, error)
+}
+
+/// Обработчик для превышения лимита запросов (429 Too Many Requests).
+///
+/// Заголовки `Retry-After` и `RateLimit-*` добавляет fairing
+/// `RateLimitHeaders`, здесь формируется только JSON-тело.
+#[catch(429)]
+pub fn too_many_requests(req: &Request) -> Json<ErrorResponse> {
+    let message = match cached_decision(req).and_then(|d| d.retry_after_seconds) {
+        Some(seconds) => format!("Too many requests. Retry after {seconds} seconds."),
+        None => "Too many requests".to_string(),
+    };
+    error_json(req, ErrorResponse::with_code(message, "RATE_LIMITED"))
 }
 
 /// Обработчик для внутренних ошибок сервера (500 Internal Server Error).
@@ -307,11 +1668,13 @@ pub fn not_found() -> Json<ErrorResponse> {
 /// Вызывается при необработанных исключениях (паниках) в коде.
 /// В продакшене важно логировать такие ошибки для отладки.
 #[catch(500)]
-pub fn internal_error() -> Json<ErrorResponse> {
-    Json(ErrorResponse::with_code(
-        "Internal server error",
-        "INTERNAL_ERROR",
-    ))
+pub fn internal_error(req: &Request) -> Json<ErrorResponse> {
+    // Guard аутентификации отвечает 500, если сервер собран без конфигурации
+    let error = match cached_error(req) {
+        Some(e) => ErrorResponse::with_code(e.to_string(), e.code()),
+        None => ErrorResponse::with_code("Internal server error", "INTERNAL_ERROR"),
+    };
+    error_json(req, error)
 }
 
 /// Обработчик для ошибок валидации запроса (422 Unprocessable Entity).
@@ -330,11 +1693,16 @@ pub fn internal_error() -> Json<ErrorResponse> {
 /// {"questions": "..."}         // Опечатка в имени поля
 /// ```
 #[catch(422)]
-pub fn unprocessable_entity() -> Json<ErrorResponse> {
-    Json(ErrorResponse::with_code(
-        "Invalid request format. Check your JSON.",
-        "INVALID_REQUEST",
-    ))
+pub fn unprocessable_entity(req: &Request) -> Json<ErrorResponse> {
+    error_json(
+        req,
+        ErrorResponse::with_code("Invalid request format. Check your JSON.", "INVALID_REQUEST"),
+    )
+}
+
+/// Добавляет к ошибке идентификатор запроса и упаковывает её в JSON.
+fn error_json(req: &Request, error: ErrorResponse) -> Json<ErrorResponse> {
+    Json(error.with_request_id(RequestId::of(req).as_str()))
 }
 
 // ============================================================================
@@ -360,7 +1728,9 @@ pub fn unprocessable_entity() -> Json<ErrorResponse> {
 #[cfg(test)]
 mod tests {
     use crate::config::AppConfig;
-    use crate::handlers::{health, index};
+    use crate::handlers::{health, index, parse_time};
+    use crate::reload::{LiveRuntime, Runtime};
+    use crate::services::MockAiService;
     // routes! - макрос, который создаёт Vec маршрутов из функций-handlers
     use rocket::{routes, local::blocking::Client, Build, Rocket};
 
@@ -382,7 +1752,8 @@ mod tests {
         // .manage() добавляет State
         // .mount() регистрирует маршруты
         rocket::build()
-            .manage(config)
+            .manage(LiveRuntime::new(Runtime::new(config.clone(), Box::new(MockAiService::new()), None)))
+            .manage(config) // без AppConfig guard аутентификации отвечает 500
             .mount("/", routes![index, health])  // routes! - макрос!
     }
 
@@ -404,4 +1775,30 @@ mod tests {
         let response = client.get("/health").dispatch();
         assert_eq!(response.status().code, 200);
     }
+
+    /// Тест: без AppConfig защищённый эндпоинт не открывается всем
+    #[test]
+    fn test_auth_fails_closed_without_config() {
+        let config = AppConfig::load().expect("config.toml");
+        let rocket = rocket::build()
+            .manage(LiveRuntime::new(Runtime::new(config, Box::new(MockAiService::new()), None)))
+            .mount("/", routes![health]);
+        let client = Client::tracked(rocket).expect("valid rocket instance");
+        assert_eq!(client.get("/health").dispatch().status().code, 500);
+    }
+
+    /// Даты в фильтре истории: UTC, конец периода - включительно.
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("1970-01-01", false), Some(0));
+        assert_eq!(parse_time("1970-01-01", true), Some(86_400_000));
+        assert_eq!(parse_time("2026-10-18", false), Some(1_792_281_600_000));
+        assert_eq!(parse_time("2024-02-29", false), Some(1_709_164_800_000));
+        assert_eq!(parse_time("1760781600000", false), Some(1_760_781_600_000));
+        assert_eq!(parse_time("1760781600000", true), Some(1_760_781_600_001));
+
+        for invalid in ["", "2025-02-29", "2026-13-01", "2026-1-1", "вчера", "-5"] {
+            assert_eq!(parse_time(invalid, false), None, "{invalid}");
+        }
+    }
 }
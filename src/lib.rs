@@ -3,7 +3,26 @@
 //! Этот модуль экспортирует все основные компоненты приложения,
 //! что позволяет использовать их в тестах и других проектах.
 
+pub mod auth;
+pub mod build_info;
+pub mod concurrency;
 pub mod config;
+pub mod cors;
+pub mod experiment;
+pub mod export;
+pub mod guardrails;
 pub mod handlers;
+pub mod logging;
+pub mod metrics;
 pub mod models;
+pub mod moderation;
+pub mod rate_limit;
+pub mod readiness;
+pub mod reload;
+pub mod request_id;
+pub mod secrets;
 pub mod services;
+pub mod shutdown;
+pub mod storage;
+pub mod telemetry;
+pub mod templates;
@@ -0,0 +1,345 @@
+//! Модуль инициализации логирования.
+//!
+//! Настраивает `tracing_subscriber` по секции `[logging]` config.toml:
+//!
+//! - формат вывода: `compact`, `pretty` или `json`;
+//! - уровни для отдельных модулей (`[logging.targets]`);
+//! - переменная окружения `RUST_LOG`, которая заменяет уровни из конфига;
+//! - необязательная запись в файл с ротацией (`[logging.file]`);
+//! - необязательный экспорт span'ов в OpenTelemetry (`[telemetry]`,
+//!   модуль `telemetry`).
+//!
+//! # Для студентов: Зачем нужно логирование?
+//!
+//! Логирование - это способ отслеживать, что происходит в программе:
+//! - При разработке: отладка без debugger'а
+//! - В продакшене: диагностика проблем
+//!
+//! ## Уровни логирования (от детального к критическому)
+//!
+//! ```text
+//! TRACE  →  Очень детальная информация (каждый шаг)
+//! DEBUG  →  Отладочная информация
+//! INFO   →  Важные события (запуск, завершение)
+//! WARN   →  Предупреждения (что-то подозрительное)
+//! ERROR  →  Ошибки (что-то сломалось)
+//! ```
+//!
+//! ## Пример использования
+//!
+//! ```rust,ignore
+//! info!("Сервер запущен на порту {}", port);
+//! error!("Не удалось подключиться к БД: {}", err);
+//! ```
+//!
+//! # Для студентов: Слои (layers)
+//!
+//! `tracing_subscriber` собирается из слоёв, как конструктор:
+//!
+//! ```text
+//! registry()                  ← хранит span'ы
+//!   .with(EnvFilter)          ← решает, КАКИЕ события пропускать
+//!   .with([консоль, файл])    ← решают, КУДА и В КАКОМ ВИДЕ писать
+//! ```
+//!
+//! Каждый формат (`compact`, `pretty`, `json`) - это свой тип слоя, поэтому
+//! слои упаковываются в `Box<dyn Layer<S>>`: так их можно выбрать в runtime
+//! и сложить в один `Vec`.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+use crate::config::{LogFileConfig, LoggingConfig, TelemetryConfig};
+use crate::telemetry::{self, TelemetryError, TelemetryGuard};
+
+// ============================================================================
+// ТИПЫ
+// ============================================================================
+
+/// Ошибки настройки логирования.
+#[derive(Error, Debug)]
+pub enum LoggingError {
+    /// Неизвестное значение `format`
+    #[error("Неизвестный формат логов '{0}' (допустимо: compact, pretty, json)")]
+    UnknownFormat(String),
+
+    /// Неизвестный уровень в `level` или `[logging.targets]`
+    #[error("Неизвестный уровень логов '{0}' (допустимо: trace, debug, info, warn, error, off)")]
+    UnknownLevel(String),
+
+    /// Неизвестное значение `rotation`
+    #[error("Неизвестная ротация логов '{0}' (допустимо: minutely, hourly, daily, never)")]
+    UnknownRotation(String),
+
+    /// Некорректный фильтр (например, в `RUST_LOG`)
+    #[error("Некорректный фильтр логов '{0}': {1}")]
+    InvalidFilter(String, String),
+
+    /// Не удалось создать файл логов
+    #[error("Не удалось открыть файл логов в '{0}': {1}")]
+    File(String, String),
+
+    /// Некорректная секция `[telemetry]` или ошибка экспортёра
+    #[error(transparent)]
+    Telemetry(#[from] TelemetryError),
+
+    /// Глобальный subscriber уже установлен
+    #[error("Логирование уже инициализировано")]
+    AlreadyInitialized,
+}
+
+/// Формат вывода логов.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Одна строка на событие, без имени модуля
+    Compact,
+    /// Многострочный человекочитаемый вывод
+    Pretty,
+    /// Одна JSON-строка на событие - для сборщиков логов
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = LoggingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "compact" => Ok(LogFormat::Compact),
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(LoggingError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Держит фоновый поток записи в файл и экспорт трассировок.
+///
+/// # Для студентов: Guard и `Drop`
+///
+/// Запись в файл идёт в отдельном потоке, чтобы не тормозить обработку
+/// запросов. Когда guard уничтожается, он дописывает буфер на диск.
+/// Поэтому guard нужно хранить до конца работы программы - мы передаём
+/// его в Rocket через `.manage()`.
+pub struct LoggingGuard {
+    _file: Option<WorkerGuard>,
+    telemetry: TelemetryGuard,
+}
+
+impl LoggingGuard {
+    /// Сбрасывает трассировки, не дожидаясь уничтожения guard'а.
+    ///
+    /// Файл логов пишется фоновым потоком непрерывно, а его буфер
+    /// дописывается при `drop` после остановки Rocket.
+    pub fn flush(&self) {
+        self.telemetry.flush();
+    }
+}
+
+// ============================================================================
+// ФИЛЬТР
+// ============================================================================
+
+/// Собирает директивы фильтра из конфигурации: `"info,rocket=warn,..."`.
+pub fn filter_directives(config: &LoggingConfig) -> Result<String, LoggingError> {
+    let mut directives = vec![parse_level(&config.level)?.to_string()];
+
+    // Сортируем, чтобы результат не зависел от порядка в HashMap
+    let mut targets: Vec<_> = config.targets.iter().collect();
+    targets.sort();
+    for (target, level) in targets {
+        directives.push(format!("{}={}", target, parse_level(level)?));
+    }
+
+    Ok(directives.join(",").to_lowercase())
+}
+
+/// Строит фильтр: `RUST_LOG` (если задан и не пуст) заменяет настройки конфига.
+pub fn build_filter(
+    config: &LoggingConfig,
+    rust_log: Option<&str>,
+) -> Result<EnvFilter, LoggingError> {
+    let directives = match rust_log.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => value.to_string(),
+        None => filter_directives(config)?,
+    };
+    EnvFilter::try_new(&directives)
+        .map_err(|e| LoggingError::InvalidFilter(directives.clone(), e.to_string()))
+}
+
+/// Разбирает уровень логов: "trace", "debug", "info", "warn", "error" или "off".
+pub fn parse_level(level: &str) -> Result<LevelFilter, LoggingError> {
+    LevelFilter::from_str(level).map_err(|_| LoggingError::UnknownLevel(level.to_string()))
+}
+
+// ============================================================================
+// СЛОИ
+// ============================================================================
+
+/// Слой форматирования в нужном формате, упакованный в `Box`.
+fn fmt_layer<S, W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        LogFormat::Compact => layer
+            .with_target(false) // Не показывать имя модуля
+            .with_level(true) // Показывать уровень (INFO, ERROR...)
+            .compact()
+            .boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    }
+}
+
+/// Разбирает период ротации: "minutely", "hourly", "daily" или "never".
+pub fn parse_rotation(rotation: &str) -> Result<Rotation, LoggingError> {
+    match rotation.to_ascii_lowercase().as_str() {
+        "minutely" => Ok(Rotation::MINUTELY),
+        "hourly" => Ok(Rotation::HOURLY),
+        "daily" => Ok(Rotation::DAILY),
+        "never" => Ok(Rotation::NEVER),
+        _ => Err(LoggingError::UnknownRotation(rotation.to_string())),
+    }
+}
+
+/// Создаёт файловый appender с ротацией по секции `[logging.file]`.
+fn rolling_appender(config: &LogFileConfig) -> Result<RollingFileAppender, LoggingError> {
+    let rotation = parse_rotation(&config.rotation)?;
+
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&config.prefix);
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+
+    builder
+        .build(&config.directory)
+        .map_err(|e| LoggingError::File(config.directory.clone(), e.to_string()))
+}
+
+// ============================================================================
+// ИНИЦИАЛИЗАЦИЯ
+// ============================================================================
+
+/// Инициализирует глобальную систему логирования.
+///
+/// Возвращает guard, который нужно хранить до завершения программы.
+pub fn init(
+    config: &LoggingConfig,
+    telemetry_config: &TelemetryConfig,
+) -> Result<LoggingGuard, LoggingError> {
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let filter = build_filter(config, rust_log.as_deref())?;
+
+    let console_format: LogFormat = config.format.parse()?;
+    let mut layers = vec![fmt_layer(console_format, std::io::stdout, true)];
+
+    let mut file_guard = None;
+    if let Some(file) = &config.file {
+        let format: LogFormat = file.format.as_deref().unwrap_or(&config.format).parse()?;
+        let (writer, guard) = tracing_appender::non_blocking(rolling_appender(file)?);
+        // В файле цветовые escape-коды не нужны
+        layers.push(fmt_layer(format, writer, false));
+        file_guard = Some(guard);
+    }
+
+    let (otel_layer, telemetry_guard) = telemetry::layer(telemetry_config)?;
+    layers.extend(otel_layer);
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(layers)
+        .try_init()
+        .map_err(|_| LoggingError::AlreadyInitialized)?;
+
+    Ok(LoggingGuard {
+        _file: file_guard,
+        telemetry: telemetry_guard,
+    })
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(level: &str, targets: &[(&str, &str)]) -> LoggingConfig {
+        LoggingConfig {
+            level: level.to_string(),
+            format: "compact".to_string(),
+            targets: targets
+                .iter()
+                .map(|(t, l)| (t.to_string(), l.to_string()))
+                .collect::<HashMap<_, _>>(),
+            file: None,
+        }
+    }
+
+    #[test]
+    fn test_format_parsing() {
+        assert_eq!("pretty".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!(matches!(
+            "xml".parse::<LogFormat>(),
+            Err(LoggingError::UnknownFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_filter_directives_with_targets() {
+        let config = config("debug", &[("rocket", "warn"), ("hyper", "off")]);
+        assert_eq!(
+            filter_directives(&config).unwrap(),
+            "debug,hyper=off,rocket=warn"
+        );
+    }
+
+    #[test]
+    fn test_unknown_level_is_rejected() {
+        assert!(matches!(
+            filter_directives(&config("verbose", &[])),
+            Err(LoggingError::UnknownLevel(_))
+        ));
+        assert!(matches!(
+            filter_directives(&config("info", &[("rocket", "loud")])),
+            Err(LoggingError::UnknownLevel(_))
+        ));
+    }
+
+    #[test]
+    fn test_rust_log_overrides_config() {
+        let config = config("info", &[]);
+        let filter = build_filter(&config, Some("warn,rust_gigachat_demo=trace"))
+            .unwrap()
+            .to_string();
+        assert!(filter.contains("rust_gigachat_demo=trace"));
+        assert!(filter.contains("warn"));
+        assert!(!filter.contains("info"));
+
+        // Пустой RUST_LOG не считается
+        let filter = build_filter(&config, Some("  ")).unwrap();
+        assert_eq!(filter.to_string(), "info");
+    }
+}
@@ -13,9 +13,28 @@
 //! ```text
 //! main.rs (этот файл)
 //!    │
-//!    ├── config/    - Загрузка настроек из config.toml
+//!    ├── auth/      - Аутентификация по API-ключам
+//!    ├── build_info/ - Хеш коммита, фичи сборки, время работы
+//!    ├── concurrency/ - Очередь и лимит параллельных запросов к AI
+//!    ├── config/    - Загрузка и проверка настроек config.toml
+//!    ├── cors/      - Политика CORS для web-интерфейса
+//!    ├── experiment/ - A/B эксперименты с системными промптами
+//!    ├── export/    - Экспорт истории в Markdown, JSON Lines, HTML
+//!    ├── guardrails/ - Защита системного промпта от prompt injection
+//!    ├── logging/   - Настройка логов (форматы, файл, RUST_LOG)
+//!    ├── metrics/   - Метрики Prometheus (/metrics)
 //!    ├── models/    - Структуры данных (Request/Response)
+//!    ├── moderation/ - Стоп-листы и маскирование персональных данных
+//!    ├── rate_limit/ - Ограничение частоты запросов
+//!    ├── readiness/ - Проверки liveness/readiness
+//!    ├── reload/    - Перезагрузка config.toml без перезапуска
+//!    ├── request_id/ - X-Request-Id и span'ы запросов для логов
+//!    ├── secrets/   - Токены и ключи: env, *_FILE, зашифрованный файл
 //!    ├── services/  - Бизнес-логика (AI сервисы)
+//!    ├── shutdown/  - Плавная остановка по SIGTERM
+//!    ├── storage/   - Постоянное хранилище SQLite: диалоги, история, учёт, ключи
+//!    ├── telemetry/ - Экспорт трассировок OpenTelemetry (фича otel)
+//!    ├── templates/ - Шаблоны промптов с переменными (prompts/*.prompt)
 //!    └── handlers/  - HTTP обработчики (эндпоинты API)
 //! ```
 //!
@@ -28,8 +47,8 @@
 //! #[launch] fn rocket()     ← Rocket вызывает эту функцию
 //!     │
 //!     ├─► Загрузка конфигурации (AppConfig::load)
-//!     ├─► Инициализация логов (init_logging)
-//!     ├─► Создание AI сервиса (AiServiceFactory::create)
+//!     ├─► Инициализация логов (logging::init)
+//!     ├─► Создание AI сервиса (AiStack::build)
 //!     └─► Запуск сервера (rocket::custom(...).launch())
 //! ```
 //!
@@ -42,6 +61,19 @@
 //! # С GigaChat API
 //! export GIGACHAT_TOKEN="your_token_here"
 //! cargo run
+//!
+//! # Только проверить config.toml (код выхода 1 при ошибках)
+//! cargo run -- --check-config
+//!
+//! # Зашифровать секреты для secrets.file (пароль в SECRETS_PASSPHRASE)
+//! cargo run -- --seal-secrets secrets.env secrets.enc
+//!
+//! # Выпустить API-ключ в хранилище / отозвать его
+//! cargo run -- --add-api-key lab-group-1 ask,health
+//! cargo run -- --revoke-api-key lab-group-1
+//!
+//! # Выгрузить диалог для отчёта (markdown, jsonl или html)
+//! cargo run -- --export markdown lab-group-1 --conversation lab-3 --output lab-3.md
 //! ```
 
 // ============================================================================
@@ -59,127 +91,58 @@ extern crate rocket;
 
 // Объявление модулей проекта.
 // `mod X;` говорит компилятору: "загрузи файл src/X/mod.rs (или src/X.rs)"
+mod auth;
+mod build_info;
+mod concurrency;
 mod config;
+mod cors;
+mod experiment;
+mod export;
+mod guardrails;
 mod handlers;
+mod logging;
+mod metrics;
 mod models;
+mod moderation;
+mod rate_limit;
+mod readiness;
+mod reload;
+mod request_id;
+mod secrets;
 mod services;
+mod shutdown;
+mod storage;
+mod telemetry;
+mod templates;
 
 // Импорт конкретных элементов из модулей для удобства использования
-use config::AppConfig;
-use handlers::{ask, cors_preflight, health, index, internal_error, not_found, unprocessable_entity};
-use services::AiServiceFactory;
-use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::Header;
-use rocket::{Request, Response};
+use auth::{ApiKeyStore, AuthError, JwtVerifier};
+use concurrency::ConcurrencyLimiter;
+use config::{AppConfig, SecretsConfig};
+use cors::{Cors, CorsPolicy};
+use handlers::{
+    admin_config, admin_feedback, admin_reload, admin_usage, ask, cors_preflight, delete_history,
+    delete_history_entry, export_history, feedback, forbidden, health, history, index,
+    internal_error, list_templates, live, not_found, prometheus_metrics, ready,
+    too_many_requests, unauthorized, unprocessable_entity,
+};
+use metrics::{HttpMetrics, Metrics};
+use rate_limit::{RateLimitHeaders, RateLimiter};
+use reload::{AiStack, LiveRuntime, Reloader};
+use request_id::RequestTracing;
+use secrets::Secrets;
+use templates::TemplateLibrary;
+use shutdown::ShutdownState;
+use storage::{StorageError, StoredApiKey};
+use rocket::fairing::AdHoc;
 
 // tracing - современная библиотека логирования для Rust
 // Преимущества над println!:
 // - Уровни логирования (error, warn, info, debug, trace)
 // - Структурированные логи
 // - Фильтрация по уровням и модулям
-use tracing::{error, info};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-
-// ============================================================================
-// ИНИЦИАЛИЗАЦИЯ ЛОГИРОВАНИЯ
-// ============================================================================
-
-/// CORS‑fairing: добавляет заголовки для доступа из web‑UI.
-struct Cors;
-
-#[rocket::async_trait]
-impl Fairing for Cors {
-    fn info(&self) -> Info {
-        Info {
-            name: "Add CORS headers",
-            kind: Kind::Response,
-        }
-    }
-
-    async fn on_response<'r>(&self, _req: &'r Request<'_>, res: &mut Response<'r>) {
-        res.set_header(Header::new(
-            "Access-Control-Allow-Origin",
-            "http://127.0.0.1:8080",
-        ));
-        res.set_header(Header::new(
-            "Access-Control-Allow-Methods",
-            "GET, POST, OPTIONS",
-        ));
-        res.set_header(Header::new(
-            "Access-Control-Allow-Headers",
-            "Content-Type",
-        ));
-    }
-}
-
-/// Инициализирует систему логирования.
-///
-/// # Для студентов: Зачем нужно логирование?
-///
-/// Логирование - это способ отслеживать, что происходит в программе:
-/// - При разработке: отладка без debugger'а
-/// - В продакшене: диагностика проблем
-///
-/// ## Уровни логирования (от детального к критическому)
-///
-/// ```text
-/// TRACE  →  Очень детальная информация (каждый шаг)
-/// DEBUG  →  Отладочная информация
-/// INFO   →  Важные события (запуск, завершение)
-/// WARN   →  Предупреждения (что-то подозрительное)
-/// ERROR  →  Ошибки (что-то сломалось)
-/// ```
-///
-/// ## Пример использования
-///
-/// ```rust,ignore
-/// info!("Сервер запущен на порту {}", port);
-/// error!("Не удалось подключиться к БД: {}", err);
-/// ```
-fn init_logging(config: &AppConfig) {
-    // Преобразуем строку из конфига в enum уровня логирования.
-    // match - это паттерн-матчинг, аналог switch в других языках,
-    // но более мощный и безопасный.
-    let log_level = match config.logging.level.as_str() {
-        "trace" => tracing::Level::TRACE,
-        "debug" => tracing::Level::DEBUG,
-        "info" => tracing::Level::INFO,
-        "warn" => tracing::Level::WARN,
-        "error" => tracing::Level::ERROR,
-        _ => tracing::Level::INFO, // _ - "любое другое значение" (default)
-    };
-
-    // Настраиваем tracing_subscriber - это "получатель" логов.
-    // Registry + layers - паттерн "Строитель" (Builder).
-    let log_format = config.logging.format.as_str();
-    if log_format != "compact" {
-        eprintln!("⚠️  Формат логов '{log_format}' не поддержан, используем compact");
-    }
-
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_target(false)  // Не показывать имя модуля
-        .with_level(true)    // Показывать уровень (INFO, ERROR...)
-        .compact();          // Компактный формат вывода
-
-    tracing_subscriber::registry()
-        .with(
-            // fmt::layer() - форматирует логи для вывода в консоль
-            fmt_layer,
-        )
-        .with(
-            // Фильтр - какие логи показывать
-            tracing_subscriber::filter::Targets::new()
-                .with_default(log_level)
-                .with_target("rocket", tracing::Level::INFO), // Rocket логирует только INFO+
-        )
-        .init(); // Активируем subscriber глобально
-
-    info!(
-        "Логирование инициализировано (уровень: {}, формат: {})",
-        config.logging.level,
-        config.logging.format
-    );
-}
+use std::sync::Arc;
+use tracing::{error, info, warn};
 
 // ============================================================================
 // ТОЧКА ВХОДА
@@ -211,6 +174,18 @@ fn init_logging(config: &AppConfig) {
 /// ```
 #[launch]
 fn rocket() -> _ {
+    // `--check-config [путь]`: только проверить конфигурацию и выйти
+    check_config_mode();
+    // `--seal-secrets <вход> <выход>`: зашифровать файл секретов и выйти
+    seal_secrets_mode();
+    // `--add-api-key` / `--revoke-api-key`: управление ключами в хранилище
+    api_key_mode();
+    // `--export <формат> <клиент> ...`: выгрузить историю и выйти
+    export_mode();
+
+    // Отсчёт времени работы для /health
+    build_info::mark_started();
+
     // =========================================================================
     // ШАГ 1: Загрузка конфигурации
     // =========================================================================
@@ -235,12 +210,46 @@ fn rocket() -> _ {
     // =========================================================================
     // ШАГ 2: Инициализация логирования
     // =========================================================================
-    init_logging(&config);
+    let logging_guard = match logging::init(&config.logging, &config.telemetry) {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("❌ Ошибка настройки логирования: {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!(
+        "Логирование инициализировано (уровень: {}, формат: {})",
+        config.logging.level,
+        config.logging.format
+    );
+    if let Some(file) = &config.logging.file {
+        info!("📄 Логи также пишутся в {}/{}* ({})", file.directory, file.prefix, file.rotation);
+    }
+    if config.telemetry.enabled {
+        if telemetry::SUPPORTED {
+            info!(
+                "🔭 Трассировки OpenTelemetry: {} (сервис {})",
+                config.telemetry.exporter,
+                config.telemetry.service_name
+            );
+        } else {
+            warn!("⚠️  telemetry.enabled = true, но сборка без фичи otel - трассировки не экспортируются");
+        }
+    }
 
     // Выводим информацию о приложении через систему логирования
     info!("🚀 Запуск {}", config.application.name);
     info!("📦 Версия: {}", config.application.version);
-    info!("🌍 Окружение: {}", config.server.environment);
+    info!(
+        "🔨 Сборка: {} (фичи: {:?})",
+        build_info::GIT_HASH,
+        build_info::enabled_features()
+    );
+    info!(
+        "🌍 Профиль: {} (файлы: {})",
+        config.origin.profile,
+        config.origin.files.join(", ")
+    );
     if config.is_development() {
         info!("🧪 Режим разработки включён");
     }
@@ -253,41 +262,172 @@ fn rocket() -> _ {
         "📝 System prompt length: {} chars",
         config.application.system_prompt.chars().count()
     );
+    if config.experiment.enabled {
+        let variants: Vec<String> = config
+            .experiment
+            .variants
+            .iter()
+            .map(|variant| format!("{} ({})", variant.id, variant.weight))
+            .collect();
+        info!("🔀 A/B эксперимент '{}': {}", config.experiment.name, variants.join(", "));
+    }
+    if config.guardrails.enabled {
+        info!(
+            "🛡️ Guardrails: вопрос - {}, ответ - {}",
+            config.guardrails.input_action, config.guardrails.output_action
+        );
+    }
+    if config.moderation.enabled {
+        info!(
+            "🧹 Модерация: стоп-лист - {} ({} слов, {} выражений), персональные данные - {}",
+            config.moderation.blocklist_action,
+            config.moderation.keywords.len(),
+            config.moderation.patterns.len(),
+            config.moderation.pii_action
+        );
+    }
 
     // =========================================================================
     // ШАГ 3: Создание AI сервиса
     // =========================================================================
     //
-    // Для студентов: Обратите внимание на тип переменной:
+    // Для студентов: Обратите внимание на тип поля `ai_service`:
     //   Box<dyn services::AiService>
     // Это trait object - мы не знаем конкретный тип (GigaChat или Mock),
     // но знаем, что он реализует трейт AiService.
-    let system_prompt = if config.application.system_prompt.trim().is_empty() {
+    //
+    // Сервис создаётся через AiStack: тем же способом его пересоздаёт
+    // перезагрузка конфигурации (модуль reload).
+    let metrics_registry = if config.metrics.enabled {
+        info!("📈 Метрики Prometheus: {}", config.metrics.path);
+        Some(Arc::new(Metrics::new()))
+    } else {
         None
+    };
+
+    // Декоратор: тот же трейт AiService, но не больше max_concurrent
+    // одновременных запросов. Счётчики очереди передаём в /health.
+    let limiter = if config.concurrency.enabled {
+        info!(
+            "🚥 Лимит параллельных запросов к AI: {} (очередь до {}, таймаут {}s)",
+            config.concurrency.max_concurrent,
+            config.concurrency.max_queue,
+            config.concurrency.queue_timeout_seconds
+        );
+        Some(ConcurrencyLimiter::new(&config.concurrency))
     } else {
-        Some(config.application.system_prompt.clone())
+        None
     };
+    let concurrency_stats = limiter.as_ref().map(ConcurrencyLimiter::stats);
 
-    let ai_service: Box<dyn services::AiService> = if config.is_gigachat_enabled() {
-        // Вложенный match - проверяем наличие токена
-        match config.get_gigachat_token() {
-            Ok(token) => {
-                info!("✅ Токен GigaChat найден, используем реальный API");
-                AiServiceFactory::create(&config.gigachat, Some(token), system_prompt)
-            }
-            Err(_) => {
-                // Токен не найден, но это НЕ фатальная ошибка - используем mock
-                error!("⚠️  Токен GigaChat не найден в переменной окружения GIGACHAT_TOKEN");
-                info!("💡 Переключаемся на mock mode");
-                AiServiceFactory::create(&config.gigachat, None, None)
-            }
+    let ai_stack = AiStack::new(metrics_registry.clone(), limiter);
+    let runtime = ai_stack.build(config.clone());
+    let backend_info = &runtime.backend;
+    info!(
+        "🤖 AI сервис: {} (модель: {}, системный промпт: {})",
+        backend_info.name,
+        backend_info.model.as_deref().unwrap_or("-"),
+        if backend_info.system_prompt_applied { "да" } else { "нет" }
+    );
+    if let Some(reason) = &backend_info.fallback_reason {
+        warn!("⚠️  Работаем в режиме degraded: {}", reason);
+    }
+    let live_runtime = LiveRuntime::new(runtime);
+    let reloader = Reloader::new(live_runtime.clone(), ai_stack);
+
+    // =========================================================================
+    // ШАГ 3.1: Постоянное хранилище
+    // =========================================================================
+    //
+    // Открываем базу и применяем миграции до старта сервера: если схема
+    // не обновилась, работать с ней нельзя.
+    let storage = match storage::open(&config.storage) {
+        Ok(storage) => storage,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    info!("🗄️  Хранилище: {}", storage.describe());
+    if config.storage.backend == "memory" {
+        warn!("⚠️  storage.backend = \"memory\": история и учёт пропадут при перезапуске");
+    }
+
+    // =========================================================================
+    // ШАГ 3.2: Библиотека шаблонов промптов
+    // =========================================================================
+    //
+    // Все шаблоны разбираются при запуске: ошибка в файле - повод не
+    // стартовать, а не сюрприз на середине занятия.
+    let template_library = match TemplateLibrary::load(&config.templates) {
+        Ok(library) => library,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(directory) = &config.templates.directory {
+        if template_library.is_empty() {
+            warn!("⚠️  В каталоге шаблонов {} нет файлов *.prompt", directory);
+        } else {
+            info!("📚 Шаблоны промптов из {}: {}", directory, template_library.names().join(", "));
+        }
+    }
+
+    // =========================================================================
+    // ШАГ 3.3: Хранилище API-ключей
+    // =========================================================================
+    //
+    // Ошибка в файле ключей - фатальная: лучше не запуститься, чем случайно
+    // открыть доступ к API всем желающим.
+    let key_store = ApiKeyStore::from_config(&config.auth).and_then(|store| {
+        let stored = storage.api_keys().map_err(|e| AuthError::KeyStore(e.to_string()))?;
+        store.with_stored(stored)
+    });
+    let key_store = match key_store {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
         }
-    } else {
-        info!("ℹ️  GigaChat API отключён в конфигурации, используем mock mode");
-        AiServiceFactory::create(&config.gigachat, None, None)
     };
 
-    info!("🤖 AI сервис: {}", ai_service.name());
+    if config.is_auth_enabled() {
+        info!("🔑 Аутентификация включена, API-ключей: {}", key_store.len());
+        if key_store.is_empty() {
+            error!("⚠️  Аутентификация включена, но ни одного ключа не задано - /ask недоступен");
+        }
+        if config.auth.jwt.enabled {
+            info!("🎫 Проверка JWT включена (RS256/ES256 по JWKS)");
+        }
+    } else {
+        info!("🔓 Аутентификация выключена (auth.enabled = false)");
+    }
+
+    if config.rate_limit.enabled {
+        info!("🚦 Ограничение частоты запросов включено");
+    }
+    let rate_limiter = RateLimiter::new(config.rate_limit.clone());
+
+    info!("🌐 CORS: разрешённые origin {:?}", config.cors.allowed_origins);
+    let cors_policy = CorsPolicy::new(config.cors.clone());
+
+    // JWKS загружается асинхронно (возможно, по сети), поэтому используем
+    // fairing на этапе ignite: он выполняется внутри async runtime Rocket.
+    // Если ключи не загрузились - сервер не стартует.
+    let jwt_config = config.auth.jwt.clone();
+    let jwt_fairing = AdHoc::try_on_ignite("JWKS loader", |rocket| async move {
+        if !jwt_config.enabled {
+            return Ok(rocket);
+        }
+        match JwtVerifier::load(&jwt_config).await {
+            Ok(verifier) => Ok(rocket.manage(verifier)),
+            Err(e) => {
+                error!("❌ {}", e);
+                Err(rocket)
+            }
+        }
+    });
 
     // =========================================================================
     // ШАГ 4: Настройка Rocket
@@ -299,9 +439,17 @@ fn rocket() -> _ {
     let figment = rocket::Config::figment()
         .merge(("address", config.server.address.clone()))
         .merge(("port", config.server.port))
-        .merge(("cli_colors", false));
+        .merge(("cli_colors", false))
+        // Плавная остановка: Rocket ловит SIGTERM/Ctrl+C и ждёт запросы
+        .merge(("shutdown.grace", config.shutdown.grace_seconds))
+        .merge(("shutdown.mercy", config.shutdown.mercy_seconds));
 
     info!("🌐 Сервер будет запущен на {}:{}", config.server.address, config.server.port);
+    info!(
+        "🛑 Плавная остановка: ждём запросы до {}s, соединения ещё {}s",
+        config.shutdown.grace_seconds,
+        config.shutdown.mercy_seconds
+    );
 
     // =========================================================================
     // ШАГ 5: Сборка и возврат экземпляра Rocket
@@ -315,10 +463,34 @@ fn rocket() -> _ {
     // .manage(T)    - сохраняет T в State, доступен во всех обработчиках
     // .mount("/", routes![...])   - регистрирует обработчики по пути "/"
     // .register("/", catchers![...]) - регистрирует обработчики ошибок
-    rocket::custom(figment)
+    let mut rocket = rocket::custom(figment);
+    if let Some(stats) = concurrency_stats {
+        rocket = rocket.manage(stats); // State<Arc<ConcurrencyStats>> - для /health
+    }
+    if let Some(registry) = metrics_registry {
+        rocket = rocket
+            .attach(HttpMetrics)
+            .manage(registry) // State<Arc<Metrics>> - для fairing'а и /metrics
+            .mount(config.metrics.path.as_str(), routes![prometheus_metrics]);
+    }
+
+    rocket
+        .attach(RequestTracing) // первым: ID и время запроса - с самого начала
         .attach(Cors)
-        .manage(config)      // State<AppConfig> - доступен через &State<AppConfig>
-        .manage(ai_service)  // State<Box<dyn AiService>> - AI сервис
+        .attach(jwt_fairing)
+        .attach(RateLimitHeaders)
+        .attach(shutdown::fairing())
+        .attach(reload::fairing())
+        .manage(config)      // State<AppConfig> - настройки на момент запуска
+        .manage(live_runtime) // State<LiveRuntime> - текущие настройки и AI сервис
+        .manage(reloader)    // State<Reloader> - для POST /admin/reload
+        .manage(key_store)   // State<ApiKeyStore> - ключи для guard'а Authenticated
+        .manage(storage)     // State<SharedStorage> - история, учёт, диалоги
+        .manage(template_library) // State<TemplateLibrary> - шаблоны для /ask и /templates
+        .manage(rate_limiter) // State<RateLimiter> - вёдра для guard'а RateLimited
+        .manage(cors_policy) // State<CorsPolicy> - правила для fairing'а Cors и preflight
+        .manage(ShutdownState::new()) // State<ShutdownState> - запросы в обработке
+        .manage(logging_guard) // держим поток записи логов в файл до конца работы
         // ─────────────────────────────────────────────────────────────────
         // Для студентов: Макросы routes! и catchers!
         // ─────────────────────────────────────────────────────────────────
@@ -338,8 +510,273 @@ fn rocket() -> _ {
         //
         // catchers! работает аналогично для функций с #[catch(код)]
         // ─────────────────────────────────────────────────────────────────
-        .mount("/", routes![index, health, ask, cors_preflight])
-        .register("/", catchers![not_found, internal_error, unprocessable_entity])
+        .mount("/", routes![
+                index,
+                health,
+                live,
+                ready,
+                ask,
+                history,
+                delete_history,
+                delete_history_entry,
+                export_history,
+                feedback,
+                list_templates,
+                admin_reload,
+                admin_config,
+                admin_usage,
+                admin_feedback,
+                cors_preflight
+            ],)
+        .register(
+            "/",
+            catchers![
+                not_found,
+                unauthorized,
+                forbidden,
+                too_many_requests,
+                internal_error,
+                unprocessable_entity
+            ],
+        )
+}
+
+/// Режим `--check-config [путь]`: проверяет конфигурацию и завершает процесс.
+///
+/// Печатает отчёт со всеми найденными проблемами и выходит с кодом 1,
+/// если конфигурация некорректна. Удобно запускать в CI перед деплоем:
+///
+/// ```bash
+/// cargo run -- --check-config config.prod.toml
+/// ```
+fn check_config_mode() {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("--check-config") {
+        return;
+    }
+    let path = args.next().unwrap_or_else(AppConfig::path);
+
+    // Шаблоны проверяем тоже: ошибка в них не даст серверу запуститься
+    let result = AppConfig::load_from(&path).map_err(|e| e.to_string()).and_then(|config| {
+        TemplateLibrary::load(&config.templates).map_err(|e| e.to_string())
+    });
+    match result {
+        Ok(_) => {
+            println!("✅ Конфигурация {} корректна", path);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("❌ {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Режим `--seal-secrets <вход> <выход>`: шифрует файл секретов и завершает процесс.
+///
+/// Вход - файл в формате `.env` (`GIGACHAT_TOKEN=...`), выход - файл для
+/// `secrets.file`. Пароль берётся из `SECRETS_PASSPHRASE` (или `_FILE`).
+///
+/// ```bash
+/// SECRETS_PASSPHRASE="..." cargo run -- --seal-secrets secrets.env secrets.enc
+/// ```
+fn seal_secrets_mode() {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() != Some("--seal-secrets") {
+        return;
+    }
+    let (Some(input), Some(output)) = (args.next(), args.next()) else {
+        eprintln!("❌ Использование: --seal-secrets <secrets.env> <secrets.enc>");
+        std::process::exit(2);
+    };
+
+    let result = Secrets::from_config(&SecretsConfig::default())
+        .passphrase()
+        .map_err(|e| e.to_string())
+        .and_then(|passphrase| {
+            let entries = std::fs::read_to_string(&input)
+                .map_err(|e| e.to_string())
+                .and_then(|text| secrets::parse_plain(&text))
+                .map_err(|e| format!("{}: {}", input, e))?;
+            let sealed = secrets::seal(&entries, passphrase.expose())?;
+            std::fs::write(&output, sealed).map_err(|e| format!("{}: {}", output, e))?;
+            Ok(entries.len())
+        });
+
+    match result {
+        Ok(count) => {
+            println!("✅ {} секретов зашифровано в {}", count, output);
+            println!("💡 Укажите secrets.file = \"{}\" и удалите {}", output, input);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Режимы `--add-api-key <имя> <scope,...>` и `--revoke-api-key <имя>`.
+///
+/// Ключ генерируется случайно и печатается ОДИН раз: в хранилище
+/// попадает только его SHA-256. Сервер подхватывает ключи при запуске.
+///
+/// ```bash
+/// cargo run -- --add-api-key lab-group-1 ask,health
+/// cargo run -- --revoke-api-key lab-group-1
+/// ```
+fn api_key_mode() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.first().map(String::as_str) {
+        Some(command @ ("--add-api-key" | "--revoke-api-key")) => command,
+        _ => return,
+    };
+    let Some(name) = args.get(1) else {
+        eprintln!("❌ Использование: --add-api-key <имя> <scope,...> | --revoke-api-key <имя>");
+        std::process::exit(2);
+    };
+
+    let config = AppConfig::load().unwrap_or_else(|e| {
+        eprintln!("❌ Ошибка загрузки конфигурации: {}", e);
+        std::process::exit(1);
+    });
+    let result = storage::open(&config.storage).and_then(|storage| {
+        if command == "--revoke-api-key" {
+            return storage.delete_api_key(name).map(|deleted| {
+                if deleted {
+                    format!("Ключ '{}' отозван (вступит в силу после перезапуска)", name)
+                } else {
+                    format!("Ключа '{}' нет в хранилище", name)
+                }
+            });
+        }
+
+        let scopes: Vec<String> = args
+            .get(2)
+            .map(|scopes| scopes.split(',').map(|s| s.trim().to_string()).collect())
+            .unwrap_or_else(|| vec!["ask".to_string()]);
+        if let Some(unknown) = scopes
+            .iter()
+            .find(|scope| !config::validation::KNOWN_SCOPES.contains(&scope.as_str()))
+        {
+            eprintln!(
+                "❌ Неизвестный scope '{}' ({})",
+                unknown,
+                config::validation::KNOWN_SCOPES.join(", ")
+            );
+            std::process::exit(2);
+        }
+        if config.auth.keys.iter().any(|key| &key.name == name) {
+            return Err(StorageError::Duplicate(name.clone()));
+        }
+
+        let key = auth::generate_key();
+        storage.save_api_key(&StoredApiKey {
+            name: name.clone(),
+            key_hash: auth::hash_key(&key),
+            scopes: scopes.clone(),
+            created_at: storage::now_millis(),
+        })?;
+        Ok(format!(
+            "Ключ '{}' (scopes: {}) сохранён в {}:\n{}\n💡 Сохраните его сейчас - повторно он не показывается",
+            name,
+            scopes.join(", "),
+            storage.describe(),
+            key
+        ))
+    });
+
+    match result {
+        Ok(message) => {
+            println!("✅ {}", message);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Режим `--export <формат> <клиент> [--conversation <id>] [--from <дата>] [--to <дата>] [--output <файл>]`.
+///
+/// Выгружает историю клиента (имя API-ключа, `sub` из JWT или
+/// `anonymous`) так же, как `GET /export`, но без запущенного сервера.
+/// Без `--output` документ печатается в stdout.
+///
+/// ```bash
+/// cargo run -- --export html lab-group-1 --from 2026-10-01 --output report.html
+/// ```
+fn export_mode() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("--export") {
+        return;
+    }
+    let usage = || -> ! {
+        eprintln!(
+            "❌ Использование: --export <{}> <клиент> [--conversation <id>] [--from <дата>] [--to <дата>] [--output <файл>]",
+            export::ExportFormat::NAMES.join("|")
+        );
+        std::process::exit(2);
+    };
+    let (Some(format), Some(subject)) = (args.get(1), args.get(2)) else { usage() };
+    let Some(format) = export::ExportFormat::parse(format) else { usage() };
+
+    let mut filter = storage::HistoryFilter {
+        subject: subject.clone(),
+        ..storage::HistoryFilter::default()
+    };
+    let mut output = None;
+    for option in args[3..].chunks(2) {
+        let [name, value] = option else { usage() };
+        let time = |end| {
+            handlers::parse_time(value, end).unwrap_or_else(|| {
+                eprintln!("❌ Некорректная дата '{}': нужна YYYY-MM-DD или миллисекунды Unix", value);
+                std::process::exit(2);
+            })
+        };
+        match name.as_str() {
+            "--conversation" => filter.conversation_id = Some(value.clone()),
+            "--from" => filter.from = Some(time(false)),
+            "--to" => filter.until = Some(time(true)),
+            "--output" => output = Some(value.clone()),
+            _ => usage(),
+        }
+    }
+
+    let config = AppConfig::load().unwrap_or_else(|e| {
+        eprintln!("❌ Ошибка загрузки конфигурации: {}", e);
+        std::process::exit(1);
+    });
+    let result = storage::open(&config.storage)
+        .map_err(|e| e.to_string())
+        .and_then(|storage| {
+            export::ExportDocument::load(storage.as_ref(), &filter).map_err(|e| e.to_string())
+        })
+        .and_then(|document| {
+            let text = document.render(format);
+            match &output {
+                Some(path) => std::fs::write(path, text)
+                    .map(|()| format!("{} записей сохранено в {}", document.exchanges.len(), path))
+                    .map_err(|e| format!("{}: {}", path, e)),
+                None => {
+                    print!("{}", text);
+                    Ok(format!("{} записей выгружено", document.exchanges.len()))
+                }
+            }
+        });
+
+    match result {
+        Ok(message) => {
+            // stdout занят документом - сообщение идёт в stderr
+            eprintln!("✅ {}", message);
+            std::process::exit(0);
+        }
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,482 @@
+//! Модуль метрик в формате Prometheus.
+//!
+//! Prometheus периодически опрашивает эндпоинт `/metrics` и сохраняет
+//! значения во временные ряды, по которым строятся графики и алерты.
+//!
+//! # Что собирается
+//!
+//! | Метрика                            | Тип       | Метки                      |
+//! |------------------------------------|-----------|----------------------------|
+//! | `http_requests_total`              | counter   | method, route, status      |
+//! | `http_request_duration_seconds`    | histogram | method, route, status      |
+//! | `http_requests_in_flight`          | gauge     | -                          |
+//! | `ai_requests_total`                | counter   | backend, outcome           |
+//! | `ai_request_duration_seconds`      | histogram | backend, outcome           |
+//! | `ai_errors_total`                  | counter   | backend, kind              |
+//! | `ai_tokens_total`                  | counter   | backend, type              |
+//! | `ai_queue_*`                       | gauge     | - (при `[concurrency]`)    |
+//!
+//! Токены оцениваются по длине текста (~4 символа на токен): `AiService`
+//! возвращает только текст ответа, без статистики использования.
+//! Кеша ответов в приложении пока нет, поэтому нет и метрики попаданий в кеш.
+//!
+//! # Для студентов: Формат Prometheus
+//!
+//! Текстовый формат очень простой - строка на значение:
+//!
+//! ```text
+//! # HELP http_requests_total Total number of HTTP requests.
+//! # TYPE http_requests_total counter
+//! http_requests_total{method="GET",route="/health",status="200"} 42
+//! ```
+//!
+//! Гистограмма - это набор счётчиков "сколько наблюдений <= le" плюс сумма
+//! и количество. По ним Prometheus считает перцентили задержки.
+//!
+//! # Для студентов: Кардинальность меток
+//!
+//! Каждое сочетание меток - отдельный временной ряд. Поэтому в метку
+//! `route` пишется ШАБЛОН маршрута (`/ask`), а не реальный путь, а все
+//! запросы без маршрута (404) попадают в `route="unmatched"`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response};
+
+use crate::models::ConcurrencyStatus;
+use crate::services::{AiService, AiServiceError, AskContext};
+
+/// Границы корзин гистограмм задержки (секунды).
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Примерное число символов на токен (для оценки расхода токенов).
+const CHARS_PER_TOKEN: usize = 4;
+
+// ============================================================================
+// ГИСТОГРАММА
+// ============================================================================
+
+/// Гистограмма с кумулятивными корзинами (как в Prometheus).
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+// ============================================================================
+// РЕЕСТР МЕТРИК
+// ============================================================================
+
+/// Метки HTTP-метрик: метод, шаблон маршрута, статус.
+type HttpKey = (String, String, u16);
+
+#[derive(Debug, Default)]
+struct Registry {
+    http: BTreeMap<HttpKey, Histogram>,
+    ai_calls: BTreeMap<(String, &'static str), Histogram>,
+    ai_errors: BTreeMap<(String, &'static str), u64>,
+    ai_tokens: BTreeMap<(String, &'static str), u64>,
+}
+
+/// Хранилище метрик приложения.
+///
+/// Передаётся в Rocket как `Arc<Metrics>`: им пользуются fairing
+/// [`HttpMetrics`], декоратор [`InstrumentedAiService`] и эндпоинт `/metrics`.
+/// `BTreeMap` вместо `HashMap` - чтобы вывод был отсортирован и стабилен.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+    http_in_flight: AtomicI64,
+}
+
+impl Metrics {
+    /// Создаёт пустое хранилище.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Учитывает завершённый HTTP-запрос.
+    pub fn record_http(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let key = (method.to_string(), route.to_string(), status);
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        registry.http.entry(key).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    /// Учитывает вызов AI сервиса `backend`.
+    pub fn record_ai_call(
+        &self,
+        backend: &str,
+        elapsed: Duration,
+        result: Result<(), &AiServiceError>,
+    ) {
+        let outcome = if result.is_ok() { "success" } else { "error" };
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        registry
+            .ai_calls
+            .entry((backend.to_string(), outcome))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+        if let Err(e) = result {
+            *registry
+                .ai_errors
+                .entry((backend.to_string(), e.kind()))
+                .or_default() += 1;
+        }
+    }
+
+    /// Учитывает токены вида `kind` ("prompt" или "completion").
+    pub fn record_tokens(&self, backend: &str, kind: &'static str, tokens: u64) {
+        let mut registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        *registry
+            .ai_tokens
+            .entry((backend.to_string(), kind))
+            .or_default() += tokens;
+    }
+
+    /// Формирует текст в формате Prometheus.
+    ///
+    /// `queue` - состояние очереди к AI, если включён модуль `concurrency`.
+    pub fn render(&self, queue: Option<&ConcurrencyStatus>) -> String {
+        let registry = self.registry.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+
+        write_header(&mut out, "http_requests_total", "Total number of HTTP requests.", "counter");
+        for ((method, route, status), histogram) in &registry.http {
+            let status = status.to_string();
+            let labels = [("method", method.as_str()), ("route", route), ("status", &status)];
+            write_sample(&mut out, "http_requests_total", &labels, histogram.count);
+        }
+
+        write_header(
+            &mut out,
+            "http_request_duration_seconds",
+            "HTTP request latency in seconds.",
+            "histogram",
+        );
+        for ((method, route, status), histogram) in &registry.http {
+            let status = status.to_string();
+            let labels = [("method", method.as_str()), ("route", route), ("status", &status)];
+            write_histogram(&mut out, "http_request_duration_seconds", &labels, histogram);
+        }
+
+        write_header(
+            &mut out,
+            "http_requests_in_flight",
+            "HTTP requests currently being processed.",
+            "gauge",
+        );
+        write_sample(
+            &mut out,
+            "http_requests_in_flight",
+            &[],
+            self.http_in_flight.load(Ordering::Relaxed),
+        );
+
+        write_header(&mut out, "ai_requests_total", "Total number of AI backend calls.", "counter");
+        for ((backend, outcome), histogram) in &registry.ai_calls {
+            let labels = [("backend", backend.as_str()), ("outcome", *outcome)];
+            write_sample(&mut out, "ai_requests_total", &labels, histogram.count);
+        }
+
+        write_header(
+            &mut out,
+            "ai_request_duration_seconds",
+            "AI backend call latency in seconds.",
+            "histogram",
+        );
+        for ((backend, outcome), histogram) in &registry.ai_calls {
+            let labels = [("backend", backend.as_str()), ("outcome", *outcome)];
+            write_histogram(&mut out, "ai_request_duration_seconds", &labels, histogram);
+        }
+
+        write_header(&mut out, "ai_errors_total", "AI backend errors by kind.", "counter");
+        for ((backend, kind), count) in &registry.ai_errors {
+            let labels = [("backend", backend.as_str()), ("kind", *kind)];
+            write_sample(&mut out, "ai_errors_total", &labels, count);
+        }
+
+        write_header(
+            &mut out,
+            "ai_tokens_total",
+            "Estimated tokens sent to and received from AI (about 4 characters per token).",
+            "counter",
+        );
+        for ((backend, kind), count) in &registry.ai_tokens {
+            let labels = [("backend", backend.as_str()), ("type", *kind)];
+            write_sample(&mut out, "ai_tokens_total", &labels, count);
+        }
+
+        if let Some(queue) = queue {
+            let gauges = [
+                ("ai_queue_in_flight", "AI calls currently executing.", queue.in_flight as u64),
+                ("ai_queue_depth", "AI calls waiting in the queue.", queue.queue_depth as u64),
+                ("ai_queue_max_wait_ms", "Longest queue wait in milliseconds.", queue.max_wait_ms),
+                ("ai_queue_avg_wait_ms", "Average queue wait in milliseconds.", queue.avg_wait_ms),
+            ];
+            for (name, help, value) in gauges {
+                write_header(&mut out, name, help, "gauge");
+                write_sample(&mut out, name, &[], value);
+            }
+            write_header(
+                &mut out,
+                "ai_queue_rejected_total",
+                "AI calls rejected because the queue was full or the wait timed out.",
+                "counter",
+            );
+            write_sample(
+                &mut out,
+                "ai_queue_rejected_total",
+                &[("reason", "queue_full")],
+                queue.rejected_total,
+            );
+            write_sample(
+                &mut out,
+                "ai_queue_rejected_total",
+                &[("reason", "timeout")],
+                queue.timed_out_total,
+            );
+        }
+
+        out
+    }
+}
+
+// ============================================================================
+// ФОРМАТИРОВАНИЕ
+// ============================================================================
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn write_sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    let _ = writeln!(out, "{name}{} {value}", format_labels(labels));
+}
+
+fn write_histogram(out: &mut String, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+    for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+        let le = bound.to_string();
+        let mut with_le = labels.to_vec();
+        with_le.push(("le", &le));
+        write_sample(out, &format!("{name}_bucket"), &with_le, count);
+    }
+    let mut with_inf = labels.to_vec();
+    with_inf.push(("le", "+Inf"));
+    write_sample(out, &format!("{name}_bucket"), &with_inf, histogram.count);
+    write_sample(out, &format!("{name}_sum"), labels, histogram.sum);
+    write_sample(out, &format!("{name}_count"), labels, histogram.count);
+}
+
+/// `{a="1",b="2"}`; значения экранируются по правилам Prometheus.
+fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| {
+            let escaped = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{key}=\"{escaped}\"")
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Грубая оценка числа токенов по длине текста.
+pub fn estimate_tokens(text: &str) -> u64 {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN) as u64
+}
+
+// ============================================================================
+// FAIRING ДЛЯ HTTP-МЕТРИК
+// ============================================================================
+
+/// Время начала запроса (хранится в `req.local_cache`).
+struct RequestStart(Instant);
+
+/// Fairing: считает HTTP-запросы, их задержку и число выполняющихся.
+///
+/// Хранилище берёт из `State<Arc<Metrics>>`; без него ничего не делает.
+pub struct HttpMetrics;
+
+#[rocket::async_trait]
+impl Fairing for HttpMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Prometheus HTTP metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        if let Some(metrics) = req.rocket().state::<Arc<Metrics>>() {
+            metrics.http_in_flight.fetch_add(1, Ordering::Relaxed);
+            req.local_cache(|| Some(RequestStart(Instant::now())));
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(metrics) = req.rocket().state::<Arc<Metrics>>() else {
+            return;
+        };
+        let Some(RequestStart(started)) = req.local_cache(|| None::<RequestStart>) else {
+            return;
+        };
+        metrics.http_in_flight.fetch_sub(1, Ordering::Relaxed);
+
+        let route = req
+            .route()
+            .map(|route| route.uri.to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        metrics.record_http(
+            req.method().as_str(),
+            &route,
+            res.status().code,
+            started.elapsed(),
+        );
+    }
+}
+
+// ============================================================================
+// ДЕКОРАТОР AI СЕРВИСА
+// ============================================================================
+
+/// AI сервис, который записывает в метрики каждый вызов.
+///
+/// Как и `ConcurrencyLimitedService`, это декоратор: тот же трейт
+/// `AiService`, поэтому обработчики ничего не знают о метриках.
+pub struct InstrumentedAiService {
+    inner: Box<dyn AiService>,
+    metrics: Arc<Metrics>,
+}
+
+impl InstrumentedAiService {
+    /// Оборачивает сервис `inner`, записывая вызовы в `metrics`.
+    pub fn new(inner: Box<dyn AiService>, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+#[async_trait]
+impl AiService for InstrumentedAiService {
+    async fn ask(&self, question: &str, ctx: &AskContext) -> Result<String, AiServiceError> {
+        let started = Instant::now();
+        let result = self.inner.ask(question, ctx).await;
+        let backend = self.inner.name();
+
+        self.metrics
+            .record_ai_call(backend, started.elapsed(), result.as_ref().map(|_| ()));
+        if let Ok(answer) = &result {
+            self.metrics
+                .record_tokens(backend, "prompt", estimate_tokens(question));
+            self.metrics
+                .record_tokens(backend, "completion", estimate_tokens(answer));
+        }
+        result
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn system_prompt_applied(&self) -> bool {
+        self.inner.system_prompt_applied()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+
+    async fn probe(&self) -> Result<(), AiServiceError> {
+        self.inner.probe().await
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::MockAiService;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.03);
+        histogram.observe(2.0);
+
+        // 0.03 попадает во все корзины начиная с 0.05
+        assert_eq!(histogram.buckets[2], 0); // le=0.025
+        assert_eq!(histogram.buckets[3], 1); // le=0.05
+        assert_eq!(histogram.buckets[8], 2); // le=2.5
+        assert_eq!(histogram.count, 2);
+    }
+
+    #[test]
+    fn test_render_http_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_http("GET", "/health", 200, Duration::from_millis(3));
+        metrics.record_http("GET", "/health", 200, Duration::from_millis(30));
+
+        let text = metrics.render(None);
+        assert!(text.contains("# TYPE http_requests_total counter"));
+        assert!(text.contains(r#"http_requests_total{method="GET",route="/health",status="200"} 2"#));
+        assert!(text.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/health",status="200",le="0.005"} 1"#
+        ));
+        assert!(text.contains(
+            r#"http_request_duration_seconds_bucket{method="GET",route="/health",status="200",le="+Inf"} 2"#
+        ));
+        assert!(text.contains("http_requests_in_flight 0"));
+        assert!(!text.contains("ai_queue_depth"));
+    }
+
+    #[test]
+    fn test_label_escaping() {
+        assert_eq!(
+            format_labels(&[("route", "a\"b\\c\nd")]),
+            r#"{route="a\"b\\c\nd"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_instrumented_service_records_calls_and_tokens() {
+        let metrics = Arc::new(Metrics::new());
+        let service = InstrumentedAiService::new(Box::new(MockAiService::new()), Arc::clone(&metrics));
+
+        service.ask("Привет!", &AskContext::default()).await.unwrap();
+
+        let text = metrics.render(None);
+        assert!(text.contains(r#"ai_requests_total{backend="Mock AI Service",outcome="success"} 1"#));
+        // "Привет!" - 7 символов → 2 токена
+        assert!(text.contains(r#"ai_tokens_total{backend="Mock AI Service",type="prompt"} 2"#));
+        assert!(text.contains(r#"ai_tokens_total{backend="Mock AI Service",type="completion"}"#));
+    }
+}
@@ -72,6 +72,9 @@
 // Serialize - трейт для преобразования структуры → JSON (сериализация)
 // Deserialize - трейт для создания структуры ← JSON (десериализация)
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::services::AiService;
 
 // ============================================================================
 // МОДЕЛИ ЗАПРОСОВ (REQUEST) - только Deserialize!
@@ -127,6 +130,25 @@ pub struct AskRequest {
     /// Вопрос пользователя.
     /// Serde автоматически сопоставляет JSON-поле "question" с этим полем.
     pub question: String,
+
+    /// Идентификатор диалога (латиница, цифры, `-`, `_`, до 64 символов).
+    ///
+    /// Необязательное поле: `#[serde(default)]` подставляет `None`, если
+    /// его нет в JSON. Вопросы с одним `conversation_id` сохраняются в
+    /// хранилище как один диалог; новый идентификатор начинает новый диалог.
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+
+    /// Шаблон промпта из `GET /templates` (необязательно).
+    ///
+    /// С шаблоном модель получает не сам вопрос, а текст шаблона, в
+    /// котором `{{question}}` заменён вопросом.
+    #[serde(default)]
+    pub template: Option<String>,
+
+    /// Значения переменных шаблона: `{"language": "Rust", "level": "начинающий"}`
+    #[serde(default)]
+    pub variables: BTreeMap<String, String>,
 }
 
 // ============================================================================
@@ -213,6 +235,7 @@ pub struct AskRequest {
 ///
 /// ```json
 /// {
+///   "answer_id": "9b1d6c1e-...",
 ///   "answer": "Rust - это системный язык...",
 ///   "source": "gigachat"
 /// }
@@ -220,6 +243,10 @@ pub struct AskRequest {
 #[derive(Debug, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct AskResponse {
+    /// Идентификатор ответа: по нему ставится оценка (`POST /feedback`)
+    /// и удаляется запись истории (`DELETE /history/<id>`)
+    pub answer_id: String,
+
     /// Текст ответа от AI.
     /// 
     /// Источник значения:
@@ -239,6 +266,76 @@ pub struct AskResponse {
     ///
     /// Сам текст системного промпта НЕ возвращается клиенту.
     pub system_prompt_applied: bool,
+
+    /// Диалог, к которому сохранён ответ (если он указан в запросе)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+
+    /// Шаблон промпта, по которому задан вопрос (если он указан в запросе)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+
+    /// Вариант системного промпта, если идёт A/B эксперимент
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_variant: Option<String>,
+
+    /// Сработавшие проверки модуля `guardrails` (пусто - не выводится)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub guardrails: Vec<GuardrailDecision>,
+}
+
+/// Что делать, когда проверка `guardrails` сработала.
+///
+/// В JSON и config.toml - `"block"`, `"sanitize"` и `"flag"`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum GuardAction {
+    /// Отказать в ответе
+    Block,
+    /// Вырезать подозрительный фрагмент и продолжить
+    Sanitize,
+    /// Пропустить как есть, но отметить
+    Flag,
+}
+
+impl GuardAction {
+    /// Значение в JSON и config.toml.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GuardAction::Block => "block",
+            GuardAction::Sanitize => "sanitize",
+            GuardAction::Flag => "flag",
+        }
+    }
+
+    /// Обратное к [`GuardAction::as_str`].
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "block" => Some(GuardAction::Block),
+            "sanitize" => Some(GuardAction::Sanitize),
+            "flag" => Some(GuardAction::Flag),
+            _ => None,
+        }
+    }
+}
+
+/// Решение одной проверки `guardrails`.
+///
+/// ```json
+/// {"check": "prompt_injection", "action": "flag",
+///  "detail": "matched 'ignore ... instructions'"}
+/// ```
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct GuardrailDecision {
+    /// `prompt_injection` (вопрос) или `system_prompt_leak` (ответ)
+    pub check: &'static str,
+
+    /// Принятое действие
+    pub action: GuardAction,
+
+    /// Что именно сработало
+    pub detail: String,
 }
 
 /// Информация о состоянии сервера (health check).
@@ -263,22 +360,627 @@ pub struct AskResponse {
 ///
 /// ```json
 /// {
-///   "status": "ok",
+///   "status": "degraded",
 ///   "version": "0.1.0",
-///   "gigachat_enabled": true
+///   "gigachat_enabled": false,
+///   "backend": {
+///     "name": "Mock AI Service",
+///     "system_prompt_applied": false,
+///     "fallback_reason": "GIGACHAT_TOKEN is not set, using mock"
+///   },
+///   "uptime_seconds": 42,
+///   "build": {"git_hash": "1b3b10a", "features": ["gigachat"]}
 /// }
 /// ```
 #[derive(Debug, Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct HealthResponse {
-    /// Статус сервера: "ok" или "error"
+    /// Статус сервера: "ok" или "degraded" (работает не тот бэкенд,
+    /// что задан в конфигурации)
     pub status: String,
     
     /// Версия приложения (из config.toml)
     pub version: String,
     
-    /// Флаг: используется реальный GigaChat или mock
+    /// Флаг: отвечает ли реальный GigaChat (а не mock).
+    /// Отражает фактический бэкенд, а не `gigachat.enabled` из конфига.
     pub gigachat_enabled: bool,
+
+    /// Активный AI бэкенд
+    pub backend: BackendInfo,
+
+    /// Сколько секунд работает сервер
+    pub uptime_seconds: u64,
+
+    /// Сведения о сборке
+    pub build: BuildInfo,
+
+    /// Состояние очереди к AI (только при `concurrency.enabled = true`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<ConcurrencyStatus>,
+}
+
+/// Состояние ограничителя параллельных запросов к AI.
+///
+/// Входит в ответ `/health`, чтобы было видно, насколько загружена очередь.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct ConcurrencyStatus {
+    /// Максимум одновременных запросов
+    pub max_concurrent: usize,
+
+    /// Максимальная длина очереди
+    pub max_queue: usize,
+
+    /// Запросов выполняется прямо сейчас
+    pub in_flight: usize,
+
+    /// Запросов ждёт в очереди
+    pub queue_depth: usize,
+
+    /// Среднее время ожидания в очереди (мс), среди ждавших запросов
+    pub avg_wait_ms: u64,
+
+    /// Максимальное время ожидания в очереди (мс)
+    pub max_wait_ms: u64,
+
+    /// Отклонено из-за переполненной очереди
+    pub rejected_total: u64,
+
+    /// Отклонено по таймауту ожидания
+    pub timed_out_total: u64,
+}
+
+/// Какой AI бэкенд реально отвечает на вопросы.
+///
+/// Может отличаться от настроек: без `GIGACHAT_TOKEN` приложение
+/// переключается на mock, и `fallback_reason` объясняет почему.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct BackendInfo {
+    /// Имя активного сервиса ("GigaChat", "Mock AI Service")
+    pub name: String,
+
+    /// Модель, которой отправляются вопросы (у mock её нет)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Применяется ли системный промпт
+    pub system_prompt_applied: bool,
+
+    /// Почему используется не тот бэкенд, что задан в конфигурации
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_reason: Option<String>,
+}
+
+impl BackendInfo {
+    /// Описание сервиса `service`; `fallback_reason` - почему выбран именно он.
+    pub fn new(service: &dyn AiService, fallback_reason: Option<String>) -> Self {
+        Self {
+            name: service.name().to_string(),
+            model: service.model().map(str::to_string),
+            system_prompt_applied: service.system_prompt_applied(),
+            fallback_reason,
+        }
+    }
+}
+
+/// Сведения о сборке: какой код запущен.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct BuildInfo {
+    /// Короткий хеш git-коммита ("unknown", если собрано без `.git`)
+    pub git_hash: String,
+
+    /// Включённые cargo-фичи
+    pub features: Vec<String>,
+}
+
+/// Ответ `GET /health/live`: процесс жив и обрабатывает запросы.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct LivenessResponse {
+    /// Всегда "ok" - если сервер смог ответить, он жив
+    pub status: String,
+}
+
+/// Состояние зависимости. Порядок вариантов - от лучшего к худшему,
+/// поэтому общий статус - это максимум по всем проверкам.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum CheckStatus {
+    /// Работает
+    Ok,
+    /// Работает с ограничениями (запросы обслуживаются)
+    Degraded,
+    /// Не работает - сервис не готов принимать трафик
+    Fail,
+}
+
+/// Результат проверки одной зависимости для `/health/ready`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct DependencyCheck {
+    /// Имя зависимости: "ai_backend", "jwks", "ai_queue"
+    pub name: String,
+
+    /// Результат проверки
+    pub status: CheckStatus,
+
+    /// Пояснение: причина ошибки, число ключей и т.п.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+
+    /// Сколько заняла проверка (мс), если она обращалась к зависимости
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+/// Ответ `GET /health/ready`: готов ли сервис принимать трафик.
+///
+/// # Пример JSON
+///
+/// ```json
+/// {
+///   "status": "degraded",
+///   "backend": {"name": "Mock AI Service", "fallback_reason": "GIGACHAT_TOKEN is not set, using mock"},
+///   "checks": [{"name": "ai_backend", "status": "degraded", "detail": "..."}]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReadinessResponse {
+    /// "ok", "degraded" или "unavailable" (тогда HTTP 503)
+    pub status: String,
+
+    /// Активный AI бэкенд
+    pub backend: BackendInfo,
+
+    /// Результаты проверок зависимостей
+    pub checks: Vec<DependencyCheck>,
+}
+
+/// Ответ `POST /admin/reload` - итог перезагрузки конфигурации.
+///
+/// ```json
+/// {
+///   "status": "reloaded",
+///   "generation": 2,
+///   "backend": {"name": "GigaChat", "model": "GigaChat-Pro", "system_prompt_applied": true},
+///   "changed": ["application", "gigachat"],
+///   "restart_required": ["server"]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ReloadResponse {
+    /// "reloaded" или "unchanged" (файл не менялся)
+    pub status: String,
+
+    /// Номер версии конфигурации: 1 при запуске, +1 за каждую перезагрузку
+    pub generation: u64,
+
+    /// AI бэкенд, который обслуживает новые запросы
+    pub backend: BackendInfo,
+
+    /// Применённые секции config.toml
+    pub changed: Vec<String>,
+
+    /// Изменённые секции, которые вступят в силу только после перезапуска
+    pub restart_required: Vec<String>,
+}
+
+/// Ответ `GET /admin/config` - итоговая конфигурация и источники значений.
+///
+/// Секреты (ключи API, токены, пароли) заменены на `"***"`.
+///
+/// ```json
+/// {
+///   "profile": "production",
+///   "files": ["config.toml", "config.production.toml"],
+///   "generation": 1,
+///   "config": {"server": {"port": 8080, ...}, "auth": {"keys": [{"name": "frontend", "key": "***"}]}},
+///   "sources": {"server.port": "env:PORT", "logging.format": "profile:production", "shutdown.grace_seconds": "default"}
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ConfigResponse {
+    /// Активный профиль (`APP_PROFILE`)
+    pub profile: String,
+
+    /// Прочитанные файлы конфигурации
+    pub files: Vec<String>,
+
+    /// Версия конфигурации (см. [`ReloadResponse::generation`])
+    pub generation: u64,
+
+    /// Итоговая конфигурация без секретов
+    pub config: rocket::serde::json::Value,
+
+    /// Ключ → слой, из которого взято значение:
+    /// `default`, файл, `profile:<имя>` или `env`
+    pub sources: BTreeMap<String, String>,
+}
+
+/// Использование AI одним клиентом (строка ответа `GET /admin/usage`).
+///
+/// Токены оцениваются по длине текста, как в метриках (см. модуль `metrics`).
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct UsageSummary {
+    /// Имя API-ключа, `sub` из JWT или "anonymous"
+    pub subject: String,
+
+    /// Сколько раз вызывался AI
+    pub requests: u64,
+
+    /// Сколько вызовов завершились ошибкой
+    pub errors: u64,
+
+    /// Токены в вопросах
+    pub prompt_tokens: u64,
+
+    /// Токены в ответах
+    pub completion_tokens: u64,
+
+    /// Время последнего вызова, миллисекунды Unix
+    pub last_request_at: i64,
+}
+
+/// Ответ `GET /admin/usage` - использование AI по клиентам из хранилища.
+///
+/// ```json
+/// {
+///   "storage": "sqlite data/gigachat.db",
+///   "subjects": [{"subject": "frontend", "requests": 12, "errors": 1,
+///                 "prompt_tokens": 240, "completion_tokens": 3100,
+///                 "last_request_at": 1760781600000}]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct UsageResponse {
+    /// Хранилище, из которого взяты данные
+    pub storage: String,
+
+    /// Использование по клиентам, по алфавиту
+    pub subjects: Vec<UsageSummary>,
+}
+
+/// Параметры, с которыми AI отвечал на вопрос (сохраняются в истории).
+///
+/// У mock-сервиса нет модели и температуры - эти поля пропускаются.
+#[derive(Debug, Serialize, Clone, PartialEq, Default)]
+#[serde(crate = "rocket::serde")]
+pub struct AskParameters {
+    /// Модель GigaChat
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+
+    /// Температура генерации
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Ограничение длины ответа в токенах
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+
+    /// Применялся ли системный промпт
+    pub system_prompt_applied: bool,
+
+    /// Версия системного промпта (см. `ApplicationConfig::system_prompt_version`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_prompt_version: Option<String>,
+
+    /// A/B эксперимент (`experiment.name`), в котором выбран вариант
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub experiment: Option<String>,
+
+    /// Вариант промпта в A/B эксперименте (см. модуль `experiment`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_variant: Option<String>,
+
+    /// Шаблон промпта (см. модуль `templates`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Вопрос и ответ из истории (элемент ответа `GET /history`).
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryEntry {
+    /// Идентификатор записи (для `DELETE /history/<id>`)
+    pub id: String,
+
+    /// Вопрос
+    pub question: String,
+
+    /// Ответ AI
+    pub answer: String,
+
+    /// Источник ответа (как `AskResponse.source`)
+    pub source: String,
+
+    /// Диалог, если вопрос задан с `conversation_id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+
+    /// Параметры генерации
+    pub parameters: AskParameters,
+
+    /// Время ответа, миллисекунды Unix
+    pub created_at: i64,
+}
+
+/// Ответ `GET /history` - страница истории, от новых записей к старым.
+///
+/// ```json
+/// {
+///   "items": [{"id": "9b1d...", "question": "Что такое Rust?", "answer": "...",
+///              "source": "gigachat", "parameters": {"model": "GigaChat",
+///              "temperature": 0.7, "max_tokens": 1024, "system_prompt_applied": true},
+///              "created_at": 1760781600000}],
+///   "next_cursor": "MTc2MDc4MTYwMDAwMDo5YjFk..."
+/// }
+/// ```
+///
+/// `next_cursor` передаётся в `?cursor=` за следующей страницей;
+/// его нет, когда записи кончились.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct HistoryResponse {
+    /// Записи страницы
+    pub items: Vec<HistoryEntry>,
+
+    /// Курсор следующей страницы
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Оценка ответа: полезен или нет.
+///
+/// В JSON - строки `"up"` и `"down"` (`rename_all = "lowercase"`).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum Rating {
+    /// 👍 ответ помог
+    Up,
+    /// 👎 ответ не помог
+    Down,
+}
+
+impl Rating {
+    /// Значение в JSON и в базе.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Rating::Up => "up",
+            Rating::Down => "down",
+        }
+    }
+
+    /// Обратное к [`Rating::as_str`].
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "up" => Some(Rating::Up),
+            "down" => Some(Rating::Down),
+            _ => None,
+        }
+    }
+}
+
+/// Запрос `POST /feedback` - оценка ответа.
+///
+/// ```json
+/// {"answer_id": "9b1d6c1e-...", "rating": "down",
+///  "comment": "Rocket принят за ракету", "categories": ["off_topic"]}
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedbackRequest {
+    /// `AskResponse.answer_id` оцениваемого ответа
+    pub answer_id: String,
+
+    /// Оценка
+    pub rating: Rating,
+
+    /// Комментарий (необязателен)
+    #[serde(default)]
+    pub comment: Option<String>,
+
+    /// Что не так с ответом: `wrong`, `off_topic`, `too_long`, `other`
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+/// Ответ `POST /feedback`.
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedbackResponse {
+    /// Оценённый ответ
+    pub answer_id: String,
+
+    /// Сохранённая оценка
+    pub rating: Rating,
+
+    /// `true`, если оценка заменила поставленную ранее
+    pub updated: bool,
+}
+
+/// Отзывы на ответы одной модели с одной версией системного промпта.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedbackSummary {
+    /// Модель (`None` - mock)
+    pub model: Option<String>,
+
+    /// A/B эксперимент (`None` - ответ дан вне эксперимента или сохранён
+    /// до того, как имя эксперимента стало записываться в историю)
+    pub experiment: Option<String>,
+
+    /// Вариант A/B эксперимента (`None` - ответ дан вне эксперимента)
+    pub prompt_variant: Option<String>,
+
+    /// Версия системного промпта (`None` - промпт не применялся)
+    pub system_prompt_version: Option<String>,
+
+    /// Сколько ответов дано (с оценкой и без)
+    pub answers: u64,
+
+    /// Оценок 👍
+    pub up: u64,
+
+    /// Оценок 👎
+    pub down: u64,
+
+    /// Доля 👍 среди оценок; `None`, пока оценок нет
+    pub satisfaction: Option<f64>,
+
+    /// Категория → сколько раз отмечена
+    pub categories: BTreeMap<String, u64>,
+}
+
+/// Оценка вместе с вопросом и ответом - для разбора преподавателем.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedbackReview {
+    /// Оценённый ответ
+    pub answer_id: String,
+
+    /// Кто оценил (он же задавал вопрос)
+    pub subject: String,
+
+    /// Оценка
+    pub rating: Rating,
+
+    /// Комментарий
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+
+    /// Отмеченные категории
+    pub categories: Vec<String>,
+
+    /// Вопрос
+    pub question: String,
+
+    /// Ответ AI
+    pub answer: String,
+
+    /// Параметры, с которыми дан ответ
+    pub parameters: AskParameters,
+
+    /// Время оценки, миллисекунды Unix
+    pub created_at: i64,
+}
+
+/// Итог A/B эксперимента по одному варианту промпта (все модели вместе).
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct VariantSummary {
+    /// Имя эксперимента (`None` - ответы сохранены до того, как имя
+    /// стало записываться в историю)
+    pub experiment: Option<String>,
+
+    /// Идентификатор варианта из `[[experiment.variants]]`
+    pub prompt_variant: String,
+
+    /// Сколько ответов дано (с оценкой и без)
+    pub answers: u64,
+
+    /// Оценок 👍
+    pub up: u64,
+
+    /// Оценок 👎
+    pub down: u64,
+
+    /// Доля 👍 среди оценок; `None`, пока оценок нет
+    pub satisfaction: Option<f64>,
+}
+
+/// Ответ `GET /admin/feedback`.
+///
+/// ```json
+/// {
+///   "storage": "sqlite data/gigachat.db (схема v6)",
+///   "groups": [{"model": "GigaChat", "experiment": "socratic-2026-10",
+///               "prompt_variant": "socratic",
+///               "system_prompt_version": "3f2a9c01b7de",
+///               "answers": 120, "up": 40, "down": 8, "satisfaction": 0.83,
+///               "categories": {"off_topic": 5, "too_long": 3}}],
+///   "variants": [{"experiment": "socratic-2026-10", "prompt_variant": "socratic",
+///                 "answers": 120, "up": 40, "down": 8, "satisfaction": 0.83}],
+///   "recent": [{"answer_id": "9b1d...", "subject": "lab-group-1", "rating": "down",
+///               "comment": "Rocket принят за ракету", "categories": ["off_topic"], ...}]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct FeedbackReport {
+    /// Хранилище, из которого взяты данные
+    pub storage: String,
+
+    /// Сводка по моделям и версиям промпта
+    pub groups: Vec<FeedbackSummary>,
+
+    /// Сводка по вариантам A/B эксперимента (нет ответов в эксперименте - нет поля)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantSummary>,
+
+    /// Последние оценки, от новых к старым
+    pub recent: Vec<FeedbackReview>,
+}
+
+/// Переменная шаблона промпта.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct TemplateVariable {
+    /// Имя (`language`, `level`, ...)
+    pub name: String,
+
+    /// Значение по умолчанию
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    /// `true`, если значения по умолчанию нет и его нужно передать в `variables`
+    pub required: bool,
+}
+
+/// Шаблон промпта в ответе `GET /templates`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(crate = "rocket::serde")]
+pub struct TemplateInfo {
+    /// Имя для `AskRequest.template`
+    pub name: String,
+
+    /// Для чего шаблон
+    pub description: String,
+
+    /// Переменные, кроме `question` (он всегда берётся из вопроса)
+    pub variables: Vec<TemplateVariable>,
+}
+
+/// Ответ `GET /templates`.
+///
+/// ```json
+/// {
+///   "templates": [{"name": "explain", "description": "Объяснить тему простыми словами",
+///                  "variables": [{"name": "level", "default": "начинающий", "required": false},
+///                                {"name": "language", "default": "Rust", "required": false}]}]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct TemplatesResponse {
+    /// Шаблоны по алфавиту
+    pub templates: Vec<TemplateInfo>,
+}
+
+/// Ответ на удаление истории (`DELETE /history`, `DELETE /history/<id>`).
+#[derive(Debug, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct DeleteHistoryResponse {
+    /// Сколько записей удалено
+    pub deleted: u64,
 }
 
 /// Ответ с ошибкой - стандартный формат для всех ошибок API.
@@ -350,6 +1052,11 @@ pub struct ErrorResponse {
     /// Пропускается в JSON, если равен None.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<String>,
+
+    /// Идентификатор запроса (`X-Request-Id`) - чтобы найти запрос в логах.
+    /// Пропускается в JSON, если равен None.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl ErrorResponse {
@@ -374,6 +1081,7 @@ impl ErrorResponse {
         Self {
             error: error.into(),  // .into() преобразует в String
             code: None,
+            request_id: None,
         }
     }
 
@@ -389,6 +1097,17 @@ impl ErrorResponse {
         response.code = Some(code.into());
         response
     }
+
+    /// Добавляет идентификатор запроса (builder-стиль: принимает и возвращает `self`).
+    ///
+    /// ```rust,ignore
+    /// ErrorResponse::with_code("Endpoint not found", "NOT_FOUND")
+    ///     .with_request_id("3f1c2a9e-...");
+    /// ```
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
 }
 
 #[cfg(test)]
@@ -410,6 +1129,11 @@ mod tests {
         let request: AskRequest = serde_json::from_str(json).unwrap();
         
         assert_eq!(request.question, "Что такое Rust?");
+        assert!(request.conversation_id.is_none());
+
+        let json = r#"{"question": "А макросы?", "conversation_id": "lab-3"}"#;
+        let request: AskRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.conversation_id.as_deref(), Some("lab-3"));
     }
 
     /// Тест СЕРИАЛИЗАЦИИ AskResponse (структура → JSON).
@@ -419,9 +1143,14 @@ mod tests {
     #[test]
     fn test_ask_response_serialization() {
         let response = AskResponse {
+            answer_id: "9b1d".to_string(),
             answer: "Rust - это язык программирования".to_string(),
             source: "mock".to_string(),
             system_prompt_applied: false,
+            conversation_id: None,
+            template: None,
+            prompt_variant: None,
+            guardrails: Vec::new(),
         };
         
         // Serialize: AskResponse → JSON
@@ -429,6 +1158,42 @@ mod tests {
         
         assert!(json.contains("Rust"));
         assert!(json.contains("mock"));
+        assert!(!json.contains("conversation_id"));
+        assert!(!json.contains("guardrails"));
+    }
+
+    /// У mock нет модели и температуры - в JSON остаётся только признак промпта.
+    #[test]
+    fn test_ask_parameters_serialization() {
+        let json = serde_json::to_string(&AskParameters::default()).unwrap();
+        assert_eq!(json, r#"{"system_prompt_applied":false}"#);
+
+        let parameters = AskParameters {
+            model: Some("GigaChat".to_string()),
+            temperature: Some(0.5),
+            max_tokens: Some(1024),
+            system_prompt_applied: true,
+            system_prompt_version: Some("3f2a9c01b7de".to_string()),
+            experiment: None,
+            prompt_variant: None,
+            template: None,
+        };
+        let json = serde_json::to_string(&parameters).unwrap();
+        assert!(json.contains(r#""model":"GigaChat""#));
+        assert!(json.contains(r#""temperature":0.5"#));
+    }
+
+    #[test]
+    fn test_feedback_request_deserialization() {
+        let json = r#"{"answer_id": "9b1d", "rating": "down", "categories": ["off_topic"]}"#;
+        let request: FeedbackRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.rating, Rating::Down);
+        assert_eq!(request.categories, ["off_topic"]);
+        assert!(request.comment.is_none());
+
+        let json = r#"{"answer_id": "9b1d", "rating": "meh"}"#;
+        assert!(serde_json::from_str::<FeedbackRequest>(json).is_err());
+        assert_eq!(Rating::parse(Rating::Up.as_str()), Some(Rating::Up));
     }
 
     /// Тест ErrorResponse с кодом и без.
@@ -442,6 +1207,14 @@ mod tests {
         // С кодом
         let error_with_code = ErrorResponse::with_code("Тестовая ошибка", "TEST_ERROR");
         assert_eq!(error_with_code.code, Some("TEST_ERROR".to_string()));
+        assert!(error_with_code.request_id.is_none());
+
+        // С идентификатором запроса
+        let error_with_id = ErrorResponse::with_code("Тестовая ошибка", "TEST_ERROR")
+            .with_request_id("req-1");
+        assert_eq!(error_with_id.request_id, Some("req-1".to_string()));
+        let json = serde_json::to_string(&error_with_id).unwrap();
+        assert!(json.contains(r#""request_id":"req-1""#));
     }
 
     /// Тест skip_serializing_if для ErrorResponse.
@@ -0,0 +1,564 @@
+//! Модуль модерации вопросов и ответов.
+//!
+//! Сервис учебный: в вопросах не должно быть запрещённых тем, а
+//! персональные данные студентов (почта, телефон, паспорт) не должны
+//! уходить с нашего сервера в GigaChat. [`ModeratedAiService`] - декоратор
+//! над любым [`AiService`], который пропускает вопрос и ответ через
+//! конвейер проверок:
+//!
+//! ```text
+//!          ┌─────────── ModeratedAiService ───────────┐
+//! вопрос ──┼─► blocklist ─► pii ─► [ AI сервис ] ─► blocklist ─► pii ─┼──► ответ
+//!          └────┬────────────┬──────────────────────────────┬────────┘
+//!               │ block      │ redact                       │ warn
+//!               ▼            ▼                              ▼
+//!        400 CONTENT_BLOCKED  "пишите на [email]"      запись в лог
+//! ```
+//!
+//! Заблокированный ответ - не ошибка клиента: вопрос прошёл проверку,
+//! а модель ответила недопустимым. Для него отдельный код -
+//! 502 `ANSWER_BLOCKED`.
+//!
+//! Проверка - любой тип с трейтом [`ContentCheck`]: она только находит
+//! фрагменты, а что с ними делать (`block`, `redact`, `warn`), решает
+//! конвейер [`Moderation`]. Встроенные проверки - [`Blocklist`] и
+//! [`PiiDetector`]; новую можно добавить через [`Moderation::with_check`].
+//!
+//! Декоратор - самый внешний в `AiStack`: заблокированный вопрос не
+//! занимает место в очереди и не попадает в метрики вызовов AI. В историю
+//! записывается исходный вопрос - она хранится на нашем сервере.
+//!
+//! # Для студентов: Почему маска, а не удаление?
+//!
+//! Если просто вырезать почту из "напишите мне на a@b.ru", модель получит
+//! "напишите мне на" и начнёт гадать. Маска `[email]` сохраняет смысл
+//! фразы и прямо говорит модели, что здесь были данные, которых она не видит.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use async_trait::async_trait;
+use regex::Regex;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::config::ModerationConfig;
+use crate::services::{AiService, AiServiceError, AskContext};
+
+// ============================================================================
+// ДЕЙСТВИЯ И ОШИБКИ
+// ============================================================================
+
+/// Что делать с текстом, в котором проверка что-то нашла.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    /// Отказать в ответе
+    Block,
+    /// Заменить найденное маской
+    Redact,
+    /// Пропустить, записав в лог
+    Warn,
+}
+
+impl ModerationAction {
+    /// Значение в config.toml.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ModerationAction::Block => "block",
+            ModerationAction::Redact => "redact",
+            ModerationAction::Warn => "warn",
+        }
+    }
+
+    /// Обратное к [`ModerationAction::as_str`].
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "block" => Some(ModerationAction::Block),
+            "redact" => Some(ModerationAction::Redact),
+            "warn" => Some(ModerationAction::Warn),
+            _ => None,
+        }
+    }
+}
+
+/// Ошибки сборки конвейера из `[moderation]`.
+#[derive(Error, Debug)]
+pub enum ModerationError {
+    /// Стоп-выражение не компилируется
+    #[error("неверное регулярное выражение '{pattern}': {source}")]
+    InvalidPattern {
+        pattern: String,
+        #[source]
+        source: regex::Error,
+    },
+
+    /// Неизвестный вид персональных данных
+    #[error("неизвестный вид персональных данных '{0}' (допустимо email, phone, passport)")]
+    UnknownPii(String),
+
+    /// Неизвестное действие
+    #[error("неизвестное действие '{0}' (допустимо block, redact, warn)")]
+    UnknownAction(String),
+}
+
+/// Где проверяется текст.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Вопрос - до вызова AI
+    Question,
+    /// Ответ - после вызова AI
+    Answer,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Question => "вопрос",
+            Stage::Answer => "ответ",
+        })
+    }
+}
+
+// ============================================================================
+// ПРОВЕРКИ
+// ============================================================================
+
+/// Найденный проверкой фрагмент текста.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// Границы фрагмента в байтах
+    pub range: Range<usize>,
+
+    /// Что найдено: `email`, `телефон`...; маска - `[метка]`
+    pub label: &'static str,
+}
+
+/// Проверка текста - шаг конвейера [`Moderation`].
+///
+/// Проверка только ищет: блокировать, маскировать или предупреждать,
+/// решает конвейер по действию, с которым проверка добавлена.
+pub trait ContentCheck: Send + Sync {
+    /// Имя проверки для логов и сообщений об ошибке.
+    fn name(&self) -> &str;
+
+    /// Все найденные фрагменты (могут пересекаться).
+    fn find(&self, text: &str) -> Vec<Finding>;
+}
+
+/// Стоп-лист: целые слова без учёта регистра и регулярные выражения.
+pub struct Blocklist {
+    regexes: Vec<Regex>,
+}
+
+impl Blocklist {
+    /// Метка найденного фрагмента.
+    pub const LABEL: &'static str = "стоп-слово";
+
+    /// Собирает стоп-лист; пустые слова пропускаются.
+    pub fn new(keywords: &[String], patterns: &[String]) -> Result<Self, ModerationError> {
+        let words: Vec<String> = keywords
+            .iter()
+            .map(|keyword| keyword.trim())
+            .filter(|keyword| !keyword.is_empty())
+            .map(regex::escape)
+            .collect();
+        let mut regexes = Vec::new();
+        if !words.is_empty() {
+            let pattern = format!(r"(?i)\b(?:{})\b", words.join("|"));
+            regexes.push(Regex::new(&pattern).map_err(|source| ModerationError::InvalidPattern {
+                pattern,
+                source,
+            })?);
+        }
+        for pattern in patterns {
+            regexes.push(Regex::new(pattern).map_err(|source| ModerationError::InvalidPattern {
+                pattern: pattern.clone(),
+                source,
+            })?);
+        }
+        Ok(Self { regexes })
+    }
+}
+
+impl ContentCheck for Blocklist {
+    fn name(&self) -> &str {
+        "blocklist"
+    }
+
+    fn find(&self, text: &str) -> Vec<Finding> {
+        self.regexes
+            .iter()
+            .flat_map(|regex| regex.find_iter(text))
+            .map(|found| Finding {
+                range: found.range(),
+                label: Self::LABEL,
+            })
+            .collect()
+    }
+}
+
+/// Персональные данные: адреса почты, телефоны, номера паспортов.
+pub struct PiiDetector {
+    kinds: Vec<(&'static str, Regex)>,
+}
+
+/// Виды персональных данных: имя в config.toml, метка и выражение.
+///
+/// Телефон - российский (`+7`/`8` и 10 цифр) или международный с `+`;
+/// паспорт - серия и номер РФ (`45 06 123456`), то есть любые 10 цифр
+/// с такой разбивкой.
+const PII_KINDS: &[(&str, &str, &str)] = &[
+    ("email", "email", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+    (
+        "phone",
+        "телефон",
+        r"(?:\+\d{1,3}|\b8)[\s-]?\(?\d{3}\)?[\s-]?\d{3}[\s-]?\d{2}[\s-]?\d{2}\b",
+    ),
+    ("passport", "паспорт", r"\b\d{2}\s?\d{2}\s?\d{6}\b"),
+];
+
+impl PiiDetector {
+    /// Детектор видов `kinds` (`email`, `phone`, `passport`).
+    pub fn new(kinds: &[String]) -> Result<Self, ModerationError> {
+        let kinds = kinds
+            .iter()
+            .map(|kind| {
+                PII_KINDS
+                    .iter()
+                    .find(|(name, _, _)| name == kind)
+                    .map(|(_, label, pattern)| {
+                        (*label, Regex::new(pattern).expect("встроенное выражение корректно"))
+                    })
+                    .ok_or_else(|| ModerationError::UnknownPii(kind.clone()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { kinds })
+    }
+
+    /// Известен ли вид персональных данных.
+    pub fn is_known(kind: &str) -> bool {
+        PII_KINDS.iter().any(|(name, _, _)| *name == kind)
+    }
+}
+
+impl ContentCheck for PiiDetector {
+    fn name(&self) -> &str {
+        "pii"
+    }
+
+    fn find(&self, text: &str) -> Vec<Finding> {
+        self.kinds
+            .iter()
+            .flat_map(|(label, regex)| {
+                regex.find_iter(text).map(|found| Finding {
+                    range: found.range(),
+                    label,
+                })
+            })
+            .collect()
+    }
+}
+
+// ============================================================================
+// КОНВЕЙЕР
+// ============================================================================
+
+/// Конвейер проверок: каждая со своим действием, по порядку добавления.
+///
+/// Проверки после `redact` видят уже замаскированный текст.
+#[derive(Default)]
+pub struct Moderation {
+    steps: Vec<(Box<dyn ContentCheck>, ModerationAction)>,
+}
+
+impl Moderation {
+    /// Пустой конвейер: пропускает всё.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Конвейер из секции `[moderation]`: стоп-лист, затем персональные данные.
+    pub fn from_config(config: &ModerationConfig) -> Result<Self, ModerationError> {
+        let mut moderation = Self::new();
+        if !config.keywords.is_empty() || !config.patterns.is_empty() {
+            moderation = moderation.with_check(
+                Box::new(Blocklist::new(&config.keywords, &config.patterns)?),
+                action(&config.blocklist_action)?,
+            );
+        }
+        if !config.pii.is_empty() {
+            moderation = moderation.with_check(
+                Box::new(PiiDetector::new(&config.pii)?),
+                action(&config.pii_action)?,
+            );
+        }
+        Ok(moderation)
+    }
+
+    /// Добавляет проверку в конец конвейера.
+    pub fn with_check(mut self, check: Box<dyn ContentCheck>, action: ModerationAction) -> Self {
+        self.steps.push((check, action));
+        self
+    }
+
+    /// Пропускает текст через все проверки.
+    ///
+    /// Возвращает текст (с масками, если сработал `redact`) или, при
+    /// `block`, причину отказа.
+    pub fn apply<'a>(&self, stage: Stage, text: &'a str) -> Result<Cow<'a, str>, String> {
+        let mut text = Cow::Borrowed(text);
+        for (check, action) in &self.steps {
+            let findings = check.find(&text);
+            if findings.is_empty() {
+                continue;
+            }
+            let labels = labels(&findings);
+            warn!(
+                "Moderation: {} found {} in {:?}, action {}",
+                check.name(),
+                labels,
+                stage,
+                action.as_str()
+            );
+            match action {
+                ModerationAction::Block => {
+                    return Err(format!("{} не прошёл проверку {} ({})", stage, check.name(), labels))
+                }
+                ModerationAction::Redact => text = Cow::Owned(redact(&text, findings)),
+                ModerationAction::Warn => {}
+            }
+        }
+        Ok(text)
+    }
+}
+
+fn action(value: &str) -> Result<ModerationAction, ModerationError> {
+    ModerationAction::parse(value).ok_or_else(|| ModerationError::UnknownAction(value.to_string()))
+}
+
+/// Метки находок без повторов, в порядке появления: "email, телефон".
+fn labels(findings: &[Finding]) -> String {
+    let mut labels: Vec<&str> = Vec::new();
+    for finding in findings {
+        if !labels.contains(&finding.label) {
+            labels.push(finding.label);
+        }
+    }
+    labels.join(", ")
+}
+
+/// Заменяет фрагменты масками `[метка]`; пересекающиеся сливаются в один.
+fn redact(text: &str, mut findings: Vec<Finding>) -> String {
+    findings.sort_by_key(|finding| finding.range.start);
+    let mut redacted = String::with_capacity(text.len());
+    let mut position = 0;
+    for finding in findings {
+        if finding.range.start >= position {
+            redacted.push_str(&text[position..finding.range.start]);
+            redacted.push_str(&format!("[{}]", finding.label));
+        }
+        position = position.max(finding.range.end);
+    }
+    redacted.push_str(&text[position..]);
+    redacted
+}
+
+// ============================================================================
+// ДЕКОРАТОР AI СЕРВИСА
+// ============================================================================
+
+/// AI сервис, который модерирует вопрос до вызова и ответ после.
+///
+/// Как `ConcurrencyLimitedService` и `InstrumentedAiService`, это
+/// декоратор: обработчик `/ask` видит тот же `Box<dyn AiService>`.
+/// Отказ - [`AiServiceError::ContentBlocked`] для вопроса и
+/// [`AiServiceError::AnswerBlocked`] для ответа.
+pub struct ModeratedAiService {
+    inner: Box<dyn AiService>,
+    moderation: Moderation,
+}
+
+impl ModeratedAiService {
+    /// Оборачивает сервис `inner` конвейером `moderation`.
+    pub fn new(inner: Box<dyn AiService>, moderation: Moderation) -> Self {
+        Self { inner, moderation }
+    }
+}
+
+#[async_trait]
+impl AiService for ModeratedAiService {
+    async fn ask(&self, question: &str, ctx: &AskContext) -> Result<String, AiServiceError> {
+        let question = self
+            .moderation
+            .apply(Stage::Question, question)
+            .map_err(AiServiceError::ContentBlocked)?;
+        let answer = self.inner.ask(&question, ctx).await?;
+        match self.moderation.apply(Stage::Answer, &answer) {
+            Ok(Cow::Borrowed(_)) => Ok(answer),
+            Ok(Cow::Owned(redacted)) => Ok(redacted),
+            Err(reason) => Err(AiServiceError::AnswerBlocked(reason)),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn system_prompt_applied(&self) -> bool {
+        self.inner.system_prompt_applied()
+    }
+
+    fn model(&self) -> Option<&str> {
+        self.inner.model()
+    }
+
+    async fn probe(&self) -> Result<(), AiServiceError> {
+        self.inner.probe().await
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn config() -> ModerationConfig {
+        ModerationConfig {
+            enabled: true,
+            keywords: vec!["казино".to_string()],
+            patterns: vec![r"(?i)ответы\s+к\s+экзамену".to_string()],
+            ..ModerationConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_pii_is_redacted() {
+        let moderation = Moderation::from_config(&config()).unwrap();
+        let text = "Пишите на ivan.petrov@mail.ru или +7 (912) 345-67-89, паспорт 45 06 123456";
+        let redacted = moderation.apply(Stage::Question, text).unwrap();
+        assert_eq!(redacted, "Пишите на [email] или [телефон], паспорт [паспорт]");
+
+        for phone in ["89123456789", "8 912 345 67 89", "+44 207 946 09 58"] {
+            assert_eq!(moderation.apply(Stage::Question, phone).unwrap(), "[телефон]", "{phone}");
+        }
+
+        // Обычный текст не меняется и не копируется
+        let clean = moderation.apply(Stage::Question, "Что такое Vec<u8>? Версия 1.75").unwrap();
+        assert!(matches!(clean, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_blocklist() {
+        let moderation = Moderation::from_config(&config()).unwrap();
+        let reason = moderation.apply(Stage::Question, "Лучшее КАЗИНО онлайн").unwrap_err();
+        assert_eq!(reason, "вопрос не прошёл проверку blocklist (стоп-слово)");
+        assert!(moderation.apply(Stage::Answer, "Где ответы к экзамену?").is_err());
+        // Только целые слова: "казинообразный" - не стоп-слово
+        assert!(moderation.apply(Stage::Question, "казинообразный").is_ok());
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut config = config();
+        config.blocklist_action = "redact".to_string();
+        config.pii_action = "warn".to_string();
+        let moderation = Moderation::from_config(&config).unwrap();
+        let text = "казино и a@b.ru";
+        assert_eq!(moderation.apply(Stage::Answer, text).unwrap(), "[стоп-слово] и a@b.ru");
+
+        config.pii_action = "block".to_string();
+        let moderation = Moderation::from_config(&config).unwrap();
+        assert!(moderation.apply(Stage::Answer, text).unwrap_err().contains("email"));
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let mut config = config();
+        config.patterns = vec!["(".to_string()];
+        assert!(matches!(Moderation::from_config(&config), Err(ModerationError::InvalidPattern { .. })));
+
+        let mut config = self::config();
+        config.pii = vec!["snils".to_string()];
+        assert!(matches!(Moderation::from_config(&config), Err(ModerationError::UnknownPii(_))));
+
+        let mut config = self::config();
+        config.pii_action = "mask".to_string();
+        assert!(matches!(Moderation::from_config(&config), Err(ModerationError::UnknownAction(_))));
+    }
+
+    /// Своя проверка подключается в конвейер наравне со встроенными.
+    struct Shouting;
+
+    impl ContentCheck for Shouting {
+        fn name(&self) -> &str {
+            "shouting"
+        }
+
+        fn find(&self, text: &str) -> Vec<Finding> {
+            text.match_indices("!!!")
+                .map(|(start, found)| Finding {
+                    range: start..start + found.len(),
+                    label: "крик",
+                })
+                .collect()
+        }
+    }
+
+    /// Сервис, который запоминает вопросы и отвечает ими же.
+    struct Echo(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl AiService for Echo {
+        async fn ask(&self, question: &str, _ctx: &AskContext) -> Result<String, AiServiceError> {
+            self.0.lock().unwrap().push(question.to_string());
+            Ok(format!("Вы спросили: {question}. Мой телефон 8-800-555-35-35"))
+        }
+
+        fn name(&self) -> &str {
+            "Echo"
+        }
+
+        fn system_prompt_applied(&self) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decorator() {
+        let moderation = Moderation::from_config(&config())
+            .unwrap()
+            .with_check(Box::new(Shouting), ModerationAction::Block);
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let service = ModeratedAiService::new(Box::new(Echo(Arc::clone(&asked))), moderation);
+        let ctx = AskContext::default();
+
+        // В сервис уходит вопрос с маской, ответ тоже маскируется
+        let answer = service.ask("Мой email a@b.ru", &ctx).await.unwrap();
+        assert_eq!(asked.lock().unwrap().as_slice(), ["Мой email [email]"]);
+        assert_eq!(answer, "Вы спросили: Мой email [email]. Мой телефон [телефон]");
+
+        // Заблокированный вопрос до сервиса не доходит
+        let error = service.ask("Ответь!!!", &ctx).await.unwrap_err();
+        assert!(matches!(error, AiServiceError::ContentBlocked(_)));
+        assert_eq!(asked.lock().unwrap().len(), 1);
+        assert_eq!(service.name(), "Echo");
+
+        // Телефон в ответе при pii_action = "block" - отказ уже после вызова
+        let strict = ModerationConfig {
+            pii_action: "block".to_string(),
+            ..config()
+        };
+        let service = ModeratedAiService::new(
+            Box::new(Echo(Arc::clone(&asked))),
+            Moderation::from_config(&strict).unwrap(),
+        );
+        let error = service.ask("Что такое Rust?", &ctx).await.unwrap_err();
+        assert!(matches!(error, AiServiceError::AnswerBlocked(_)));
+        assert_eq!(asked.lock().unwrap().len(), 2);
+    }
+}
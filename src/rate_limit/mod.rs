@@ -0,0 +1,527 @@
+//! Модуль ограничения частоты запросов (rate limiting).
+//!
+//! Один скрипт студента, отправляющий `/ask` в цикле, способен исчерпать
+//! лимиты GigaChat для всей группы. Этот модуль ограничивает частоту
+//! запросов алгоритмом token bucket на трёх уровнях:
+//!
+//! ```text
+//!            ┌─────────────┐   ┌─────────────┐   ┌─────────────┐
+//! запрос ──► │ per_ip      │ ─►│ per_key     │ ─►│ global      │ ─► handler
+//!            └─────────────┘   └─────────────┘   └─────────────┘
+//!                  │ пусто           │ пусто           │ пусто
+//!                  └─────────────────┴─────────────────┴──► 429 Too Many Requests
+//! ```
+//!
+//! Токен списывается, только если он есть на ВСЕХ уровнях - иначе
+//! отклонённый запрос "съедал" бы квоту других уровней.
+//!
+//! # Компоненты
+//!
+//! - [`RateLimiter`] - состояние вёдер (передаётся в Rocket через `.manage()`);
+//! - [`RateLimited`] - request guard, который списывает токен;
+//! - [`RateLimitHeaders`] - fairing, добавляющий заголовки `RateLimit-*`
+//!   и `Retry-After` к ответу.
+//!
+//! # Для студентов: Откуда берётся IP клиента
+//!
+//! За обратным прокси (nginx) все соединения приходят с адреса прокси,
+//! а настоящий IP он передаёт заголовком (`X-Real-IP`, `X-Forwarded-For`).
+//! Но такой заголовок может прислать и сам клиент - и получать новое
+//! ведро на каждый запрос. Поэтому заголовок читается, только если
+//! соединение пришло от прокси из `rate_limit.trusted_proxies`
+//! (см. [`RateLimiter::client_ip`]).
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::Response;
+use tracing::warn;
+
+use crate::auth::{AuthMethod, Principal};
+use crate::config::{BucketConfig, RateLimitConfig};
+
+/// Сколько вёдер хранится в одной таблице. Когда таблица заполнена, новое
+/// ведро вытесняет восстановившиеся, а если таких нет - самое давнее.
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+// ============================================================================
+// TOKEN BUCKET
+// ============================================================================
+
+/// Одно "ведро с токенами".
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(config: &BucketConfig, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(config.capacity),
+            updated: now,
+        }
+    }
+
+    /// Восполняет токены за время, прошедшее с последнего обращения.
+    fn refill(&mut self, config: &BucketConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let rate = f64::from(config.refill_per_minute) / 60.0;
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(config.capacity));
+        self.updated = now;
+    }
+
+    /// Через сколько секунд появится `tokens` токенов.
+    fn seconds_until(&self, config: &BucketConfig, tokens: f64) -> u64 {
+        let missing = (tokens - self.tokens).max(0.0);
+        if missing == 0.0 {
+            return 0;
+        }
+        if config.refill_per_minute == 0 {
+            return u64::MAX;
+        }
+        (missing * 60.0 / f64::from(config.refill_per_minute)).ceil() as u64
+    }
+
+    fn is_full(&self, config: &BucketConfig) -> bool {
+        self.tokens >= f64::from(config.capacity)
+    }
+}
+
+// ============================================================================
+// РЕШЕНИЕ
+// ============================================================================
+
+/// Результат проверки лимита - из него формируются заголовки ответа.
+///
+/// Если действует несколько уровней, в заголовках показывается самый
+/// "тесный" (с наименьшим остатком).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitDecision {
+    /// Пропущен ли запрос
+    pub allowed: bool,
+
+    /// Размер ведра (`RateLimit-Limit`)
+    pub limit: u32,
+
+    /// Сколько запросов осталось прямо сейчас (`RateLimit-Remaining`)
+    pub remaining: u32,
+
+    /// Через сколько секунд ведро полностью восстановится (`RateLimit-Reset`)
+    pub reset_seconds: u64,
+
+    /// Через сколько секунд можно повторить запрос (`Retry-After`), если отклонён
+    pub retry_after_seconds: Option<u64>,
+
+    /// Какой уровень стал ограничивающим: "ip", "key" или "global"
+    pub scope: &'static str,
+}
+
+/// Состояние всех вёдер под одной блокировкой.
+#[derive(Default)]
+struct Buckets {
+    per_ip: HashMap<IpAddr, TokenBucket>,
+    per_key: HashMap<String, TokenBucket>,
+    global: Option<TokenBucket>,
+}
+
+/// Ограничитель частоты запросов.
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    trusted_proxies: Vec<IpAddr>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Создаёт ограничитель по секции `[rate_limit]`.
+    ///
+    /// Адреса `trusted_proxies` проверены при загрузке конфигурации;
+    /// нераспознанный здесь просто не считается прокси.
+    pub fn new(config: RateLimitConfig) -> Self {
+        let trusted_proxies = config
+            .trusted_proxies
+            .iter()
+            .filter_map(|proxy| proxy.parse().ok())
+            .collect();
+        Self {
+            config,
+            trusted_proxies,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// Определяет IP клиента для лимита per_ip.
+    ///
+    /// `remote` - адрес соединения, `header` - значение `ip_header`.
+    /// Заголовку верим, только если соединение пришло от доверенного
+    /// прокси. В `X-Forwarded-For` каждый прокси дописывает адрес в конец,
+    /// поэтому клиент - последний адрес, не принадлежащий нашим прокси:
+    /// всё левее мог подставить сам клиент.
+    pub fn client_ip(&self, remote: Option<IpAddr>, header: Option<&str>) -> Option<IpAddr> {
+        let remote = remote?;
+        if !self.trusted_proxies.contains(&remote) {
+            return Some(remote);
+        }
+        let forwarded = header
+            .into_iter()
+            .flat_map(|value| value.rsplit(','))
+            .map(|address| address.trim().parse::<IpAddr>())
+            .find(|address| {
+                address
+                    .as_ref()
+                    .map_or(true, |address| !self.trusted_proxies.contains(address))
+            });
+        // Мусор в заголовке - не повод делить ведро с прокси: берём адрес прокси
+        Some(forwarded.and_then(Result::ok).unwrap_or(remote))
+    }
+
+    /// Включено ли ограничение.
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Проверяет лимиты и при успехе списывает по токену на каждом уровне.
+    ///
+    /// `now` передаётся явно, чтобы тесты могли "перематывать" время.
+    /// Возвращает `None`, если ни один уровень не настроен.
+    pub fn check(
+        &self,
+        ip: Option<IpAddr>,
+        key: Option<&str>,
+        now: Instant,
+    ) -> Option<RateLimitDecision> {
+        let mut guard = self.buckets.lock().expect("rate limiter lock poisoned");
+        let buckets = &mut *guard;
+
+        // Собираем вёдра всех действующих уровней
+        let mut levels: Vec<(&'static str, &BucketConfig, &mut TokenBucket)> = Vec::new();
+
+        if let Some(config) = &self.config.global {
+            let bucket = buckets
+                .global
+                .get_or_insert_with(|| TokenBucket::full(config, now));
+            levels.push(("global", config, bucket));
+        }
+        if let (Some(config), Some(key)) = (&self.config.per_key, key) {
+            if !buckets.per_key.contains_key(key) {
+                make_room(&mut buckets.per_key, config, now);
+            }
+            let bucket = buckets
+                .per_key
+                .entry(key.to_string())
+                .or_insert_with(|| TokenBucket::full(config, now));
+            levels.push(("key", config, bucket));
+        }
+        if let (Some(config), Some(ip)) = (&self.config.per_ip, ip) {
+            if !buckets.per_ip.contains_key(&ip) {
+                make_room(&mut buckets.per_ip, config, now);
+            }
+            let bucket = buckets
+                .per_ip
+                .entry(ip)
+                .or_insert_with(|| TokenBucket::full(config, now));
+            levels.push(("ip", config, bucket));
+        }
+
+        if levels.is_empty() {
+            return None;
+        }
+
+        for (_, config, bucket) in levels.iter_mut() {
+            bucket.refill(config, now);
+        }
+
+        let allowed = levels.iter().all(|(_, _, bucket)| bucket.tokens >= 1.0);
+        if allowed {
+            for (_, _, bucket) in levels.iter_mut() {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        // Для заголовков выбираем самый "тесный" уровень
+        let (scope, config, bucket) = levels
+            .iter()
+            .min_by(|a, b| a.2.tokens.total_cmp(&b.2.tokens))
+            .expect("levels is not empty");
+
+        Some(RateLimitDecision {
+            allowed,
+            limit: config.capacity,
+            remaining: bucket.tokens.max(0.0).floor() as u32,
+            reset_seconds: bucket.seconds_until(config, f64::from(config.capacity)),
+            retry_after_seconds: (!allowed).then(|| {
+                levels
+                    .iter()
+                    .map(|(_, config, bucket)| bucket.seconds_until(config, 1.0))
+                    .max()
+                    .unwrap_or(1)
+                    .max(1)
+            }),
+            scope,
+        })
+    }
+}
+
+/// Освобождает место для нового ведра, если таблица заполнена, чтобы она
+/// не росла бесконечно от случайных IP.
+///
+/// Сначала удаляются полностью восстановившиеся вёдра: их удаление ничего
+/// не меняет. Если все вёдра заняты (кто-то перебирает адреса быстрее,
+/// чем они восстанавливаются), вытесняется давнее всех использованное.
+fn make_room<K: Clone + Eq + Hash>(
+    map: &mut HashMap<K, TokenBucket>,
+    config: &BucketConfig,
+    now: Instant,
+) {
+    if map.len() < MAX_TRACKED_BUCKETS {
+        return;
+    }
+    map.retain(|_, bucket| {
+        let mut bucket = bucket.clone();
+        bucket.refill(config, now);
+        !bucket.is_full(config)
+    });
+    if map.len() < MAX_TRACKED_BUCKETS {
+        return;
+    }
+    let oldest = map
+        .iter()
+        .min_by_key(|(_, bucket)| bucket.updated)
+        .map(|(key, _)| key.clone());
+    if let Some(oldest) = oldest {
+        map.remove(&oldest);
+    }
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Request guard: списывает токен или отклоняет запрос с 429.
+///
+/// Guard нужно указывать ПОСЛЕ `Authenticated<...>`: тогда лимит per_key
+/// применяется к уже известному клиенту (Rocket вызывает guards слева направо).
+///
+/// ```rust,ignore
+/// pub async fn ask(auth: Authenticated<scopes::Ask>, _limit: RateLimited, ...)
+/// ```
+pub struct RateLimited;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RateLimited {
+    type Error = RateLimitDecision;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(limiter) = req.rocket().state::<RateLimiter>() else {
+            return Outcome::Success(RateLimited);
+        };
+        if !limiter.is_enabled() {
+            return Outcome::Success(RateLimited);
+        }
+
+        let key = req
+            .local_cache(|| None::<Principal>)
+            .as_ref()
+            .filter(|principal| principal.method != AuthMethod::Anonymous)
+            .map(|principal| principal.subject.clone());
+
+        let ip = limiter.client_ip(
+            req.remote().map(|remote| remote.ip()),
+            req.headers().get_one(&limiter.config.ip_header),
+        );
+        let Some(decision) = limiter.check(ip, key.as_deref(), Instant::now()) else {
+            return Outcome::Success(RateLimited);
+        };
+
+        // Сохраняем решение: его прочитают fairing (заголовки) и catcher 429
+        req.local_cache(|| Some(decision.clone()));
+
+        if decision.allowed {
+            Outcome::Success(RateLimited)
+        } else {
+            warn!(
+                "Лимит запросов ({}) превышен: {} {} от {:?}",
+                decision.scope,
+                req.method(),
+                req.uri(),
+                ip
+            );
+            Outcome::Error((Status::TooManyRequests, decision))
+        }
+    }
+}
+
+/// Возвращает решение ограничителя, сохранённое guard'ом в кеше запроса.
+pub fn cached_decision(req: &Request<'_>) -> Option<RateLimitDecision> {
+    req.local_cache(|| None::<RateLimitDecision>).clone()
+}
+
+// ============================================================================
+// FAIRING ДЛЯ ЗАГОЛОВКОВ
+// ============================================================================
+
+/// Fairing: добавляет к ответу стандартные заголовки лимитов.
+///
+/// ```text
+/// RateLimit-Limit: 10
+/// RateLimit-Remaining: 0
+/// RateLimit-Reset: 54
+/// Retry-After: 6          ← только для 429
+/// ```
+pub struct RateLimitHeaders;
+
+#[rocket::async_trait]
+impl Fairing for RateLimitHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limit headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let Some(decision) = cached_decision(req) else {
+            return;
+        };
+
+        res.set_header(Header::new("RateLimit-Limit", decision.limit.to_string()));
+        res.set_header(Header::new("RateLimit-Remaining", decision.remaining.to_string()));
+        res.set_header(Header::new("RateLimit-Reset", decision.reset_seconds.to_string()));
+        if let Some(retry_after) = decision.retry_after_seconds {
+            res.set_header(Header::new("Retry-After", retry_after.to_string()));
+        }
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn bucket(capacity: u32, refill_per_minute: u32) -> Option<BucketConfig> {
+        Some(BucketConfig {
+            capacity,
+            refill_per_minute,
+        })
+    }
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([10, 0, 0, last]))
+    }
+
+    #[test]
+    fn test_per_ip_limit_and_refill() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            per_ip: bucket(2, 60), // 1 токен в секунду
+            ..RateLimitConfig::default()
+        });
+        let start = Instant::now();
+
+        assert!(limiter.check(ip(1), None, start).unwrap().allowed);
+        let second = limiter.check(ip(1), None, start).unwrap();
+        assert!(second.allowed);
+        assert_eq!(second.remaining, 0);
+
+        let rejected = limiter.check(ip(1), None, start).unwrap();
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after_seconds, Some(1));
+        assert_eq!(rejected.scope, "ip");
+
+        // Другой IP не затронут
+        assert!(limiter.check(ip(2), None, start).unwrap().allowed);
+
+        // Через секунду токен восстановился
+        let later = start + Duration::from_secs(1);
+        assert!(limiter.check(ip(1), None, later).unwrap().allowed);
+    }
+
+    #[test]
+    fn test_rejected_request_does_not_consume_other_levels() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            per_key: bucket(1, 1),
+            global: bucket(10, 10),
+            ..RateLimitConfig::default()
+        });
+        let now = Instant::now();
+
+        assert!(limiter.check(None, Some("alice"), now).unwrap().allowed);
+        for _ in 0..5 {
+            let decision = limiter.check(None, Some("alice"), now).unwrap();
+            assert!(!decision.allowed);
+            assert_eq!(decision.scope, "key");
+        }
+
+        // Глобальное ведро потратило только 1 токен из 10
+        let decision = limiter.check(None, Some("bob"), now).unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.scope, "key");
+        let decision = limiter.check(None, None, now).unwrap();
+        assert_eq!(decision.remaining, 7);
+    }
+
+    #[test]
+    fn test_header_is_trusted_only_from_proxy() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            trusted_proxies: vec!["10.0.0.5".to_string()],
+            ..RateLimitConfig::default()
+        });
+        let proxy = ip(5);
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+
+        // Напрямую - заголовок игнорируется
+        assert_eq!(limiter.client_ip(ip(9), Some("203.0.113.7")), ip(9));
+        // Через прокси - последний адрес, который дописал не наш прокси
+        assert_eq!(limiter.client_ip(proxy, Some("203.0.113.7")), Some(client));
+        assert_eq!(
+            limiter.client_ip(proxy, Some("1.2.3.4, 203.0.113.7, 10.0.0.5")),
+            Some(client)
+        );
+        // Без заголовка или с мусором - адрес прокси
+        assert_eq!(limiter.client_ip(proxy, None), proxy);
+        assert_eq!(limiter.client_ip(proxy, Some("unknown")), proxy);
+        assert_eq!(limiter.client_ip(None, Some("203.0.113.7")), None);
+    }
+
+    #[test]
+    fn test_bucket_table_is_capped() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            per_ip: bucket(1, 1),
+            ..RateLimitConfig::default()
+        });
+        let start = Instant::now();
+
+        // Все вёдра пусты: восстановившихся для удаления нет
+        let address = |i: usize| IpAddr::from((i as u32).to_be_bytes());
+        for i in 0..=MAX_TRACKED_BUCKETS {
+            let now = start + Duration::from_millis(i as u64);
+            assert!(limiter.check(Some(address(i)), None, now).unwrap().allowed);
+        }
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.per_ip.len(), MAX_TRACKED_BUCKETS);
+        // Вытеснено давнее всех использованное ведро
+        assert!(!buckets.per_ip.contains_key(&address(0)));
+        assert!(buckets.per_ip.contains_key(&address(MAX_TRACKED_BUCKETS)));
+    }
+
+    #[test]
+    fn test_no_levels_configured() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            ..RateLimitConfig::default()
+        });
+        assert!(limiter.check(ip(1), Some("alice"), Instant::now()).is_none());
+    }
+}
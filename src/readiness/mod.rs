@@ -0,0 +1,302 @@
+//! Модуль проверок живости (liveness) и готовности (readiness).
+//!
+//! Оркестратор (Kubernetes, Serverless Containers) задаёт сервису два
+//! разных вопроса:
+//!
+//! ```text
+//! GET /health/live   "Процесс жив?"        нет → контейнер перезапускают
+//! GET /health/ready  "Можно слать трафик?" нет → запросы идут в другие копии
+//! ```
+//!
+//! Liveness не проверяет зависимости: если GigaChat недоступен,
+//! перезапуск нашего контейнера не поможет. Readiness проверяет:
+//!
+//! - `ai_backend` - активный AI сервис ([`AiService::probe`]); если вместо
+//!   GigaChat работает mock, проверка `degraded` с причиной;
+//! - `jwks` - загружены ли ключи JWT (только при `auth.jwt.enabled`);
+//! - `ai_queue` - не переполнена ли очередь к AI (только при
+//!   `concurrency.enabled`);
+//! - `storage` - отвечает ли постоянное хранилище (модуль `storage`);
+//! - `shutdown` - не останавливается ли сервер (модуль `shutdown`): во время
+//!   плавной остановки `fail`, чтобы балансировщик перестал слать трафик.
+//!
+//! Общий статус - худший из проверок. При `fail` ответ - 503.
+//!
+//! # Для студентов: degraded
+//!
+//! `degraded` означает "работаю, но хуже, чем должен". Такой сервис
+//! остаётся в балансировке (ответ 200), но статус виден в мониторинге.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rocket::request::{FromRequest, Outcome, Request};
+
+use crate::auth::JwtVerifier;
+use crate::concurrency::ConcurrencyStats;
+use crate::config::AppConfig;
+use crate::models::{BackendInfo, CheckStatus, ConcurrencyStatus, DependencyCheck, ReadinessResponse};
+use crate::services::AiService;
+use crate::shutdown::ShutdownState;
+use crate::storage::{self, SharedStorage, Storage, StorageError};
+
+/// Сколько ждать ответа `AiService::probe`.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// ============================================================================
+// ПРОВЕРКИ
+// ============================================================================
+
+fn check(name: &str, status: CheckStatus, detail: Option<String>) -> DependencyCheck {
+    DependencyCheck {
+        name: name.to_string(),
+        status,
+        detail,
+        latency_ms: None,
+    }
+}
+
+/// Проверяет активный AI сервис.
+pub async fn check_ai_backend(service: &dyn AiService, backend: &BackendInfo) -> DependencyCheck {
+    let started = Instant::now();
+    let result = tokio::time::timeout(PROBE_TIMEOUT, service.probe()).await;
+
+    let mut check = match result {
+        Ok(Ok(())) => match &backend.fallback_reason {
+            Some(reason) => check("ai_backend", CheckStatus::Degraded, Some(reason.clone())),
+            None => check("ai_backend", CheckStatus::Ok, None),
+        },
+        Ok(Err(e)) => check("ai_backend", CheckStatus::Fail, Some(e.to_string())),
+        Err(_) => check(
+            "ai_backend",
+            CheckStatus::Fail,
+            Some(format!("probe timed out after {}s", PROBE_TIMEOUT.as_secs())),
+        ),
+    };
+    check.latency_ms = Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX));
+    check
+}
+
+/// Проверяет набор ключей JWT.
+pub fn check_jwks(verifier: Option<&JwtVerifier>) -> DependencyCheck {
+    match verifier.map(JwtVerifier::key_count) {
+        None => check("jwks", CheckStatus::Fail, Some("JWKS is not loaded".to_string())),
+        Some(0) => check("jwks", CheckStatus::Fail, Some("JWKS has no keys".to_string())),
+        Some(count) => check("jwks", CheckStatus::Ok, Some(format!("{} keys", count))),
+    }
+}
+
+/// Проверяет очередь к AI: полная очередь означает отказы `SERVER_BUSY`.
+pub fn check_queue(queue: &ConcurrencyStatus) -> DependencyCheck {
+    let detail = format!(
+        "{}/{} in flight, {}/{} queued",
+        queue.in_flight, queue.max_concurrent, queue.queue_depth, queue.max_queue
+    );
+    let status = if queue.queue_depth >= queue.max_queue && queue.in_flight >= queue.max_concurrent {
+        CheckStatus::Degraded
+    } else {
+        CheckStatus::Ok
+    };
+    check("ai_queue", status, Some(detail))
+}
+
+/// Проверяет постоянное хранилище.
+pub fn check_storage(storage: &dyn Storage) -> DependencyCheck {
+    let started = Instant::now();
+    let mut check = match storage.ping() {
+        Ok(()) => check("storage", CheckStatus::Ok, Some(storage.describe())),
+        Err(e) => check("storage", CheckStatus::Fail, Some(e.to_string())),
+    };
+    check.latency_ms = Some(u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX));
+    check
+}
+
+/// Проверяет, не идёт ли плавная остановка.
+pub fn check_shutdown(state: &ShutdownState) -> DependencyCheck {
+    let detail = format!("{} requests in flight", state.in_flight());
+    if state.is_draining() {
+        check("shutdown", CheckStatus::Fail, Some(format!("shutting down, {}", detail)))
+    } else {
+        check("shutdown", CheckStatus::Ok, Some(detail))
+    }
+}
+
+/// Общий статус - худший из статусов проверок.
+pub fn overall_status(checks: &[DependencyCheck]) -> CheckStatus {
+    checks
+        .iter()
+        .map(|check| check.status)
+        .max()
+        .unwrap_or(CheckStatus::Ok)
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Необязательные зависимости, которые проверяет `/health/ready`.
+///
+/// Как и [`crate::concurrency::QueueSnapshot`], достаёт их из state
+/// без `&State<T>`: часть из них появляется только при включённой настройке.
+/// Guard никогда не отклоняет запрос.
+pub struct Dependencies<'r> {
+    jwt_enabled: bool,
+    jwt: Option<&'r JwtVerifier>,
+    queue: Option<ConcurrencyStatus>,
+    storage: Option<&'r SharedStorage>,
+    shutdown: Option<&'r ShutdownState>,
+}
+
+impl Dependencies<'_> {
+    /// Выполняет все проверки.
+    pub async fn check(&self, service: &dyn AiService, backend: &BackendInfo) -> ReadinessResponse {
+        let mut checks = vec![check_ai_backend(service, backend).await];
+        if self.jwt_enabled {
+            checks.push(check_jwks(self.jwt));
+        }
+        if let Some(queue) = &self.queue {
+            checks.push(check_queue(queue));
+        }
+        if let Some(storage) = self.storage {
+            // ping ждёт занятую базу - не на рабочем потоке tokio
+            let probe = storage::blocking(storage, |storage| {
+                Ok::<_, StorageError>(check_storage(storage))
+            });
+            checks.push(probe.await.unwrap_or_else(|e| {
+                check("storage", CheckStatus::Fail, Some(e.to_string()))
+            }));
+        }
+        if let Some(shutdown) = self.shutdown {
+            checks.push(check_shutdown(shutdown));
+        }
+
+        let status = match overall_status(&checks) {
+            CheckStatus::Ok => "ok",
+            CheckStatus::Degraded => "degraded",
+            CheckStatus::Fail => "unavailable",
+        };
+        ReadinessResponse {
+            status: status.to_string(),
+            backend: backend.clone(),
+            checks,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Dependencies<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let rocket = req.rocket();
+        Outcome::Success(Dependencies {
+            jwt_enabled: rocket
+                .state::<AppConfig>()
+                .is_some_and(|config| config.auth.jwt.enabled),
+            jwt: rocket.state::<JwtVerifier>(),
+            queue: rocket
+                .state::<Arc<ConcurrencyStats>>()
+                .map(|stats| stats.snapshot()),
+            storage: rocket.state::<SharedStorage>(),
+            shutdown: rocket.state::<ShutdownState>(),
+        })
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{AiServiceError, AskContext, MockAiService};
+    use async_trait::async_trait;
+
+    struct UnreachableService;
+
+    #[async_trait]
+    impl AiService for UnreachableService {
+        async fn ask(&self, _question: &str, _ctx: &AskContext) -> Result<String, AiServiceError> {
+            unreachable!()
+        }
+
+        fn name(&self) -> &str {
+            "Unreachable"
+        }
+
+        fn system_prompt_applied(&self) -> bool {
+            false
+        }
+
+        async fn probe(&self) -> Result<(), AiServiceError> {
+            Err(AiServiceError::InternalError("connection refused".to_string()))
+        }
+    }
+
+    fn backend(fallback_reason: Option<&str>) -> BackendInfo {
+        BackendInfo::new(&MockAiService::new(), fallback_reason.map(str::to_string))
+    }
+
+    #[tokio::test]
+    async fn test_ai_backend_check() {
+        let check = check_ai_backend(&MockAiService::new(), &backend(None)).await;
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert!(check.latency_ms.is_some());
+
+        let check = check_ai_backend(&MockAiService::new(), &backend(Some("token missing"))).await;
+        assert_eq!(check.status, CheckStatus::Degraded);
+        assert_eq!(check.detail.as_deref(), Some("token missing"));
+
+        let check = check_ai_backend(&UnreachableService, &backend(None)).await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.unwrap().contains("connection refused"));
+    }
+
+    #[test]
+    fn test_queue_check_and_overall_status() {
+        let mut queue = ConcurrencyStatus {
+            max_concurrent: 2,
+            max_queue: 1,
+            in_flight: 2,
+            queue_depth: 0,
+            avg_wait_ms: 0,
+            max_wait_ms: 0,
+            rejected_total: 0,
+            timed_out_total: 0,
+        };
+        let free = check_queue(&queue);
+        assert_eq!(free.status, CheckStatus::Ok);
+
+        queue.queue_depth = 1;
+        let full = check_queue(&queue);
+        assert_eq!(full.status, CheckStatus::Degraded);
+
+        let jwks = check_jwks(None);
+        assert_eq!(jwks.status, CheckStatus::Fail);
+
+        assert_eq!(overall_status(&[]), CheckStatus::Ok);
+        assert_eq!(overall_status(&[free.clone(), full.clone()]), CheckStatus::Degraded);
+        assert_eq!(overall_status(&[free, full, jwks]), CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_storage_check() {
+        let check = check_storage(&crate::storage::MemoryStorage::new());
+        assert_eq!(check.name, "storage");
+        assert_eq!(check.status, CheckStatus::Ok);
+        assert!(check.detail.unwrap().starts_with("memory"));
+        assert!(check.latency_ms.is_some());
+    }
+
+    #[test]
+    fn test_shutdown_check() {
+        let state = ShutdownState::new();
+        assert_eq!(check_shutdown(&state).status, CheckStatus::Ok);
+
+        state.begin();
+        let check = check_shutdown(&state);
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.unwrap().starts_with("shutting down"));
+    }
+}
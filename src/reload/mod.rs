@@ -0,0 +1,548 @@
+//! Модуль перезагрузки конфигурации без перезапуска сервера.
+//!
+//! На занятии преподаватель меняет `application.system_prompt`, модель или
+//! температуру в config.toml - и новые вопросы сразу идут с новыми
+//! настройками. Перезагрузку запускают:
+//!
+//! - `POST /admin/reload` (scope `admin`);
+//! - сигнал SIGHUP (`kill -HUP <pid>`, при `reload.sighup = true`);
+//! - изменение файла (при `reload.watch = true`).
+//!
+//! ```text
+//!                  ┌──────────────── LiveRuntime ────────────────┐
+//! запрос 1 ──────► │ Arc<Runtime> v1 (config, AI сервис, backend) │
+//!                  └──────────────────────┬──────────────────────┘
+//! reload: load → validate → build v2 ─────┘ подмена указателя
+//!                  ┌─────────────────────────────────────────────┐
+//! запрос 2 ──────► │ Arc<Runtime> v2                              │
+//!                  └─────────────────────────────────────────────┘
+//! ```
+//!
+//! Запрос 1 держит свой `Arc` и спокойно дорабатывает со старым сервисом;
+//! v1 освобождается, когда завершится последний такой запрос.
+//!
+//! Без перезапуска применяются секции `[application]`, `[gigachat]`,
+//! `[experiment]`, `[guardrails]` и `[moderation]`. Остальные (адрес
+//! сервера, ключи, лимиты, CORS, логи...) прочитаны при запуске: если они
+//! изменились, ответ и лог перечисляют их в `restart_required`. Лимитер
+//! очереди и метрики общие для всех версий.
+//!
+//! # Для студентов: `RwLock<Arc<T>>`
+//!
+//! `RwLock` защищает только сам указатель: читатель берёт блокировку на
+//! мгновение, чтобы склонировать `Arc`, и дальше работает без блокировок.
+//! Запись - это замена одного `Arc` на другой, тоже мгновенная.
+
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rocket::fairing::AdHoc;
+use rocket::Shutdown;
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+use crate::concurrency::ConcurrencyLimiter;
+use crate::config::{AppConfig, ConfigError, ReloadConfig, ValidationReport};
+use crate::metrics::{InstrumentedAiService, Metrics};
+use crate::models::{BackendInfo, ReloadResponse};
+use crate::moderation::{ModeratedAiService, Moderation};
+use crate::secrets::SecretError;
+use crate::services::{AiService, AiServiceFactory};
+
+/// Секции, которые применяются без перезапуска.
+pub const RELOADABLE_SECTIONS: &[&str] = &[
+    "application",
+    "gigachat",
+    "experiment",
+    "guardrails",
+    "moderation",
+];
+
+/// Ошибки перезагрузки. Текущая конфигурация при ошибке не меняется.
+#[derive(Error, Debug)]
+pub enum ReloadError {
+    /// Файл не читается или не соответствует структуре `AppConfig`
+    #[error(transparent)]
+    Load(#[from] ConfigError),
+
+    /// Значения прочитаны, но недопустимы (см. [`AppConfig::validate`])
+    #[error(transparent)]
+    Invalid(#[from] ValidationReport),
+
+    /// Поток перезагрузки завершился паникой
+    #[error("Перезагрузка прервана: {0}")]
+    Interrupted(String),
+}
+
+// ============================================================================
+// ТЕКУЩЕЕ СОСТОЯНИЕ
+// ============================================================================
+
+/// Согласованный набор: конфигурация и созданный по ней AI сервис.
+pub struct Runtime {
+    /// Конфигурация, по которой создан сервис
+    pub config: AppConfig,
+
+    /// AI сервис со всеми декораторами (метрики, очередь)
+    pub ai_service: Box<dyn AiService>,
+
+    /// Описание активного бэкенда для `/health`
+    pub backend: BackendInfo,
+
+    /// Номер версии: 1 при запуске, +1 за каждую перезагрузку
+    pub generation: u64,
+}
+
+impl Runtime {
+    /// Первая версия состояния.
+    pub fn new(
+        config: AppConfig,
+        ai_service: Box<dyn AiService>,
+        fallback_reason: Option<String>,
+    ) -> Self {
+        let backend = BackendInfo::new(ai_service.as_ref(), fallback_reason);
+        Self {
+            config,
+            ai_service,
+            backend,
+            generation: 1,
+        }
+    }
+}
+
+/// Текущий [`Runtime`], который можно подменить на лету.
+///
+/// Хранится в Rocket через `.manage()`. Клонирование дешёвое: копии
+/// указывают на одно и то же состояние.
+#[derive(Clone)]
+pub struct LiveRuntime(Arc<RwLock<Arc<Runtime>>>);
+
+impl LiveRuntime {
+    /// Создаёт хранилище с начальным состоянием.
+    pub fn new(runtime: Runtime) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(runtime))))
+    }
+
+    /// Снимок текущего состояния; обработчик держит его до конца запроса.
+    pub fn current(&self) -> Arc<Runtime> {
+        let current = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(&current)
+    }
+
+    fn replace(&self, runtime: Runtime) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(runtime);
+    }
+}
+
+// ============================================================================
+// СБОРКА AI СЕРВИСА
+// ============================================================================
+
+/// Выбирает AI бэкенд по конфигурации.
+///
+/// Вместе с сервисом возвращает причину, по которой вместо GigaChat
+/// выбран mock (видна в `/health` и `/health/ready`).
+pub fn select_backend(config: &AppConfig) -> (Box<dyn AiService>, Option<String>) {
+    if !config.is_gigachat_enabled() {
+        info!("ℹ️  GigaChat API отключён в конфигурации, используем mock mode");
+        return (AiServiceFactory::create(&config.gigachat, None, None), None);
+    }
+
+    let system_prompt = if config.application.system_prompt.trim().is_empty() {
+        None
+    } else {
+        Some(config.application.system_prompt.clone())
+    };
+
+    match config.get_gigachat_token() {
+        Ok(_) if !cfg!(feature = "gigachat") => {
+            error!("⚠️  Сборка без фичи gigachat - используем mock mode");
+            (
+                AiServiceFactory::create(&config.gigachat, None, None),
+                Some("built without the gigachat feature, using mock".to_string()),
+            )
+        }
+        Ok(token) => {
+            info!("✅ Токен GigaChat найден, используем реальный API");
+            (
+                AiServiceFactory::create(&config.gigachat, Some(token), system_prompt),
+                None,
+            )
+        }
+        Err(ConfigError::SecretError(SecretError::Missing(_))) => {
+            // Токен не найден, но это НЕ фатальная ошибка - используем mock
+            error!("⚠️  Токен GigaChat не найден (GIGACHAT_TOKEN, GIGACHAT_TOKEN_FILE или файл секретов)");
+            info!("💡 Переключаемся на mock mode");
+            (
+                AiServiceFactory::create(&config.gigachat, None, None),
+                Some("GIGACHAT_TOKEN is not set, using mock".to_string()),
+            )
+        }
+        Err(e) => {
+            // Токен задан, но не читается (нет файла, неверный пароль)
+            error!("⚠️  Токен GigaChat недоступен: {}", e);
+            info!("💡 Переключаемся на mock mode");
+            (
+                AiServiceFactory::create(&config.gigachat, None, None),
+                Some("GigaChat token is unavailable, using mock".to_string()),
+            )
+        }
+    }
+}
+
+/// Декораторы, общие для всех версий AI сервиса.
+///
+/// Метрики и очередь создаются один раз при запуске: после перезагрузки
+/// счётчики не обнуляются, а старые и новые запросы делят одни слоты.
+#[derive(Default)]
+pub struct AiStack {
+    metrics: Option<Arc<Metrics>>,
+    limiter: Option<ConcurrencyLimiter>,
+}
+
+impl AiStack {
+    /// Декораторы из секций `[metrics]` и `[concurrency]` (`None` - выключены).
+    pub fn new(metrics: Option<Arc<Metrics>>, limiter: Option<ConcurrencyLimiter>) -> Self {
+        Self { metrics, limiter }
+    }
+
+    /// Создаёт AI сервис по конфигурации и оборачивает его декораторами.
+    ///
+    /// Метрики оборачивают сервис ДО очереди: время ожидания в очереди
+    /// не попадает в задержку вызова AI, а отказы SERVER_BUSY - в ошибки AI.
+    /// Модерация (секция `[moderation]`) - самый внешний слой: отклонённый
+    /// вопрос не ждёт в очереди. Она собирается заново при каждой
+    /// перезагрузке, поэтому стоп-листы меняются без перезапуска.
+    pub fn build(&self, config: AppConfig) -> Runtime {
+        let (service, fallback_reason) = select_backend(&config);
+        let service: Box<dyn AiService> = match &self.metrics {
+            Some(metrics) => Box::new(InstrumentedAiService::new(service, Arc::clone(metrics))),
+            None => service,
+        };
+        let service: Box<dyn AiService> = match &self.limiter {
+            Some(limiter) => Box::new(limiter.wrap(service)),
+            None => service,
+        };
+        let service: Box<dyn AiService> = if config.moderation.enabled {
+            let moderation = Moderation::from_config(&config.moderation)
+                .expect("секция [moderation] проверена при загрузке конфигурации");
+            Box::new(ModeratedAiService::new(service, moderation))
+        } else {
+            service
+        };
+        Runtime::new(config, service, fallback_reason)
+    }
+}
+
+// ============================================================================
+// ПЕРЕЗАГРУЗКА
+// ============================================================================
+
+/// Имена секций, различающихся в двух конфигурациях.
+pub fn changed_sections(old: &AppConfig, new: &AppConfig) -> Vec<&'static str> {
+    [
+        ("server", old.server != new.server),
+        ("gigachat", old.gigachat != new.gigachat),
+        ("logging", old.logging != new.logging),
+        ("application", old.application != new.application),
+        ("auth", old.auth != new.auth),
+        ("rate_limit", old.rate_limit != new.rate_limit),
+        ("concurrency", old.concurrency != new.concurrency),
+        ("cors", old.cors != new.cors),
+        ("metrics", old.metrics != new.metrics),
+        ("telemetry", old.telemetry != new.telemetry),
+        ("shutdown", old.shutdown != new.shutdown),
+        ("reload", old.reload != new.reload),
+        ("storage", old.storage != new.storage),
+        ("templates", old.templates != new.templates),
+        ("experiment", old.experiment != new.experiment),
+        ("guardrails", old.guardrails != new.guardrails),
+        ("moderation", old.moderation != new.moderation),
+    ]
+    .into_iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(name, _)| name)
+    .collect()
+}
+
+/// Перечитывает config.toml и подменяет [`LiveRuntime`].
+///
+/// Хранится в Rocket через `.manage()`; используется обработчиком
+/// `POST /admin/reload` и фоновой задачей из [`fairing`].
+#[derive(Clone)]
+pub struct Reloader {
+    live: LiveRuntime,
+    stack: Arc<AiStack>,
+    // Две перезагрузки одновременно (SIGHUP + запрос) выполняются по очереди
+    lock: Arc<Mutex<()>>,
+}
+
+impl Reloader {
+    /// Создаёт перезагрузчик для `live`; новые сервисы получают декораторы `stack`.
+    pub fn new(live: LiveRuntime, stack: AiStack) -> Self {
+        Self {
+            live,
+            stack: Arc::new(stack),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Загружает конфигурацию из файла и применяет её.
+    pub fn reload(&self) -> Result<ReloadResponse, ReloadError> {
+        self.apply(AppConfig::load()?)
+    }
+
+    /// Как [`Reloader::reload`], но в пуле блокирующих потоков tokio.
+    ///
+    /// Перезагрузка читает файлы, расшифровывает секреты и ждёт мьютекс
+    /// другой перезагрузки - на рабочем потоке tokio это задержало бы
+    /// остальные запросы. Так вызывают `POST /admin/reload` и фоновая задача.
+    pub async fn reload_async(&self) -> Result<ReloadResponse, ReloadError> {
+        let reloader = self.clone();
+        tokio::task::spawn_blocking(move || reloader.reload())
+            .await
+            .unwrap_or_else(|e| Err(ReloadError::Interrupted(e.to_string())))
+    }
+
+    /// Применяет уже загруженную конфигурацию.
+    pub fn apply(&self, loaded: AppConfig) -> Result<ReloadResponse, ReloadError> {
+        loaded.validate()?;
+        let _lock = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let old = self.live.current();
+        let (changed, restart_required): (Vec<_>, Vec<_>) = changed_sections(&old.config, &loaded)
+            .into_iter()
+            .partition(|section| RELOADABLE_SECTIONS.contains(section));
+        if !restart_required.is_empty() {
+            warn!(
+                "⚠️  Изменения в секциях {:?} вступят в силу после перезапуска",
+                restart_required
+            );
+        }
+
+        if changed.is_empty() {
+            return Ok(ReloadResponse {
+                status: "unchanged".to_string(),
+                generation: old.generation,
+                backend: old.backend.clone(),
+                changed: Vec::new(),
+                restart_required: restart_required.into_iter().map(str::to_string).collect(),
+            });
+        }
+
+        // Секции, требующие перезапуска, остаются прежними: состояние
+        // должно описывать то, что реально работает
+        let mut config = old.config.clone();
+        config.application = loaded.application;
+        config.gigachat = loaded.gigachat;
+        config.experiment = loaded.experiment;
+        config.guardrails = loaded.guardrails;
+        config.moderation = loaded.moderation;
+        config.origin.adopt(&loaded.origin, RELOADABLE_SECTIONS);
+        // Файл проверен целиком, но остальные секции берутся из работающей
+        // конфигурации: `experiment.enabled` из файла с `auth.enabled = true`
+        // не должен включиться на сервере, где аутентификация выключена
+        config.validate()?;
+
+        let mut runtime = self.stack.build(config);
+        runtime.generation = old.generation + 1;
+        let response = ReloadResponse {
+            status: "reloaded".to_string(),
+            generation: runtime.generation,
+            backend: runtime.backend.clone(),
+            changed: changed.into_iter().map(str::to_string).collect(),
+            restart_required: restart_required.into_iter().map(str::to_string).collect(),
+        };
+        self.live.replace(runtime);
+
+        info!(
+            "🔄 Конфигурация перезагружена (версия {}, секции {:?}, бэкенд {})",
+            response.generation,
+            response.changed,
+            response.backend.name
+        );
+        Ok(response)
+    }
+}
+
+// ============================================================================
+// SIGHUP И СЛЕЖЕНИЕ ЗА ФАЙЛОМ
+// ============================================================================
+
+/// Подписка на SIGHUP; на других платформах сигнал никогда не приходит.
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new(enabled: bool) -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let signal = if enabled {
+                signal(SignalKind::hangup())
+                    .map_err(|e| warn!("⚠️  Не удалось подписаться на SIGHUP: {}", e))
+                    .ok()
+            } else {
+                None
+            };
+            Self { signal }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = enabled;
+            Self {}
+        }
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            signal.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+fn modified_at(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok())
+        .collect()
+}
+
+/// Ждёт SIGHUP или изменения файлов и перезагружает конфигурацию.
+///
+/// `files` - config.toml и файл профиля, если он есть.
+async fn watch(reloader: Reloader, settings: ReloadConfig, files: Vec<String>, mut shutdown: Shutdown) {
+    let mut modified = modified_at(&files);
+    let mut hangup = Hangup::new(settings.sighup);
+    let mut ticker = tokio::time::interval(Duration::from_secs(settings.poll_interval_seconds.max(1)));
+
+    loop {
+        let trigger = tokio::select! {
+            _ = &mut shutdown => return,
+            _ = hangup.recv() => "SIGHUP",
+            _ = ticker.tick(), if settings.watch => {
+                let current = modified_at(&files);
+                if current == modified {
+                    continue;
+                }
+                modified = current;
+                "file changed"
+            }
+        };
+
+        info!("🔄 Перезагрузка конфигурации: {}", trigger);
+        if let Err(e) = reloader.reload_async().await {
+            error!("❌ Конфигурация не перезагружена, работаем со старой: {}", e);
+        }
+    }
+}
+
+/// Fairing, запускающий после старта фоновую задачу перезагрузки.
+///
+/// Задача завершается вместе с сервером (через `rocket::Shutdown`).
+pub fn fairing() -> AdHoc {
+    AdHoc::on_liftoff("Config reload", |rocket| {
+        Box::pin(async move {
+            let (Some(reloader), Some(config)) =
+                (rocket.state::<Reloader>().cloned(), rocket.state::<AppConfig>())
+            else {
+                return;
+            };
+            // Секция [reload] сама применяется только при запуске
+            let settings = config.reload.clone();
+            let files = config.origin.files.clone();
+            if settings.watch {
+                info!(
+                    "👀 Слежение за {} (каждые {}s)",
+                    files.join(", "),
+                    settings.poll_interval_seconds
+                );
+            }
+            tokio::spawn(watch(reloader, settings, files, rocket.shutdown()));
+        })
+    })
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::MockAiService;
+
+    fn reloader() -> (Reloader, LiveRuntime) {
+        let config = AppConfig::load().expect("Failed to load config");
+        let live = LiveRuntime::new(Runtime::new(config, Box::new(MockAiService::new()), None));
+        (Reloader::new(live.clone(), AiStack::default()), live)
+    }
+
+    #[test]
+    fn test_unchanged_config_keeps_generation() {
+        let (reloader, live) = reloader();
+        let config = live.current().config.clone();
+
+        let response = reloader.apply(config).unwrap();
+        assert_eq!(response.status, "unchanged");
+        assert_eq!(response.generation, 1);
+    }
+
+    #[test]
+    fn test_reload_swaps_runtime() {
+        let (reloader, live) = reloader();
+        let before = live.current();
+
+        let mut config = before.config.clone();
+        config.application.system_prompt = "Отвечай стихами".to_string();
+        config.experiment.name = "rhymes".to_string();
+        config.server.port += 1;
+
+        let response = reloader.apply(config).unwrap();
+        assert_eq!(response.status, "reloaded");
+        assert_eq!(response.generation, 2);
+        assert_eq!(response.changed, vec!["application", "experiment"]);
+        assert_eq!(response.restart_required, vec!["server"]);
+
+        let after = live.current();
+        assert_eq!(after.config.application.system_prompt, "Отвечай стихами");
+        assert_eq!(after.config.experiment.name, "rhymes");
+        // Порт без перезапуска не меняется
+        assert_eq!(after.config.server.port, before.config.server.port);
+        // Старый снимок жив, пока его держат
+        assert_eq!(before.generation, 1);
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let (reloader, live) = reloader();
+        let mut config = live.current().config.clone();
+        config.gigachat.temperature = 5.0;
+
+        assert!(matches!(reloader.apply(config), Err(ReloadError::Invalid(_))));
+        assert_eq!(live.current().generation, 1);
+    }
+    #[test]
+    fn test_merged_config_is_validated() {
+        // auth меняется только перезапуском: в файле она включена, а в
+        // работающем сервере - нет, поэтому эксперимент не применяется
+        let (reloader, live) = reloader();
+        assert!(!live.current().config.auth.enabled);
+        let mut config = live.current().config.clone();
+        config.auth.enabled = true;
+        config.experiment.enabled = true;
+
+        let Err(ReloadError::Invalid(report)) = reloader.apply(config) else {
+            panic!("эксперимент включён без аутентификации");
+        };
+        assert!(report.issues.iter().any(|issue| issue.path == "experiment.enabled"), "{report}");
+        assert!(!live.current().config.experiment.enabled);
+        assert_eq!(live.current().generation, 1);
+    }
+}
@@ -0,0 +1,207 @@
+//! Модуль идентификаторов запросов (request ID) и span'ов трассировки.
+//!
+//! Когда сервер обрабатывает много запросов одновременно, их строки в логе
+//! перемешиваются, и непонятно, к какому запросу относится "Error getting
+//! answer". Решение - у каждого запроса свой идентификатор:
+//!
+//! ```text
+//! клиент ── X-Request-Id: abc (необязательно) ──► RequestTracing (fairing)
+//!                                                    │ нет заголовка → UUID v4
+//!                                                    ▼
+//!                            span "request" {request_id, method, uri, ...}
+//!                                                    │
+//!                  handler, ErrorResponse, AiService получают тот же ID
+//!                                                    ▼
+//! клиент ◄── X-Request-Id: abc ── лог "request completed" {status, latency_ms}
+//! ```
+//!
+//! # Для студентов: Span в tracing
+//!
+//! Span - это "отрезок" выполнения программы с набором полей. Все события
+//! (`info!`, `error!`), записанные внутри span'а, наследуют его поля, поэтому
+//! в логе у каждой строки будет `request_id=...`.
+//!
+//! Асинхронный код нельзя просто "войти" в span: future может заснуть на
+//! `.await` и продолжиться в другом потоке. Для этого есть
+//! `future.instrument(span)` - span входит при каждом опросе future.
+
+use std::fmt;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{Data, Response};
+use tracing::{field, info, info_span, Span};
+use uuid::Uuid;
+
+use crate::telemetry;
+
+/// Имя HTTP-заголовка с идентификатором запроса.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Максимальная длина принимаемого от клиента идентификатора.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+// ============================================================================
+// ИДЕНТИФИКАТОР
+// ============================================================================
+
+/// Идентификатор запроса вместе с его span'ом.
+///
+/// Используется как request guard: `request_id: RequestId` в параметрах
+/// обработчика. Guard никогда не отклоняет запрос.
+#[derive(Debug, Clone)]
+pub struct RequestId {
+    id: String,
+    span: Span,
+}
+
+impl RequestId {
+    /// Идентификатор текущего запроса (создаётся при первом обращении).
+    ///
+    /// Обычно его создаёт fairing [`RequestTracing`] до маршрутизации.
+    /// Без fairing'а идентификатор всё равно будет один на запрос -
+    /// его создаст первый обратившийся guard или catcher.
+    pub fn of<'r>(req: &'r Request<'_>) -> &'r RequestId {
+        &req.local_cache(|| RequestTrace::start(req)).id
+    }
+
+    /// Строковое значение идентификатора.
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    /// Span запроса - для `.instrument()` в асинхронных обработчиках.
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.id)
+    }
+}
+
+/// Принимает идентификатор клиента, только если он "безопасен" для логов:
+/// не пустой, не длиннее 128 символов, из букв, цифр и `-_.:`.
+///
+/// Иначе клиент мог бы подделать строки лога переводом строки
+/// или раздуть лог мегабайтным заголовком.
+pub fn is_valid_request_id(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= MAX_REQUEST_ID_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+// ============================================================================
+// СОСТОЯНИЕ ЗАПРОСА
+// ============================================================================
+
+/// То, что хранится в `req.local_cache` на время запроса.
+struct RequestTrace {
+    id: RequestId,
+    started: Instant,
+}
+
+impl RequestTrace {
+    fn start(req: &Request<'_>) -> Self {
+        let id = req
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .filter(|value| is_valid_request_id(value))
+            .map(str::to_string)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        // Пустые поля (field::Empty) заполняются в конце запроса.
+        // otel.kind - тип span'а для OpenTelemetry (модуль telemetry)
+        let span = info_span!(
+            "request",
+            otel.kind = "server",
+            request_id = %id,
+            method = %req.method(),
+            uri = %req.uri().path(),
+            route = field::Empty,
+            status = field::Empty,
+            latency_ms = field::Empty,
+        );
+        // Продолжаем трассу шлюза, если он прислал traceparent
+        telemetry::set_remote_parent(&span, req.headers());
+
+        RequestTrace {
+            id: RequestId { id, span },
+            started: Instant::now(),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(req).clone())
+    }
+}
+
+// ============================================================================
+// FAIRING
+// ============================================================================
+
+/// Fairing: назначает идентификатор, пишет итог запроса в лог и
+/// возвращает `X-Request-Id` клиенту.
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request ID and tracing span",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        // Создаём идентификатор до маршрутизации, чтобы время запроса
+        // считалось с самого начала
+        RequestId::of(req);
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let trace = req.local_cache(|| RequestTrace::start(req));
+        let span = trace.id.span();
+
+        let latency_ms = trace.started.elapsed().as_secs_f64() * 1000.0;
+        if let Some(route) = req.route() {
+            span.record("route", field::display(&route.uri));
+        }
+        span.record("status", res.status().code);
+        span.record("latency_ms", field::display(format_args!("{latency_ms:.1}")));
+        span.in_scope(|| info!("request completed"));
+
+        res.set_header(Header::new(REQUEST_ID_HEADER, trace.id.as_str().to_string()));
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_id_validation() {
+        assert!(is_valid_request_id("3f1c2a9e-5b7d-4e1f-9a0b-1c2d3e4f5a6b"));
+        assert!(is_valid_request_id("frontend:42.retry_1"));
+
+        assert!(!is_valid_request_id(""));
+        assert!(!is_valid_request_id("line\ninjection"));
+        assert!(!is_valid_request_id("with space"));
+        assert!(!is_valid_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,383 @@
+//! Модуль секретов: токены и ключи, которые нельзя показывать.
+//!
+//! Секрет ищется по имени (например, `GIGACHAT_TOKEN`) в трёх местах,
+//! первое найденное значение побеждает:
+//!
+//! ```text
+//! 1. GIGACHAT_TOKEN=...            - переменная окружения
+//! 2. GIGACHAT_TOKEN_FILE=/run/...  - путь к файлу (Docker/Kubernetes secrets)
+//! 3. secrets.file = "secrets.enc"  - локальный зашифрованный файл секретов
+//! ```
+//!
+//! Найденное значение хранится в обёртке [`Secret`]: её `Debug`, `Display`
+//! и `Serialize` выводят `***`, поэтому секрет не попадёт ни в логи,
+//! ни в `GET /admin/config`, даже если всю конфигурацию вывести через `{:?}`.
+//! Само значение доступно только явным вызовом [`Secret::expose`].
+//!
+//! # Зашифрованный файл секретов
+//!
+//! Файл создаётся командой `--seal-secrets` из обычного файла в формате
+//! `.env` (`GIGACHAT_TOKEN=...`), который после этого можно удалить:
+//!
+//! ```bash
+//! export SECRETS_PASSPHRASE="длинная фраза"
+//! cargo run -- --seal-secrets secrets.env secrets.enc
+//! ```
+//!
+//! Ключ шифрования выводится из пароля (`SECRETS_PASSPHRASE` или
+//! `SECRETS_PASSPHRASE_FILE`) через PBKDF2-HMAC-SHA256, данные шифруются
+//! AES-256-GCM. Неверный пароль или повреждённый файл дают ошибку, а не мусор.
+//!
+//! # Для студентов: Почему обёртка, а не String?
+//!
+//! `#[derive(Debug)]` на структуре конфигурации выводит ВСЕ поля. Одна
+//! строка `debug!("{:?}", config)` - и токен в логах, а логи уходят в
+//! агрегатор, к которому доступ есть у многих. Тип [`Secret`] делает
+//! утечку невозможной на уровне компилятора: способа случайно вывести
+//! значение просто нет.
+
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::num::NonZeroU32;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::config::SecretsConfig;
+
+/// Как секрет выглядит в `Debug`, `Display` и JSON.
+pub const REDACTED: &str = "***";
+
+/// Суффикс переменной с путём к файлу секрета: `GIGACHAT_TOKEN_FILE`.
+pub const FILE_SUFFIX: &str = "_FILE";
+
+/// Переменная с паролем зашифрованного файла секретов.
+pub const PASSPHRASE_ENV: &str = "SECRETS_PASSPHRASE";
+
+/// Заголовок зашифрованного файла (формат и версия).
+const STORE_HEADER: &str = "rust-gigachat-demo secrets v1";
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+// ============================================================================
+// ОБЁРТКА
+// ============================================================================
+
+/// Значение, которое никогда не выводится: токен, ключ API, пароль.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Оборачивает значение.
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Настоящее значение - только для передачи туда, где оно нужно
+    /// (заголовок запроса к API, сравнение ключей).
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({REDACTED})")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self)
+    }
+}
+
+// ============================================================================
+// ОШИБКИ
+// ============================================================================
+
+/// Ошибки получения секретов.
+#[derive(Error, Debug)]
+pub enum SecretError {
+    /// Секрет не найден ни в одном источнике
+    #[error("секрет {0} не задан (переменная {0}, {0}_FILE или файл секретов)")]
+    Missing(String),
+
+    /// Файл, указанный в `<ИМЯ>_FILE`, не читается
+    #[error("{name}: не удалось прочитать {path}: {source}")]
+    File {
+        name: String,
+        path: String,
+        source: std::io::Error,
+    },
+
+    /// Для зашифрованного файла не задан пароль
+    #[error("не задан пароль файла секретов ({PASSPHRASE_ENV} или {PASSPHRASE_ENV}_FILE)")]
+    MissingPassphrase,
+
+    /// Файл секретов не читается, повреждён или пароль неверный
+    #[error("файл секретов {path}: {reason}")]
+    Store { path: String, reason: String },
+}
+
+// ============================================================================
+// ИСТОЧНИКИ
+// ============================================================================
+
+/// Поиск секретов: переменные окружения, `*_FILE`, зашифрованный файл.
+pub struct Secrets {
+    store: Option<String>,
+    env: HashMap<String, String>,
+}
+
+impl Secrets {
+    /// Источники из секции `[secrets]` и переменных окружения процесса.
+    pub fn from_config(config: &SecretsConfig) -> Self {
+        Self::new(config.file.clone(), env::vars().collect())
+    }
+
+    /// Источники с явно заданными переменными окружения (удобно в тестах).
+    pub fn new(store: Option<String>, env: HashMap<String, String>) -> Self {
+        Self { store, env }
+    }
+
+    /// Ищет секрет по имени.
+    ///
+    /// Пустые значения считаются незаданными.
+    pub fn get(&self, name: &str) -> Result<Secret, SecretError> {
+        if let Some(value) = self.env.get(name).filter(|value| !value.is_empty()) {
+            debug!("Секрет {} взят из переменной окружения", name);
+            return Ok(Secret::new(value.as_str()));
+        }
+
+        let file_var = format!("{name}{FILE_SUFFIX}");
+        if let Some(path) = self.env.get(&file_var).filter(|path| !path.is_empty()) {
+            let value = read_secret_file(path).map_err(|source| SecretError::File {
+                name: file_var.clone(),
+                path: path.clone(),
+                source,
+            })?;
+            if !value.is_empty() {
+                debug!("Секрет {} взят из файла {} ({})", name, path, file_var);
+                return Ok(Secret::new(value));
+            }
+        }
+
+        if let Some(path) = &self.store {
+            let passphrase = self.passphrase()?;
+            let text = std::fs::read_to_string(path).map_err(|e| SecretError::Store {
+                path: path.clone(),
+                reason: e.to_string(),
+            })?;
+            let mut entries = open(&text, passphrase.expose()).map_err(|reason| SecretError::Store {
+                path: path.clone(),
+                reason,
+            })?;
+            if let Some(value) = entries.remove(name).filter(|value| !value.is_empty()) {
+                debug!("Секрет {} взят из файла секретов {}", name, path);
+                return Ok(Secret::new(value));
+            }
+        }
+
+        Err(SecretError::Missing(name.to_string()))
+    }
+
+    /// Пароль зашифрованного файла - тоже секрет, но без самого файла.
+    pub fn passphrase(&self) -> Result<Secret, SecretError> {
+        match Secrets::new(None, self.env.clone()).get(PASSPHRASE_ENV) {
+            Err(SecretError::Missing(_)) => Err(SecretError::MissingPassphrase),
+            other => other,
+        }
+    }
+}
+
+/// Читает файл секрета; перевод строки в конце (`echo "..." > file`) отбрасывается.
+fn read_secret_file(path: &str) -> std::io::Result<String> {
+    let value = std::fs::read_to_string(path)?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+// ============================================================================
+// ЗАШИФРОВАННЫЙ ФАЙЛ
+// ============================================================================
+
+/// Шифрует набор секретов паролем; результат - текст для файла секретов.
+///
+/// ```text
+/// rust-gigachat-demo secrets v1
+/// base64(соль[16] | nonce[12] | AES-256-GCM(JSON) | tag[16])
+/// ```
+pub fn seal(entries: &BTreeMap<String, String>, passphrase: &str) -> Result<String, String> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt).map_err(|_| "генератор случайных чисел недоступен")?;
+    rng.fill(&mut nonce).map_err(|_| "генератор случайных чисел недоступен")?;
+
+    let mut data = serde_json::to_vec(entries).map_err(|e| e.to_string())?;
+    derive_key(passphrase, &salt)
+        .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+        .map_err(|_| "ошибка шифрования")?;
+
+    let mut blob = Vec::with_capacity(SALT_LEN + NONCE_LEN + data.len());
+    blob.extend_from_slice(&salt);
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&data);
+    Ok(format!("{STORE_HEADER}\n{}\n", BASE64.encode(blob)))
+}
+
+/// Расшифровывает текст, созданный [`seal`].
+pub fn open(text: &str, passphrase: &str) -> Result<BTreeMap<String, String>, String> {
+    let body = text
+        .trim()
+        .strip_prefix(STORE_HEADER)
+        .ok_or("неизвестный формат (создайте файл командой --seal-secrets)")?;
+    let mut blob = BASE64
+        .decode(body.trim())
+        .map_err(|_| "файл повреждён (некорректный base64)")?;
+    if blob.len() < SALT_LEN + NONCE_LEN {
+        return Err("файл повреждён (слишком короткий)".to_string());
+    }
+
+    let (salt, rest) = blob.split_at_mut(SALT_LEN);
+    let (nonce, data) = rest.split_at_mut(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "файл повреждён")?;
+    let plain = derive_key(passphrase, salt)
+        .open_in_place(nonce, Aad::empty(), data)
+        .map_err(|_| "неверный пароль или файл повреждён")?;
+
+    serde_json::from_slice(plain).map_err(|e| format!("файл повреждён: {e}"))
+}
+
+/// Разбирает файл в формате `.env`: строки `ИМЯ=значение`, `#` - комментарий.
+///
+/// Это вход для [`seal`]: из него команда `--seal-secrets` создаёт
+/// зашифрованный файл.
+pub fn parse_plain(text: &str) -> Result<BTreeMap<String, String>, String> {
+    let mut entries = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line
+            .strip_prefix("export ")
+            .unwrap_or(line)
+            .split_once('=')
+            .ok_or_else(|| format!("строка {}: ожидается ИМЯ=значение", number + 1))?;
+        let value = value.trim();
+        let value = [('"', '"'), ('\'', '\'')]
+            .iter()
+            .find_map(|(open, close)| value.strip_prefix(*open)?.strip_suffix(*close))
+            .unwrap_or(value);
+        entries.insert(name.trim().to_string(), value.to_string());
+    }
+    Ok(entries)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> LessSafeKey {
+    let mut key = [0u8; 32];
+    let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).expect("ненулевое число итераций");
+    pbkdf2::derive(pbkdf2::PBKDF2_HMAC_SHA256, iterations, salt, passphrase.as_bytes(), &mut key);
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).expect("ключ AES-256 - 32 байта"))
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn temp_file(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("secrets-test-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret = Secret::new("sk-123");
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+        assert_eq!(secret.to_string(), REDACTED);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), r#""***""#);
+        assert_eq!(secret.expose(), "sk-123");
+    }
+
+    #[test]
+    fn test_env_then_file() {
+        let path = temp_file("token", "from-file\n");
+
+        let secrets = Secrets::new(None, env(&[("TOKEN", "from-env"), ("TOKEN_FILE", &path)]));
+        assert_eq!(secrets.get("TOKEN").unwrap().expose(), "from-env");
+
+        let secrets = Secrets::new(None, env(&[("TOKEN_FILE", &path)]));
+        assert_eq!(secrets.get("TOKEN").unwrap().expose(), "from-file");
+
+        let secrets = Secrets::new(None, env(&[("TOKEN_FILE", "/nonexistent/token")]));
+        assert!(matches!(secrets.get("TOKEN"), Err(SecretError::File { .. })));
+
+        assert!(matches!(Secrets::new(None, env(&[])).get("TOKEN"), Err(SecretError::Missing(_))));
+    }
+
+    #[test]
+    fn test_parse_plain() {
+        let entries = parse_plain("# токены\nGIGACHAT_TOKEN=\"abc=\"\nexport OTHER = 'x'\n").unwrap();
+        assert_eq!(entries["GIGACHAT_TOKEN"], "abc=");
+        assert_eq!(entries["OTHER"], "x");
+        assert!(parse_plain("broken line").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_store() {
+        let entries = BTreeMap::from([("GIGACHAT_TOKEN".to_string(), "sealed".to_string())]);
+        let text = seal(&entries, "passphrase").unwrap();
+        assert!(!text.contains("sealed"));
+        assert_eq!(open(&text, "passphrase").unwrap(), entries);
+        assert!(open(&text, "wrong").is_err());
+
+        let path = temp_file("store", &text);
+        let secrets = Secrets::new(Some(path.clone()), env(&[(PASSPHRASE_ENV, "passphrase")]));
+        assert_eq!(secrets.get("GIGACHAT_TOKEN").unwrap().expose(), "sealed");
+        assert!(matches!(secrets.get("OTHER"), Err(SecretError::Missing(_))));
+
+        let secrets = Secrets::new(Some(path), env(&[]));
+        assert!(matches!(secrets.get("GIGACHAT_TOKEN"), Err(SecretError::MissingPassphrase)));
+    }
+}
@@ -56,6 +56,7 @@ use gigalib::controllers::{
 };
 
 use crate::config::GigaChatConfig;
+use crate::secrets::Secret;
 
 // ============================================================================
 // ТИПЫ ОШИБОК
@@ -79,15 +80,89 @@ use crate::config::GigaChatConfig;
 pub enum AiServiceError {
     /// Ошибка при обращении к API (сеть, таймаут, неверный ответ)
     #[error("Ошибка API: {0}")]
+    // Создаётся только клиентом GigaChat (фича `gigachat`)
+    #[cfg_attr(not(feature = "gigachat"), allow(dead_code))]
     ApiError(String),
 
     /// Ошибка конфигурации (отсутствует токен, неверные параметры)
     #[error("Ошибка конфигурации: {0}")]
+    #[cfg_attr(not(feature = "gigachat"), allow(dead_code))]
     ConfigError(String),
 
     /// Внутренняя ошибка (проблемы с потоками, паника)
     #[error("Внутренняя ошибка: {0}")]
     InternalError(String),
+
+    /// Сервер перегружен: все слоты заняты и очередь полна или ожидание
+    /// превысило таймаут (см. модуль `concurrency`)
+    #[error("Сервер перегружен: {0}")]
+    Busy(String),
+
+    /// Модерация не пропустила вопрос (см. модуль `moderation`)
+    #[error("Заблокировано модерацией: {0}")]
+    ContentBlocked(String),
+
+    /// Модерация не пропустила ответ модели: вопрос был в порядке,
+    /// поэтому это не ошибка клиента
+    #[error("Ответ заблокирован модерацией: {0}")]
+    AnswerBlocked(String),
+}
+
+impl AiServiceError {
+    /// Короткое имя вида ошибки - для метрик и логов.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AiServiceError::ApiError(_) => "api",
+            AiServiceError::ConfigError(_) => "config",
+            AiServiceError::InternalError(_) => "internal",
+            AiServiceError::Busy(_) => "busy",
+            AiServiceError::ContentBlocked(_) | AiServiceError::AnswerBlocked(_) => "blocked",
+        }
+    }
+}
+
+// ============================================================================
+// КОНТЕКСТ ЗАПРОСА
+// ============================================================================
+
+/// Сведения о HTTP-запросе, в рамках которого вызывается AI.
+///
+/// Сервису не нужен весь HTTP-запрос - только то, что помогает связать
+/// обращение к AI с исходным запросом (например, в логах), и то, что
+/// меняется от клиента к клиенту.
+///
+/// ## Почему `request_id` не уходит в GigaChat заголовком?
+///
+/// API GigaChat принимает `X-Request-ID`, но `gigalib` 0.1.3 собирает
+/// заголовки запроса сам (`GigaClient::send_messages`) и не даёт добавить
+/// свои. Поэтому идентификатор попадает только в span `gigachat` наших
+/// логов; когда клиент научится передавать заголовки, его нужно будет
+/// отправлять и в API.
+#[derive(Debug, Clone, Default)]
+pub struct AskContext {
+    /// Идентификатор запроса (`X-Request-Id`, см. модуль `request_id`)
+    // Читает только клиент GigaChat (фича `gigachat`)
+    #[cfg_attr(not(feature = "gigachat"), allow(dead_code))]
+    pub request_id: Option<String>,
+
+    /// Системный промпт варианта A/B эксперимента (см. модуль `experiment`);
+    /// `None` - промпт, с которым создан сервис
+    pub system_prompt: Option<String>,
+}
+
+impl AskContext {
+    /// Контекст с идентификатором запроса.
+    pub fn with_request_id(request_id: impl Into<String>) -> Self {
+        Self {
+            request_id: Some(request_id.into()),
+            system_prompt: None,
+        }
+    }
+
+    /// Тот же контекст, но с системным промптом вместо промпта сервиса.
+    pub fn with_system_prompt(self, system_prompt: Option<String>) -> Self {
+        Self { system_prompt, ..self }
+    }
 }
 
 // ============================================================================
@@ -127,7 +202,7 @@ pub enum AiServiceError {
 /// ```rust,ignore
 /// // Функция принимает ЛЮБОЙ тип, реализующий AiService
 /// async fn process(service: &dyn AiService, q: &str) -> Result<String, AiServiceError> {
-///     service.ask(q).await
+///     service.ask(q, &AskContext::default()).await
 /// }
 /// ```
 #[async_trait]
@@ -137,6 +212,7 @@ pub trait AiService: Send + Sync {
     /// # Аргументы
     ///
     /// * `question` - Вопрос пользователя
+    /// * `ctx` - Контекст HTTP-запроса (идентификатор для логов)
     ///
     /// # Возвращает
     ///
@@ -145,7 +221,7 @@ pub trait AiService: Send + Sync {
     /// # Ошибки
     ///
     /// Возвращает `AiServiceError` при ошибке обращения к API.
-    async fn ask(&self, question: &str) -> Result<String, AiServiceError>;
+    async fn ask(&self, question: &str, ctx: &AskContext) -> Result<String, AiServiceError>;
 
     /// Возвращает имя сервиса.
     ///
@@ -162,12 +238,29 @@ pub trait AiService: Send + Sync {
 
     /// Применён ли системный промпт к запросам этого сервиса.
     fn system_prompt_applied(&self) -> bool;
+
+    /// Модель, которой отправляются вопросы (`None` - у сервиса нет модели).
+    fn model(&self) -> Option<&str> {
+        None
+    }
+
+    /// Быстрая проверка готовности сервиса - для `GET /health/ready`.
+    ///
+    /// Вызывается часто (каждые несколько секунд), поэтому не должна
+    /// обращаться к модели и тратить токены. По умолчанию сервис готов всегда.
+    async fn probe(&self) -> Result<(), AiServiceError> {
+        Ok(())
+    }
 }
 
 // ============================================================================
 // РЕАЛИЗАЦИЯ GIGACHAT СЕРВИСА
 // ============================================================================
 
+/// Адрес API GigaChat для проверки доступности (`probe`).
+#[cfg(feature = "gigachat")]
+const GIGACHAT_API_ADDR: &str = "gigachat.devices.sberbank.ru:443";
+
 /// Реализация AI сервиса с использованием GigaChat API.
 ///
 /// # Для студентов: Условная компиляция
@@ -188,8 +281,8 @@ pub trait AiService: Send + Sync {
 /// ```
 #[cfg(feature = "gigachat")]
 pub struct GigaChatService {
-    /// Токен авторизации для GigaChat API
-    token: String,
+    /// Токен авторизации для GigaChat API (в `Debug` и логах - `***`)
+    token: Secret,
     
     /// Конфигурация (модель, температура, max_tokens)
     config: GigaChatConfig,
@@ -211,6 +304,7 @@ impl GigaChatService {
     ///
     /// ```rust
     /// use rust_gigachat_demo::config::GigaChatConfig;
+    /// use rust_gigachat_demo::secrets::Secret;
     /// use rust_gigachat_demo::services::GigaChatService;
     ///
     /// let config = GigaChatConfig {
@@ -219,11 +313,12 @@ impl GigaChatService {
     ///     max_tokens: 128,
     ///     temperature: 0.7,
     ///     timeout_seconds: 30,
+    ///     mock_fallback: true,
     /// };
-    /// let token = "TOKEN".to_string();
+    /// let token = Secret::new("TOKEN");
     /// let _service = GigaChatService::new(token, config, None);
     /// ```
-    pub fn new(token: String, config: GigaChatConfig, system_prompt: Option<String>) -> Self {
+    pub fn new(token: Secret, config: GigaChatConfig, system_prompt: Option<String>) -> Self {
         Self { 
             token, 
             config,
@@ -266,8 +361,14 @@ impl AiService for GigaChatService {
     ///       |                                   |
     ///       <------ результат -------------------|
     /// ```
-    async fn ask(&self, question: &str) -> Result<String, AiServiceError> {
-        if self.token.trim().is_empty() {
+    ///
+    /// Поток `spawn_blocking` не наследует текущий span, поэтому создаём
+    /// span `gigachat` с идентификатором запроса заранее и входим в него
+    /// внутри потока: логи вызова GigaChat связаны с исходным запросом.
+    /// Заголовком `X-Request-ID` идентификатор не отправляется - `gigalib`
+    /// этого не умеет (см. [`AskContext`]).
+    async fn ask(&self, question: &str, ctx: &AskContext) -> Result<String, AiServiceError> {
+        if self.token.expose().trim().is_empty() {
             return Err(AiServiceError::ConfigError(
                 "GigaChat token is empty".to_string(),
             ));
@@ -275,11 +376,13 @@ impl AiService for GigaChatService {
 
         // Клонируем данные, чтобы передать их в другой поток.
         // `move` в замыкании забирает владение, поэтому нужны копии.
-        let token = self.token.clone();
+        let token = self.token.expose().to_string();
         let config = self.config.clone();
-        let system_prompt = self
+        // Промпт варианта эксперимента важнее промпта из конфигурации
+        let system_prompt = ctx
             .system_prompt
             .as_ref()
+            .or(self.system_prompt.as_ref())
             .map(|prompt| prompt.trim().to_string())
             .filter(|prompt| !prompt.is_empty());
         let question = question.to_string();
@@ -291,11 +394,18 @@ impl AiService for GigaChatService {
             question
         };
         
+        let span = tracing::info_span!(
+            "gigachat",
+            request_id = ctx.request_id.as_deref().unwrap_or("-"),
+        );
+
         // spawn_blocking запускает замыкание в отдельном потоке,
         // предназначенном для блокирующих операций.
         // Это НЕ блокирует async runtime Rocket.
         let result = tokio::task::spawn_blocking(move || {
             use gigalib::http::message::MessageConfigBuilder;
+
+            let _span = span.enter();
             
             // Внутри blocking-потока создаём клиента.
             // Здесь GigaClient безопасен, т.к. мы в обычном (не async) контексте.
@@ -336,6 +446,24 @@ impl AiService for GigaChatService {
         "GigaChat"
     }
 
+    fn model(&self) -> Option<&str> {
+        Some(&self.config.model)
+    }
+
+    /// Проверяет токен и доступность API по сети (TCP-соединение с
+    /// сервером GigaChat). Запрос к модели не отправляется.
+    async fn probe(&self) -> Result<(), AiServiceError> {
+        if self.token.expose().trim().is_empty() {
+            return Err(AiServiceError::ConfigError(
+                "GigaChat token is empty".to_string(),
+            ));
+        }
+        tokio::net::TcpStream::connect(GIGACHAT_API_ADDR)
+            .await
+            .map(|_| ())
+            .map_err(|e| AiServiceError::ApiError(format!("{}: {}", GIGACHAT_API_ADDR, e)))
+    }
+
     fn system_prompt_applied(&self) -> bool {
         self.system_prompt
             .as_ref()
@@ -398,7 +526,7 @@ impl Default for MockAiService {
 
 #[async_trait]
 impl AiService for MockAiService {
-    async fn ask(&self, question: &str) -> Result<String, AiServiceError> {
+    async fn ask(&self, question: &str, _ctx: &AskContext) -> Result<String, AiServiceError> {
         // Return mock response based on question keywords
         let question_lower = question.to_lowercase();
         
@@ -573,7 +701,7 @@ impl AiServiceFactory {
     #[cfg(feature = "gigachat")]
     pub fn create(
         config: &GigaChatConfig,
-        token: Option<String>,
+        token: Option<Secret>,
         system_prompt: Option<String>,
     ) -> Box<dyn AiService> {
         match (config.enabled, token) {
@@ -599,7 +727,7 @@ impl AiServiceFactory {
     #[cfg(not(feature = "gigachat"))]
     pub fn create(
         _config: &GigaChatConfig,
-        _token: Option<String>,
+        _token: Option<Secret>,
         _system_prompt: Option<String>,
     ) -> Box<dyn AiService> {
         Box::new(MockAiService::new())
@@ -659,7 +787,7 @@ mod tests {
     async fn test_mock_service() {
         let service = MockAiService::new();
         // .await - ждём завершения асинхронной операции
-        let answer = service.ask("Что такое Rust?").await.unwrap();
+        let answer = service.ask("Что такое Rust?", &AskContext::default()).await.unwrap();
         assert!(answer.contains("Rust"));
     }
 
@@ -0,0 +1,255 @@
+//! Модуль плавной остановки сервера (graceful shutdown).
+//!
+//! При передеплое Serverless Container (или `kubectl rollout`) процессу
+//! приходит SIGTERM, а через некоторое время - SIGKILL. Если остановиться
+//! сразу, начатые запросы `/ask` оборвутся на полуслове.
+//!
+//! ```text
+//! SIGTERM / Ctrl+C
+//!     │
+//!     ├─► Rocket перестаёт принимать новые соединения
+//!     ├─► ShutdownState::begin()  → /health/ready: 503 (shutting_down)
+//!     │                             новые /ask:     503 SHUTTING_DOWN
+//!     ├─► ждём начатые /ask (и вызовы AI) не дольше shutdown.grace_seconds
+//!     ├─► сбрасываем хранилище на диск (Storage::flush)
+//!     ├─► сбрасываем трассировки (LoggingGuard::flush)
+//!     └─► Rocket закрывает соединения (ещё shutdown.mercy_seconds) и
+//!         завершается; guard логов дописывает файл при drop
+//! ```
+//!
+//! Потоковых ответов (SSE) и кешей в проекте пока нет. Когда они появятся,
+//! потоки должны следить за `rocket::Shutdown` и отправлять финальное
+//! событие с ошибкой, а сброс кешей добавляется в [`fairing`] рядом со
+//! сбросом хранилища.
+//!
+//! # Для студентов: Сигналы
+//!
+//! Rocket сам ловит Ctrl+C (SIGINT) и SIGTERM (на Unix) - это настройки
+//! `shutdown.ctrlc` и `shutdown.signals` в его конфигурации. Мы задаём
+//! только длительность периодов `grace` и `mercy`, а в fairing
+//! `on_shutdown` выполняем свои действия.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use rocket::fairing::AdHoc;
+use rocket::request::{FromRequest, Outcome, Request};
+use thiserror::Error;
+use tokio::sync::Notify;
+use tracing::{info, warn};
+
+use crate::logging::LoggingGuard;
+use crate::storage::SharedStorage;
+
+/// Новый запрос пришёл, когда сервер уже останавливается.
+#[derive(Error, Debug, PartialEq)]
+#[error("server is shutting down")]
+pub struct ShuttingDown;
+
+// ============================================================================
+// СОСТОЯНИЕ
+// ============================================================================
+
+/// Состояние остановки: флаг и счётчик запросов в обработке.
+///
+/// Хранится в Rocket через `.manage()`; его читают обработчик `/ask`,
+/// проверка готовности и fairing остановки.
+#[derive(Debug, Default)]
+pub struct ShutdownState {
+    draining: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Notify,
+}
+
+impl ShutdownState {
+    /// Создаёт состояние "работаем, запросов нет".
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Переводит сервер в режим остановки. Возвращает число запросов в обработке.
+    pub fn begin(&self) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+        self.in_flight()
+    }
+
+    /// Идёт ли остановка.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Сколько запросов сейчас в обработке.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Регистрирует начатый запрос; во время остановки новые не принимаются.
+    ///
+    /// Запрос считается завершённым, когда возвращённый guard уничтожается.
+    pub fn enter(&self) -> Result<InFlight<'_>, ShuttingDown> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        // Флаг проверяем ПОСЛЕ увеличения счётчика: иначе запрос мог бы
+        // проскочить между begin() и подсчётом в drained()
+        if self.is_draining() {
+            self.leave();
+            return Err(ShuttingDown);
+        }
+        Ok(InFlight(self))
+    }
+
+    /// Ждёт, пока все запросы завершатся, но не дольше `timeout`.
+    ///
+    /// Возвращает `false`, если по истечении времени запросы ещё остались.
+    pub async fn drained(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Подписываемся на уведомление ДО проверки счётчика,
+            // чтобы не пропустить последний leave()
+            let notified = self.idle.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.in_flight() == 0 {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return self.in_flight() == 0;
+            }
+        }
+    }
+
+    fn leave(&self) {
+        if self.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.idle.notify_waiters();
+        }
+    }
+}
+
+/// Запрос в обработке; при `drop` уменьшает счётчик [`ShutdownState`].
+#[must_use = "запрос считается завершённым, как только guard уничтожен"]
+pub struct InFlight<'a>(&'a ShutdownState);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Доступ к [`ShutdownState`] для обработчиков.
+///
+/// Как и [`crate::concurrency::QueueSnapshot`], читает state без `&State<T>`:
+/// в тестах и примерах состояние остановки может быть не передано.
+/// Guard никогда не отклоняет запрос - решение принимает обработчик.
+pub struct Drain<'r>(pub Option<&'r ShutdownState>);
+
+impl<'r> Drain<'r> {
+    /// Регистрирует запрос; `Ok(None)`, если состояние остановки не подключено.
+    pub fn enter(&self) -> Result<Option<InFlight<'r>>, ShuttingDown> {
+        self.0.map(ShutdownState::enter).transpose()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Drain<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Drain(req.rocket().state::<ShutdownState>()))
+    }
+}
+
+// ============================================================================
+// FAIRING
+// ============================================================================
+
+/// Fairing, выполняемый Rocket при получении сигнала остановки.
+///
+/// Переводит [`ShutdownState`] в режим остановки, ждёт начатые запросы
+/// в пределах `shutdown.grace`, сбрасывает хранилище и трассировки.
+pub fn fairing() -> AdHoc {
+    AdHoc::on_shutdown("Graceful shutdown", |rocket| {
+        Box::pin(async move {
+            let grace = Duration::from_secs(u64::from(rocket.config().shutdown.grace));
+
+            if let Some(state) = rocket.state::<ShutdownState>() {
+                let in_flight = state.begin();
+                info!(
+                    "🛑 Остановка: новые запросы не принимаются, ждём {} в обработке (до {}s)",
+                    in_flight,
+                    grace.as_secs()
+                );
+                if state.drained(grace).await {
+                    info!("✅ Все запросы завершены");
+                } else {
+                    warn!(
+                        "⚠️  Grace-период истёк, {} запросов будут прерваны",
+                        state.in_flight()
+                    );
+                }
+            }
+
+            // Запросы завершены (или прерваны) - больше никто не пишет
+            if let Some(storage) = rocket.state::<SharedStorage>() {
+                match storage.flush() {
+                    Ok(()) => info!("💾 Хранилище сброшено на диск"),
+                    Err(e) => warn!("⚠️  Не удалось сбросить хранилище: {}", e),
+                }
+            }
+
+            if let Some(logging) = rocket.state::<LoggingGuard>() {
+                logging.flush();
+            }
+        })
+    })
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_rejected_while_draining() {
+        let state = ShutdownState::new();
+        let first = state.enter().unwrap();
+        assert_eq!(state.in_flight(), 1);
+
+        assert_eq!(state.begin(), 1);
+        assert!(state.is_draining());
+        assert_eq!(state.enter().err(), Some(ShuttingDown));
+        assert_eq!(state.in_flight(), 1);
+
+        drop(first);
+        assert_eq!(state.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_drained_waits_for_in_flight() {
+        let state = std::sync::Arc::new(ShutdownState::new());
+        assert!(state.drained(Duration::from_millis(10)).await);
+
+        let worker = {
+            let state = std::sync::Arc::clone(&state);
+            tokio::spawn(async move {
+                let _guard = state.enter().unwrap();
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            })
+        };
+        // Даём задаче зарегистрироваться
+        while state.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+        state.begin();
+
+        assert!(!state.drained(Duration::from_millis(5)).await);
+        assert!(state.drained(Duration::from_secs(5)).await);
+        worker.await.unwrap();
+    }
+}
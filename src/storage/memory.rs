@@ -0,0 +1,317 @@
+//! Хранилище в памяти процесса.
+//!
+//! Данные теряются при перезапуске. Подходит для тестов и для
+//! `storage.backend = "memory"`, когда сохранять ничего не нужно.
+
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use super::{
+    satisfaction, words, Conversation, Exchange, Feedback, HistoryCursor, HistoryFilter,
+    HistoryPage, Storage, StorageError, StoredApiKey, UsageRecord,
+};
+use crate::models::{FeedbackReview, FeedbackSummary, Rating, UsageSummary};
+
+/// Группа сводки оценок: модель, вариант эксперимента, версия промпта.
+type GroupKey = (Option<String>, Option<String>, Option<String>, Option<String>);
+
+#[derive(Debug, Default)]
+struct Data {
+    conversations: BTreeMap<String, Conversation>,
+    exchanges: Vec<Exchange>,
+    feedback: BTreeMap<String, Feedback>,
+    usage: Vec<UsageRecord>,
+    api_keys: BTreeMap<String, StoredApiKey>,
+}
+
+/// Реализация [`Storage`] на коллекциях в памяти.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    data: Mutex<Data>,
+}
+
+impl MemoryStorage {
+    /// Создаёт пустое хранилище.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        self.data.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Data {
+    /// Удаляет записи, для которых `delete` вернул `true`, и опустевшие
+    /// диалоги клиента. Возвращает число удалённых записей.
+    fn delete_exchanges(&mut self, subject: &str, delete: impl Fn(&Exchange) -> bool) -> u64 {
+        let before = self.exchanges.len();
+        self.exchanges.retain(|exchange| !delete(exchange));
+        let deleted = (before - self.exchanges.len()) as u64;
+
+        let exchanges = &self.exchanges;
+        // Как ON DELETE CASCADE: оценка удаляется вместе с ответом
+        self.feedback
+            .retain(|answer_id, _| exchanges.iter().any(|exchange| &exchange.id == answer_id));
+        self.conversations.retain(|id, conversation| {
+            conversation.subject != subject
+                || exchanges
+                    .iter()
+                    .any(|exchange| exchange.conversation_id.as_deref() == Some(id.as_str()))
+        });
+        deleted
+    }
+}
+
+/// Запись подходит под фильтр (как `WHERE` в SQLite-версии).
+fn matches(filter: &HistoryFilter, terms: &[String], exchange: &Exchange) -> bool {
+    let text = words(&format!("{} {}", exchange.question, exchange.answer));
+    exchange.subject == filter.subject
+        && filter
+            .conversation_id
+            .as_ref()
+            .is_none_or(|id| exchange.conversation_id.as_ref() == Some(id))
+        && filter.source.as_ref().is_none_or(|source| &exchange.source == source)
+        && filter.from.is_none_or(|from| exchange.created_at >= from)
+        && filter.until.is_none_or(|until| exchange.created_at < until)
+        && terms
+            .iter()
+            .all(|term| text.iter().any(|word| word.starts_with(term.as_str())))
+}
+
+impl Storage for MemoryStorage {
+    fn describe(&self) -> String {
+        "memory (данные теряются при перезапуске)".to_string()
+    }
+
+    fn ping(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    fn conversation(&self, id: &str) -> Result<Option<Conversation>, StorageError> {
+        Ok(self.data().conversations.get(id).cloned())
+    }
+
+    fn save_conversation(&self, conversation: &Conversation) -> Result<(), StorageError> {
+        let mut data = self.data();
+        match data.conversations.get_mut(&conversation.id) {
+            // Как ON CONFLICT в SQLite: владелец и время создания не меняются
+            Some(existing) => {
+                existing.title = conversation.title.clone();
+                existing.updated_at = conversation.updated_at;
+            }
+            None => {
+                data.conversations
+                    .insert(conversation.id.clone(), conversation.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn save_exchange(&self, exchange: &Exchange) -> Result<(), StorageError> {
+        self.data().exchanges.push(exchange.clone());
+        Ok(())
+    }
+
+    fn history(
+        &self,
+        filter: &HistoryFilter,
+        after: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        let limit = limit.max(1);
+        let terms = filter.search_terms();
+        let data = self.data();
+        let mut items: Vec<Exchange> = data
+            .exchanges
+            .iter()
+            .filter(|exchange| matches(filter, &terms, exchange))
+            .filter(|exchange| after.is_none_or(|cursor| cursor.precedes(exchange)))
+            .cloned()
+            .collect();
+        items.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+
+        let next = (items.len() > limit).then(|| HistoryCursor::after(&items[limit - 1]));
+        items.truncate(limit);
+        Ok(HistoryPage { items, next })
+    }
+
+    fn delete_exchange(&self, subject: &str, id: &str) -> Result<bool, StorageError> {
+        let deleted = self
+            .data()
+            .delete_exchanges(subject, |exchange| exchange.subject == subject && exchange.id == id);
+        Ok(deleted > 0)
+    }
+
+    fn delete_history(&self, filter: &HistoryFilter) -> Result<u64, StorageError> {
+        let terms = filter.search_terms();
+        Ok(self
+            .data()
+            .delete_exchanges(&filter.subject, |exchange| matches(filter, &terms, exchange)))
+    }
+
+    fn exchange(&self, id: &str) -> Result<Option<Exchange>, StorageError> {
+        Ok(self.data().exchanges.iter().find(|exchange| exchange.id == id).cloned())
+    }
+
+    fn save_feedback(&self, feedback: &Feedback) -> Result<bool, StorageError> {
+        Ok(self
+            .data()
+            .feedback
+            .insert(feedback.answer_id.clone(), feedback.clone())
+            .is_some())
+    }
+
+    fn feedback_summary(&self) -> Result<Vec<FeedbackSummary>, StorageError> {
+        let data = self.data();
+        let mut groups: BTreeMap<GroupKey, FeedbackSummary> = BTreeMap::new();
+        for exchange in &data.exchanges {
+            let parameters = &exchange.parameters;
+            let key = (
+                parameters.model.clone(),
+                parameters.experiment.clone(),
+                parameters.prompt_variant.clone(),
+                parameters.system_prompt_version.clone(),
+            );
+            let group = groups.entry(key).or_insert_with(|| FeedbackSummary {
+                model: parameters.model.clone(),
+                experiment: parameters.experiment.clone(),
+                prompt_variant: parameters.prompt_variant.clone(),
+                system_prompt_version: parameters.system_prompt_version.clone(),
+                answers: 0,
+                up: 0,
+                down: 0,
+                satisfaction: None,
+                categories: BTreeMap::new(),
+            });
+            group.answers += 1;
+            let Some(feedback) = data.feedback.get(&exchange.id) else {
+                continue;
+            };
+            match feedback.rating {
+                Rating::Up => group.up += 1,
+                Rating::Down => group.down += 1,
+            }
+            for category in &feedback.categories {
+                *group.categories.entry(category.clone()).or_default() += 1;
+            }
+        }
+        Ok(groups
+            .into_values()
+            .map(|group| FeedbackSummary {
+                satisfaction: satisfaction(group.up, group.down),
+                ..group
+            })
+            .collect())
+    }
+
+    fn recent_feedback(&self, limit: usize) -> Result<Vec<FeedbackReview>, StorageError> {
+        let data = self.data();
+        let mut recent: Vec<&Feedback> = data.feedback.values().collect();
+        recent.sort_by(|a, b| (b.created_at, &b.answer_id).cmp(&(a.created_at, &a.answer_id)));
+        Ok(recent
+            .into_iter()
+            .take(limit)
+            .filter_map(|feedback| {
+                let exchange = data.exchanges.iter().find(|e| e.id == feedback.answer_id)?;
+                Some(FeedbackReview {
+                    answer_id: feedback.answer_id.clone(),
+                    subject: feedback.subject.clone(),
+                    rating: feedback.rating,
+                    comment: feedback.comment.clone(),
+                    categories: feedback.categories.clone(),
+                    question: exchange.question.clone(),
+                    answer: exchange.answer.clone(),
+                    parameters: exchange.parameters.clone(),
+                    created_at: feedback.created_at,
+                })
+            })
+            .collect())
+    }
+
+    fn record_usage(&self, record: &UsageRecord) -> Result<(), StorageError> {
+        self.data().usage.push(record.clone());
+        Ok(())
+    }
+
+    fn usage_summary(&self) -> Result<Vec<UsageSummary>, StorageError> {
+        let mut summary: BTreeMap<&str, UsageSummary> = BTreeMap::new();
+        let data = self.data();
+        for record in &data.usage {
+            let entry = summary
+                .entry(record.subject.as_str())
+                .or_insert_with(|| UsageSummary {
+                    subject: record.subject.clone(),
+                    requests: 0,
+                    errors: 0,
+                    prompt_tokens: 0,
+                    completion_tokens: 0,
+                    last_request_at: record.created_at,
+                });
+            entry.requests += 1;
+            entry.errors += u64::from(record.outcome != "ok");
+            entry.prompt_tokens += record.prompt_tokens;
+            entry.completion_tokens += record.completion_tokens;
+            entry.last_request_at = entry.last_request_at.max(record.created_at);
+        }
+        Ok(summary.into_values().collect())
+    }
+
+    fn api_keys(&self) -> Result<Vec<StoredApiKey>, StorageError> {
+        Ok(self.data().api_keys.values().cloned().collect())
+    }
+
+    fn save_api_key(&self, key: &StoredApiKey) -> Result<(), StorageError> {
+        let mut data = self.data();
+        if data.api_keys.contains_key(&key.name) {
+            return Err(StorageError::Duplicate(key.name.clone()));
+        }
+        data.api_keys.insert(key.name.clone(), key.clone());
+        Ok(())
+    }
+
+    fn delete_api_key(&self, name: &str) -> Result<bool, StorageError> {
+        Ok(self.data().api_keys.remove(name).is_some())
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance;
+
+    #[test]
+    fn test_conversations() {
+        let storage = MemoryStorage::new();
+        conformance::conversations(&storage);
+        assert_eq!(storage.data().exchanges.len(), 1);
+    }
+
+    #[test]
+    fn test_history() {
+        conformance::history(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn test_feedback() {
+        conformance::feedback(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn test_usage_summary() {
+        conformance::usage_summary(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn test_api_keys() {
+        conformance::api_keys(&MemoryStorage::new());
+    }
+}
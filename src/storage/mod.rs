@@ -0,0 +1,808 @@
+//! Модуль постоянного хранилища.
+//!
+//! Без хранилища всё живёт в памяти процесса и пропадает при перезапуске.
+//! Здесь хранятся:
+//!
+//! - диалоги ([`Conversation`]) - вопросы с одним `conversation_id`;
+//! - история вопросов и ответов ([`Exchange`]) - для `GET /history`,
+//!   с постраничным выводом ([`HistoryCursor`]) и полнотекстовым поиском;
+//! - оценки ответов ([`Feedback`]) - для `GET /admin/feedback`;
+//! - учёт использования AI ([`UsageRecord`]) - для `GET /admin/usage`;
+//! - API-ключи ([`StoredApiKey`]), выпущенные командой `--add-api-key`.
+//!
+//! ```text
+//!                 ┌───────────────────┐
+//! handlers ─────► │ trait Storage     │
+//! auth            └───────────────────┘
+//!                    ▲             ▲
+//!        ┌───────────┴───┐   ┌─────┴──────────┐
+//!        │ SqliteStorage │   │ MemoryStorage  │
+//!        │ (файл на диске│   │ (тесты,        │
+//!        │  + миграции)  │   │  эксперименты) │
+//!        └───────────────┘   └────────────────┘
+//! ```
+//!
+//! Движок выбирается в секции `[storage]` config.toml. При запуске SQLite
+//! применяет недостающие миграции из каталога `migrations/`; состояние
+//! хранилища показывает `/health/ready`, а при остановке журнал WAL
+//! сбрасывается в основной файл базы (см. модуль `shutdown`).
+//!
+//! # Для студентов: Зачем трейт?
+//!
+//! Обработчикам всё равно, где лежат данные. Они получают
+//! `Arc<dyn Storage>`, а тесты подставляют [`MemoryStorage`] - без файлов
+//! на диске и без очистки после себя. Так же устроен `AiService`:
+//! GigaChat и Mock за одним трейтом.
+//!
+//! # Для студентов: Синхронные методы
+//!
+//! Методы трейта синхронные: обычно запрос к локальному SQLite занимает
+//! микросекунды. Но соединение одно и защищено `Mutex`, а занятая база
+//! ждёт до `storage.busy_timeout_ms` - на рабочем потоке tokio такое
+//! ожидание задержало бы чужие запросы. Поэтому обработчики вызывают
+//! хранилище через [`blocking`], в пуле блокирующих потоков (так же
+//! `reload` перечитывает конфигурацию). Для сетевой базы (PostgreSQL)
+//! понадобился бы `async_trait`, как у `AiService`.
+
+pub mod memory;
+pub mod sqlite;
+
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::request::{FromRequest, Outcome, Request};
+use thiserror::Error;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+
+use crate::config::StorageConfig;
+use crate::models::{
+    AskParameters, FeedbackReview, FeedbackSummary, HistoryEntry, Rating, UsageSummary,
+};
+
+pub use memory::MemoryStorage;
+pub use sqlite::SqliteStorage;
+
+/// Допустимые значения `storage.backend`.
+pub const BACKENDS: &[&str] = &["sqlite", "memory"];
+
+/// Хранилище, общее для всех обработчиков (`State<SharedStorage>`).
+pub type SharedStorage = Arc<dyn Storage>;
+
+// ============================================================================
+// ТИПЫ ОШИБОК
+// ============================================================================
+
+/// Ошибки хранилища.
+#[derive(Error, Debug)]
+pub enum StorageError {
+    /// Не удалось открыть или создать файл базы
+    #[error("Не удалось открыть хранилище {path}: {reason}")]
+    Open {
+        /// Путь к файлу базы
+        path: String,
+        /// Причина
+        reason: String,
+    },
+
+    /// Миграция схемы не применилась
+    #[error("Миграция {version} не применилась: {reason}")]
+    Migration {
+        /// Номер миграции
+        version: u32,
+        /// Причина
+        reason: String,
+    },
+
+    /// Запись с таким ключом уже есть (например, API-ключ с тем же именем)
+    #[error("'{0}' уже существует")]
+    Duplicate(String),
+
+    /// Ошибка SQLite при выполнении запроса
+    #[error("Ошибка базы данных: {0}")]
+    Database(#[from] rusqlite::Error),
+
+    /// Неизвестное значение `storage.backend`
+    #[error("Неизвестный движок хранилища '{0}'")]
+    UnknownBackend(String),
+
+    /// Поток с запросом к хранилищу завершился паникой
+    #[error("Запрос к хранилищу прерван: {0}")]
+    Interrupted(String),
+}
+
+// ============================================================================
+// ЗАПИСИ
+// ============================================================================
+
+/// Диалог - цепочка вопросов одного пользователя.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversation {
+    /// Идентификатор из `AskRequest.conversation_id`
+    pub id: String,
+
+    /// Владелец: имя API-ключа, `sub` из JWT или "anonymous"
+    pub subject: String,
+
+    /// Заголовок - начало первого вопроса
+    pub title: String,
+
+    /// Время создания, миллисекунды Unix
+    pub created_at: i64,
+
+    /// Время последнего вопроса, миллисекунды Unix
+    pub updated_at: i64,
+}
+
+/// Вопрос и ответ AI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    /// Идентификатор (UUID)
+    pub id: String,
+
+    /// Диалог, если вопрос задан с `conversation_id`
+    pub conversation_id: Option<String>,
+
+    /// Кто спрашивал
+    pub subject: String,
+
+    /// Вопрос
+    pub question: String,
+
+    /// Ответ AI
+    pub answer: String,
+
+    /// Источник ответа (`AskResponse.source`)
+    pub source: String,
+
+    /// Параметры генерации (модель, температура, ...)
+    pub parameters: AskParameters,
+
+    /// Время ответа, миллисекунды Unix
+    pub created_at: i64,
+}
+
+/// Запись хранилища → элемент ответа `GET /history` (и строка экспорта JSON Lines).
+impl From<Exchange> for HistoryEntry {
+    fn from(exchange: Exchange) -> Self {
+        HistoryEntry {
+            id: exchange.id,
+            question: exchange.question,
+            answer: exchange.answer,
+            source: exchange.source,
+            conversation_id: exchange.conversation_id,
+            parameters: exchange.parameters,
+            created_at: exchange.created_at,
+        }
+    }
+}
+
+/// Условия отбора истории для `GET /history` и `DELETE /history`.
+///
+/// Все заданные условия должны выполняться одновременно.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HistoryFilter {
+    /// Чья история (всегда задан: чужие записи не видны)
+    pub subject: String,
+
+    /// Только записи диалога
+    pub conversation_id: Option<String>,
+
+    /// Только ответы этого источника (`"gigachat"`, `"mock ai service"`)
+    pub source: Option<String>,
+
+    /// Не раньше, миллисекунды Unix (включительно)
+    pub from: Option<i64>,
+
+    /// Раньше, миллисекунды Unix (не включительно)
+    pub until: Option<i64>,
+
+    /// Слова, которые должны встретиться в вопросе или ответе
+    /// (каждое - как начало слова, без учёта регистра)
+    pub search: Option<String>,
+}
+
+impl HistoryFilter {
+    /// Слова поискового запроса в нижнем регистре.
+    ///
+    /// Знаки препинания и кавычки разделяют слова и в поиск не попадают -
+    /// так же SQLite FTS5 (токенизатор `unicode61`) разбивает текст.
+    pub fn search_terms(&self) -> Vec<String> {
+        words(self.search.as_deref().unwrap_or_default())
+    }
+}
+
+/// Слова текста в нижнем регистре.
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Позиция в истории: последняя показанная запись.
+///
+/// История отсортирована от новых записей к старым по `(created_at, id)`;
+/// следующая страница начинается с записей строго "меньше" курсора.
+///
+/// # Для студентов: Курсор вместо номера страницы
+///
+/// С `?page=2` новая запись, добавленная между запросами, сдвигает
+/// страницы - одна запись покажется дважды. Курсор указывает на саму
+/// запись, поэтому сдвигов нет, а база находит место по индексу, не
+/// пропуская `OFFSET` строк.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryCursor {
+    /// Время последней показанной записи
+    pub created_at: i64,
+
+    /// Её идентификатор (различает записи с одинаковым временем)
+    pub id: String,
+}
+
+impl HistoryCursor {
+    /// Курсор, указывающий на запись.
+    pub fn after(exchange: &Exchange) -> Self {
+        Self {
+            created_at: exchange.created_at,
+            id: exchange.id.clone(),
+        }
+    }
+
+    /// Непрозрачная строка для клиента (`next_cursor`).
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}:{}", self.created_at, self.id))
+    }
+
+    /// Разбирает строку из `?cursor=`; `None`, если она испорчена.
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+        let (created_at, id) = raw.split_once(':')?;
+        Some(Self {
+            created_at: created_at.parse().ok()?,
+            id: id.to_string(),
+        })
+    }
+
+    /// Запись идёт после курсора (старше него).
+    pub fn precedes(&self, exchange: &Exchange) -> bool {
+        (exchange.created_at, exchange.id.as_str()) < (self.created_at, self.id.as_str())
+    }
+}
+
+/// Страница истории.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryPage {
+    /// Записи, от новых к старым
+    pub items: Vec<Exchange>,
+
+    /// Курсор следующей страницы; `None`, если записей больше нет
+    pub next: Option<HistoryCursor>,
+}
+
+/// Оценка ответа (`POST /feedback`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feedback {
+    /// Оценённый ответ ([`Exchange::id`])
+    pub answer_id: String,
+
+    /// Кто оценил
+    pub subject: String,
+
+    /// Оценка
+    pub rating: Rating,
+
+    /// Комментарий
+    pub comment: Option<String>,
+
+    /// Отмеченные категории (`wrong`, `off_topic`, ...)
+    pub categories: Vec<String>,
+
+    /// Время оценки, миллисекунды Unix
+    pub created_at: i64,
+}
+
+/// Один вызов AI для учёта использования (в том числе неудачный).
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    /// Кто вызывал
+    pub subject: String,
+
+    /// Имя AI сервиса
+    pub backend: String,
+
+    /// Результат: "ok", "busy", "blocked" (модерация) или "error"
+    pub outcome: String,
+
+    /// Оценка токенов вопроса
+    pub prompt_tokens: u64,
+
+    /// Оценка токенов ответа
+    pub completion_tokens: u64,
+
+    /// Длительность вызова, миллисекунды
+    pub latency_ms: u64,
+
+    /// Время вызова, миллисекунды Unix
+    pub created_at: i64,
+}
+
+/// API-ключ из хранилища. Сам ключ не хранится - только его SHA-256.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredApiKey {
+    /// Имя ключа (уникальное вместе с ключами из config.toml)
+    pub name: String,
+
+    /// SHA-256 ключа в hex (см. `auth::hash_key`)
+    pub key_hash: String,
+
+    /// Области доступа
+    pub scopes: Vec<String>,
+
+    /// Время выпуска, миллисекунды Unix
+    pub created_at: i64,
+}
+
+// ============================================================================
+// ТРЕЙТ
+// ============================================================================
+
+/// Постоянное хранилище приложения.
+pub trait Storage: Send + Sync {
+    /// Описание для логов и `/health/ready`: `sqlite data/gigachat.db (схема v6)`.
+    fn describe(&self) -> String;
+
+    /// Проверяет, что хранилище отвечает.
+    fn ping(&self) -> Result<(), StorageError>;
+
+    /// Сбрасывает буферы на диск (вызывается при остановке сервера).
+    fn flush(&self) -> Result<(), StorageError>;
+
+    /// Диалог по идентификатору.
+    fn conversation(&self, id: &str) -> Result<Option<Conversation>, StorageError>;
+
+    /// Создаёт диалог или обновляет заголовок и `updated_at` существующего.
+    fn save_conversation(&self, conversation: &Conversation) -> Result<(), StorageError>;
+
+    /// Сохраняет вопрос и ответ.
+    fn save_exchange(&self, exchange: &Exchange) -> Result<(), StorageError>;
+
+    /// До `limit` записей истории после курсора `after`, от новых к старым.
+    fn history(
+        &self,
+        filter: &HistoryFilter,
+        after: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError>;
+
+    /// Удаляет запись истории клиента `subject`; опустевший диалог удаляется
+    /// вместе с ней. `false`, если записи нет (или она чужая).
+    fn delete_exchange(&self, subject: &str, id: &str) -> Result<bool, StorageError>;
+
+    /// Удаляет все записи, подходящие под фильтр, и опустевшие диалоги
+    /// клиента (их заголовки - тоже текст вопросов). Возвращает число
+    /// удалённых записей.
+    fn delete_history(&self, filter: &HistoryFilter) -> Result<u64, StorageError>;
+
+    /// Запись истории по идентификатору.
+    fn exchange(&self, id: &str) -> Result<Option<Exchange>, StorageError>;
+
+    /// Сохраняет оценку ответа. `true`, если она заменила предыдущую.
+    fn save_feedback(&self, feedback: &Feedback) -> Result<bool, StorageError>;
+
+    /// Оценки по моделям и версиям системного промпта (`None` - первыми).
+    fn feedback_summary(&self) -> Result<Vec<FeedbackSummary>, StorageError>;
+
+    /// До `limit` последних оценок вместе с вопросами и ответами.
+    fn recent_feedback(&self, limit: usize) -> Result<Vec<FeedbackReview>, StorageError>;
+
+    /// Записывает вызов AI.
+    fn record_usage(&self, record: &UsageRecord) -> Result<(), StorageError>;
+
+    /// Использование AI по клиентам, по алфавиту.
+    fn usage_summary(&self) -> Result<Vec<UsageSummary>, StorageError>;
+
+    /// Все API-ключи из хранилища.
+    fn api_keys(&self) -> Result<Vec<StoredApiKey>, StorageError>;
+
+    /// Добавляет API-ключ; `StorageError::Duplicate`, если имя занято.
+    fn save_api_key(&self, key: &StoredApiKey) -> Result<(), StorageError>;
+
+    /// Удаляет API-ключ. `false`, если ключа с таким именем нет.
+    fn delete_api_key(&self, name: &str) -> Result<bool, StorageError>;
+}
+
+/// Открывает хранилище по секции `[storage]`.
+///
+/// Для SQLite создаёт каталог и файл базы и применяет миграции.
+pub fn open(config: &StorageConfig) -> Result<SharedStorage, StorageError> {
+    match config.backend.as_str() {
+        "sqlite" => Ok(Arc::new(SqliteStorage::open(config)?)),
+        "memory" => Ok(Arc::new(MemoryStorage::new())),
+        other => Err(StorageError::UnknownBackend(other.to_string())),
+    }
+}
+
+/// Выполняет `call` с хранилищем в пуле блокирующих потоков tokio.
+///
+/// ```rust,ignore
+/// let page = storage::blocking(storage, move |s| s.history(&filter, None, 20)).await?;
+/// ```
+pub async fn blocking<T, E, F>(storage: &SharedStorage, call: F) -> Result<T, E>
+where
+    T: Send + 'static,
+    E: From<StorageError> + Send + 'static,
+    F: FnOnce(&dyn Storage) -> Result<T, E> + Send + 'static,
+{
+    let storage = Arc::clone(storage);
+    tokio::task::spawn_blocking(move || call(storage.as_ref()))
+        .await
+        .unwrap_or_else(|e| Err(StorageError::Interrupted(e.to_string()).into()))
+}
+
+/// Доля 👍 среди оценок; `None`, если оценок нет.
+pub fn satisfaction(up: u64, down: u64) -> Option<f64> {
+    let rated = up + down;
+    (rated > 0).then(|| up as f64 / rated as f64)
+}
+
+/// Текущее время в миллисекундах Unix.
+pub fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX))
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Доступ к хранилищу для обработчиков.
+///
+/// Как и [`crate::shutdown::Drain`], читает state без `&State<T>`: в тестах
+/// и примерах хранилище может быть не передано - тогда ответы просто
+/// не сохраняются. Guard никогда не отклоняет запрос.
+pub struct Store<'r>(pub Option<&'r SharedStorage>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Store<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Store(req.rocket().state::<SharedStorage>()))
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+/// Общие проверки для всех реализаций [`Storage`].
+#[cfg(test)]
+pub(crate) mod conformance {
+    use super::*;
+
+    fn usage(subject: &str, outcome: &str, created_at: i64) -> UsageRecord {
+        UsageRecord {
+            subject: subject.to_string(),
+            backend: "Mock AI Service".to_string(),
+            outcome: outcome.to_string(),
+            prompt_tokens: 2,
+            completion_tokens: 10,
+            latency_ms: 5,
+            created_at,
+        }
+    }
+
+    pub fn conversations(storage: &dyn Storage) {
+        assert_eq!(storage.conversation("lab-1").unwrap(), None);
+
+        let mut conversation = Conversation {
+            id: "lab-1".to_string(),
+            subject: "frontend".to_string(),
+            title: "Что такое Rust?".to_string(),
+            created_at: 1_000,
+            updated_at: 1_000,
+        };
+        storage.save_conversation(&conversation).unwrap();
+        conversation.updated_at = 2_000;
+        storage.save_conversation(&conversation).unwrap();
+        assert_eq!(storage.conversation("lab-1").unwrap(), Some(conversation));
+
+        storage
+            .save_exchange(&Exchange {
+                id: "e-1".to_string(),
+                conversation_id: Some("lab-1".to_string()),
+                subject: "frontend".to_string(),
+                question: "Что такое Rust?".to_string(),
+                answer: "Язык программирования".to_string(),
+                source: "mock ai service".to_string(),
+                parameters: AskParameters::default(),
+                created_at: 2_000,
+            })
+            .unwrap();
+    }
+
+    fn exchange(id: &str, subject: &str, question: &str, source: &str, created_at: i64) -> Exchange {
+        Exchange {
+            id: id.to_string(),
+            conversation_id: None,
+            subject: subject.to_string(),
+            question: question.to_string(),
+            answer: format!("Ответ на «{question}»"),
+            source: source.to_string(),
+            parameters: AskParameters {
+                model: Some("GigaChat".to_string()),
+                temperature: Some(0.5),
+                max_tokens: Some(512),
+                system_prompt_applied: true,
+                system_prompt_version: Some("3f2a9c01b7de".to_string()),
+                experiment: None,
+                prompt_variant: None,
+                template: None,
+            },
+            created_at,
+        }
+    }
+
+    fn ids(page: &HistoryPage) -> Vec<&str> {
+        page.items.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    pub fn history(storage: &dyn Storage) {
+        storage
+            .save_conversation(&Conversation {
+                id: "lab-2".to_string(),
+                subject: "alice".to_string(),
+                title: "Что такое трейт?".to_string(),
+                created_at: 100,
+                updated_at: 300,
+            })
+            .unwrap();
+        let mut in_lab = exchange("a3", "alice", "Что такое трейт?", "gigachat", 300);
+        in_lab.conversation_id = Some("lab-2".to_string());
+        let saved = [
+            exchange("a1", "alice", "Что такое Rust?", "gigachat", 100),
+            exchange("a2", "alice", "Зачем нужны Макросы", "mock ai service", 200),
+            in_lab,
+            // Одинаковое время: порядок задаёт id
+            exchange("a4", "alice", "Как работает borrow checker?", "gigachat", 300),
+            exchange("b1", "bob", "Что такое Rust?", "gigachat", 150),
+        ];
+        for exchange in &saved {
+            storage.save_exchange(exchange).unwrap();
+        }
+        let alice = HistoryFilter {
+            subject: "alice".to_string(),
+            ..HistoryFilter::default()
+        };
+
+        // Постраничный вывод: от новых к старым, чужих записей нет
+        let first = storage.history(&alice, None, 3).unwrap();
+        assert_eq!(ids(&first), ["a4", "a3", "a2"]);
+        assert_eq!(first.items[1], saved[2]);
+        let cursor = first.next.clone().unwrap();
+        assert_eq!(HistoryCursor::decode(&cursor.encode()), Some(cursor.clone()));
+        let second = storage.history(&alice, Some(&cursor), 3).unwrap();
+        assert_eq!(ids(&second), ["a1"]);
+        assert_eq!(second.next, None);
+        // Ровно limit записей - следующей страницы нет
+        assert_eq!(storage.history(&alice, Some(&cursor), 1).unwrap().next, None);
+
+        let only = |filter: HistoryFilter| ids(&storage.history(&filter, None, 10).unwrap())
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            only(HistoryFilter { source: Some("mock ai service".to_string()), ..alice.clone() }),
+            ["a2"]
+        );
+        assert_eq!(
+            only(HistoryFilter { from: Some(200), until: Some(300), ..alice.clone() }),
+            ["a2"]
+        );
+        assert_eq!(
+            only(HistoryFilter { conversation_id: Some("lab-2".to_string()), ..alice.clone() }),
+            ["a3"]
+        );
+
+        // Поиск: без учёта регистра, по началу слова, в вопросе и ответе
+        let search = |query: &str| only(HistoryFilter { search: Some(query.to_string()), ..alice.clone() });
+        assert_eq!(search("макрос"), ["a2"]);
+        assert_eq!(search("RUST"), ["a1"]);
+        assert_eq!(search("ответ borrow"), ["a4"]);
+        assert_eq!(search("ответ \"что"), ["a3", "a1"]);
+        assert!(search("python").is_empty());
+
+        // Удаление одной записи: чужую удалить нельзя
+        assert!(!storage.delete_exchange("bob", "a1").unwrap());
+        assert!(storage.delete_exchange("alice", "a1").unwrap());
+        assert!(!storage.delete_exchange("alice", "a1").unwrap());
+
+        // Массовое удаление; опустевший диалог удаляется вместе с историей
+        assert_eq!(storage.delete_history(&HistoryFilter { from: Some(300), ..alice.clone() }).unwrap(), 2);
+        assert_eq!(storage.conversation("lab-2").unwrap(), None);
+        assert_eq!(storage.delete_history(&alice).unwrap(), 1);
+        assert!(storage.history(&alice, None, 10).unwrap().items.is_empty());
+        let bob = HistoryFilter {
+            subject: "bob".to_string(),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(&storage.history(&bob, None, 10).unwrap()), ["b1"]);
+    }
+
+    fn rate(answer_id: &str, subject: &str, rating: Rating, categories: &[&str], created_at: i64) -> Feedback {
+        Feedback {
+            answer_id: answer_id.to_string(),
+            subject: subject.to_string(),
+            rating,
+            comment: None,
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+            created_at,
+        }
+    }
+
+    pub fn feedback(storage: &dyn Storage) {
+        let mut mock = exchange("e3", "bob", "Что такое Rocket?", "mock ai service", 3);
+        mock.parameters = AskParameters::default();
+        let mut socratic = exchange("e4", "carol", "Что такое трейт?", "gigachat", 4);
+        socratic.parameters.experiment = Some("socratic-2026-10".to_string());
+        socratic.parameters.prompt_variant = Some("socratic".to_string());
+        // Тот же вариант в следующем эксперименте
+        let mut rerun = exchange("e5", "carol", "Что такое макрос?", "gigachat", 5);
+        rerun.parameters.experiment = Some("socratic-2026-11".to_string());
+        rerun.parameters.prompt_variant = Some("socratic".to_string());
+        for saved in [
+            exchange("e1", "alice", "Что такое Rust?", "gigachat", 1),
+            exchange("e2", "alice", "Что такое Cargo?", "gigachat", 2),
+            mock,
+            socratic,
+            rerun,
+        ] {
+            storage.save_exchange(&saved).unwrap();
+        }
+        assert_eq!(storage.exchange("e1").unwrap().unwrap().question, "Что такое Rust?");
+        assert_eq!(storage.exchange("e9").unwrap(), None);
+        let parameters = storage.exchange("e4").unwrap().unwrap().parameters;
+        assert_eq!(parameters.experiment.as_deref(), Some("socratic-2026-10"));
+        assert_eq!(parameters.prompt_variant.as_deref(), Some("socratic"));
+
+        assert!(!storage.save_feedback(&rate("e1", "alice", Rating::Up, &[], 10)).unwrap());
+        assert!(!storage.save_feedback(&rate("e2", "alice", Rating::Up, &[], 20)).unwrap());
+        let mut off_topic = rate("e3", "bob", Rating::Down, &["off_topic", "too_long"], 30);
+        off_topic.comment = Some("Rocket принят за ракету".to_string());
+        assert!(!storage.save_feedback(&off_topic).unwrap());
+        // Повторная оценка заменяет первую
+        assert!(storage.save_feedback(&rate("e1", "alice", Rating::Down, &["wrong"], 40)).unwrap());
+        assert!(!storage.save_feedback(&rate("e4", "carol", Rating::Up, &[], 5)).unwrap());
+
+        // Ответы варианта эксперимента - отдельная группа в каждом эксперименте
+        let summary = storage.feedback_summary().unwrap();
+        assert_eq!(summary.len(), 4);
+        assert_eq!(
+            summary[0],
+            FeedbackSummary {
+                model: None,
+                experiment: None,
+                prompt_variant: None,
+                system_prompt_version: None,
+                answers: 1,
+                up: 0,
+                down: 1,
+                satisfaction: Some(0.0),
+                categories: [("off_topic".to_string(), 1), ("too_long".to_string(), 1)].into(),
+            }
+        );
+        assert_eq!(summary[1].model.as_deref(), Some("GigaChat"));
+        assert_eq!(summary[1].system_prompt_version.as_deref(), Some("3f2a9c01b7de"));
+        assert_eq!((summary[1].answers, summary[1].up, summary[1].down), (2, 1, 1));
+        assert_eq!(summary[1].satisfaction, Some(0.5));
+        assert_eq!(summary[1].categories, [("wrong".to_string(), 1)].into());
+        assert_eq!(summary[2].experiment.as_deref(), Some("socratic-2026-10"));
+        assert_eq!(summary[2].prompt_variant.as_deref(), Some("socratic"));
+        assert_eq!((summary[2].answers, summary[2].up, summary[2].satisfaction), (1, 1, Some(1.0)));
+        assert_eq!(summary[3].experiment.as_deref(), Some("socratic-2026-11"));
+        assert_eq!((summary[3].answers, summary[3].up, summary[3].satisfaction), (1, 0, None));
+
+        let recent = storage.recent_feedback(2).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].answer_id, "e1");
+        assert_eq!(recent[0].rating, Rating::Down);
+        assert_eq!(recent[0].question, "Что такое Rust?");
+        assert_eq!(recent[0].parameters.model.as_deref(), Some("GigaChat"));
+        assert_eq!(recent[1].comment.as_deref(), Some("Rocket принят за ракету"));
+        assert_eq!(recent[1].categories, ["off_topic", "too_long"]);
+
+        // Удаление записи истории удаляет и её оценку
+        assert!(storage.delete_exchange("alice", "e1").unwrap());
+        let summary = storage.feedback_summary().unwrap();
+        assert_eq!((summary[1].answers, summary[1].up, summary[1].down), (1, 1, 0));
+        assert_eq!(storage.recent_feedback(10).unwrap().len(), 3);
+    }
+
+    pub fn usage_summary(storage: &dyn Storage) {
+        storage.record_usage(&usage("teacher", "ok", 10)).unwrap();
+        storage.record_usage(&usage("frontend", "ok", 20)).unwrap();
+        storage.record_usage(&usage("frontend", "error", 30)).unwrap();
+
+        let summary = storage.usage_summary().unwrap();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(
+            summary[0],
+            UsageSummary {
+                subject: "frontend".to_string(),
+                requests: 2,
+                errors: 1,
+                prompt_tokens: 4,
+                completion_tokens: 20,
+                last_request_at: 30,
+            }
+        );
+        assert_eq!(summary[1].subject, "teacher");
+    }
+
+    pub fn api_keys(storage: &dyn Storage) {
+        let key = StoredApiKey {
+            name: "lab-group-1".to_string(),
+            key_hash: "ab12".to_string(),
+            scopes: vec!["ask".to_string(), "health".to_string()],
+            created_at: 1_000,
+        };
+        storage.save_api_key(&key).unwrap();
+        assert!(matches!(
+            storage.save_api_key(&key),
+            Err(StorageError::Duplicate(name)) if name == "lab-group-1"
+        ));
+        assert_eq!(storage.api_keys().unwrap(), vec![key]);
+
+        assert!(storage.delete_api_key("lab-group-1").unwrap());
+        assert!(!storage.delete_api_key("lab-group-1").unwrap());
+        assert!(storage.api_keys().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_by_backend() {
+        let config = StorageConfig {
+            backend: "memory".to_string(),
+            ..StorageConfig::default()
+        };
+        let storage = open(&config).unwrap();
+        assert!(storage.describe().starts_with("memory"));
+        assert!(storage.ping().is_ok());
+
+        let config = StorageConfig {
+            backend: "postgres".to_string(),
+            ..StorageConfig::default()
+        };
+        assert!(matches!(open(&config), Err(StorageError::UnknownBackend(_))));
+    }
+
+    #[test]
+    fn test_history_cursor() {
+        let cursor = HistoryCursor {
+            created_at: 1_760_781_600_000,
+            id: "9b1d-4e".to_string(),
+        };
+        assert_eq!(HistoryCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(HistoryCursor::decode("не курсор"), None);
+        assert_eq!(HistoryCursor::decode(&URL_SAFE_NO_PAD.encode("abc:1")), None);
+    }
+
+    #[test]
+    fn test_satisfaction() {
+        assert_eq!(satisfaction(0, 0), None);
+        assert_eq!(satisfaction(3, 1), Some(0.75));
+    }
+
+    #[test]
+    fn test_now_millis() {
+        // 2020-01-01 - заведомо в прошлом
+        assert!(now_millis() > 1_577_836_800_000);
+    }
+}
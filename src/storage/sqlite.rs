@@ -0,0 +1,689 @@
+//! Хранилище во встроенной базе SQLite.
+//!
+//! База - один файл (`storage.path`), сервер баз данных не нужен.
+//! Схема описана SQL-миграциями в каталоге `migrations/`: они встраиваются
+//! в бинарник через `include_str!` и применяются при открытии базы.
+//!
+//! # Для студентов: Миграции
+//!
+//! Номер последней применённой миграции SQLite хранит в заголовке файла
+//! (`PRAGMA user_version`). При запуске применяются только миграции с
+//! бОльшим номером, каждая - в своей транзакции: если миграция упала,
+//! база остаётся в предыдущей версии. Уже выпущенные миграции не
+//! редактируют - изменения схемы добавляются новым файлом.
+//!
+//! # Для студентов: WAL
+//!
+//! В режиме `journal_mode = WAL` запись идёт в журнал `*.db-wal`, а читатели
+//! не ждут писателя. При остановке [`SqliteStorage::flush`] переносит журнал
+//! в основной файл, чтобы база была целой даже без `-wal` рядом.
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, ErrorCode, OptionalExtension, Row};
+
+use super::{
+    satisfaction, Conversation, Exchange, Feedback, HistoryCursor, HistoryFilter, HistoryPage,
+    Storage, StorageError, StoredApiKey, UsageRecord,
+};
+use crate::config::StorageConfig;
+use crate::models::{AskParameters, FeedbackReview, FeedbackSummary, Rating, UsageSummary};
+
+/// Миграции схемы по порядку; номер версии - позиция в списке + 1.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../migrations/0001_init.sql"),
+    include_str!("../../migrations/0002_history.sql"),
+    include_str!("../../migrations/0003_feedback.sql"),
+    include_str!("../../migrations/0004_templates.sql"),
+    include_str!("../../migrations/0005_experiments.sql"),
+    include_str!("../../migrations/0006_experiment_name.sql"),
+];
+
+/// Столбцы `exchanges` в порядке, который ожидает [`exchange_from_row`].
+const EXCHANGE_COLUMNS: &str = "id, conversation_id, subject, question, answer, source,
+     model, temperature, max_tokens, system_prompt_applied, system_prompt_version, experiment,
+     prompt_variant, template, created_at";
+
+/// Реализация [`Storage`] поверх SQLite.
+///
+/// Одно соединение под `Mutex`: запросы выполняются по очереди,
+/// чего для учебного сервиса достаточно.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+    path: String,
+    version: u32,
+}
+
+impl SqliteStorage {
+    /// Открывает (или создаёт) базу и применяет недостающие миграции.
+    pub fn open(config: &StorageConfig) -> Result<Self, StorageError> {
+        let open_error = |reason: String| StorageError::Open {
+            path: config.path.clone(),
+            reason,
+        };
+
+        if let Some(dir) = Path::new(&config.path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(|e| open_error(e.to_string()))?;
+        }
+        let mut conn = Connection::open(&config.path).map_err(|e| open_error(e.to_string()))?;
+        conn.busy_timeout(Duration::from_millis(config.busy_timeout_ms))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+
+        let version = migrate(&mut conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            path: config.path.clone(),
+            version,
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Применяет миграции новее `user_version`. Возвращает итоговую версию схемы.
+fn migrate(conn: &mut Connection) -> Result<u32, StorageError> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    let latest = MIGRATIONS.len() as u32;
+    if current > latest {
+        return Err(StorageError::Migration {
+            version: current,
+            reason: format!("база создана более новой версией приложения (известно до {latest})"),
+        });
+    }
+
+    for (version, sql) in (1..).zip(MIGRATIONS).skip(current as usize) {
+        let apply = |conn: &mut Connection| -> rusqlite::Result<()> {
+            let tx = conn.transaction()?;
+            tx.execute_batch(sql)?;
+            tx.pragma_update(None, "user_version", version)?;
+            tx.commit()
+        };
+        apply(conn).map_err(|e| StorageError::Migration {
+            version,
+            reason: e.to_string(),
+        })?;
+    }
+    Ok(latest)
+}
+
+/// Нарушение UNIQUE/PRIMARY KEY → [`StorageError::Duplicate`].
+fn duplicate(error: rusqlite::Error, name: &str) -> StorageError {
+    match &error {
+        rusqlite::Error::SqliteFailure(e, _) if e.code == ErrorCode::ConstraintViolation => {
+            StorageError::Duplicate(name.to_string())
+        }
+        _ => error.into(),
+    }
+}
+
+/// SQLite хранит целые как i64; счётчики и длительности не бывают отрицательными.
+fn to_sql(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+fn from_sql(value: i64) -> u64 {
+    u64::try_from(value).unwrap_or(0)
+}
+
+/// Параметры генерации из восьми столбцов подряд, начиная с `first`.
+fn parameters_from_row(row: &Row<'_>, first: usize) -> rusqlite::Result<AskParameters> {
+    Ok(AskParameters {
+        model: row.get(first)?,
+        temperature: row.get::<_, Option<f64>>(first + 1)?.map(|t| t as f32),
+        max_tokens: row.get(first + 2)?,
+        system_prompt_applied: row.get(first + 3)?,
+        system_prompt_version: row.get(first + 4)?,
+        experiment: row.get(first + 5)?,
+        prompt_variant: row.get(first + 6)?,
+        template: row.get(first + 7)?,
+    })
+}
+
+fn exchange_from_row(row: &Row<'_>) -> rusqlite::Result<Exchange> {
+    Ok(Exchange {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        subject: row.get(2)?,
+        question: row.get(3)?,
+        answer: row.get(4)?,
+        source: row.get(5)?,
+        parameters: parameters_from_row(row, 6)?,
+        created_at: row.get(14)?,
+    })
+}
+
+/// Список строк, сохранённый в столбце как JSON-массив.
+fn strings_from_row(row: &Row<'_>, index: usize) -> rusqlite::Result<Vec<String>> {
+    let json: String = row.get(index)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+fn strings_to_sql(values: &[String]) -> String {
+    serde_json::to_string(values).expect("список строк сериализуется в JSON")
+}
+
+fn rating_from_row(row: &Row<'_>, index: usize) -> rusqlite::Result<Rating> {
+    let rating: String = row.get(index)?;
+    Rating::parse(&rating).ok_or_else(|| {
+        rusqlite::Error::FromSqlConversionFailure(
+            index,
+            rusqlite::types::Type::Text,
+            format!("неизвестная оценка '{rating}'").into(),
+        )
+    })
+}
+
+/// Условие `WHERE` и его параметры для фильтра истории.
+///
+/// Поиск идёт через индекс FTS5: каждое слово запроса - в кавычках
+/// (операторы вроде `OR` и `NEAR` не срабатывают) и со `*` - совпадает
+/// начало слова.
+fn history_where(filter: &HistoryFilter) -> (String, Vec<Value>) {
+    let mut conditions = vec!["subject = ?".to_string()];
+    let mut values = vec![Value::Text(filter.subject.clone())];
+    if let Some(id) = &filter.conversation_id {
+        conditions.push("conversation_id = ?".to_string());
+        values.push(Value::Text(id.clone()));
+    }
+    if let Some(source) = &filter.source {
+        conditions.push("source = ?".to_string());
+        values.push(Value::Text(source.clone()));
+    }
+    if let Some(from) = filter.from {
+        conditions.push("created_at >= ?".to_string());
+        values.push(Value::Integer(from));
+    }
+    if let Some(until) = filter.until {
+        conditions.push("created_at < ?".to_string());
+        values.push(Value::Integer(until));
+    }
+    let terms = filter.search_terms();
+    if !terms.is_empty() {
+        let query: Vec<String> = terms.iter().map(|term| format!("\"{term}\"*")).collect();
+        conditions.push(
+            "rowid IN (SELECT rowid FROM exchanges_fts WHERE exchanges_fts MATCH ?)".to_string(),
+        );
+        values.push(Value::Text(query.join(" ")));
+    }
+    (conditions.join(" AND "), values)
+}
+
+/// Удаляет опустевшие диалоги клиента (вызывается после удаления истории).
+fn delete_empty_conversations(conn: &Connection, subject: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM conversations WHERE subject = ?1
+         AND NOT EXISTS (SELECT 1 FROM exchanges WHERE conversation_id = conversations.id)",
+        [subject],
+    )
+}
+
+impl Storage for SqliteStorage {
+    fn describe(&self) -> String {
+        format!("sqlite {} (схема v{})", self.path, self.version)
+    }
+
+    fn ping(&self) -> Result<(), StorageError> {
+        self.conn().query_row("SELECT 1", [], |_| Ok(()))?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        let conn = self.conn();
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        conn.execute_batch("PRAGMA optimize")?;
+        Ok(())
+    }
+
+    fn conversation(&self, id: &str) -> Result<Option<Conversation>, StorageError> {
+        let conversation = self
+            .conn()
+            .query_row(
+                "SELECT id, subject, title, created_at, updated_at
+                 FROM conversations WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok(Conversation {
+                        id: row.get(0)?,
+                        subject: row.get(1)?,
+                        title: row.get(2)?,
+                        created_at: row.get(3)?,
+                        updated_at: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(conversation)
+    }
+
+    fn save_conversation(&self, conversation: &Conversation) -> Result<(), StorageError> {
+        self.conn().execute(
+            "INSERT INTO conversations (id, subject, title, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET title = excluded.title, updated_at = excluded.updated_at",
+            params![
+                conversation.id,
+                conversation.subject,
+                conversation.title,
+                conversation.created_at,
+                conversation.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn save_exchange(&self, exchange: &Exchange) -> Result<(), StorageError> {
+        self.conn().execute(
+            &format!("INSERT INTO exchanges ({EXCHANGE_COLUMNS})
+                      VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"),
+            params![
+                exchange.id,
+                exchange.conversation_id,
+                exchange.subject,
+                exchange.question,
+                exchange.answer,
+                exchange.source,
+                exchange.parameters.model,
+                exchange.parameters.temperature.map(f64::from),
+                exchange.parameters.max_tokens,
+                exchange.parameters.system_prompt_applied,
+                exchange.parameters.system_prompt_version,
+                exchange.parameters.experiment,
+                exchange.parameters.prompt_variant,
+                exchange.parameters.template,
+                exchange.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn history(
+        &self,
+        filter: &HistoryFilter,
+        after: Option<&HistoryCursor>,
+        limit: usize,
+    ) -> Result<HistoryPage, StorageError> {
+        let limit = limit.max(1);
+        let (mut condition, mut values) = history_where(filter);
+        if let Some(cursor) = after {
+            condition.push_str(" AND (created_at < ? OR (created_at = ? AND id < ?))");
+            values.push(Value::Integer(cursor.created_at));
+            values.push(Value::Integer(cursor.created_at));
+            values.push(Value::Text(cursor.id.clone()));
+        }
+        // На одну запись больше: по ней видно, есть ли следующая страница
+        values.push(Value::Integer(to_sql(limit as u64 + 1)));
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&format!(
+            "SELECT {EXCHANGE_COLUMNS} FROM exchanges WHERE {condition}
+             ORDER BY created_at DESC, id DESC LIMIT ?"
+        ))?;
+        let mut items = statement
+            .query_map(params_from_iter(values), exchange_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let next = (items.len() > limit).then(|| HistoryCursor::after(&items[limit - 1]));
+        items.truncate(limit);
+        Ok(HistoryPage { items, next })
+    }
+
+    fn delete_exchange(&self, subject: &str, id: &str) -> Result<bool, StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let deleted = tx.execute("DELETE FROM exchanges WHERE subject = ?1 AND id = ?2", [subject, id])?;
+        delete_empty_conversations(&tx, subject)?;
+        tx.commit()?;
+        Ok(deleted > 0)
+    }
+
+    fn delete_history(&self, filter: &HistoryFilter) -> Result<u64, StorageError> {
+        let (condition, values) = history_where(filter);
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let deleted = tx.execute(
+            &format!("DELETE FROM exchanges WHERE {condition}"),
+            params_from_iter(values),
+        )?;
+        delete_empty_conversations(&tx, &filter.subject)?;
+        tx.commit()?;
+        Ok(deleted as u64)
+    }
+
+    fn exchange(&self, id: &str) -> Result<Option<Exchange>, StorageError> {
+        let exchange = self
+            .conn()
+            .query_row(
+                &format!("SELECT {EXCHANGE_COLUMNS} FROM exchanges WHERE id = ?1"),
+                [id],
+                exchange_from_row,
+            )
+            .optional()?;
+        Ok(exchange)
+    }
+
+    fn save_feedback(&self, feedback: &Feedback) -> Result<bool, StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let existed = tx
+            .query_row("SELECT 1 FROM feedback WHERE answer_id = ?1", [&feedback.answer_id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        tx.execute(
+            "INSERT INTO feedback (answer_id, subject, rating, comment, categories, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (answer_id) DO UPDATE SET
+                 subject = excluded.subject, rating = excluded.rating, comment = excluded.comment,
+                 categories = excluded.categories, created_at = excluded.created_at",
+            params![
+                feedback.answer_id,
+                feedback.subject,
+                feedback.rating.as_str(),
+                feedback.comment,
+                strings_to_sql(&feedback.categories),
+                feedback.created_at
+            ],
+        )?;
+        tx.commit()?;
+        Ok(existed)
+    }
+
+    fn feedback_summary(&self) -> Result<Vec<FeedbackSummary>, StorageError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT e.model, e.experiment, e.prompt_variant, e.system_prompt_version, COUNT(*),
+                    COUNT(CASE WHEN f.rating = 'up' THEN 1 END),
+                    COUNT(CASE WHEN f.rating = 'down' THEN 1 END)
+             FROM exchanges e LEFT JOIN feedback f ON f.answer_id = e.id
+             GROUP BY e.model, e.experiment, e.prompt_variant, e.system_prompt_version
+             ORDER BY e.model, e.experiment, e.prompt_variant, e.system_prompt_version",
+        )?;
+        let mut groups = statement
+            .query_map([], |row| {
+                let (up, down) = (from_sql(row.get(5)?), from_sql(row.get(6)?));
+                Ok(FeedbackSummary {
+                    model: row.get(0)?,
+                    experiment: row.get(1)?,
+                    prompt_variant: row.get(2)?,
+                    system_prompt_version: row.get(3)?,
+                    answers: from_sql(row.get(4)?),
+                    up,
+                    down,
+                    satisfaction: satisfaction(up, down),
+                    categories: Default::default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Категории хранятся JSON-массивом: json_each разворачивает его в строки
+        let mut statement = conn.prepare(
+            "SELECT e.model, e.experiment, e.prompt_variant, e.system_prompt_version, c.value, COUNT(*)
+             FROM feedback f JOIN exchanges e ON e.id = f.answer_id, json_each(f.categories) c
+             GROUP BY e.model, e.experiment, e.prompt_variant, e.system_prompt_version, c.value",
+        )?;
+        let counts = statement.query_map([], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, String>(4)?,
+                from_sql(row.get(5)?),
+            ))
+        })?;
+        for count in counts {
+            let (model, experiment, variant, version, category, count) = count?;
+            if let Some(group) = groups.iter_mut().find(|g| {
+                g.model == model
+                    && g.experiment == experiment
+                    && g.prompt_variant == variant
+                    && g.system_prompt_version == version
+            })
+            {
+                group.categories.insert(category, count);
+            }
+        }
+        Ok(groups)
+    }
+
+    fn recent_feedback(&self, limit: usize) -> Result<Vec<FeedbackReview>, StorageError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT f.answer_id, f.subject, f.rating, f.comment, f.categories, f.created_at,
+                    e.question, e.answer, e.model, e.temperature, e.max_tokens,
+                    e.system_prompt_applied, e.system_prompt_version, e.experiment,
+                    e.prompt_variant, e.template
+             FROM feedback f JOIN exchanges e ON e.id = f.answer_id
+             ORDER BY f.created_at DESC, f.answer_id DESC LIMIT ?1",
+        )?;
+        let rows = statement.query_map([to_sql(limit as u64)], |row| {
+            Ok(FeedbackReview {
+                answer_id: row.get(0)?,
+                subject: row.get(1)?,
+                rating: rating_from_row(row, 2)?,
+                comment: row.get(3)?,
+                categories: strings_from_row(row, 4)?,
+                created_at: row.get(5)?,
+                question: row.get(6)?,
+                answer: row.get(7)?,
+                parameters: parameters_from_row(row, 8)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn record_usage(&self, record: &UsageRecord) -> Result<(), StorageError> {
+        self.conn().execute(
+            "INSERT INTO usage_records
+                 (subject, backend, outcome, prompt_tokens, completion_tokens, latency_ms, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                record.subject,
+                record.backend,
+                record.outcome,
+                to_sql(record.prompt_tokens),
+                to_sql(record.completion_tokens),
+                to_sql(record.latency_ms),
+                record.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn usage_summary(&self) -> Result<Vec<UsageSummary>, StorageError> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT subject, COUNT(*), SUM(outcome <> 'ok'),
+                    SUM(prompt_tokens), SUM(completion_tokens), MAX(created_at)
+             FROM usage_records GROUP BY subject ORDER BY subject",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(UsageSummary {
+                subject: row.get(0)?,
+                requests: from_sql(row.get(1)?),
+                errors: from_sql(row.get(2)?),
+                prompt_tokens: from_sql(row.get(3)?),
+                completion_tokens: from_sql(row.get(4)?),
+                last_request_at: row.get(5)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn api_keys(&self) -> Result<Vec<StoredApiKey>, StorageError> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT name, key_hash, scopes, created_at FROM api_keys ORDER BY name")?;
+        let rows = statement.query_map([], |row| {
+            Ok(StoredApiKey {
+                name: row.get(0)?,
+                key_hash: row.get(1)?,
+                scopes: strings_from_row(row, 2)?,
+                created_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    fn save_api_key(&self, key: &StoredApiKey) -> Result<(), StorageError> {
+        self.conn()
+            .execute(
+                "INSERT INTO api_keys (name, key_hash, scopes, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![key.name, key.key_hash, strings_to_sql(&key.scopes), key.created_at],
+            )
+            .map_err(|e| duplicate(e, &key.name))?;
+        Ok(())
+    }
+
+    fn delete_api_key(&self, name: &str) -> Result<bool, StorageError> {
+        let deleted = self.conn().execute("DELETE FROM api_keys WHERE name = ?1", [name])?;
+        Ok(deleted > 0)
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::conformance;
+
+    /// Конфигурация с уникальным файлом во временном каталоге.
+    fn config() -> StorageConfig {
+        let path = std::env::temp_dir()
+            .join(format!("gigachat-test-{}", uuid::Uuid::new_v4()))
+            .join("app.db");
+        StorageConfig {
+            backend: "sqlite".to_string(),
+            path: path.to_string_lossy().into_owned(),
+            ..StorageConfig::default()
+        }
+    }
+
+    fn cleanup(config: &StorageConfig) {
+        if let Some(dir) = Path::new(&config.path).parent() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+
+    #[test]
+    fn test_conversations() {
+        let config = config();
+        let storage = SqliteStorage::open(&config).unwrap();
+        conformance::conversations(&storage);
+
+        let count: i64 = storage
+            .conn()
+            .query_row("SELECT COUNT(*) FROM exchanges WHERE conversation_id = 'lab-1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, 1);
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_history() {
+        let config = config();
+        conformance::history(&SqliteStorage::open(&config).unwrap());
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_feedback() {
+        let config = config();
+        conformance::feedback(&SqliteStorage::open(&config).unwrap());
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_usage_summary() {
+        let config = config();
+        conformance::usage_summary(&SqliteStorage::open(&config).unwrap());
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_api_keys() {
+        let config = config();
+        conformance::api_keys(&SqliteStorage::open(&config).unwrap());
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_data_survives_reopen() {
+        let config = config();
+        {
+            let storage = SqliteStorage::open(&config).unwrap();
+            assert_eq!(storage.describe(), format!("sqlite {} (схема v6)", config.path));
+            storage.record_usage(&UsageRecord {
+                subject: "frontend".to_string(),
+                backend: "Mock AI Service".to_string(),
+                outcome: "ok".to_string(),
+                prompt_tokens: 1,
+                completion_tokens: 1,
+                latency_ms: 1,
+                created_at: 1,
+            })
+            .unwrap();
+            storage.flush().unwrap();
+        }
+
+        // Повторное открытие не применяет миграции заново
+        let storage = SqliteStorage::open(&config).unwrap();
+        assert!(storage.ping().is_ok());
+        assert_eq!(storage.usage_summary().unwrap()[0].requests, 1);
+        cleanup(&config);
+    }
+
+    /// Записи, сохранённые схемой v1, после миграции находятся поиском.
+    #[test]
+    fn test_upgrade_indexes_old_exchanges() {
+        let config = config();
+        std::fs::create_dir_all(Path::new(&config.path).parent().unwrap()).unwrap();
+        {
+            let conn = Connection::open(&config.path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.pragma_update(None, "user_version", 1).unwrap();
+            conn.execute(
+                "INSERT INTO exchanges (id, subject, question, answer, source, created_at)
+                 VALUES ('old', 'alice', 'Что такое время жизни?', '...', 'gigachat', 1)",
+                [],
+            )
+            .unwrap();
+        }
+
+        let storage = SqliteStorage::open(&config).unwrap();
+        let filter = HistoryFilter {
+            subject: "alice".to_string(),
+            search: Some("жизни".to_string()),
+            ..HistoryFilter::default()
+        };
+        let page = storage.history(&filter, None, 10).unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].parameters, AskParameters::default());
+        cleanup(&config);
+    }
+
+    #[test]
+    fn test_newer_schema_is_rejected() {
+        let config = config();
+        drop(SqliteStorage::open(&config).unwrap());
+        let conn = Connection::open(&config.path).unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        drop(conn);
+
+        let error = SqliteStorage::open(&config).err().unwrap();
+        assert!(matches!(error, StorageError::Migration { version: 99, .. }), "{error}");
+        cleanup(&config);
+    }
+}
@@ -0,0 +1,217 @@
+//! Модуль экспорта трассировок OpenTelemetry.
+//!
+//! Логи отвечают на вопрос "что произошло", трассировка - "где ушло время".
+//! Span'ы, которые уже пишет приложение (`request` из модуля `request_id`,
+//! `ai_call` вокруг запроса к AI, `gigachat` внутри сервиса), отправляются
+//! в систему трассировки платформы по протоколу OTLP:
+//!
+//! ```text
+//! шлюз ── traceparent: 00-<trace_id>-<span_id>-01 ──► span "request"   (server)
+//!                                                       └─ span "ai_call" (client)
+//!                                                            └─ span "gigachat"
+//!                                     tracing-opentelemetry │
+//!                                                           ▼
+//!                                   экспортёр: otlp | stdout | file (JSON Lines)
+//! ```
+//!
+//! Интеграция необязательна: код OpenTelemetry компилируется только с фичей
+//! `otel` (`cargo run --features otel`) и включается секцией `[telemetry]`.
+//! Без фичи функции модуля ничего не делают.
+//!
+//! # Для студентов: W3C Trace Context
+//!
+//! Заголовок `traceparent` передаёт контекст трассы между сервисами:
+//! `00-<trace_id: 32 hex>-<span_id родителя: 16 hex>-<флаги>`. Если шлюз
+//! прислал его, span запроса становится дочерним для span'а шлюза, и в
+//! системе трассировки запрос виден целиком, от шлюза до GigaChat.
+//!
+//! Исходящий `traceparent` к GigaChat не передаётся: запросы отправляет
+//! библиотека `gigalib`, и добавить в них заголовки нельзя. Span `ai_call`
+//! при этом всё равно показывает время каждого вызова.
+//!
+//! # Ограничения
+//!
+//! Span'ы проходят через тот же фильтр, что и логи (`[logging]`, `RUST_LOG`).
+//! Наши span'ы имеют уровень INFO - при `level = "warn"` они не экспортируются.
+
+use std::str::FromStr;
+
+use rocket::http::HeaderMap;
+use thiserror::Error;
+use tracing::{Span, Subscriber};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::config::TelemetryConfig;
+
+#[cfg(feature = "otel")]
+mod otel;
+
+/// Собрано ли приложение с фичей `otel`.
+pub const SUPPORTED: bool = cfg!(feature = "otel");
+
+/// Слой `tracing_subscriber`, упакованный в `Box` (как слои в модуле `logging`).
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+// ============================================================================
+// ТИПЫ
+// ============================================================================
+
+/// Ошибки настройки экспорта трассировок.
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    /// Неизвестное значение `exporter`
+    #[error("Неизвестный экспортёр трассировок '{0}' (допустимо: otlp, stdout, file)")]
+    UnknownExporter(String),
+
+    /// `sample_ratio` вне диапазона [0, 1]
+    #[error("telemetry.sample_ratio должен быть от 0.0 до 1.0, получено {0}")]
+    InvalidSampleRatio(f64),
+
+    /// Не удалось создать экспортёр
+    #[cfg_attr(not(feature = "otel"), allow(dead_code))]
+    #[error("Не удалось создать экспортёр трассировок: {0}")]
+    Exporter(String),
+}
+
+/// Куда отправлять span'ы.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExporterKind {
+    /// OTLP/HTTP коллектор (`endpoint`) - для продакшена
+    Otlp,
+    /// JSON-строки в stdout - для отладки
+    Stdout,
+    /// JSON-строки в файл (`file`) - для отладки и тестов
+    File,
+}
+
+impl FromStr for ExporterKind {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "otlp" => Ok(ExporterKind::Otlp),
+            "stdout" => Ok(ExporterKind::Stdout),
+            "file" => Ok(ExporterKind::File),
+            _ => Err(TelemetryError::UnknownExporter(s.to_string())),
+        }
+    }
+}
+
+/// Держит провайдер трассировок; при уничтожении отправляет
+/// накопленные span'ы и останавливает экспорт.
+///
+/// Хранится внутри `LoggingGuard` до конца работы программы.
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl TelemetryGuard {
+    /// Отправляет накопленные span'ы, не останавливая экспорт.
+    ///
+    /// Вызывается при плавной остановке (модуль `shutdown`): если после
+    /// grace-периода процесс убьют SIGKILL, трассы уже будут у коллектора.
+    pub fn flush(&self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = &self.provider {
+            if let Err(e) = provider.force_flush() {
+                eprintln!("⚠️  Не удалось отправить span'ы: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("⚠️  Не удалось отправить последние span'ы: {}", e);
+            }
+        }
+    }
+}
+
+// ============================================================================
+// ИНИЦИАЛИЗАЦИЯ
+// ============================================================================
+
+/// Проверяет секцию `[telemetry]` и возвращает выбранный экспортёр.
+pub fn validate(config: &TelemetryConfig) -> Result<ExporterKind, TelemetryError> {
+    let exporter = config.exporter.parse()?;
+    if !(0.0..=1.0).contains(&config.sample_ratio) {
+        return Err(TelemetryError::InvalidSampleRatio(config.sample_ratio));
+    }
+    Ok(exporter)
+}
+
+/// Слой `tracing_subscriber`, превращающий span'ы в span'ы OpenTelemetry.
+///
+/// `None`, если экспорт выключен в конфиге или приложение собрано без `otel`.
+pub fn layer<S>(
+    config: &TelemetryConfig,
+) -> Result<(Option<BoxedLayer<S>>, TelemetryGuard), TelemetryError>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    if !config.enabled {
+        return Ok((None, TelemetryGuard::default()));
+    }
+    let exporter = validate(config)?;
+
+    #[cfg(feature = "otel")]
+    {
+        let provider = otel::provider(config, exporter)?;
+        let layer = otel::layer(&provider);
+        Ok((Some(layer), TelemetryGuard { provider: Some(provider) }))
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = exporter;
+        Ok((None, TelemetryGuard::default()))
+    }
+}
+
+/// Делает span запроса дочерним для span'а из заголовка `traceparent`.
+///
+/// Без заголовка (или без фичи `otel`) span начинает новую трассу.
+pub fn set_remote_parent(span: &Span, headers: &HeaderMap<'_>) {
+    #[cfg(feature = "otel")]
+    otel::set_remote_parent(span, headers);
+
+    #[cfg(not(feature = "otel"))]
+    let _ = (span, headers);
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_config() {
+        let config = TelemetryConfig {
+            exporter: "FILE".to_string(),
+            ..TelemetryConfig::default()
+        };
+        assert_eq!(validate(&config).unwrap(), ExporterKind::File);
+
+        let config = TelemetryConfig {
+            exporter: "jaeger".to_string(),
+            ..TelemetryConfig::default()
+        };
+        assert!(matches!(validate(&config), Err(TelemetryError::UnknownExporter(_))));
+
+        let config = TelemetryConfig {
+            sample_ratio: 1.5,
+            ..TelemetryConfig::default()
+        };
+        assert!(matches!(validate(&config), Err(TelemetryError::InvalidSampleRatio(_))));
+    }
+}
@@ -0,0 +1,292 @@
+//! Реализация экспорта на OpenTelemetry SDK (только с фичей `otel`).
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{Status, TraceContextExt, TracerProvider};
+use opentelemetry::Key;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use rocket::http::HeaderMap;
+use serde_json::{json, Map, Value};
+use tracing::{debug, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::{BoxedLayer, ExporterKind, TelemetryError};
+use crate::config::TelemetryConfig;
+
+// ============================================================================
+// ПРОВАЙДЕР И СЛОЙ
+// ============================================================================
+
+/// Создаёт провайдер трассировок с выбранным экспортёром.
+///
+/// OTLP отправляет span'ы пачками из фонового потока, чтобы не задерживать
+/// запросы сетью. `stdout` и `file` пишут каждый span сразу при закрытии -
+/// это дёшево, и файл можно читать без ожидания.
+pub(super) fn provider(
+    config: &TelemetryConfig,
+    exporter: ExporterKind,
+) -> Result<SdkTracerProvider, TelemetryError> {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        // Если шлюз уже решил, записывать ли трассу, следуем его решению
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))));
+
+    let builder = match exporter {
+        ExporterKind::Otlp => {
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(config.endpoint.clone())
+                .build()
+                .map_err(|e| TelemetryError::Exporter(e.to_string()))?;
+            builder.with_batch_exporter(exporter)
+        }
+        ExporterKind::Stdout => builder.with_simple_exporter(JsonLinesExporter::stdout()),
+        ExporterKind::File => builder.with_simple_exporter(
+            JsonLinesExporter::file(Path::new(&config.file))
+                .map_err(|e| TelemetryError::Exporter(format!("{}: {}", config.file, e)))?,
+        ),
+    };
+
+    Ok(builder.build())
+}
+
+/// Слой, передающий span'ы `tracing` в провайдер.
+pub(super) fn layer<S>(provider: &SdkTracerProvider) -> BoxedLayer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+        .boxed()
+}
+
+// ============================================================================
+// W3C TRACE CONTEXT
+// ============================================================================
+
+/// Доступ к заголовкам Rocket для пропагатора.
+struct HeaderExtractor<'a>(&'a HeaderMap<'a>);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get_one(key)
+    }
+
+    /// Rocket не отдаёт имена заголовков по ссылке, поэтому перечисляем
+    /// только заголовки W3C Trace Context - другие пропагатору не нужны.
+    fn keys(&self) -> Vec<&str> {
+        ["traceparent", "tracestate"]
+            .into_iter()
+            .filter(|name| self.0.contains(*name))
+            .collect()
+    }
+}
+
+pub(super) fn set_remote_parent(span: &Span, headers: &HeaderMap<'_>) {
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+    // Некорректный или отсутствующий traceparent - просто новая трасса
+    if !parent.span().span_context().is_valid() {
+        return;
+    }
+    if let Err(e) = span.set_parent(parent) {
+        debug!("traceparent ignored: {}", e);
+    }
+}
+
+// ============================================================================
+// ЭКСПОРТЁР JSON LINES
+// ============================================================================
+
+/// Куда пишет [`JsonLinesExporter`].
+#[derive(Debug)]
+enum Output {
+    Stdout,
+    File(File),
+}
+
+/// Экспортёр, записывающий каждый span одной JSON-строкой.
+///
+/// Нужен для отладки без коллектора: `exporter = "stdout"` или `"file"`.
+#[derive(Debug)]
+struct JsonLinesExporter {
+    output: Mutex<Output>,
+    service_name: Option<String>,
+}
+
+impl JsonLinesExporter {
+    fn stdout() -> Self {
+        Self::new(Output::Stdout)
+    }
+
+    fn file(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(Output::File(file)))
+    }
+
+    fn new(output: Output) -> Self {
+        Self {
+            output: Mutex::new(output),
+            service_name: None,
+        }
+    }
+
+    fn write_lines(&self, batch: &[SpanData]) -> io::Result<()> {
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        for span in batch {
+            let line = span_to_json(span, self.service_name.as_deref()).to_string();
+            match &mut *output {
+                Output::Stdout => writeln!(io::stdout().lock(), "{}", line)?,
+                Output::File(file) => writeln!(file, "{}", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SpanExporter for JsonLinesExporter {
+    fn export(
+        &self,
+        batch: Vec<SpanData>,
+    ) -> impl std::future::Future<Output = OTelSdkResult> + Send {
+        let result = self
+            .write_lines(&batch)
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()));
+        std::future::ready(result)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.service_name = resource
+            .get(&Key::new("service.name"))
+            .map(|value| value.to_string());
+    }
+}
+
+/// Представление span'а в JSON (поля названы как в OTLP).
+fn span_to_json(span: &SpanData, service_name: Option<&str>) -> Value {
+    let nanos = |time: std::time::SystemTime| {
+        time.duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0)
+    };
+    let attributes: Map<String, Value> = span
+        .attributes
+        .iter()
+        .map(|kv| (kv.key.to_string(), Value::String(kv.value.to_string())))
+        .collect();
+    let status = match &span.status {
+        Status::Unset => "unset",
+        Status::Ok => "ok",
+        Status::Error { .. } => "error",
+    };
+
+    json!({
+        "service_name": service_name,
+        "trace_id": span.span_context.trace_id().to_string(),
+        "span_id": span.span_context.span_id().to_string(),
+        "parent_span_id": span.parent_span_id.to_string(),
+        "name": span.name,
+        "kind": format!("{:?}", span.span_kind).to_lowercase(),
+        "start_time_unix_nano": nanos(span.start_time),
+        "end_time_unix_nano": nanos(span.end_time),
+        "attributes": attributes,
+        "status": status,
+    })
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::http::Header;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    fn traceparent_headers() -> HeaderMap<'static> {
+        let mut headers = HeaderMap::new();
+        headers.add(Header::new(
+            "traceparent",
+            format!("00-{}-{}-01", TRACE_ID, PARENT_ID),
+        ));
+        headers
+    }
+
+    #[test]
+    fn test_spans_are_exported_to_file_with_remote_parent() {
+        let path = std::env::temp_dir().join(format!("otel-test-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let config = TelemetryConfig {
+            enabled: true,
+            service_name: "test-service".to_string(),
+            exporter: "file".to_string(),
+            file: path.to_string_lossy().into_owned(),
+            ..TelemetryConfig::default()
+        };
+
+        let provider = provider(&config, ExporterKind::File).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", otel.kind = "server");
+            set_remote_parent(&request, &traceparent_headers());
+            request.in_scope(|| {
+                tracing::info_span!("ai_call", otel.kind = "client").in_scope(|| {});
+            });
+        });
+        provider.shutdown().unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        let spans: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // Дочерний span закрывается первым
+        assert_eq!(spans.len(), 2);
+        let (ai_call, request) = (&spans[0], &spans[1]);
+        assert_eq!(ai_call["name"], "ai_call");
+        assert_eq!(ai_call["kind"], "client");
+        assert_eq!(ai_call["parent_span_id"], request["span_id"]);
+        assert_eq!(request["kind"], "server");
+        assert_eq!(request["parent_span_id"], PARENT_ID);
+        assert_eq!(request["service_name"], "test-service");
+        for span in &spans {
+            assert_eq!(span["trace_id"], TRACE_ID);
+        }
+    }
+
+    #[test]
+    fn test_invalid_traceparent_is_ignored() {
+        let mut headers = HeaderMap::new();
+        headers.add(Header::new("traceparent", "garbage"));
+        let parent = TraceContextPropagator::new().extract(&HeaderExtractor(&headers));
+        assert!(!parent.span().span_context().is_valid());
+
+        let parent = TraceContextPropagator::new().extract(&HeaderExtractor(&traceparent_headers()));
+        assert_eq!(parent.span().span_context().trace_id().to_string(), TRACE_ID);
+    }
+}
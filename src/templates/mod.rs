@@ -0,0 +1,572 @@
+//! Модуль библиотеки шаблонов промптов.
+//!
+//! Один системный промпт не покрывает все сценарии занятия: то нужно
+//! объяснить тему, то проверить код, то составить тест. Шаблоны - это
+//! именованные заготовки запроса к модели с переменными:
+//!
+//! ```text
+//! prompts/explain.prompt
+//! ┌──────────────────────────────────────────────────────────┐
+//! │ ---                                                      │
+//! │ description = "Объяснить тему простыми словами"          │ ← заголовок (TOML)
+//! │ [defaults]                                               │
+//! │ level = "начинающий"                                     │
+//! │ ---                                                      │
+//! │ Объясни студенту уровня {{level}} на примерах {{language}}:│ ← текст
+//! │ {{question}}                                             │
+//! └──────────────────────────────────────────────────────────┘
+//! ```
+//!
+//! Клиент выбирает шаблон полем `template` в `POST /ask` и передаёт
+//! значения в `variables`; `{{question}}` - всегда текст вопроса. Значение
+//! переменной берётся из запроса, затем из `[defaults]` шаблона, затем из
+//! `[templates.defaults]` config.toml. Список шаблонов - `GET /templates`.
+//!
+//! Все файлы проверяются при запуске (и `--check-config`): ошибка в
+//! шаблоне не даёт серверу стартовать, а не всплывает посреди занятия.
+//! Изменения в каталоге применяются после перезапуска.
+//!
+//! # Для студентов: Почему не `str::replace`?
+//!
+//! Текст шаблона один раз разбирается на куски "текст" и "переменная".
+//! При подстановке значения просто склеиваются: если студент напишет в
+//! вопросе `{{level}}`, это останется текстом, а не станет переменной.
+
+use std::collections::BTreeMap;
+
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config::TemplatesConfig;
+use crate::models::{TemplateInfo, TemplateVariable};
+
+/// Расширение файлов шаблонов.
+pub const EXTENSION: &str = "prompt";
+
+/// Переменная, в которую подставляется вопрос.
+pub const QUESTION: &str = "question";
+
+/// Наибольшая длина значения переменной, символов.
+pub const MAX_VALUE_CHARS: usize = 200;
+
+/// Наибольшая длина имени шаблона или переменной.
+const MAX_NAME_LEN: usize = 64;
+
+/// Ограждение заголовка шаблона.
+const FRONT_MATTER: &str = "---";
+
+// ============================================================================
+// ТИПЫ ОШИБОК
+// ============================================================================
+
+/// Ошибки загрузки шаблонов и подстановки переменных.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    /// Каталог шаблонов не читается
+    #[error("Не удалось прочитать каталог шаблонов {path}: {reason}")]
+    Directory {
+        /// Путь к каталогу
+        path: String,
+        /// Причина
+        reason: String,
+    },
+
+    /// В файлах шаблонов есть ошибки (все сразу, по одной на строку)
+    #[error("Шаблоны содержат ошибки ({}):\n  - {}", .0.len(), .0.join("\n  - "))]
+    Invalid(Vec<String>),
+
+    /// Шаблона с таким именем нет
+    #[error("Шаблон '{0}' не найден")]
+    Unknown(String),
+
+    /// Переменным не задано значение ни в запросе, ни по умолчанию
+    #[error("Шаблону '{template}' не хватает переменных: {}", .names.join(", "))]
+    MissingVariables {
+        /// Имя шаблона
+        template: String,
+        /// Имена переменных
+        names: Vec<String>,
+    },
+
+    /// Переменные из запроса не используются шаблоном (или шаблон не выбран)
+    #[error("Шаблон '{template}' не использует переменные: {}", .names.join(", "))]
+    UnknownVariables {
+        /// Имя шаблона
+        template: String,
+        /// Имена переменных
+        names: Vec<String>,
+    },
+
+    /// Значение переменной длиннее [`MAX_VALUE_CHARS`]
+    #[error("Значение переменной '{0}' длиннее {MAX_VALUE_CHARS} символов")]
+    ValueTooLong(String),
+}
+
+// ============================================================================
+// ШАБЛОН
+// ============================================================================
+
+/// Кусок разобранного текста шаблона.
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Variable(String),
+}
+
+/// Заголовок файла шаблона (между строками `---`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FrontMatter {
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    defaults: BTreeMap<String, String>,
+}
+
+/// Шаблон промпта, прочитанный из файла `<имя>.prompt`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    /// Имя (имя файла без расширения)
+    pub name: String,
+
+    /// Описание для `GET /templates`
+    pub description: String,
+
+    /// Значения переменных по умолчанию из заголовка
+    pub defaults: BTreeMap<String, String>,
+
+    segments: Vec<Segment>,
+}
+
+impl PromptTemplate {
+    /// Разбирает файл шаблона. Ошибки возвращаются все сразу.
+    pub fn parse(name: &str, source: &str) -> Result<Self, Vec<String>> {
+        let mut problems = Vec::new();
+        if !is_valid_name(name, '-') {
+            problems.push(format!(
+                "имя '{}': только строчные латинские буквы, цифры, '-' и '_', до {} символов",
+                name, MAX_NAME_LEN
+            ));
+        }
+
+        let (front_matter, body) = match split_front_matter(source) {
+            Ok(parts) => parts,
+            Err(problem) => return Err(vec![problem]),
+        };
+        let front_matter: FrontMatter = match front_matter {
+            Some(text) => config::Config::builder()
+                .add_source(config::File::from_str(text, config::FileFormat::Toml))
+                .build()
+                .and_then(|settings| settings.try_deserialize())
+                .unwrap_or_else(|e| {
+                    problems.push(format!("заголовок: {}", e));
+                    FrontMatter::default()
+                }),
+            None => FrontMatter::default(),
+        };
+
+        let parsed = parse_body(body.trim());
+        // Без разобранного текста не с чем сверять переменные
+        let body_ok = parsed.is_ok();
+        let segments = parsed.unwrap_or_else(|problem| {
+            problems.push(problem);
+            Vec::new()
+        });
+        let template = Self {
+            name: name.to_string(),
+            description: front_matter.description.trim().to_string(),
+            defaults: front_matter.defaults,
+            segments,
+        };
+
+        if body_ok && !template.variables().contains(&QUESTION) {
+            problems.push(format!("в тексте нет {{{{{}}}}}: вопрос некуда подставить", QUESTION));
+        }
+        for name in template.defaults.keys() {
+            if name == QUESTION {
+                problems.push(format!("[defaults]: у {{{{{}}}}} не бывает значения по умолчанию", QUESTION));
+            } else if body_ok && !template.variables().contains(&name.as_str()) {
+                problems.push(format!("[defaults]: переменная '{}' не используется в тексте", name));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(template)
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// Переменные в порядке первого появления в тексте.
+    pub fn variables(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for segment in &self.segments {
+            if let Segment::Variable(name) = segment {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Подставляет вопрос и переменные.
+    ///
+    /// Значение ищется в `values` (из запроса), затем в умолчаниях шаблона,
+    /// затем в общих умолчаниях `global`.
+    pub fn render(
+        &self,
+        question: &str,
+        values: &BTreeMap<String, String>,
+        global: &BTreeMap<String, String>,
+    ) -> Result<String, TemplateError> {
+        let variables = self.variables();
+        let unknown: Vec<String> = values
+            .keys()
+            .filter(|name| name.as_str() == QUESTION || !variables.contains(&name.as_str()))
+            .cloned()
+            .collect();
+        if !unknown.is_empty() {
+            return Err(TemplateError::UnknownVariables {
+                template: self.name.clone(),
+                names: unknown,
+            });
+        }
+        if let Some((name, _)) = values.iter().find(|(_, value)| value.chars().count() > MAX_VALUE_CHARS) {
+            return Err(TemplateError::ValueTooLong(name.clone()));
+        }
+
+        let value = |name: &str| -> Option<&str> {
+            if name == QUESTION {
+                return Some(question);
+            }
+            values
+                .get(name)
+                .or_else(|| self.defaults.get(name))
+                .or_else(|| global.get(name))
+                .map(String::as_str)
+        };
+        let missing: Vec<String> = variables
+            .iter()
+            .filter(|name| value(name).is_none())
+            .map(|name| name.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables {
+                template: self.name.clone(),
+                names: missing,
+            });
+        }
+
+        Ok(self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.as_str(),
+                Segment::Variable(name) => value(name).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Описание для `GET /templates`: переменные без `question`, с умолчаниями.
+    pub fn info(&self, global: &BTreeMap<String, String>) -> TemplateInfo {
+        TemplateInfo {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            variables: self
+                .variables()
+                .into_iter()
+                .filter(|name| *name != QUESTION)
+                .map(|name| {
+                    let default = self.defaults.get(name).or_else(|| global.get(name)).cloned();
+                    TemplateVariable {
+                        name: name.to_string(),
+                        required: default.is_none(),
+                        default,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Имя шаблона или переменной: строчная латиница, цифры, `_` и `extra`.
+fn is_valid_name(name: &str, extra: char) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == extra)
+}
+
+/// Отделяет заголовок `---\n...\n---` от текста шаблона.
+fn split_front_matter(source: &str) -> Result<(Option<&str>, &str), String> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let Some(rest) = source
+        .strip_prefix(FRONT_MATTER)
+        .filter(|rest| rest.starts_with('\n') || rest.starts_with("\r\n"))
+    else {
+        return Ok((None, source));
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if offset > 0 && line.trim_end() == FRONT_MATTER {
+            return Ok((Some(&rest[..offset]), &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    Err("заголовок не закрыт строкой '---'".to_string())
+}
+
+/// Разбирает текст на куски; `{{ имя }}` - переменная (пробелы внутри допустимы).
+fn parse_body(body: &str) -> Result<Vec<Segment>, String> {
+    if body.is_empty() {
+        return Err("текст шаблона пустой".to_string());
+    }
+    let mut segments = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_string()));
+        }
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("'{{{{' без закрывающих '}}}}': {}", excerpt(&rest[start..])))?;
+        let name = after[..end].trim();
+        if !is_valid_name(name, '_') {
+            return Err(format!(
+                "некорректное имя переменной '{}': только строчные латинские буквы, цифры и '_'",
+                name
+            ));
+        }
+        segments.push(Segment::Variable(name.to_string()));
+        rest = &after[end + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+/// Начало строки для сообщения об ошибке.
+fn excerpt(text: &str) -> String {
+    text.lines().next().unwrap_or_default().chars().take(40).collect()
+}
+
+// ============================================================================
+// БИБЛИОТЕКА
+// ============================================================================
+
+/// Все шаблоны из каталога `templates.directory`.
+///
+/// Создаётся один раз при запуске и передаётся в Rocket через `.manage()`.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateLibrary {
+    templates: BTreeMap<String, PromptTemplate>,
+    defaults: BTreeMap<String, String>,
+}
+
+impl TemplateLibrary {
+    /// Загружает и проверяет шаблоны из секции `[templates]`.
+    ///
+    /// Без `directory` библиотека пустая. Файлы с другим расширением
+    /// (например, README.md рядом с шаблонами) пропускаются.
+    ///
+    /// # Ошибки
+    ///
+    /// `TemplateError::Directory`, если каталог не читается;
+    /// `TemplateError::Invalid` со списком всех проблем во всех файлах.
+    pub fn load(config: &TemplatesConfig) -> Result<Self, TemplateError> {
+        let mut library = Self {
+            templates: BTreeMap::new(),
+            defaults: config.defaults.clone(),
+        };
+        let Some(directory) = &config.directory else {
+            return Ok(library);
+        };
+        let directory_error = |e: std::io::Error| TemplateError::Directory {
+            path: directory.clone(),
+            reason: e.to_string(),
+        };
+
+        let mut paths = Vec::new();
+        for entry in std::fs::read_dir(directory).map_err(directory_error)? {
+            let path = entry.map_err(directory_error)?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some(EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut problems = Vec::new();
+        for path in &paths {
+            let shown = path.display().to_string();
+            let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+            match std::fs::read_to_string(path) {
+                Ok(source) => match PromptTemplate::parse(name, &source) {
+                    Ok(template) => {
+                        library.templates.insert(template.name.clone(), template);
+                    }
+                    Err(found) => {
+                        problems.extend(found.into_iter().map(|problem| format!("{}: {}", shown, problem)))
+                    }
+                },
+                Err(e) => problems.push(format!("{}: {}", shown, e)),
+            }
+        }
+        if problems.is_empty() {
+            Ok(library)
+        } else {
+            Err(TemplateError::Invalid(problems))
+        }
+    }
+
+    /// В библиотеке нет ни одного шаблона.
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    /// Имена шаблонов по алфавиту.
+    pub fn names(&self) -> Vec<&str> {
+        self.templates.keys().map(String::as_str).collect()
+    }
+
+    /// Описания всех шаблонов по алфавиту (для `GET /templates`).
+    pub fn list(&self) -> Vec<TemplateInfo> {
+        self.templates.values().map(|t| t.info(&self.defaults)).collect()
+    }
+
+    /// Текст запроса к модели по шаблону `name`.
+    pub fn render(
+        &self,
+        name: &str,
+        question: &str,
+        values: &BTreeMap<String, String>,
+    ) -> Result<String, TemplateError> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| TemplateError::Unknown(name.to_string()))?
+            .render(question, values, &self.defaults)
+    }
+}
+
+// ============================================================================
+// REQUEST GUARD
+// ============================================================================
+
+/// Доступ к библиотеке шаблонов для обработчиков.
+///
+/// Как [`crate::storage::Store`], не требует `.manage()`: в тестах без
+/// библиотеки запрос с `template` просто получает "шаблон не найден".
+pub struct Templates<'r>(pub Option<&'r TemplateLibrary>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Templates<'r> {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Templates(req.rocket().state::<TemplateLibrary>()))
+    }
+}
+
+// ============================================================================
+// ТЕСТЫ
+// ============================================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPLAIN: &str = "---
+description = \"Объяснить тему\"
+
+[defaults]
+level = \"начинающий\"
+---
+Объясни студенту уровня {{level}} на примерах {{ language }}:
+{{question}}
+";
+
+    fn values(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_and_render() {
+        let template = PromptTemplate::parse("explain", EXPLAIN).unwrap();
+        assert_eq!(template.description, "Объяснить тему");
+        assert_eq!(template.variables(), vec!["level", "language", "question"]);
+
+        let global = values(&[("language", "Rust")]);
+        let prompt = template.render("Что такое {{level}}?", &BTreeMap::new(), &global).unwrap();
+        assert_eq!(prompt, "Объясни студенту уровня начинающий на примерах Rust:\nЧто такое {{level}}?");
+
+        let prompt = template
+            .render("Что такое трейт?", &values(&[("level", "опытный"), ("language", "Go")]), &global)
+            .unwrap();
+        assert!(prompt.starts_with("Объясни студенту уровня опытный на примерах Go:"));
+    }
+
+    #[test]
+    fn test_render_errors() {
+        let template = PromptTemplate::parse("explain", EXPLAIN).unwrap();
+        assert_eq!(
+            template.render("?", &BTreeMap::new(), &BTreeMap::new()),
+            Err(TemplateError::MissingVariables {
+                template: "explain".to_string(),
+                names: vec!["language".to_string()],
+            })
+        );
+        assert!(matches!(
+            template.render("?", &values(&[("languag", "Rust")]), &BTreeMap::new()),
+            Err(TemplateError::UnknownVariables { names, .. }) if names == ["languag"]
+        ));
+        let long = "x".repeat(MAX_VALUE_CHARS + 1);
+        assert_eq!(
+            template.render("?", &values(&[("language", &long)]), &BTreeMap::new()),
+            Err(TemplateError::ValueTooLong("language".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_all_problems() {
+        let problems = PromptTemplate::parse("Quiz!", "---\n[defaults]\ntopic = \"x\"\n---\nВопросы по теме").unwrap_err();
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with("имя 'Quiz!'"));
+        assert!(problems[1].contains("{{question}}"));
+        assert!(problems[2].contains("'topic' не используется"));
+
+        let problems = PromptTemplate::parse("quiz", "Тест: {{question}} {{Level}}").unwrap_err();
+        assert!(problems[0].contains("'Level'"));
+        let problems = PromptTemplate::parse("quiz", "Тест: {{question").unwrap_err();
+        assert!(problems[0].contains("без закрывающих"));
+        let problems = PromptTemplate::parse("quiz", "---\ndescription = \"x\"\n{{question}}").unwrap_err();
+        assert!(problems[0].contains("не закрыт"));
+        let problems = PromptTemplate::parse("quiz", "---\nauthor = \"x\"\n---\n{{question}}").unwrap_err();
+        assert!(problems[0].starts_with("заголовок:"));
+    }
+
+    /// Шаблоны из каталога prompts/ (их же использует config.toml) корректны.
+    #[test]
+    fn test_bundled_templates() {
+        let config = TemplatesConfig {
+            directory: Some("prompts".to_string()),
+            defaults: values(&[("language", "Rust"), ("level", "начинающий")]),
+        };
+        let library = TemplateLibrary::load(&config).unwrap();
+        assert_eq!(library.names(), vec!["explain", "fix-compiler-error", "quiz", "review-code"]);
+        for info in library.list() {
+            assert!(!info.description.is_empty(), "{}", info.name);
+        }
+
+        let missing = TemplatesConfig {
+            directory: Some("no-such-dir".to_string()),
+            ..TemplatesConfig::default()
+        };
+        assert!(matches!(TemplateLibrary::load(&missing), Err(TemplateError::Directory { .. })));
+        assert!(TemplateLibrary::load(&TemplatesConfig::default()).unwrap().is_empty());
+    }
+}